@@ -30,6 +30,9 @@ public sealed class JustCacheFixture : IDisposable
         JustCache.ClearAll();
         JustCache.ClearNotifications();
         JustCache.SetMaxItems(1_000_000);
+        JustCache.SetMaxMemoryBytes(0);
+        JustCache.SetEvictionPolicy(JustCache.EvictionPolicy.AllKeysLru);
+        JustCache.SetTypeOverwrite(false);
     }
 
     public void Dispose()
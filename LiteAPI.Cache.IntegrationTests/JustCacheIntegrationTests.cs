@@ -68,6 +68,74 @@ public sealed class JustCacheIntegrationTests
         Assert.Equal(list, loadedList);
     }
 
+    [Fact]
+    public void String_Counters_And_Ranges_Work()
+    {
+        Assert.Equal(1, JustCache.Incr("str:counter"));
+        Assert.Equal(11, JustCache.IncrBy("str:counter", 10));
+        Assert.Equal(10, JustCache.Decr("str:counter"));
+        Assert.Equal(7, JustCache.DecrBy("str:counter", 3));
+        Assert.Equal(7.5, JustCache.IncrByFloat("str:counter", 0.5));
+        Assert.Throws<ArgumentException>(() => JustCache.Incr("str:counter"));
+
+        Assert.Equal(5, JustCache.Append("str:text", Encoding.UTF8.GetBytes("Hello")));
+        Assert.Equal(11, JustCache.SetRange("str:text", 5, Encoding.UTF8.GetBytes(" World")));
+        Assert.Equal("World", Encoding.UTF8.GetString(JustCache.GetRange("str:text", -5, -1)));
+        Assert.Equal(11, JustCache.StrLen("str:text"));
+        Assert.Empty(JustCache.GetRange("str:none", 0, -1));
+
+        Assert.Equal("Hello World", Encoding.UTF8.GetString(JustCache.GetSet("str:text", Array.Empty<byte>())!));
+        Assert.Empty(JustCache.GetDel("str:text")!);
+        Assert.Null(JustCache.GetDel("str:text"));
+        Assert.Equal(0, JustCache.StrLen("str:text"));
+    }
+
+    [Fact]
+    public void Conditional_Sets_Work()
+    {
+        var a = Encoding.UTF8.GetBytes("a");
+        var b = Encoding.UTF8.GetBytes("b");
+        Assert.True(JustCache.SetEx("set:lock", a, JustCache.SetFlags.Nx, TimeSpan.FromMinutes(1)));
+        Assert.False(JustCache.SetEx("set:lock", b, JustCache.SetFlags.Nx));
+        Assert.False(JustCache.SetEx("set:none", b, JustCache.SetFlags.Xx));
+
+        var (written, old) = JustCache.SetExGet("set:lock", b, JustCache.SetFlags.Xx | JustCache.SetFlags.KeepTtl);
+        Assert.True(written);
+        Assert.Equal("a", Encoding.UTF8.GetString(old!));
+        Assert.True(JustCache.TtlMs("set:lock") > 0);
+        Assert.Null(JustCache.SetExGet("set:new", a).Old);
+
+        Assert.True(JustCache.SetEx("set:at", a, JustCache.SetFlags.None, DateTimeOffset.UtcNow.AddMinutes(1)));
+        Assert.True(JustCache.TtlMs("set:at") > 0);
+        Assert.Throws<ArgumentException>(() => JustCache.SetEx("set:at", a, JustCache.SetFlags.Nx | JustCache.SetFlags.Xx));
+    }
+
+    [Fact]
+    public void Multi_Key_Batches_Work()
+    {
+        JustCache.MSet(new Dictionary<string, byte[]>
+        {
+            ["batch:a"] = Encoding.UTF8.GetBytes("1"),
+            ["batch:b"] = Encoding.UTF8.GetBytes("2"),
+        });
+        var values = JustCache.MGet(new[] { "batch:a", "batch:none", "batch:b" });
+        Assert.Equal("1", Encoding.UTF8.GetString(values[0]!));
+        Assert.Null(values[1]);
+        Assert.Equal("2", Encoding.UTF8.GetString(values[2]!));
+
+        var taken = new Dictionary<string, byte[]> { ["batch:a"] = Array.Empty<byte>(), ["batch:c"] = Array.Empty<byte>() };
+        Assert.False(JustCache.MSetNx(taken));
+        Assert.Null(JustCache.MGet(new[] { "batch:c" })[0]);
+
+        var binKey = new byte[] { 0, 0xFF };
+        Assert.True(JustCache.MSetNx(new[] { new KeyValuePair<byte[], byte[]>(binKey, new byte[] { 7 }) }));
+        Assert.Equal(new byte[] { 7 }, JustCache.MGet(new[] { binKey })[0]);
+        Assert.Equal(1, JustCache.Del(new[] { binKey }));
+
+        Assert.Equal(2, JustCache.Del(new[] { "batch:a", "batch:b", "batch:none" }));
+        Assert.Throws<ArgumentException>(() => JustCache.MGet(Array.Empty<string>()));
+    }
+
     [Fact]
     public void Hashes_Work()
     {
@@ -82,6 +150,40 @@ public sealed class JustCacheIntegrationTests
         Assert.Equal("Tashkent", Encoding.UTF8.GetString(all["city"]));
     }
 
+    [Fact]
+    public void Hash_Field_Commands_Work()
+    {
+        var created = JustCache.HMSet("hash:acct", new Dictionary<string, byte[]>
+        {
+            ["name"] = Encoding.UTF8.GetBytes("Bob"),
+            ["visits"] = Encoding.UTF8.GetBytes("1"),
+        });
+        Assert.Equal(2, created);
+        Assert.False(JustCache.HSetNx("hash:acct", "name", Encoding.UTF8.GetBytes("Eve")));
+        Assert.True(JustCache.HSetNx("hash:acct", "score", Encoding.UTF8.GetBytes("1.5")));
+
+        Assert.Equal(11, JustCache.HIncrBy("hash:acct", "visits", 10));
+        Assert.Equal(2.0, JustCache.HIncrByFloat("hash:acct", "score", 0.5));
+        Assert.Equal(3, JustCache.HLen("hash:acct"));
+        Assert.True(JustCache.HExists("hash:acct", "visits"));
+        Assert.Equal(new[] { "name", "score", "visits" }, JustCache.HKeys("hash:acct").OrderBy(k => k));
+        Assert.Equal(3, JustCache.HVals("hash:acct").Count);
+
+        var values = JustCache.HMGet("hash:acct", new[] { "name", "missing" });
+        Assert.Equal("Bob", Encoding.UTF8.GetString(values[0]!));
+        Assert.Null(values[1]);
+
+        JustCache.HSetString("hash:acct", "name", "not a number");
+        Assert.Throws<JustCacheException>(() => JustCache.HIncrBy("hash:acct", "name", 1));
+
+        Assert.True(JustCache.HDel("hash:acct", "name"));
+        Assert.False(JustCache.HDel("hash:acct", "name"));
+        JustCache.HDel("hash:acct", "score");
+        JustCache.HDel("hash:acct", "visits");
+        Assert.Equal(0, JustCache.HLen("hash:acct"));
+        Assert.Empty(JustCache.HKeys("hash:acct"));
+    }
+
     [Fact]
     public void Lists_Work()
     {
@@ -96,6 +198,47 @@ public sealed class JustCacheIntegrationTests
         Assert.Equal("b", items[0]);
     }
 
+    [Fact]
+    public void Lists_Work_As_Queues()
+    {
+        var bytes = (string s) => Encoding.UTF8.GetBytes(s);
+        Assert.Equal(3, JustCache.RPushRange("list:q", new[] { bytes("a"), bytes("b"), bytes("c") }));
+        JustCache.RPushString("list:q", "d");
+        Assert.Equal(4, JustCache.LLen("list:q"));
+        Assert.Equal("a", JustCache.LPopString("list:q"));
+        Assert.Equal("d", JustCache.LIndexString("list:q", -1));
+
+        JustCache.LSet("list:q", 0, bytes("B"));
+        Assert.Throws<ArgumentException>(() => JustCache.LSet("list:q", 9, bytes("x")));
+        Assert.Equal(4, JustCache.LInsert("list:q", before: true, bytes("c"), bytes("c0")));
+        Assert.Equal(-1, JustCache.LInsert("list:q", before: true, bytes("nope"), bytes("x")));
+        Assert.Equal(1, JustCache.LRem("list:q", 0, bytes("c0")));
+        JustCache.LTrim("list:q", 0, 1);
+        Assert.Equal(new[] { "B", "c" }, JustCache.LRangeStrings("list:q", 0, -1));
+
+        Assert.Equal("c", Encoding.UTF8.GetString(JustCache.LMove("list:q", "list:done", fromLeft: false, toLeft: true)!));
+        Assert.Equal("B", JustCache.RPopString("list:q"));
+        Assert.Null(JustCache.LPop("list:q"));
+        Assert.Equal(0, JustCache.LLen("list:q"));
+        Assert.Equal(new[] { "c" }, JustCache.LRangeStrings("list:done", 0, -1));
+    }
+
+    [Fact]
+    public void Blocking_Pops_Wait_For_Pushes()
+    {
+        Assert.Null(JustCache.BLPop(new[] { "list:blocked" }, TimeSpan.FromMilliseconds(20)));
+
+        var waiter = Task.Run(() => JustCache.BRPop(new[] { "list:empty", "list:blocked" }, TimeSpan.FromSeconds(5)));
+        Thread.Sleep(100);
+        JustCache.RPushString("list:blocked", "job");
+
+        var popped = waiter.Result;
+        Assert.NotNull(popped);
+        Assert.Equal("list:blocked", popped.Value.Key);
+        Assert.Equal("job", Encoding.UTF8.GetString(popped.Value.Value));
+        Assert.Equal(0, JustCache.LLen("list:blocked"));
+    }
+
     [Fact]
     public void Sets_Work()
     {
@@ -104,6 +247,34 @@ public sealed class JustCacheIntegrationTests
         Assert.False(JustCache.SIsMemberString("set:tags", "y"));
     }
 
+    [Fact]
+    public void Set_Members_And_Algebra_Work()
+    {
+        foreach (var m in new[] { "1", "2", "3" })
+            JustCache.SAddString("set:a", m);
+        foreach (var m in new[] { "2", "3", "4" })
+            JustCache.SAddString("set:b", m);
+        string[] Sorted(List<byte[]> items) => items.Select(Encoding.UTF8.GetString).OrderBy(m => m).ToArray();
+
+        Assert.Equal(3, JustCache.SCard("set:a"));
+        Assert.Equal(new[] { true, false }, JustCache.SMIsMember("set:a", new[] { Encoding.UTF8.GetBytes("1"), Encoding.UTF8.GetBytes("4") }));
+        Assert.Equal(new[] { "1", "2", "3", "4" }, Sorted(JustCache.SUnion(new[] { "set:a", "set:b", "set:missing" })));
+        Assert.Equal(new[] { "2", "3" }, Sorted(JustCache.SInter(new[] { "set:a", "set:b" })));
+        Assert.Equal(new[] { "1" }, Sorted(JustCache.SDiff(new[] { "set:a", "set:b" })));
+
+        Assert.Equal(1, JustCache.SDiffStore("set:d", new[] { "set:b", "set:a" }));
+        Assert.Equal(new[] { "4" }, Sorted(JustCache.SMembers("set:d")));
+        Assert.Equal(0, JustCache.SInterStore("set:d", new[] { "set:a", "set:missing" }));
+        Assert.Empty(JustCache.SMembers("set:d"));
+
+        Assert.Equal(2, Sorted(JustCache.SRandMember("set:a", 2)).Distinct().Count());
+        Assert.Equal(5, JustCache.SRandMember("set:a", -5).Count);
+        Assert.True(JustCache.SRemString("set:a", "1"));
+        var popped = JustCache.SPop("set:a", 5);
+        Assert.Equal(new[] { "2", "3" }, Sorted(popped));
+        Assert.Equal(0, JustCache.SCard("set:a"));
+    }
+
     [Fact]
     public void SortedSets_Work()
     {
@@ -115,6 +286,36 @@ public sealed class JustCacheIntegrationTests
         Assert.Equal(new[] { "alice", "carol", "bob" }, members);
     }
 
+    [Fact]
+    public void SortedSets_Support_Scores_Ranks_And_Ranges()
+    {
+        JustCache.ZAdd("z:scores", 1, "a");
+        JustCache.ZAdd("z:scores", 2, "b");
+        Assert.True(JustCache.ZAdd("z:scores", 3, "c", JustCache.ZAddFlags.Nx));
+        Assert.False(JustCache.ZAdd("z:scores", 0, "c", JustCache.ZAddFlags.Gt));
+        Assert.Throws<ArgumentException>(() => JustCache.ZAdd("z:scores", 1, "a", JustCache.ZAddFlags.Nx | JustCache.ZAddFlags.Xx));
+
+        Assert.Equal(4.5, JustCache.ZIncrBy("z:scores", 2.5, "b"));
+        Assert.Equal(3, JustCache.ZScore("z:scores", "c"));
+        Assert.Null(JustCache.ZScore("z:scores", "nope"));
+        Assert.Equal(0, JustCache.ZRank("z:scores", "b", reverse: true));
+        Assert.Null(JustCache.ZRank("z:scores", "nope"));
+        Assert.Equal(3, JustCache.ZCard("z:scores"));
+
+        Assert.Equal(new[] { "b", "c", "a" }, JustCache.ZRevRange("z:scores", 0, -1));
+        Assert.Equal(new[] { ("a", 1.0), ("c", 3.0) }, JustCache.ZRangeWithScores("z:scores", 0, 1));
+        Assert.Equal(new[] { ("b", 4.5), ("c", 3.0) }, JustCache.ZRangeByScore("z:scores", 1, double.PositiveInfinity, minExclusive: true, reverse: true));
+        Assert.Equal(new[] { ("c", 3.0) }, JustCache.ZRangeByScore("z:scores", 0, 10, offset: 1, count: 1));
+
+        Assert.True(JustCache.ZRem("z:scores", "a"));
+        Assert.False(JustCache.ZRem("z:scores", "a"));
+
+        foreach (var m in new[] { "x", "y", "z" })
+            JustCache.ZAdd("z:lex", 0, m);
+        Assert.Equal(new[] { "y", "z" }, JustCache.ZRangeByLex("z:lex", "(x", "+"));
+        Assert.Equal(new[] { "y", "x" }, JustCache.ZRangeByLex("z:lex", "-", "[y", reverse: true));
+    }
+
     [Fact]
     public void LruEviction_Works()
     {
@@ -132,6 +333,80 @@ public sealed class JustCacheIntegrationTests
         Assert.NotNull(JustCache.GetString("lru:k3"));
     }
 
+    [Fact]
+    public void RejectedWrites_Throw()
+    {
+        JustCache.SetMaxItems(2);
+        JustCache.SetEvictionPolicy(JustCache.EvictionPolicy.NoEviction);
+
+        JustCache.SetString("oom:k1", "1");
+        JustCache.SetString("oom:k2", "2");
+
+        var rejected = Assert.Throws<JustCacheException>(() => JustCache.SetString("oom:k3", "3"));
+        Assert.Equal(CacheStatus.OutOfMemory, rejected.Status);
+        Assert.StartsWith("OOM ", rejected.Message);
+        Assert.Throws<JustCacheException>(() => JustCache.HSetString("oom:h", "f", "v"));
+        Assert.Throws<JustCacheException>(() => JustCache.SAddString("oom:s", "m"));
+        Assert.Null(JustCache.GetString("oom:k3"));
+
+        // Overwriting an existing key needs no room.
+        JustCache.SetString("oom:k1", "updated");
+        Assert.Equal("updated", JustCache.GetString("oom:k1"));
+
+        // volatile-lru only evicts keys with a TTL.
+        JustCache.SetEvictionPolicy(JustCache.EvictionPolicy.VolatileLru);
+        Assert.Throws<JustCacheException>(() => JustCache.SetString("oom:k3", "3"));
+        JustCache.Expire("oom:k2", TimeSpan.FromMinutes(1));
+        JustCache.SetString("oom:k3", "3");
+        Assert.Null(JustCache.GetString("oom:k2"));
+        Assert.Equal(2, JustCache.Count);
+    }
+
+    [Fact]
+    public void Failures_Throw_Meaningful_Exceptions()
+    {
+        JustCache.HSetString("err:h", "f", "v");
+
+        var wrongType = Assert.Throws<JustCacheException>(() => JustCache.GetString("err:h"));
+        Assert.Equal(CacheStatus.WrongType, wrongType.Status);
+        Assert.StartsWith("WRONGTYPE", wrongType.Message);
+        Assert.Equal(CacheStatus.WrongType, JustCache.LastStatus);
+        Assert.Throws<JustCacheException>(() => JustCache.SIsMemberString("err:h", "v"));
+        var wrongWrite = Assert.Throws<JustCacheException>(() => JustCache.LPushString("err:h", "x"));
+        Assert.Equal(CacheStatus.WrongType, wrongWrite.Status);
+        Assert.Equal("v", JustCache.HGetString("err:h", "f"));
+        JustCache.SetTypeOverwrite(true);
+        JustCache.LPushString("err:h", "x");
+        Assert.Equal("x", JustCache.RPopString("err:h"));
+        JustCache.SetTypeOverwrite(false);
+
+        // A missing key is not an error for lookups.
+        Assert.Null(JustCache.GetString("err:missing"));
+        Assert.Equal(CacheStatus.NotFound, JustCache.LastStatus);
+        Assert.False(JustCache.Expire("err:missing", TimeSpan.FromSeconds(1)));
+
+        Assert.Throws<ArgumentException>(() => JustCache.FindKeys("age"));
+        Assert.False(JustCache.LoadAof(Path.Combine(Path.GetTempPath(), $"missing_{Guid.NewGuid():N}", "x.aof")));
+        Assert.Equal(CacheStatus.Io, JustCache.LastStatus);
+        Assert.NotNull(JustCache.LastError);
+
+        JustCache.SetString("err:k", "v");
+        Assert.Equal(CacheStatus.Ok, JustCache.LastStatus);
+        Assert.Null(JustCache.LastError);
+    }
+
+    [Fact]
+    public void MemoryLimit_Evicts()
+    {
+        JustCache.SetMaxMemoryBytes(4096);
+        for (var i = 0; i < 100; i++)
+            JustCache.Set($"mem:{i}", new byte[256]);
+
+        Assert.True(JustCache.UsedMemory <= 4096);
+        Assert.NotNull(JustCache.Get("mem:99"));
+        Assert.Null(JustCache.Get("mem:0"));
+    }
+
     [Fact]
     public void TtlAndExpire_Work()
     {
@@ -188,6 +463,82 @@ public sealed class JustCacheIntegrationTests
         Assert.Null(JustCache.Get(key));
     }
 
+    [Fact]
+    public void BinaryKeys_Support_Every_Type()
+    {
+        byte[] counter = [0xff, 0, (byte)'c'];
+        byte[] hash = [0xff, 0, (byte)'h'];
+        byte[] list = [0xff, 0, (byte)'l'];
+        byte[] zset = [0xff, 0, (byte)'z'];
+        byte[] stream = [0xff, 0, (byte)'x'];
+
+        Assert.Equal(5, JustCache.IncrBy(counter, 5));
+        Assert.True(JustCache.Expire(counter, TimeSpan.FromMinutes(1)));
+        Assert.InRange(JustCache.TtlMs(counter), 1, 60_000);
+
+        JustCache.HSetString(hash, "name", "Ada");
+        Assert.Equal("Ada", JustCache.HGetString(hash, "name"));
+        Assert.Equal(1, JustCache.HLen(hash));
+        Assert.Throws<JustCacheException>(() => JustCache.LLen(hash));
+
+        JustCache.RPushString(list, "a");
+        JustCache.RPushString(list, "b");
+        Assert.Equal(new[] { "a", "b" }, JustCache.LRangeStrings(list, 0, -1));
+        var popped = JustCache.BLPop(new[] { list }, TimeSpan.FromSeconds(1));
+        Assert.NotNull(popped);
+        Assert.Equal(list, popped.Value.Key);
+
+        JustCache.ZAdd(zset, 2, "b");
+        JustCache.ZAdd(zset, 1, "a");
+        Assert.Equal(new[] { "a", "b" }, JustCache.ZRange(zset, 0, -1));
+
+        var id = JustCache.XAdd(stream, new byte[] { 1, 2 });
+        var reads = JustCache.XReadBlock(new[] { (stream, JustCache.StreamId.Min) }, 0, TimeSpan.FromSeconds(1));
+        Assert.NotNull(reads);
+        Assert.Equal(stream, Assert.Single(reads).Key);
+        Assert.Equal(id, Assert.Single(reads[0].Items).Id);
+
+        JustCache.Del(new[] { counter, hash, list, zset, stream });
+    }
+
+    [Fact]
+    public void String_And_Binary_Keys_Share_One_Keyspace()
+    {
+        var key = Encoding.UTF8.GetBytes("shared:key");
+        JustCache.SetString("shared:key", "v1");
+        Assert.Equal("v1", Encoding.UTF8.GetString(JustCache.Get(key)!));
+
+        JustCache.Set(key, Encoding.UTF8.GetBytes("v2"));
+        Assert.Equal("v2", JustCache.GetString("shared:key"));
+
+        JustCache.Remove(key);
+        Assert.Null(JustCache.GetString("shared:key"));
+    }
+
+    [Fact]
+    public void Hash_Fields_And_Sorted_Set_Members_Are_Binary_Safe()
+    {
+        var hash = Encoding.UTF8.GetBytes("bin:hash");
+        var field = new byte[] { 0x66, 0x00, 0xff };
+        JustCache.HSet(hash, field, Encoding.UTF8.GetBytes("1"));
+        Assert.Equal(6, JustCache.HIncrBy(hash, field, 5));
+        Assert.False(JustCache.HExists(hash, "f"));
+        Assert.Equal("6", Encoding.UTF8.GetString(JustCache.HGet(hash, field)!));
+        Assert.Equal(field, Assert.Single(JustCache.HKeysBytes(hash)));
+        Assert.Equal(field, Assert.Single(JustCache.HGetAllBytes(hash)).Key);
+        Assert.True(JustCache.HDel(hash, field));
+
+        var zset = Encoding.UTF8.GetBytes("bin:zset");
+        var member = new byte[] { 0xff, 0x6d, 0x00 };
+        JustCache.ZAdd(zset, 1, member);
+        Assert.Equal(3, JustCache.ZIncrBy(zset, 2, member));
+        Assert.Equal(3, JustCache.ZScore(zset, member));
+        Assert.Equal(0, JustCache.ZRank(zset, member));
+        Assert.Equal(member, Assert.Single(JustCache.ZRangeBytes(zset, 0, -1)));
+        Assert.Equal(member, Assert.Single(JustCache.ZRangeByLex(zset, new byte[] { (byte)'[', 0xff }, new byte[] { (byte)'+' })));
+        Assert.True(JustCache.ZRem(zset, member));
+    }
+
     [Fact]
     public void PubSub_Works()
     {
@@ -248,9 +599,94 @@ public sealed class JustCacheIntegrationTests
         Assert.True(items.Count >= 2);
 
         Assert.Equal(id1, items[0].Id);
-        Assert.Equal("a", Encoding.UTF8.GetString(items[0].Payload));
+        Assert.Equal("a", Encoding.UTF8.GetString(items[0].Payload!));
         Assert.Equal(id2, items[1].Id);
-        Assert.Equal("b", Encoding.UTF8.GetString(items[1].Payload));
+        Assert.Equal("b", Encoding.UTF8.GetString(items[1].Payload!));
+    }
+
+    [Fact]
+    public void Streams_Store_Field_Value_Entries()
+    {
+        var fields = new[]
+        {
+            new KeyValuePair<string, byte[]>("temp", Encoding.UTF8.GetBytes("21")),
+            new KeyValuePair<string, byte[]>("hum", Encoding.UTF8.GetBytes("60")),
+        };
+        var id = JustCache.XAdd("stream:sensors", fields);
+        Assert.Throws<ArgumentException>(() => JustCache.XAdd("stream:sensors", Array.Empty<KeyValuePair<string, byte[]>>()));
+
+        var item = Assert.Single(JustCache.XRange("stream:sensors", id, id));
+        Assert.Equal(new[] { "temp", "hum" }, item.Fields!.Select(f => f.Key));
+        Assert.Equal("60", Encoding.UTF8.GetString(item.Fields![1].Value));
+        Assert.Null(item.Payload);
+    }
+
+    [Fact]
+    public void Streams_Trim_Delete_And_Read_Backwards()
+    {
+        var payload = Encoding.UTF8.GetBytes("p");
+        var ids = Enumerable.Range(1, 5)
+            .Select(ms => JustCache.XAdd("stream:capped", payload, new JustCache.StreamId((ulong)ms, 0)))
+            .ToList();
+        Assert.Throws<ArgumentException>(() => JustCache.XAdd("stream:capped", payload, ids[4]));
+
+        var newest = JustCache.XAdd("stream:capped", payload, maxLen: 4);
+        Assert.True(newest > ids[4]);
+        Assert.Equal(4, JustCache.XLen("stream:capped"));
+        Assert.Equal(new[] { newest, ids[4] }, JustCache.XRevRange("stream:capped", JustCache.StreamId.Max, JustCache.StreamId.Min, 2).Select(i => i.Id));
+        Assert.Equal(ids[2], Assert.Single(JustCache.XRange("stream:capped", JustCache.StreamId.Min, JustCache.StreamId.Max, 1)).Id);
+
+        Assert.Equal(1, JustCache.XDel("stream:capped", ids[2], new JustCache.StreamId(99, 0)));
+        Assert.Equal(1, JustCache.XTrim("stream:capped", minId: ids[4]));
+        Assert.Equal(2, JustCache.XTrim("stream:capped", maxLen: 0));
+        Assert.Equal(0, JustCache.XLen("stream:capped"));
+        Assert.Equal(0, JustCache.XLen("stream:none"));
+    }
+
+    [Fact]
+    public void XReadBlock_Waits_For_New_Entries()
+    {
+        var first = JustCache.XAdd("stream:events", Encoding.UTF8.GetBytes("a"));
+        var read = JustCache.XReadBlock(new[] { ("stream:events", JustCache.StreamId.Min) }, 0, TimeSpan.FromSeconds(1));
+        Assert.Equal(first, Assert.Single(read!["stream:events"]).Id);
+
+        Assert.Null(JustCache.XReadBlock(new[] { ("stream:events", JustCache.NewEntriesOnly) }, 0, TimeSpan.FromMilliseconds(20)));
+
+        var reader = Task.Run(() => JustCache.XReadBlock(new[] { ("stream:events", first) }, 1, TimeSpan.FromSeconds(5)));
+        Thread.Sleep(100);
+        var second = JustCache.XAdd("stream:events", Encoding.UTF8.GetBytes("b"));
+        var item = Assert.Single(reader.Result!["stream:events"]);
+        Assert.Equal(second, item.Id);
+        Assert.Equal("b", Encoding.UTF8.GetString(item.Payload!));
+    }
+
+    [Fact]
+    public void Consumer_Groups_Deliver_Each_Entry_Once()
+    {
+        JustCache.XGroupCreate("stream:jobs", "workers", JustCache.NewEntriesOnly, mkStream: true);
+        Assert.Throws<ArgumentException>(() => JustCache.XGroupCreate("stream:jobs", "workers"));
+        var id1 = JustCache.XAdd("stream:jobs", Encoding.UTF8.GetBytes("a"));
+        var id2 = JustCache.XAdd("stream:jobs", Encoding.UTF8.GetBytes("b"));
+
+        var newOnly = new[] { ("stream:jobs", JustCache.NewEntriesOnly) };
+        Assert.Equal(id1, Assert.Single(JustCache.XReadGroup("workers", "alice", newOnly, count: 1)!["stream:jobs"]).Id);
+        Assert.Equal(id2, Assert.Single(JustCache.XReadGroup("workers", "bob", newOnly)!["stream:jobs"]).Id);
+        Assert.Null(JustCache.XReadGroup("workers", "bob", newOnly, block: TimeSpan.FromMilliseconds(20)));
+
+        var pending = JustCache.XPending("stream:jobs", "workers");
+        Assert.Equal(new[] { "alice", "bob" }, pending.Select(p => p.Consumer));
+        Assert.All(pending, p => Assert.Equal(1, p.Deliveries));
+
+        Assert.Equal(id1, Assert.Single(JustCache.XClaim("stream:jobs", "workers", "bob", TimeSpan.Zero, new[] { id1 })).Id);
+        Assert.Equal(2, JustCache.XPending("stream:jobs", "workers", consumer: "bob").Count);
+        var (next, claimed, _) = JustCache.XAutoClaim("stream:jobs", "workers", "carol", TimeSpan.Zero, count: 1);
+        Assert.Equal(id2, next);
+        Assert.Equal(id1, Assert.Single(claimed).Id);
+
+        Assert.Equal(2, JustCache.XAck("stream:jobs", "workers", id1, id2));
+        Assert.Empty(JustCache.XPending("stream:jobs", "workers"));
+        Assert.True(JustCache.XGroupDestroy("stream:jobs", "workers"));
+        Assert.Throws<ArgumentException>(() => JustCache.XReadGroup("workers", "bob", newOnly));
     }
 
     [Fact]
@@ -0,0 +1,209 @@
+using System.Runtime.InteropServices;
+using System.Text;
+
+namespace LiteAPI.Cache;
+
+public static partial class JustCache
+{
+    #region Multi-key (MGET/MSET/MSETNX/DEL)
+
+    [DllImport(WindowsLib, EntryPoint = "cache_mget", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_mget_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_mget", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_mget_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_mget", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_mget_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_mget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_mget_b_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_mget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_mget_b_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_mget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_mget_b_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_mset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_mset_win(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_mset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_mset_linux(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_mset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_mset_mac(byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_mset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_mset_b_win(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_mset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_mset_b_linux(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_mset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_mset_b_mac(byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_msetnx", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_msetnx_win(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_msetnx", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_msetnx_linux(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_msetnx", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_msetnx_mac(byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_msetnx_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_msetnx_b_win(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_msetnx_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_msetnx_b_linux(byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_msetnx_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_msetnx_b_mac(byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_del", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_del_win(byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_del", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_del_linux(byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_del", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_del_mac(byte[] keys, UIntPtr keysLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_del_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_del_b_win(byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_del_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_del_b_linux(byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_del_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_del_b_mac(byte[] keys, UIntPtr keysLen);
+
+    // Values of several keys in one call, read under one set of locks; null for keys that are missing
+    // or hold another type.
+    public static List<byte[]?> MGet(IEnumerable<string> keys) => MGetCore(PackKeys(keys), binary: false);
+
+    public static List<byte[]?> MGet(IEnumerable<byte[]> keys) => MGetCore(PackItems(keys), binary: true);
+
+    // Sets every pair at once (dropping their TTLs). If one key has no room, nothing is written.
+    public static void MSet(IEnumerable<KeyValuePair<string, byte[]>> items) =>
+        ThrowIfRejected(MSetCore(PackPairs(items), binary: false, onlyIfNoneExist: false));
+
+    public static void MSet(IEnumerable<KeyValuePair<byte[], byte[]>> items) =>
+        ThrowIfRejected(MSetCore(PackPairs(items), binary: true, onlyIfNoneExist: false));
+
+    // Sets every pair only if none of the keys exists; returns whether they were set.
+    public static bool MSetNx(IEnumerable<KeyValuePair<string, byte[]>> items)
+    {
+        var res = MSetCore(PackPairs(items), binary: false, onlyIfNoneExist: true);
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool MSetNx(IEnumerable<KeyValuePair<byte[], byte[]>> items)
+    {
+        var res = MSetCore(PackPairs(items), binary: true, onlyIfNoneExist: true);
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    // Removes several keys at once; returns how many of them existed.
+    public static long Del(IEnumerable<string> keys) => DelCore(PackKeys(keys), binary: false);
+
+    public static long Del(IEnumerable<byte[]> keys) => DelCore(PackItems(keys), binary: true);
+
+    private static List<byte[]?> MGetCore(byte[] blob, bool binary)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = binary ? cache_mget_b_win(blob, (UIntPtr)blob.Length, out len) : cache_mget_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = binary ? cache_mget_b_linux(blob, (UIntPtr)blob.Length, out len) : cache_mget_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = binary ? cache_mget_b_mac(blob, (UIntPtr)blob.Length, out len) : cache_mget_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]?>();
+        }
+        return ParseOptionalItemsBlob(CopyAndFree(ptr, len));
+    }
+
+    private static int MSetCore(byte[] blob, bool binary, bool onlyIfNoneExist)
+    {
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            return (binary, onlyIfNoneExist) switch
+            {
+                (false, false) => cache_mset_win(blob, (UIntPtr)blob.Length),
+                (true, false) => cache_mset_b_win(blob, (UIntPtr)blob.Length),
+                (false, true) => cache_msetnx_win(blob, (UIntPtr)blob.Length),
+                (true, true) => cache_msetnx_b_win(blob, (UIntPtr)blob.Length),
+            };
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            return (binary, onlyIfNoneExist) switch
+            {
+                (false, false) => cache_mset_linux(blob, (UIntPtr)blob.Length),
+                (true, false) => cache_mset_b_linux(blob, (UIntPtr)blob.Length),
+                (false, true) => cache_msetnx_linux(blob, (UIntPtr)blob.Length),
+                (true, true) => cache_msetnx_b_linux(blob, (UIntPtr)blob.Length),
+            };
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            return (binary, onlyIfNoneExist) switch
+            {
+                (false, false) => cache_mset_mac(blob, (UIntPtr)blob.Length),
+                (true, false) => cache_mset_b_mac(blob, (UIntPtr)blob.Length),
+                (false, true) => cache_msetnx_mac(blob, (UIntPtr)blob.Length),
+                (true, true) => cache_msetnx_b_mac(blob, (UIntPtr)blob.Length),
+            };
+        throw new PlatformNotSupportedException();
+    }
+
+    private static long DelCore(byte[] blob, bool binary)
+    {
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = binary ? cache_del_b_win(blob, (UIntPtr)blob.Length) : cache_del_win(blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = binary ? cache_del_b_linux(blob, (UIntPtr)blob.Length) : cache_del_linux(blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = binary ? cache_del_b_mac(blob, (UIntPtr)blob.Length) : cache_del_mac(blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Items alternating key and value, as cache_mset takes them.
+    private static byte[] PackPairs(IEnumerable<KeyValuePair<string, byte[]>> items)
+    {
+        ArgumentNullException.ThrowIfNull(items);
+        return PackPairs(items.Select(kv => new KeyValuePair<byte[], byte[]>(
+            Encoding.UTF8.GetBytes(kv.Key ?? throw new ArgumentNullException(nameof(items))), kv.Value)));
+    }
+
+    private static byte[] PackPairs(IEnumerable<KeyValuePair<byte[], byte[]>> items)
+    {
+        ArgumentNullException.ThrowIfNull(items);
+
+        var flat = new List<byte[]>();
+        foreach (var (key, value) in items)
+        {
+            flat.Add(key ?? throw new ArgumentNullException(nameof(items)));
+            flat.Add(value ?? throw new ArgumentNullException(nameof(items)));
+        }
+        return PackItems(flat);
+    }
+
+    #endregion
+}
@@ -0,0 +1,604 @@
+using System.Buffers.Binary;
+using System.Runtime.InteropServices;
+
+namespace LiteAPI.Cache;
+
+// Overloads taking hash fields and sorted-set members as byte[], backed by the cache_*_bf and
+// cache_*_bm exports. Fields and members are compared byte for byte, and a string field or member
+// is the same one as its UTF-8 bytes. The *Bytes methods return fields and members as raw bytes.
+public static partial class JustCache
+{
+    #region Binary fields: Hashes
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hset_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hset_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hset_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hset_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hset_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hset_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hget_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hget_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hget_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hget_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hget_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hget_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hsetnx_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hsetnx_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hsetnx_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hdel_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hdel_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_hdel_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hexists_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hexists_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_hexists_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hincrby_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, long increment, out long value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hincrby_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, long increment, out long value);
+
+    [DllImport(MacLib, EntryPoint = "cache_hincrby_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, long increment, out long value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hincrbyfloat_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_bf_win(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, double increment, out double value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hincrbyfloat_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_bf_linux(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, double increment, out double value);
+
+    [DllImport(MacLib, EntryPoint = "cache_hincrbyfloat_bf", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_bf_mac(byte[] key, UIntPtr keyLen, byte[] field, UIntPtr fieldLen, double increment, out double value);
+
+
+    public static void HSet(byte[] key, byte[] field, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hset_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hset_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hset_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static byte[]? HGet(byte[] key, byte[] field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hget_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hget_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hget_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static bool HSetNx(byte[] key, byte[] field, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hsetnx_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hsetnx_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hsetnx_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool HDel(byte[] key, byte[] field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hdel_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hdel_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hdel_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool HExists(byte[] key, byte[] field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hexists_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hexists_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hexists_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static long HIncrBy(byte[] key, byte[] field, long increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        long value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hincrby_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hincrby_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hincrby_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    public static double HIncrByFloat(byte[] key, byte[] field, double increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        double value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hincrbyfloat_bf_win(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hincrbyfloat_bf_linux(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hincrbyfloat_bf_mac(key, (UIntPtr)key.Length, field, (UIntPtr)field.Length, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    // The fields of the hash at `key` as raw bytes.
+    public static List<byte[]> HKeysBytes(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hkeys_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hkeys_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hkeys_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // The field/value pairs of the hash at `key`, with fields as raw bytes.
+    public static List<KeyValuePair<byte[], byte[]>> HGetAllBytes(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hgetall_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hgetall_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hgetall_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<KeyValuePair<byte[], byte[]>>();
+        }
+
+        return ParseBinaryPairsBlob(CopyAndFree(ptr, len));
+    }
+
+    #endregion
+
+    #region Binary members: Sorted sets
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zadd_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_bm_win(byte[] key, UIntPtr keyLen, double score, byte[] member, UIntPtr memberLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zadd_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_bm_linux(byte[] key, UIntPtr keyLen, double score, byte[] member, UIntPtr memberLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_zadd_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_bm_mac(byte[] key, UIntPtr keyLen, double score, byte[] member, UIntPtr memberLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zadd_flags_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_bm_win(byte[] key, UIntPtr keyLen, double score, byte[] member, UIntPtr memberLen, int flags);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zadd_flags_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_bm_linux(byte[] key, UIntPtr keyLen, double score, byte[] member, UIntPtr memberLen, int flags);
+
+    [DllImport(MacLib, EntryPoint = "cache_zadd_flags_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_bm_mac(byte[] key, UIntPtr keyLen, double score, byte[] member, UIntPtr memberLen, int flags);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrem_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_bm_win(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrem_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_bm_linux(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrem_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_bm_mac(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zincrby_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_bm_win(byte[] key, UIntPtr keyLen, double increment, byte[] member, UIntPtr memberLen, out double score);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zincrby_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_bm_linux(byte[] key, UIntPtr keyLen, double increment, byte[] member, UIntPtr memberLen, out double score);
+
+    [DllImport(MacLib, EntryPoint = "cache_zincrby_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_bm_mac(byte[] key, UIntPtr keyLen, double increment, byte[] member, UIntPtr memberLen, out double score);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zscore_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_bm_win(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen, out double score);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zscore_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_bm_linux(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen, out double score);
+
+    [DllImport(MacLib, EntryPoint = "cache_zscore_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_bm_mac(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen, out double score);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrank_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_bm_win(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen, int rev);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrank_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_bm_linux(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen, int rev);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrank_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_bm_mac(byte[] key, UIntPtr keyLen, byte[] member, UIntPtr memberLen, int rev);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrangebylex_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_bm_win(byte[] key, UIntPtr keyLen, byte[] min, UIntPtr minLen, byte[] max, UIntPtr maxLen, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrangebylex_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_bm_linux(byte[] key, UIntPtr keyLen, byte[] min, UIntPtr minLen, byte[] max, UIntPtr maxLen, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrangebylex_bm", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_bm_mac(byte[] key, UIntPtr keyLen, byte[] min, UIntPtr minLen, byte[] max, UIntPtr maxLen, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+
+    public static void ZAdd(byte[] key, double score, byte[] member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zadd_bm_win(key, (UIntPtr)key.Length, score, member, (UIntPtr)member.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zadd_bm_linux(key, (UIntPtr)key.Length, score, member, (UIntPtr)member.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zadd_bm_mac(key, (UIntPtr)key.Length, score, member, (UIntPtr)member.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static bool ZAdd(byte[] key, double score, byte[] member, ZAddFlags flags)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zadd_flags_bm_win(key, (UIntPtr)key.Length, score, member, (UIntPtr)member.Length, (int)flags);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zadd_flags_bm_linux(key, (UIntPtr)key.Length, score, member, (UIntPtr)member.Length, (int)flags);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zadd_flags_bm_mac(key, (UIntPtr)key.Length, score, member, (UIntPtr)member.Length, (int)flags);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool ZRem(byte[] key, byte[] member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zrem_bm_win(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zrem_bm_linux(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zrem_bm_mac(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static double ZIncrBy(byte[] key, double increment, byte[] member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        double score;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zincrby_bm_win(key, (UIntPtr)key.Length, increment, member, (UIntPtr)member.Length, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zincrby_bm_linux(key, (UIntPtr)key.Length, increment, member, (UIntPtr)member.Length, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zincrby_bm_mac(key, (UIntPtr)key.Length, increment, member, (UIntPtr)member.Length, out score);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return score;
+    }
+
+    public static double? ZScore(byte[] key, byte[] member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        double score;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zscore_bm_win(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zscore_bm_linux(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zscore_bm_mac(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length, out score);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1 ? score : null;
+    }
+
+    public static long? ZRank(byte[] key, byte[] member, bool reverse = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        var rev = reverse ? 1 : 0;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zrank_bm_win(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length, rev);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zrank_bm_linux(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length, rev);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zrank_bm_mac(key, (UIntPtr)key.Length, member, (UIntPtr)member.Length, rev);
+        else
+            throw new PlatformNotSupportedException();
+        if (res == (long)CacheStatus.NotFound)
+            return null;
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // The members ranked `start` to `end` as raw bytes.
+    public static List<byte[]> ZRangeBytes(byte[] key, int start, int end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrange_b_win(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrange_b_linux(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrange_b_mac(key, (UIntPtr)key.Length, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<(byte[] Member, double Score)> ZRangeWithScoresBytes(byte[] key, int start, int end, bool reverse = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        var rev = reverse ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrange_withscores_b_win(key, (UIntPtr)key.Length, start, end, rev, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrange_withscores_b_linux(key, (UIntPtr)key.Length, start, end, rev, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrange_withscores_b_mac(key, (UIntPtr)key.Length, start, end, rev, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<(byte[], double)>();
+        }
+
+        return ParseBinaryScoredBlob(CopyAndFree(ptr, len));
+    }
+
+    // Bounds are raw bytes prefixed with `[` (inclusive) or `(` (exclusive), or `-` / `+`.
+    public static List<byte[]> ZRangeByLex(byte[] key, byte[] min, byte[] max, bool reverse = false, int offset = 0, int count = -1)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(min);
+        ArgumentNullException.ThrowIfNull(max);
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        var rev = reverse ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrangebylex_bm_win(key, (UIntPtr)key.Length, min, (UIntPtr)min.Length, max, (UIntPtr)max.Length, rev, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrangebylex_bm_linux(key, (UIntPtr)key.Length, min, (UIntPtr)min.Length, max, (UIntPtr)max.Length, rev, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrangebylex_bm_mac(key, (UIntPtr)key.Length, min, (UIntPtr)min.Length, max, (UIntPtr)max.Length, rev, (UIntPtr)offset, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    private static List<KeyValuePair<byte[], byte[]>> ParseBinaryPairsBlob(byte[] blob)
+    {
+        // format: [Count (u32)] [KeyLen (u32)] [Key] [ValLen (u32)] [Val] ...
+        var result = new List<KeyValuePair<byte[], byte[]>>();
+        if (blob.Length < 4)
+            return result;
+
+        int offset = 0;
+        uint count = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+
+        for (uint i = 0; i < count; i++)
+        {
+            if (offset + 4 > blob.Length) break;
+            uint keyLen = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (offset + keyLen > blob.Length) break;
+            var field = blob.AsSpan(offset, (int)keyLen).ToArray();
+            offset += (int)keyLen;
+
+            if (offset + 4 > blob.Length) break;
+            uint valLen = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (offset + valLen > blob.Length) break;
+            var val = blob.AsSpan(offset, (int)valLen).ToArray();
+            offset += (int)valLen;
+
+            result.Add(new KeyValuePair<byte[], byte[]>(field, val));
+        }
+
+        return result;
+    }
+
+    private static List<(byte[] Member, double Score)> ParseBinaryScoredBlob(byte[] blob)
+    {
+        // format: [Count (u32)] [MemberLen (u32)] [Member] [Score (f64)] ...
+        var result = new List<(byte[], double)>();
+        if (blob.Length < 4)
+            return result;
+
+        int offset = 0;
+        uint count = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+
+        for (uint i = 0; i < count; i++)
+        {
+            if (offset + 4 > blob.Length) break;
+            uint mlen = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (offset + mlen + 8 > blob.Length) break;
+
+            var member = blob.AsSpan(offset, (int)mlen).ToArray();
+            offset += (int)mlen;
+            double score = BinaryPrimitives.ReadDoubleLittleEndian(blob.AsSpan(offset, 8));
+            offset += 8;
+            result.Add((member, score));
+        }
+
+        return result;
+    }
+
+    #endregion
+}
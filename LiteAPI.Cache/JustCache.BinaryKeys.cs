@@ -0,0 +1,3008 @@
+using System.Buffers.Binary;
+using System.Runtime.InteropServices;
+using System.Text;
+
+namespace LiteAPI.Cache;
+
+// Overloads taking byte[] keys, backed by the cache_*_b exports. Keys are used byte for byte and
+// share one keyspace with string keys: a string key is the same key as its UTF-8 bytes.
+public static partial class JustCache
+{
+    #region Binary keys: Strings
+
+    [DllImport(WindowsLib, EntryPoint = "cache_incrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrby_b_win(byte[] key, UIntPtr keyLen, long incr, out long value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_incrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrby_b_linux(byte[] key, UIntPtr keyLen, long incr, out long value);
+
+    [DllImport(MacLib, EntryPoint = "cache_incrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrby_b_mac(byte[] key, UIntPtr keyLen, long incr, out long value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_incrbyfloat_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrbyfloat_b_win(byte[] key, UIntPtr keyLen, double incr, out double value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_incrbyfloat_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrbyfloat_b_linux(byte[] key, UIntPtr keyLen, double incr, out double value);
+
+    [DllImport(MacLib, EntryPoint = "cache_incrbyfloat_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrbyfloat_b_mac(byte[] key, UIntPtr keyLen, double incr, out double value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_append_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_append_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_append_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_append_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_append_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_append_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_setrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_setrange_b_win(byte[] key, UIntPtr keyLen, ulong offset, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_setrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_setrange_b_linux(byte[] key, UIntPtr keyLen, ulong offset, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_setrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_setrange_b_mac(byte[] key, UIntPtr keyLen, ulong offset, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_getrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getrange_b_win(byte[] key, UIntPtr keyLen, long start, long end, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_getrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getrange_b_linux(byte[] key, UIntPtr keyLen, long start, long end, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_getrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getrange_b_mac(byte[] key, UIntPtr keyLen, long start, long end, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_strlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_strlen_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_strlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_strlen_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_strlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_strlen_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_getset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getset_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, out UIntPtr outLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_getset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getset_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, out UIntPtr outLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_getset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getset_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, out UIntPtr outLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_getdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getdel_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_getdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getdel_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_getdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getdel_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_set_ex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_ex_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, int flags, ulong ttl, out IntPtr oldValue, out UIntPtr oldLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_set_ex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_ex_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, int flags, ulong ttl, out IntPtr oldValue, out UIntPtr oldLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_set_ex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_ex_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, int flags, ulong ttl, out IntPtr oldValue, out UIntPtr oldLen);
+
+
+    public static long IncrBy(byte[] key, long increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        int res;
+        long value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_incrby_b_win(key, (UIntPtr)key.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_incrby_b_linux(key, (UIntPtr)key.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_incrby_b_mac(key, (UIntPtr)key.Length, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    public static long Incr(byte[] key) => IncrBy(key, 1);
+
+    public static long Decr(byte[] key) => IncrBy(key, -1);
+
+    public static long DecrBy(byte[] key, long decrement)
+    {
+        if (decrement == long.MinValue)
+            throw new ArgumentException("decrement would overflow", nameof(decrement));
+        return IncrBy(key, -decrement);
+    }
+
+    public static double IncrByFloat(byte[] key, double increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        int res;
+        double value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_incrbyfloat_b_win(key, (UIntPtr)key.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_incrbyfloat_b_linux(key, (UIntPtr)key.Length, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_incrbyfloat_b_mac(key, (UIntPtr)key.Length, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    public static long Append(byte[] key, byte[] val)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_append_b_win(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_append_b_linux(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_append_b_mac(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long SetRange(byte[] key, long offset, byte[] val)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_setrange_b_win(key, (UIntPtr)key.Length, (ulong)offset, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_setrange_b_linux(key, (UIntPtr)key.Length, (ulong)offset, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_setrange_b_mac(key, (UIntPtr)key.Length, (ulong)offset, val, (UIntPtr)val.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static byte[] GetRange(byte[] key, long start, long end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_getrange_b_win(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_getrange_b_linux(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_getrange_b_mac(key, (UIntPtr)key.Length, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return Array.Empty<byte>();
+        }
+        return CopyAndFree(ptr, len);
+    }
+
+    public static long StrLen(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_strlen_b_win(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_strlen_b_linux(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_strlen_b_mac(key, (UIntPtr)key.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static byte[]? GetSet(byte[] key, byte[] val)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_getset_b_win(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_getset_b_linux(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_getset_b_mac(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+        return TakeStringResult(ptr, len);
+    }
+
+    public static byte[]? GetDel(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_getdel_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_getdel_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_getdel_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+        return TakeStringResult(ptr, len);
+    }
+
+    public static bool SetEx(byte[] key, byte[] val, SetFlags flags = SetFlags.None, TimeSpan? ttl = null)
+    {
+        if (ttl is { } t && t <= TimeSpan.Zero)
+            throw new ArgumentOutOfRangeException(nameof(ttl));
+        return SetExCore(key, val, flags & ~(SetFlags.Get | SetFlags.ExAt), ttl is { } d ? (ulong)d.TotalMilliseconds : 0).Written;
+    }
+
+    public static bool SetEx(byte[] key, byte[] val, SetFlags flags, DateTimeOffset expireAt)
+    {
+        var at = expireAt.ToUnixTimeMilliseconds();
+        if (at <= 0)
+            throw new ArgumentOutOfRangeException(nameof(expireAt));
+        return SetExCore(key, val, (flags & ~SetFlags.Get) | SetFlags.ExAt, (ulong)at).Written;
+    }
+
+    public static (bool Written, byte[]? Old) SetExGet(byte[] key, byte[] val, SetFlags flags = SetFlags.None, TimeSpan? ttl = null)
+    {
+        if (ttl is { } t && t <= TimeSpan.Zero)
+            throw new ArgumentOutOfRangeException(nameof(ttl));
+        return SetExCore(key, val, (flags & ~SetFlags.ExAt) | SetFlags.Get, ttl is { } d ? (ulong)d.TotalMilliseconds : 0);
+    }
+
+    private static (bool Written, byte[]? Old) SetExCore(byte[] key, byte[] val, SetFlags flags, ulong ttl)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        int res;
+        IntPtr old;
+        UIntPtr oldLen;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_set_ex_b_win(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length, (int)flags, ttl, out old, out oldLen);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_set_ex_b_linux(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length, (int)flags, ttl, out old, out oldLen);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_set_ex_b_mac(key, (UIntPtr)key.Length, val, (UIntPtr)val.Length, (int)flags, ttl, out old, out oldLen);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+
+        if (!flags.HasFlag(SetFlags.Get))
+            return (res == 1, null);
+        if (old != IntPtr.Zero && oldLen != UIntPtr.Zero)
+            return (res == 1, CopyAndFree(old, oldLen));
+        return (res == 1, oldLen == UIntPtr.MaxValue ? null : Array.Empty<byte>());
+    }
+
+    public static void SetString(byte[] key, string val)
+    {
+        ArgumentNullException.ThrowIfNull(val);
+
+        // Convert string to byte array
+        byte[] bytes = System.Text.Encoding.UTF8.GetBytes(val);
+        Set(key, bytes);
+    }
+
+    public static string? GetString(byte[] key)
+    {
+        byte[]? bytes = Get(key);
+        if (bytes == null)
+            return null;
+
+        // Convert byte array to string
+        return System.Text.Encoding.UTF8.GetString(bytes);
+    }
+
+    public static bool TryGet(byte[] key, out byte[] value)
+    {
+        var bytes = Get(key);
+        if (bytes == null)
+        {
+            value = Array.Empty<byte>();
+            return false;
+        }
+        value = bytes;
+        return true;
+    }
+
+    public static bool TryGetString(byte[] key, out string value)
+    {
+        var s = GetString(key);
+        if (s == null)
+        {
+            value = string.Empty;
+            return false;
+        }
+        value = s;
+        return true;
+    }
+
+    #endregion
+
+    #region Binary keys: TTL
+
+    [DllImport(WindowsLib, EntryPoint = "cache_set_with_ttl_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_with_ttl_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, ulong ttlMs);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_set_with_ttl_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_with_ttl_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, ulong ttlMs);
+
+    [DllImport(MacLib, EntryPoint = "cache_set_with_ttl_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_with_ttl_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len, ulong ttlMs);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_expire_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_expire_b_win(byte[] key, UIntPtr keyLen, ulong ttlMs);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_expire_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_expire_b_linux(byte[] key, UIntPtr keyLen, ulong ttlMs);
+
+    [DllImport(MacLib, EntryPoint = "cache_expire_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_expire_b_mac(byte[] key, UIntPtr keyLen, ulong ttlMs);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_ttl_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_ttl_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_ttl_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_ttl_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_ttl_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_ttl_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    public static void SetWithTtl(byte[] key, byte[] val, TimeSpan ttl)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        ulong ttlMs = (ulong)Math.Max(0, (long)ttl.TotalMilliseconds);
+        var len = (UIntPtr)val.Length;
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_set_with_ttl_b_win(key, (UIntPtr)key.Length, val, len, ttlMs);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_set_with_ttl_b_linux(key, (UIntPtr)key.Length, val, len, ttlMs);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_set_with_ttl_b_mac(key, (UIntPtr)key.Length, val, len, ttlMs);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static void SetStringWithTtl(byte[] key, string val, TimeSpan ttl)
+    {
+        ArgumentNullException.ThrowIfNull(val);
+        SetWithTtl(key, System.Text.Encoding.UTF8.GetBytes(val), ttl);
+    }
+
+    public static bool Expire(byte[] key, TimeSpan ttl)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        ulong ttlMs = (ulong)Math.Max(0, (long)ttl.TotalMilliseconds);
+        int res;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_expire_b_win(key, (UIntPtr)key.Length, ttlMs);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_expire_b_linux(key, (UIntPtr)key.Length, ttlMs);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_expire_b_mac(key, (UIntPtr)key.Length, ttlMs);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (res == (int)CacheStatus.NotFound)
+            return false;
+        ThrowIfRejected(res);
+        return true;
+    }
+
+    public static long TtlMs(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            return cache_ttl_b_win(key, (UIntPtr)key.Length);
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            return cache_ttl_b_linux(key, (UIntPtr)key.Length);
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            return cache_ttl_b_mac(key, (UIntPtr)key.Length);
+
+        throw new PlatformNotSupportedException();
+    }
+
+    #endregion
+
+    #region Binary keys: Hashes
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hset_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hset_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hset_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hget_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hget_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hget_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hgetall_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hgetall_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hgetall_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hgetall_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hgetall_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hgetall_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hsetnx_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hsetnx_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hsetnx_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hmset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hmset_b_win(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hmset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hmset_b_linux(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_hmset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hmset_b_mac(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hmget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hmget_b_win(byte[] key, UIntPtr keyLen, byte[] fields, UIntPtr fieldsLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hmget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hmget_b_linux(byte[] key, UIntPtr keyLen, byte[] fields, UIntPtr fieldsLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hmget_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hmget_b_mac(byte[] key, UIntPtr keyLen, byte[] fields, UIntPtr fieldsLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(MacLib, EntryPoint = "cache_hdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hexists_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hexists_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(MacLib, EntryPoint = "cache_hexists_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hlen_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hlen_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_hlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hlen_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hkeys_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hkeys_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hkeys_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hkeys_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hkeys_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hkeys_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hvals_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hvals_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hvals_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hvals_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hvals_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hvals_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hincrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, long increment, out long value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hincrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, long increment, out long value);
+
+    [DllImport(MacLib, EntryPoint = "cache_hincrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, long increment, out long value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hincrbyfloat_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, double increment, out double value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hincrbyfloat_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, double increment, out double value);
+
+    [DllImport(MacLib, EntryPoint = "cache_hincrbyfloat_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, double increment, out double value);
+
+
+    public static void HSet(byte[] key, string field, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hset_b_win(key, (UIntPtr)key.Length, field, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hset_b_linux(key, (UIntPtr)key.Length, field, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hset_b_mac(key, (UIntPtr)key.Length, field, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static void HSetString(byte[] key, string field, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        HSet(key, field, Encoding.UTF8.GetBytes(value));
+    }
+
+    public static byte[]? HGet(byte[] key, string field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hget_b_win(key, (UIntPtr)key.Length, field, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hget_b_linux(key, (UIntPtr)key.Length, field, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hget_b_mac(key, (UIntPtr)key.Length, field, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? HGetString(byte[] key, string field)
+    {
+        var bytes = HGet(key, field);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    public static Dictionary<string, byte[]> HGetAll(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hgetall_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hgetall_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hgetall_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new Dictionary<string, byte[]>(StringComparer.Ordinal);
+        }
+
+        var blob = CopyAndFree(ptr, len);
+        return ParseHGetAllBlob(blob);
+    }
+
+    public static bool HSetNx(byte[] key, string field, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hsetnx_b_win(key, (UIntPtr)key.Length, field, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hsetnx_b_linux(key, (UIntPtr)key.Length, field, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hsetnx_b_mac(key, (UIntPtr)key.Length, field, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static long HMSet(byte[] key, IEnumerable<KeyValuePair<string, byte[]>> fields)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(fields);
+
+        var items = new List<byte[]>();
+        foreach (var (field, value) in fields)
+        {
+            ArgumentNullException.ThrowIfNull(field, nameof(fields));
+            items.Add(Encoding.UTF8.GetBytes(field));
+            items.Add(value ?? throw new ArgumentNullException(nameof(fields)));
+        }
+        var blob = PackItems(items);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hmset_b_win(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hmset_b_linux(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hmset_b_mac(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<byte[]?> HMGet(byte[] key, IEnumerable<string> fields)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var blob = PackKeys(fields);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hmget_b_win(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hmget_b_linux(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hmget_b_mac(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]?>();
+        }
+
+        return ParseOptionalItemsBlob(CopyAndFree(ptr, len));
+    }
+
+    public static bool HDel(byte[] key, string field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hdel_b_win(key, (UIntPtr)key.Length, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hdel_b_linux(key, (UIntPtr)key.Length, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hdel_b_mac(key, (UIntPtr)key.Length, field);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool HExists(byte[] key, string field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hexists_b_win(key, (UIntPtr)key.Length, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hexists_b_linux(key, (UIntPtr)key.Length, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hexists_b_mac(key, (UIntPtr)key.Length, field);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static long HLen(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hlen_b_win(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hlen_b_linux(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hlen_b_mac(key, (UIntPtr)key.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<string> HKeys(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hkeys_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hkeys_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hkeys_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len)).Select(Encoding.UTF8.GetString).ToList();
+    }
+
+    public static List<byte[]> HVals(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hvals_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hvals_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hvals_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static long HIncrBy(byte[] key, string field, long increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        long value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hincrby_b_win(key, (UIntPtr)key.Length, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hincrby_b_linux(key, (UIntPtr)key.Length, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hincrby_b_mac(key, (UIntPtr)key.Length, field, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    public static double HIncrByFloat(byte[] key, string field, double increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        double value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hincrbyfloat_b_win(key, (UIntPtr)key.Length, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hincrbyfloat_b_linux(key, (UIntPtr)key.Length, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hincrbyfloat_b_mac(key, (UIntPtr)key.Length, field, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    #endregion
+
+    #region Binary keys: Lists
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lpush_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lpush_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lpush_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lpush_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lpush_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lpush_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_rpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_rpop_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_rpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_rpop_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_rpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_rpop_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lrange_b_win(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lrange_b_linux(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lrange_b_mac(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_rpush_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_rpush_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_rpush_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_rpush_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_rpush_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_rpush_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lpush_multi_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lpush_multi_b_win(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lpush_multi_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lpush_multi_b_linux(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_lpush_multi_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lpush_multi_b_mac(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_rpush_multi_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_rpush_multi_b_win(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_rpush_multi_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_rpush_multi_b_linux(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_rpush_multi_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_rpush_multi_b_mac(byte[] key, UIntPtr keyLen, byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lpop_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lpop_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lpop_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_llen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_llen_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_llen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_llen_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_llen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_llen_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lindex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lindex_b_win(byte[] key, UIntPtr keyLen, int index, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lindex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lindex_b_linux(byte[] key, UIntPtr keyLen, int index, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lindex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lindex_b_mac(byte[] key, UIntPtr keyLen, int index, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lset_b_win(byte[] key, UIntPtr keyLen, int index, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lset_b_linux(byte[] key, UIntPtr keyLen, int index, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lset_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lset_b_mac(byte[] key, UIntPtr keyLen, int index, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_linsert_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_linsert_b_win(byte[] key, UIntPtr keyLen, int before, byte[] pivot, UIntPtr pivotLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_linsert_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_linsert_b_linux(byte[] key, UIntPtr keyLen, int before, byte[] pivot, UIntPtr pivotLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_linsert_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_linsert_b_mac(byte[] key, UIntPtr keyLen, int before, byte[] pivot, UIntPtr pivotLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lrem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lrem_b_win(byte[] key, UIntPtr keyLen, long count, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lrem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lrem_b_linux(byte[] key, UIntPtr keyLen, long count, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lrem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lrem_b_mac(byte[] key, UIntPtr keyLen, long count, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_ltrim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_ltrim_b_win(byte[] key, UIntPtr keyLen, int start, int end);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_ltrim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_ltrim_b_linux(byte[] key, UIntPtr keyLen, int start, int end);
+
+    [DllImport(MacLib, EntryPoint = "cache_ltrim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_ltrim_b_mac(byte[] key, UIntPtr keyLen, int start, int end);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lmove_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lmove_b_win(byte[] src, UIntPtr srcLen, byte[] dst, UIntPtr dstLen, int fromLeft, int toLeft, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lmove_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lmove_b_linux(byte[] src, UIntPtr srcLen, byte[] dst, UIntPtr dstLen, int fromLeft, int toLeft, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lmove_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lmove_b_mac(byte[] src, UIntPtr srcLen, byte[] dst, UIntPtr dstLen, int fromLeft, int toLeft, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_blpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_blpop_b_win(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_blpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_blpop_b_linux(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_blpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_blpop_b_mac(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_brpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_brpop_b_win(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_brpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_brpop_b_linux(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_brpop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_brpop_b_mac(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+
+    public static void LPush(byte[] key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lpush_b_win(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lpush_b_linux(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lpush_b_mac(key, (UIntPtr)key.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static void LPushString(byte[] key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        LPush(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    public static byte[]? RPop(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_rpop_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_rpop_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_rpop_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? RPopString(byte[] key)
+    {
+        var bytes = RPop(key);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    public static List<byte[]> LRange(byte[] key, int start, int end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lrange_b_win(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lrange_b_linux(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lrange_b_mac(key, (UIntPtr)key.Length, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        var blob = CopyAndFree(ptr, len);
+        return ParseListRangeBlob(blob);
+    }
+
+    public static List<string> LRangeStrings(byte[] key, int start, int end)
+    {
+        var items = LRange(key, start, end);
+        var result = new List<string>(items.Count);
+        foreach (var item in items)
+        {
+            result.Add(Encoding.UTF8.GetString(item));
+        }
+        return result;
+    }
+
+    public static void RPush(byte[] key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_rpush_b_win(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_rpush_b_linux(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_rpush_b_mac(key, (UIntPtr)key.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static void RPushString(byte[] key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        RPush(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    public static long LPushRange(byte[] key, IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var packed = PackItems(values);
+        var len = (UIntPtr)packed.Length;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lpush_multi_b_win(key, (UIntPtr)key.Length, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lpush_multi_b_linux(key, (UIntPtr)key.Length, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lpush_multi_b_mac(key, (UIntPtr)key.Length, packed, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long RPushRange(byte[] key, IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var packed = PackItems(values);
+        var len = (UIntPtr)packed.Length;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_rpush_multi_b_win(key, (UIntPtr)key.Length, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_rpush_multi_b_linux(key, (UIntPtr)key.Length, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_rpush_multi_b_mac(key, (UIntPtr)key.Length, packed, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static byte[]? LPop(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lpop_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lpop_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lpop_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? LPopString(byte[] key)
+    {
+        var bytes = LPop(key);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    public static long LLen(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_llen_b_win(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_llen_b_linux(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_llen_b_mac(key, (UIntPtr)key.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static byte[]? LIndex(byte[] key, int index)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lindex_b_win(key, (UIntPtr)key.Length, index, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lindex_b_linux(key, (UIntPtr)key.Length, index, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lindex_b_mac(key, (UIntPtr)key.Length, index, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? LIndexString(byte[] key, int index)
+    {
+        var bytes = LIndex(key, index);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    public static void LSet(byte[] key, int index, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lset_b_win(key, (UIntPtr)key.Length, index, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lset_b_linux(key, (UIntPtr)key.Length, index, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lset_b_mac(key, (UIntPtr)key.Length, index, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static long LInsert(byte[] key, bool before, byte[] pivot, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(pivot);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var pivotLen = (UIntPtr)pivot.Length;
+        var len = (UIntPtr)value.Length;
+        var beforeFlag = before ? 1 : 0;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_linsert_b_win(key, (UIntPtr)key.Length, beforeFlag, pivot, pivotLen, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_linsert_b_linux(key, (UIntPtr)key.Length, beforeFlag, pivot, pivotLen, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_linsert_b_mac(key, (UIntPtr)key.Length, beforeFlag, pivot, pivotLen, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        if (res == (long)CacheStatus.NotFound)
+            return -1;
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long LRem(byte[] key, long count, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lrem_b_win(key, (UIntPtr)key.Length, count, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lrem_b_linux(key, (UIntPtr)key.Length, count, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lrem_b_mac(key, (UIntPtr)key.Length, count, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static void LTrim(byte[] key, int start, int end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_ltrim_b_win(key, (UIntPtr)key.Length, start, end);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_ltrim_b_linux(key, (UIntPtr)key.Length, start, end);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_ltrim_b_mac(key, (UIntPtr)key.Length, start, end);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static byte[]? LMove(byte[] source, byte[] destination, bool fromLeft, bool toLeft)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        ArgumentNullException.ThrowIfNull(source);
+
+        var fromEnd = fromLeft ? 1 : 0;
+        var toEnd = toLeft ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lmove_b_win(source, (UIntPtr)source.Length, destination, (UIntPtr)destination.Length, fromEnd, toEnd, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lmove_b_linux(source, (UIntPtr)source.Length, destination, (UIntPtr)destination.Length, fromEnd, toEnd, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lmove_b_mac(source, (UIntPtr)source.Length, destination, (UIntPtr)destination.Length, fromEnd, toEnd, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    // BLPop/BRPop over binary keys; the key that was popped comes back as its bytes.
+    public static (byte[] Key, byte[] Value)? BLPop(IEnumerable<byte[]> keys, TimeSpan timeout) =>
+        BlockingPop(keys, timeout, left: true);
+
+    public static (byte[] Key, byte[] Value)? BRPop(IEnumerable<byte[]> keys, TimeSpan timeout) =>
+        BlockingPop(keys, timeout, left: false);
+
+    private static (byte[] Key, byte[] Value)? BlockingPop(IEnumerable<byte[]> keys, TimeSpan timeout, bool left)
+    {
+        var blob = PackItems(keys);
+        var timeoutMs = BlockTimeoutMs(timeout);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = left ? cache_blpop_b_win(blob, (UIntPtr)blob.Length, timeoutMs, out len) : cache_brpop_b_win(blob, (UIntPtr)blob.Length, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = left ? cache_blpop_b_linux(blob, (UIntPtr)blob.Length, timeoutMs, out len) : cache_brpop_b_linux(blob, (UIntPtr)blob.Length, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = left ? cache_blpop_b_mac(blob, (UIntPtr)blob.Length, timeoutMs, out len) : cache_brpop_b_mac(blob, (UIntPtr)blob.Length, timeoutMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        var items = ParseListRangeBlob(CopyAndFree(ptr, len));
+        return (items[0], items[1]);
+    }
+
+    #endregion
+
+    #region Binary keys: Sets
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_sadd_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_sadd_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_sadd_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sismember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_sismember_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sismember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_sismember_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sismember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_sismember_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_srem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_srem_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_srem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_srem_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_srem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_srem_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_smismember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smismember_b_win(byte[] key, UIntPtr keyLen, byte[] members, UIntPtr membersLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_smismember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smismember_b_linux(byte[] key, UIntPtr keyLen, byte[] members, UIntPtr membersLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_smismember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smismember_b_mac(byte[] key, UIntPtr keyLen, byte[] members, UIntPtr membersLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_scard_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_scard_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_scard_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_scard_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_scard_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_scard_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_smembers_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smembers_b_win(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_smembers_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smembers_b_linux(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_smembers_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smembers_b_mac(byte[] key, UIntPtr keyLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_spop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_spop_b_win(byte[] key, UIntPtr keyLen, UIntPtr count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_spop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_spop_b_linux(byte[] key, UIntPtr keyLen, UIntPtr count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_spop_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_spop_b_mac(byte[] key, UIntPtr keyLen, UIntPtr count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_srandmember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_srandmember_b_win(byte[] key, UIntPtr keyLen, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_srandmember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_srandmember_b_linux(byte[] key, UIntPtr keyLen, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_srandmember_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_srandmember_b_mac(byte[] key, UIntPtr keyLen, long count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sunion_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sunion_b_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sunion_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sunion_b_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sunion_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sunion_b_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sinter_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sinter_b_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sinter_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sinter_b_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sinter_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sinter_b_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sdiff_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sdiff_b_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sdiff_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sdiff_b_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sdiff_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sdiff_b_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sunionstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sunionstore_b_win(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sunionstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sunionstore_b_linux(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_sunionstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sunionstore_b_mac(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sinterstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sinterstore_b_win(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sinterstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sinterstore_b_linux(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_sinterstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sinterstore_b_mac(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sdiffstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sdiffstore_b_win(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sdiffstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sdiffstore_b_linux(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_sdiffstore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sdiffstore_b_mac(byte[] dst, UIntPtr dstLen, byte[] keys, UIntPtr keysLen);
+
+
+    public static bool SAdd(byte[] key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sadd_b_win(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sadd_b_linux(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sadd_b_mac(key, (UIntPtr)key.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res > 0;
+    }
+
+    public static bool SAddString(byte[] key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        return SAdd(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    public static bool SIsMember(byte[] key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sismember_b_win(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sismember_b_linux(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sismember_b_mac(key, (UIntPtr)key.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool SIsMemberString(byte[] key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        return SIsMember(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    public static bool SRem(byte[] key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_srem_b_win(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_srem_b_linux(key, (UIntPtr)key.Length, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_srem_b_mac(key, (UIntPtr)key.Length, value, len);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool SRemString(byte[] key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        return SRem(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    public static bool[] SMIsMember(byte[] key, IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var blob = PackItems(values);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_smismember_b_win(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_smismember_b_linux(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_smismember_b_mac(key, (UIntPtr)key.Length, blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return Array.Empty<bool>();
+        }
+
+        return CopyAndFree(ptr, len).Select(b => b != 0).ToArray();
+    }
+
+    public static long SCard(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_scard_b_win(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_scard_b_linux(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_scard_b_mac(key, (UIntPtr)key.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<byte[]> SMembers(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_smembers_b_win(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_smembers_b_linux(key, (UIntPtr)key.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_smembers_b_mac(key, (UIntPtr)key.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SPop(byte[] key, int count = 1)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_spop_b_win(key, (UIntPtr)key.Length, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_spop_b_linux(key, (UIntPtr)key.Length, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_spop_b_mac(key, (UIntPtr)key.Length, (UIntPtr)count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SRandMember(byte[] key, long count = 1)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_srandmember_b_win(key, (UIntPtr)key.Length, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_srandmember_b_linux(key, (UIntPtr)key.Length, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_srandmember_b_mac(key, (UIntPtr)key.Length, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SUnion(IEnumerable<byte[]> keys)
+    {
+        var blob = PackItems(keys);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_sunion_b_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_sunion_b_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_sunion_b_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SInter(IEnumerable<byte[]> keys)
+    {
+        var blob = PackItems(keys);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_sinter_b_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_sinter_b_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_sinter_b_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SDiff(IEnumerable<byte[]> keys)
+    {
+        var blob = PackItems(keys);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_sdiff_b_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_sdiff_b_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_sdiff_b_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static long SUnionStore(byte[] destination, IEnumerable<byte[]> keys)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        var blob = PackItems(keys);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sunionstore_b_win(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sunionstore_b_linux(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sunionstore_b_mac(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long SInterStore(byte[] destination, IEnumerable<byte[]> keys)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        var blob = PackItems(keys);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sinterstore_b_win(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sinterstore_b_linux(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sinterstore_b_mac(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long SDiffStore(byte[] destination, IEnumerable<byte[]> keys)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        var blob = PackItems(keys);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sdiffstore_b_win(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sdiffstore_b_linux(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sdiffstore_b_mac(destination, (UIntPtr)destination.Length, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    #endregion
+
+    #region Binary keys: Sorted sets
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_b_win(byte[] key, UIntPtr keyLen, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_b_linux(byte[] key, UIntPtr keyLen, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+    [DllImport(MacLib, EntryPoint = "cache_zadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_b_mac(byte[] key, UIntPtr keyLen, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_b_win(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_b_linux(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_b_mac(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zadd_flags_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_b_win(byte[] key, UIntPtr keyLen, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int flags);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zadd_flags_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_b_linux(byte[] key, UIntPtr keyLen, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int flags);
+
+    [DllImport(MacLib, EntryPoint = "cache_zadd_flags_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_b_mac(byte[] key, UIntPtr keyLen, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int flags);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrem_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zincrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_b_win(byte[] key, UIntPtr keyLen, double increment, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zincrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_b_linux(byte[] key, UIntPtr keyLen, double increment, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(MacLib, EntryPoint = "cache_zincrby_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_b_mac(byte[] key, UIntPtr keyLen, double increment, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zscore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zscore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(MacLib, EntryPoint = "cache_zscore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrank_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int rev);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrank_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int rev);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrank_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int rev);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zcard_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zcard_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zcard_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zcard_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_zcard_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zcard_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrevrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrevrange_b_win(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrevrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrevrange_b_linux(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrevrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrevrange_b_mac(byte[] key, UIntPtr keyLen, int start, int end, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrange_withscores_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_withscores_b_win(byte[] key, UIntPtr keyLen, int start, int end, int rev, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrange_withscores_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_withscores_b_linux(byte[] key, UIntPtr keyLen, int start, int end, int rev, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrange_withscores_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_withscores_b_mac(byte[] key, UIntPtr keyLen, int start, int end, int rev, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrangebyscore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebyscore_b_win(byte[] key, UIntPtr keyLen, double min, double max, int flags, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrangebyscore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebyscore_b_linux(byte[] key, UIntPtr keyLen, double min, double max, int flags, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrangebyscore_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebyscore_b_mac(byte[] key, UIntPtr keyLen, double min, double max, int flags, UIntPtr offset, long count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrangebylex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string min, [MarshalAs(UnmanagedType.LPUTF8Str)] string max, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrangebylex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string min, [MarshalAs(UnmanagedType.LPUTF8Str)] string max, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrangebylex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string min, [MarshalAs(UnmanagedType.LPUTF8Str)] string max, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+
+    public static void ZAdd(byte[] key, double score, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zadd_b_win(key, (UIntPtr)key.Length, score, member);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zadd_b_linux(key, (UIntPtr)key.Length, score, member);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zadd_b_mac(key, (UIntPtr)key.Length, score, member);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static List<string> ZRange(byte[] key, int start, int end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrange_b_win(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrange_b_linux(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrange_b_mac(key, (UIntPtr)key.Length, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        var blob = CopyAndFree(ptr, len);
+        return ParseZRangeBlob(blob);
+    }
+
+    public static bool ZAdd(byte[] key, double score, string member, ZAddFlags flags)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zadd_flags_b_win(key, (UIntPtr)key.Length, score, member, (int)flags);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zadd_flags_b_linux(key, (UIntPtr)key.Length, score, member, (int)flags);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zadd_flags_b_mac(key, (UIntPtr)key.Length, score, member, (int)flags);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool ZRem(byte[] key, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zrem_b_win(key, (UIntPtr)key.Length, member);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zrem_b_linux(key, (UIntPtr)key.Length, member);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zrem_b_mac(key, (UIntPtr)key.Length, member);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static double ZIncrBy(byte[] key, double increment, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        double score;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zincrby_b_win(key, (UIntPtr)key.Length, increment, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zincrby_b_linux(key, (UIntPtr)key.Length, increment, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zincrby_b_mac(key, (UIntPtr)key.Length, increment, member, out score);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return score;
+    }
+
+    public static double? ZScore(byte[] key, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        double score;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zscore_b_win(key, (UIntPtr)key.Length, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zscore_b_linux(key, (UIntPtr)key.Length, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zscore_b_mac(key, (UIntPtr)key.Length, member, out score);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1 ? score : null;
+    }
+
+    public static long? ZRank(byte[] key, string member, bool reverse = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        var rev = reverse ? 1 : 0;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zrank_b_win(key, (UIntPtr)key.Length, member, rev);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zrank_b_linux(key, (UIntPtr)key.Length, member, rev);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zrank_b_mac(key, (UIntPtr)key.Length, member, rev);
+        else
+            throw new PlatformNotSupportedException();
+        if (res == (long)CacheStatus.NotFound)
+            return null;
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long ZCard(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zcard_b_win(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zcard_b_linux(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zcard_b_mac(key, (UIntPtr)key.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<string> ZRevRange(byte[] key, int start, int end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrevrange_b_win(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrevrange_b_linux(key, (UIntPtr)key.Length, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrevrange_b_mac(key, (UIntPtr)key.Length, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        return ParseZRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<(string Member, double Score)> ZRangeWithScores(byte[] key, int start, int end, bool reverse = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        var rev = reverse ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrange_withscores_b_win(key, (UIntPtr)key.Length, start, end, rev, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrange_withscores_b_linux(key, (UIntPtr)key.Length, start, end, rev, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrange_withscores_b_mac(key, (UIntPtr)key.Length, start, end, rev, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<(string, double)>();
+        }
+
+        return ParseScoredBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<(string Member, double Score)> ZRangeByScore(
+        byte[] key,
+        double min,
+        double max,
+        bool minExclusive = false,
+        bool maxExclusive = false,
+        bool reverse = false,
+        int offset = 0,
+        int count = -1)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        var flags = (minExclusive ? 1 : 0) | (maxExclusive ? 2 : 0) | (reverse ? 4 : 0);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrangebyscore_b_win(key, (UIntPtr)key.Length, min, max, flags, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrangebyscore_b_linux(key, (UIntPtr)key.Length, min, max, flags, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrangebyscore_b_mac(key, (UIntPtr)key.Length, min, max, flags, (UIntPtr)offset, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<(string, double)>();
+        }
+
+        return ParseScoredBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<string> ZRangeByLex(byte[] key, string min, string max, bool reverse = false, int offset = 0, int count = -1)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(min);
+        ArgumentNullException.ThrowIfNull(max);
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        var rev = reverse ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrangebylex_b_win(key, (UIntPtr)key.Length, min, max, rev, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrangebylex_b_linux(key, (UIntPtr)key.Length, min, max, rev, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrangebylex_b_mac(key, (UIntPtr)key.Length, min, max, rev, (UIntPtr)offset, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        return ParseZRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    #endregion
+
+    #region Binary keys: Streams
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern StreamId cache_xadd_b_win(byte[] key, UIntPtr keyLen, byte[] payload, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern StreamId cache_xadd_b_linux(byte[] key, UIntPtr keyLen, byte[] payload, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xadd_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern StreamId cache_xadd_b_mac(byte[] key, UIntPtr keyLen, byte[] payload, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xadd_ex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe int cache_xadd_ex_b_win(byte[] key, UIntPtr keyLen, byte[] fields, UIntPtr fieldsLen, StreamId* id, long maxLen, StreamId* minId, out StreamId outId);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xadd_ex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe int cache_xadd_ex_b_linux(byte[] key, UIntPtr keyLen, byte[] fields, UIntPtr fieldsLen, StreamId* id, long maxLen, StreamId* minId, out StreamId outId);
+
+    [DllImport(MacLib, EntryPoint = "cache_xadd_ex_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe int cache_xadd_ex_b_mac(byte[] key, UIntPtr keyLen, byte[] fields, UIntPtr fieldsLen, StreamId* id, long maxLen, StreamId* minId, out StreamId outId);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrange_b_win(byte[] key, UIntPtr keyLen, StreamId startId, StreamId endId, UIntPtr count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrange_b_linux(byte[] key, UIntPtr keyLen, StreamId startId, StreamId endId, UIntPtr count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrange_b_mac(byte[] key, UIntPtr keyLen, StreamId startId, StreamId endId, UIntPtr count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xrevrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrevrange_b_win(byte[] key, UIntPtr keyLen, StreamId endId, StreamId startId, UIntPtr count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xrevrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrevrange_b_linux(byte[] key, UIntPtr keyLen, StreamId endId, StreamId startId, UIntPtr count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xrevrange_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrevrange_b_mac(byte[] key, UIntPtr keyLen, StreamId endId, StreamId startId, UIntPtr count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xtrim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe long cache_xtrim_b_win(byte[] key, UIntPtr keyLen, long maxLen, StreamId* minId);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xtrim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe long cache_xtrim_b_linux(byte[] key, UIntPtr keyLen, long maxLen, StreamId* minId);
+
+    [DllImport(MacLib, EntryPoint = "cache_xtrim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe long cache_xtrim_b_mac(byte[] key, UIntPtr keyLen, long maxLen, StreamId* minId);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xdel_b_win(byte[] key, UIntPtr keyLen, StreamId[] ids, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xdel_b_linux(byte[] key, UIntPtr keyLen, StreamId[] ids, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xdel_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xdel_b_mac(byte[] key, UIntPtr keyLen, StreamId[] ids, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xlen_b_win(byte[] key, UIntPtr keyLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xlen_b_linux(byte[] key, UIntPtr keyLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_xlen_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xlen_b_mac(byte[] key, UIntPtr keyLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xread_block_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xread_block_b_win(byte[] keys, UIntPtr keysLen, StreamId[] afterIds, UIntPtr count, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xread_block_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xread_block_b_linux(byte[] keys, UIntPtr keysLen, StreamId[] afterIds, UIntPtr count, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xread_block_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xread_block_b_mac(byte[] keys, UIntPtr keysLen, StreamId[] afterIds, UIntPtr count, ulong timeoutMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xgroup_create_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_create_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId lastId, int mkStream);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xgroup_create_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_create_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId lastId, int mkStream);
+
+    [DllImport(MacLib, EntryPoint = "cache_xgroup_create_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_create_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId lastId, int mkStream);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xgroup_destroy_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_destroy_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xgroup_destroy_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_destroy_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group);
+
+    [DllImport(MacLib, EntryPoint = "cache_xgroup_destroy_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_destroy_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xreadgroup_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xreadgroup_b_win([MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, byte[] keys, UIntPtr keysLen, StreamId[] ids, UIntPtr count, int noAck, long blockMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xreadgroup_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xreadgroup_b_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, byte[] keys, UIntPtr keysLen, StreamId[] ids, UIntPtr count, int noAck, long blockMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xreadgroup_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xreadgroup_b_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, byte[] keys, UIntPtr keysLen, StreamId[] ids, UIntPtr count, int noAck, long blockMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xack_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xack_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId[] ids, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xack_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xack_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId[] ids, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xack_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xack_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId[] ids, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xpending_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xpending_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId startId, StreamId endId, UIntPtr count, [MarshalAs(UnmanagedType.LPUTF8Str)] string? consumer, ulong minIdleMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xpending_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xpending_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId startId, StreamId endId, UIntPtr count, [MarshalAs(UnmanagedType.LPUTF8Str)] string? consumer, ulong minIdleMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xpending_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xpending_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId startId, StreamId endId, UIntPtr count, [MarshalAs(UnmanagedType.LPUTF8Str)] string? consumer, ulong minIdleMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xclaim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xclaim_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId[] ids, UIntPtr idsLen, int justId, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xclaim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xclaim_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId[] ids, UIntPtr idsLen, int justId, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xclaim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xclaim_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId[] ids, UIntPtr idsLen, int justId, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xautoclaim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xautoclaim_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId startId, UIntPtr count, int justId, out StreamId nextId, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xautoclaim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xautoclaim_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId startId, UIntPtr count, int justId, out StreamId nextId, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xautoclaim_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xautoclaim_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId startId, UIntPtr count, int justId, out StreamId nextId, out UIntPtr len);
+
+
+    public static StreamId XAdd(byte[] key, byte[] payload)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(payload);
+
+        var len = (UIntPtr)payload.Length;
+
+        StreamId id;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            id = cache_xadd_b_win(key, (UIntPtr)key.Length, payload, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            id = cache_xadd_b_linux(key, (UIntPtr)key.Length, payload, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            id = cache_xadd_b_mac(key, (UIntPtr)key.Length, payload, len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (id == StreamId.Min)
+            throw ToException(LastStatus);
+        return id;
+    }
+
+    public static StreamId XAdd(byte[] key, byte[] payload, StreamId? id = null, long? maxLen = null, StreamId? minId = null)
+    {
+        ArgumentNullException.ThrowIfNull(payload);
+        return XAdd(key, new[] { new KeyValuePair<string, byte[]>(PayloadField, payload) }, id, maxLen, minId);
+    }
+
+    public static unsafe StreamId XAdd(
+        byte[] key,
+        IEnumerable<KeyValuePair<string, byte[]>> fields,
+        StreamId? id = null,
+        long? maxLen = null,
+        StreamId? minId = null)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(fields);
+        if (maxLen is < 0)
+            throw new ArgumentOutOfRangeException(nameof(maxLen));
+
+        var items = new List<byte[]>();
+        foreach (var (field, value) in fields)
+        {
+            ArgumentNullException.ThrowIfNull(field, nameof(fields));
+            items.Add(Encoding.UTF8.GetBytes(field));
+            items.Add(value ?? throw new ArgumentNullException(nameof(fields)));
+        }
+        if (items.Count == 0)
+            throw new ArgumentException("A stream entry needs at least one field.", nameof(fields));
+
+        var blob = PackItems(items);
+        var len = (UIntPtr)blob.Length;
+        var idValue = id.GetValueOrDefault();
+        var minIdValue = minId.GetValueOrDefault();
+        var idPtr = id.HasValue ? &idValue : null;
+        var minIdPtr = minId.HasValue ? &minIdValue : null;
+        var max = maxLen ?? -1;
+
+        int res;
+        StreamId added;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xadd_ex_b_win(key, (UIntPtr)key.Length, blob, len, idPtr, max, minIdPtr, out added);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xadd_ex_b_linux(key, (UIntPtr)key.Length, blob, len, idPtr, max, minIdPtr, out added);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xadd_ex_b_mac(key, (UIntPtr)key.Length, blob, len, idPtr, max, minIdPtr, out added);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return added;
+    }
+
+    public static List<StreamItem> XRange(byte[] key, StreamId startId, StreamId endId, int count = 0)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xrange_b_win(key, (UIntPtr)key.Length, startId, endId, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xrange_b_linux(key, (UIntPtr)key.Length, startId, endId, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xrange_b_mac(key, (UIntPtr)key.Length, startId, endId, (UIntPtr)count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamItem>();
+        }
+
+        var blob = CopyAndFree(ptr, len);
+        return ParseXRangeBlob(blob);
+    }
+
+    public static List<StreamItem> XRevRange(byte[] key, StreamId endId, StreamId startId, int count = 0)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xrevrange_b_win(key, (UIntPtr)key.Length, endId, startId, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xrevrange_b_linux(key, (UIntPtr)key.Length, endId, startId, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xrevrange_b_mac(key, (UIntPtr)key.Length, endId, startId, (UIntPtr)count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamItem>();
+        }
+
+        return ParseXRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static unsafe long XTrim(byte[] key, long? maxLen = null, StreamId? minId = null)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        if (maxLen is < 0)
+            throw new ArgumentOutOfRangeException(nameof(maxLen));
+
+        var minIdValue = minId.GetValueOrDefault();
+        var minIdPtr = minId.HasValue ? &minIdValue : null;
+        var max = maxLen ?? -1;
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xtrim_b_win(key, (UIntPtr)key.Length, max, minIdPtr);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xtrim_b_linux(key, (UIntPtr)key.Length, max, minIdPtr);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xtrim_b_mac(key, (UIntPtr)key.Length, max, minIdPtr);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long XDel(byte[] key, params StreamId[] ids)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(ids);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xdel_b_win(key, (UIntPtr)key.Length, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xdel_b_linux(key, (UIntPtr)key.Length, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xdel_b_mac(key, (UIntPtr)key.Length, ids, (UIntPtr)ids.Length);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long XLen(byte[] key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xlen_b_win(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xlen_b_linux(key, (UIntPtr)key.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xlen_b_mac(key, (UIntPtr)key.Length);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // XReadBlock over binary keys. Streams come back in the order read, each with its key bytes.
+    public static List<(byte[] Key, List<StreamItem> Items)>? XReadBlock(
+        IReadOnlyList<(byte[] Key, StreamId AfterId)> streams,
+        int count,
+        TimeSpan timeout)
+    {
+        ArgumentNullException.ThrowIfNull(streams);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        var keys = PackItems(streams.Select(s => s.Key));
+        var ids = streams.Select(s => s.AfterId).ToArray();
+        var timeoutMs = BlockTimeoutMs(timeout);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xread_block_b_win(keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xread_block_b_linux(keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xread_block_b_mac(keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, timeoutMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return ParseBinaryStreamReadsBlob(CopyAndFree(ptr, len));
+    }
+
+    public static void XGroupCreate(byte[] key, string group, StreamId? lastDeliveredId = null, bool mkStream = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+
+        var lastId = lastDeliveredId ?? NewEntriesOnly;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xgroup_create_b_win(key, (UIntPtr)key.Length, group, lastId, mkStream ? 1 : 0);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xgroup_create_b_linux(key, (UIntPtr)key.Length, group, lastId, mkStream ? 1 : 0);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xgroup_create_b_mac(key, (UIntPtr)key.Length, group, lastId, mkStream ? 1 : 0);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+    }
+
+    public static bool XGroupDestroy(byte[] key, string group)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xgroup_destroy_b_win(key, (UIntPtr)key.Length, group);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xgroup_destroy_b_linux(key, (UIntPtr)key.Length, group);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xgroup_destroy_b_mac(key, (UIntPtr)key.Length, group);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    // XReadGroup over binary keys, replying like the binary XReadBlock.
+    public static List<(byte[] Key, List<StreamItem> Items)>? XReadGroup(
+        string group,
+        string consumer,
+        IReadOnlyList<(byte[] Key, StreamId AfterId)> streams,
+        int count = 0,
+        bool noAck = false,
+        TimeSpan? block = null)
+    {
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(consumer);
+        ArgumentNullException.ThrowIfNull(streams);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        var keys = PackItems(streams.Select(s => s.Key));
+        var ids = streams.Select(s => s.AfterId).ToArray();
+        long blockMs = block is { } timeout ? (long)BlockTimeoutMs(timeout) : -1;
+        var flag = noAck ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xreadgroup_b_win(group, consumer, keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, flag, blockMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xreadgroup_b_linux(group, consumer, keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, flag, blockMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xreadgroup_b_mac(group, consumer, keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, flag, blockMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return ParseBinaryStreamReadsBlob(CopyAndFree(ptr, len));
+    }
+
+    public static long XAck(byte[] key, string group, params StreamId[] ids)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(ids);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xack_b_win(key, (UIntPtr)key.Length, group, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xack_b_linux(key, (UIntPtr)key.Length, group, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xack_b_mac(key, (UIntPtr)key.Length, group, ids, (UIntPtr)ids.Length);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<StreamPendingEntry> XPending(
+        byte[] key,
+        string group,
+        StreamId? startId = null,
+        StreamId? endId = null,
+        int count = int.MaxValue,
+        string? consumer = null,
+        TimeSpan? minIdle = null)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        var minIdleMs = IdleMs(minIdle ?? TimeSpan.Zero);
+        var start = startId ?? StreamId.Min;
+        var end = endId ?? StreamId.Max;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xpending_b_win(key, (UIntPtr)key.Length, group, start, end, (UIntPtr)count, consumer, minIdleMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xpending_b_linux(key, (UIntPtr)key.Length, group, start, end, (UIntPtr)count, consumer, minIdleMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xpending_b_mac(key, (UIntPtr)key.Length, group, start, end, (UIntPtr)count, consumer, minIdleMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamPendingEntry>();
+        }
+
+        // format: [Count (u32)] { [Ms (u64)] [Seq (u64)] [ConsumerLen (u32)] [Consumer] [IdleMs (u64)] [Deliveries (u64)] } ...
+        var blob = CopyAndFree(ptr, len);
+        var result = new List<StreamPendingEntry>();
+        int offset = 0;
+        uint n = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+        for (uint i = 0; i < n && offset + 20 <= blob.Length; i++)
+        {
+            var id = ReadStreamId(blob, ref offset);
+            int nameLen = (int)BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (offset + nameLen + 16 > blob.Length) break;
+            var name = Encoding.UTF8.GetString(blob, offset, nameLen);
+            offset += nameLen;
+            ulong idleMs = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset, 8));
+            offset += 8;
+            ulong deliveries = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset, 8));
+            offset += 8;
+            result.Add(new StreamPendingEntry(id, name, TimeSpan.FromMilliseconds(idleMs), (long)deliveries));
+        }
+        return result;
+    }
+
+    public static List<StreamItem> XClaim(byte[] key, string group, string consumer, TimeSpan minIdle, IEnumerable<StreamId> ids, bool justId = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(consumer);
+        ArgumentNullException.ThrowIfNull(ids);
+
+        var idArray = ids.ToArray();
+        var minIdleMs = IdleMs(minIdle);
+        var flag = justId ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xclaim_b_win(key, (UIntPtr)key.Length, group, consumer, minIdleMs, idArray, (UIntPtr)idArray.Length, flag, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xclaim_b_linux(key, (UIntPtr)key.Length, group, consumer, minIdleMs, idArray, (UIntPtr)idArray.Length, flag, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xclaim_b_mac(key, (UIntPtr)key.Length, group, consumer, minIdleMs, idArray, (UIntPtr)idArray.Length, flag, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamItem>();
+        }
+
+        return ParseXRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static (StreamId NextStartId, List<StreamItem> Claimed, List<StreamId> Deleted) XAutoClaim(
+        byte[] key,
+        string group,
+        string consumer,
+        TimeSpan minIdle,
+        StreamId startId = default,
+        int count = 100,
+        bool justId = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(consumer);
+        ArgumentOutOfRangeException.ThrowIfNegativeOrZero(count);
+
+        var minIdleMs = IdleMs(minIdle);
+        var flag = justId ? 1 : 0;
+        StreamId next;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xautoclaim_b_win(key, (UIntPtr)key.Length, group, consumer, minIdleMs, startId, (UIntPtr)count, flag, out next, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xautoclaim_b_linux(key, (UIntPtr)key.Length, group, consumer, minIdleMs, startId, (UIntPtr)count, flag, out next, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xautoclaim_b_mac(key, (UIntPtr)key.Length, group, consumer, minIdleMs, startId, (UIntPtr)count, flag, out next, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return (StreamId.Min, new List<StreamItem>(), new List<StreamId>());
+        }
+
+        // format: <XRange format> [DeletedCount (u32)] { [Ms (u64)] [Seq (u64)] } ...
+        var blob = CopyAndFree(ptr, len);
+        int offset = 0;
+        var claimed = ParseStreamItems(blob, ref offset);
+        var deleted = new List<StreamId>();
+        if (offset + 4 <= blob.Length)
+        {
+            uint n = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            for (uint i = 0; i < n && offset + 16 <= blob.Length; i++)
+                deleted.Add(ReadStreamId(blob, ref offset));
+        }
+        return (next, claimed, deleted);
+    }
+
+    private static List<(byte[] Key, List<StreamItem> Items)> ParseBinaryStreamReadsBlob(byte[] blob)
+    {
+        // format: [StreamCount (u32)] { [KeyLen (u32)] [Key] <XRange format> } ...
+        var result = new List<(byte[] Key, List<StreamItem> Items)>();
+        int offset = 0;
+        uint streamCount = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+        for (uint i = 0; i < streamCount && offset + 4 <= blob.Length; i++)
+        {
+            int keyLen = (int)BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            var key = blob.AsSpan(offset, keyLen).ToArray();
+            offset += keyLen;
+            result.Add((key, ParseStreamItems(blob, ref offset)));
+        }
+        return result;
+    }
+
+    #endregion
+
+    #region Binary keys: JSON
+
+    [DllImport(WindowsLib, EntryPoint = "cache_json_get_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_json_get_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string path, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_json_get_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_json_get_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string path, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_json_get_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_json_get_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string path, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_json_set_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_json_set_b_win(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string path, byte[] jsonValue, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_json_set_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_json_set_b_linux(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string path, byte[] jsonValue, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_json_set_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_json_set_b_mac(byte[] key, UIntPtr keyLen, [MarshalAs(UnmanagedType.LPUTF8Str)] string path, byte[] jsonValue, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_find_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_find_b_win([MarshalAs(UnmanagedType.LPUTF8Str)] string query, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_find_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_find_b_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string query, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_find_b", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_find_b_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string query, out UIntPtr len);
+
+
+    public static byte[]? JsonGet(byte[] key, string path)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_json_get_b_win(key, (UIntPtr)key.Length, path, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_json_get_b_linux(key, (UIntPtr)key.Length, path, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_json_get_b_mac(key, (UIntPtr)key.Length, path, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? JsonGetString(byte[] key, string path)
+    {
+        var bytes = JsonGet(key, path);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    public static bool JsonSet(byte[] key, string path, string jsonValue)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(jsonValue);
+
+        var bytes = Encoding.UTF8.GetBytes(jsonValue);
+        var len = (UIntPtr)bytes.Length;
+
+        int rc;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            rc = cache_json_set_b_win(key, (UIntPtr)key.Length, path, bytes, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            rc = cache_json_set_b_linux(key, (UIntPtr)key.Length, path, bytes, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            rc = cache_json_set_b_mac(key, (UIntPtr)key.Length, path, bytes, len);
+        else
+            throw new PlatformNotSupportedException();
+
+        // An invalid path or document is a "no"; other failures (e.g. a full cache) throw.
+        if (rc == (int)CacheStatus.InvalidArgument)
+            return false;
+        ThrowIfRejected(rc);
+        return true;
+    }
+
+    // Like FindKeys, but returns the bytes of the matching keys (which need not be UTF-8).
+    public static IReadOnlyList<byte[]> FindBinaryKeys(string query)
+    {
+        UIntPtr len;
+
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_find_b_win(query, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_find_b_linux(query, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_find_b_mac(query, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return Array.Empty<byte[]>();
+        }
+
+        var bytes = CopyAndFree(ptr, len);
+        return ParseListRangeBlob(bytes);
+    }
+
+    #endregion
+}
@@ -6,16 +6,16 @@ namespace LiteAPI.Cache;
 
 public static partial class JustCache
 {
-    #region Hashes (HSET/HGET/HGETALL)
+    #region Hashes
 
     [DllImport(WindowsLib, EntryPoint = "cache_hset", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_hset_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+    private static extern int cache_hset_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
 
     [DllImport(LinuxLib, EntryPoint = "cache_hset", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_hset_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+    private static extern int cache_hset_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
 
     [DllImport(MacLib, EntryPoint = "cache_hset", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_hset_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+    private static extern int cache_hset_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
 
 
     [DllImport(WindowsLib, EntryPoint = "cache_hget", CallingConvention = CallingConvention.Cdecl)]
@@ -38,6 +38,106 @@ public static partial class JustCache
     private static extern IntPtr cache_hgetall_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
 
 
+    [DllImport(WindowsLib, EntryPoint = "cache_hdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(MacLib, EntryPoint = "cache_hdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hdel_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hexists", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hexists", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+    [DllImport(MacLib, EntryPoint = "cache_hexists", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hexists_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hlen_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hlen_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(MacLib, EntryPoint = "cache_hlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hlen_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hkeys", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hkeys_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hkeys", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hkeys_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hkeys", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hkeys_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hvals", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hvals_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hvals", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hvals_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hvals", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hvals_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hsetnx", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hsetnx", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hsetnx", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hsetnx_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hmset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hmset_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hmset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hmset_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_hmset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_hmset_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hmget", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hmget_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] fields, UIntPtr fieldsLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hmget", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hmget_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] fields, UIntPtr fieldsLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_hmget", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_hmget_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] fields, UIntPtr fieldsLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hincrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, long increment, out long value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hincrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, long increment, out long value);
+
+    [DllImport(MacLib, EntryPoint = "cache_hincrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrby_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, long increment, out long value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_hincrbyfloat", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, double increment, out double value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_hincrbyfloat", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, double increment, out double value);
+
+    [DllImport(MacLib, EntryPoint = "cache_hincrbyfloat", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_hincrbyfloat_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string field, double increment, out double value);
+
+
     public static void HSet(string key, string field, byte[] value)
     {
         ArgumentNullException.ThrowIfNull(key);
@@ -45,14 +145,16 @@ public static partial class JustCache
         ArgumentNullException.ThrowIfNull(value);
 
         var len = (UIntPtr)value.Length;
+        int res;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            cache_hset_win(key, field, value, len);
+            res = cache_hset_win(key, field, value, len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            cache_hset_linux(key, field, value, len);
+            res = cache_hset_linux(key, field, value, len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            cache_hset_mac(key, field, value, len);
+            res = cache_hset_mac(key, field, value, len);
         else
             throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
     }
 
     public static void HSetString(string key, string field, string value)
@@ -76,7 +178,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return null;
+        }
 
         return CopyAndFree(ptr, len);
     }
@@ -102,12 +207,261 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return new Dictionary<string, byte[]>(StringComparer.Ordinal);
+        }
 
         var blob = CopyAndFree(ptr, len);
         return ParseHGetAllBlob(blob);
     }
 
+    // Sets the field only if it does not exist yet; returns true if it was set.
+    public static bool HSetNx(string key, string field, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hsetnx_win(key, field, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hsetnx_linux(key, field, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hsetnx_mac(key, field, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    // Sets several fields under one lock; returns the number of newly created fields.
+    public static long HMSet(string key, IEnumerable<KeyValuePair<string, byte[]>> fields)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(fields);
+
+        var items = new List<byte[]>();
+        foreach (var (field, value) in fields)
+        {
+            ArgumentNullException.ThrowIfNull(field, nameof(fields));
+            items.Add(Encoding.UTF8.GetBytes(field));
+            items.Add(value ?? throw new ArgumentNullException(nameof(fields)));
+        }
+        var blob = PackItems(items);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hmset_win(key, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hmset_linux(key, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hmset_mac(key, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Values of the given fields in order, null for missing ones.
+    public static List<byte[]?> HMGet(string key, IEnumerable<string> fields)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var blob = PackKeys(fields);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hmget_win(key, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hmget_linux(key, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hmget_mac(key, blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]?>();
+        }
+
+        return ParseOptionalItemsBlob(CopyAndFree(ptr, len));
+    }
+
+    // Returns true if the field was removed. A hash left empty is deleted.
+    public static bool HDel(string key, string field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hdel_win(key, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hdel_linux(key, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hdel_mac(key, field);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool HExists(string key, string field)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hexists_win(key, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hexists_linux(key, field);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hexists_mac(key, field);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static long HLen(string key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hlen_win(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hlen_linux(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hlen_mac(key);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<string> HKeys(string key)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hkeys_win(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hkeys_linux(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hkeys_mac(key, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len)).Select(Encoding.UTF8.GetString).ToList();
+    }
+
+    public static List<byte[]> HVals(string key)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_hvals_win(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_hvals_linux(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_hvals_mac(key, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // Atomically adds increment to the integer in the field (a missing field counts as 0);
+    // returns the new value.
+    public static long HIncrBy(string key, string field, long increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        long value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hincrby_win(key, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hincrby_linux(key, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hincrby_mac(key, field, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    public static double HIncrByFloat(string key, string field, double increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(field);
+
+        int res;
+        double value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_hincrbyfloat_win(key, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_hincrbyfloat_linux(key, field, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_hincrbyfloat_mac(key, field, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    private static List<byte[]?> ParseOptionalItemsBlob(byte[] blob)
+    {
+        // format: [Count (u32)] [ItemLen (u32), u32.MaxValue = missing] [Item] ...
+        var result = new List<byte[]?>();
+        if (blob.Length < 4)
+            return result;
+
+        int offset = 0;
+        uint count = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+
+        for (uint i = 0; i < count; i++)
+        {
+            if (offset + 4 > blob.Length) break;
+            uint itemLen = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (itemLen == uint.MaxValue)
+            {
+                result.Add(null);
+                continue;
+            }
+            if (offset + itemLen > blob.Length) break;
+
+            byte[] item = new byte[itemLen];
+            Buffer.BlockCopy(blob, offset, item, 0, (int)itemLen);
+            offset += (int)itemLen;
+            result.Add(item);
+        }
+
+        return result;
+    }
+
     private static Dictionary<string, byte[]> ParseHGetAllBlob(byte[] blob)
     {
         // format: [Count (u32)] [KeyLen (u32)] [Key] [ValLen (u32)] [Val] ...
@@ -6,16 +6,16 @@ namespace LiteAPI.Cache;
 
 public static partial class JustCache
 {
-    #region Lists (LPUSH/RPOP/LRANGE)
+    #region Lists
 
     [DllImport(WindowsLib, EntryPoint = "cache_lpush", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_lpush_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+    private static extern int cache_lpush_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
     [DllImport(LinuxLib, EntryPoint = "cache_lpush", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_lpush_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+    private static extern int cache_lpush_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
     [DllImport(MacLib, EntryPoint = "cache_lpush", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_lpush_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+    private static extern int cache_lpush_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
 
     [DllImport(WindowsLib, EntryPoint = "cache_rpop", CallingConvention = CallingConvention.Cdecl)]
@@ -38,20 +38,152 @@ public static partial class JustCache
     private static extern IntPtr cache_lrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, out UIntPtr len);
 
 
+    [DllImport(WindowsLib, EntryPoint = "cache_rpush", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_rpush_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_rpush", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_rpush_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_rpush", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_rpush_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lpush_multi", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lpush_multi_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lpush_multi", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lpush_multi_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_lpush_multi", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lpush_multi_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_rpush_multi", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_rpush_multi_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_rpush_multi", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_rpush_multi_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_rpush_multi", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_rpush_multi_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] items, UIntPtr itemsLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lpop_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lpop_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lpop_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_llen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_llen_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_llen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_llen_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(MacLib, EntryPoint = "cache_llen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_llen_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lindex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lindex_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int index, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lindex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lindex_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int index, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lindex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lindex_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int index, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lset_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int index, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lset_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int index, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_lset_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int index, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_linsert", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_linsert_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int before, byte[] pivot, UIntPtr pivotLen, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_linsert", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_linsert_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int before, byte[] pivot, UIntPtr pivotLen, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_linsert", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_linsert_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int before, byte[] pivot, UIntPtr pivotLen, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lrem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lrem_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long count, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lrem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lrem_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long count, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lrem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_lrem_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long count, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_ltrim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_ltrim_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_ltrim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_ltrim_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end);
+
+    [DllImport(MacLib, EntryPoint = "cache_ltrim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_ltrim_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_blpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_blpop_win(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_blpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_blpop_linux(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_blpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_blpop_mac(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_brpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_brpop_win(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_brpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_brpop_linux(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_brpop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_brpop_mac(byte[] keys, UIntPtr keysLen, ulong timeoutMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_lmove", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lmove_win([MarshalAs(UnmanagedType.LPUTF8Str)] string src, [MarshalAs(UnmanagedType.LPUTF8Str)] string dst, int fromLeft, int toLeft, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_lmove", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lmove_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string src, [MarshalAs(UnmanagedType.LPUTF8Str)] string dst, int fromLeft, int toLeft, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_lmove", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_lmove_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string src, [MarshalAs(UnmanagedType.LPUTF8Str)] string dst, int fromLeft, int toLeft, out UIntPtr len);
+
+
     public static void LPush(string key, byte[] value)
     {
         ArgumentNullException.ThrowIfNull(key);
         ArgumentNullException.ThrowIfNull(value);
 
         var len = (UIntPtr)value.Length;
+        int res;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            cache_lpush_win(key, value, len);
+            res = cache_lpush_win(key, value, len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            cache_lpush_linux(key, value, len);
+            res = cache_lpush_linux(key, value, len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            cache_lpush_mac(key, value, len);
+            res = cache_lpush_mac(key, value, len);
         else
             throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
     }
 
     public static void LPushString(string key, string value)
@@ -75,7 +207,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return null;
+        }
 
         return CopyAndFree(ptr, len);
     }
@@ -101,7 +236,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return new List<byte[]>();
+        }
 
         var blob = CopyAndFree(ptr, len);
         return ParseListRangeBlob(blob);
@@ -118,6 +256,321 @@ public static partial class JustCache
         return result;
     }
 
+    public static void RPush(string key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_rpush_win(key, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_rpush_linux(key, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_rpush_mac(key, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    public static void RPushString(string key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        RPush(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    // Pushes every value in order under one lock; returns the list length afterwards.
+    public static long LPushRange(string key, IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var packed = PackItems(values);
+        var len = (UIntPtr)packed.Length;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lpush_multi_win(key, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lpush_multi_linux(key, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lpush_multi_mac(key, packed, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long RPushRange(string key, IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var packed = PackItems(values);
+        var len = (UIntPtr)packed.Length;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_rpush_multi_win(key, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_rpush_multi_linux(key, packed, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_rpush_multi_mac(key, packed, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static byte[]? LPop(string key)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lpop_win(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lpop_linux(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lpop_mac(key, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? LPopString(string key)
+    {
+        var bytes = LPop(key);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    public static long LLen(string key)
+    {
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_llen_win(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_llen_linux(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_llen_mac(key);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Null when the key is missing or the index is out of range; negative indexes count from the tail.
+    public static byte[]? LIndex(string key, int index)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lindex_win(key, index, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lindex_linux(key, index, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lindex_mac(key, index, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    public static string? LIndexString(string key, int index)
+    {
+        var bytes = LIndex(key, index);
+        return bytes == null ? null : Encoding.UTF8.GetString(bytes);
+    }
+
+    // Throws KeyNotFoundException for a missing key and ArgumentException for an index out of range.
+    public static void LSet(string key, int index, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lset_win(key, index, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lset_linux(key, index, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lset_mac(key, index, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    // Returns the new length, 0 if the key is missing, or -1 if the pivot is not in the list.
+    public static long LInsert(string key, bool before, byte[] pivot, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(pivot);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var pivotLen = (UIntPtr)pivot.Length;
+        var len = (UIntPtr)value.Length;
+        var beforeFlag = before ? 1 : 0;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_linsert_win(key, beforeFlag, pivot, pivotLen, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_linsert_linux(key, beforeFlag, pivot, pivotLen, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_linsert_mac(key, beforeFlag, pivot, pivotLen, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        if (res == (long)CacheStatus.NotFound)
+            return -1;
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Removes count occurrences from the head (count > 0), the tail (count < 0) or all of them (0).
+    public static long LRem(string key, long count, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_lrem_win(key, count, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_lrem_linux(key, count, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_lrem_mac(key, count, value, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static void LTrim(string key, int start, int end)
+    {
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_ltrim_win(key, start, end);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_ltrim_linux(key, start, end);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_ltrim_mac(key, start, end);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+    }
+
+    // Atomically pops from one end of source and pushes onto one end of destination;
+    // returns the moved item, or null when source is missing.
+    public static byte[]? LMove(string source, string destination, bool fromLeft, bool toLeft)
+    {
+        var fromEnd = fromLeft ? 1 : 0;
+        var toEnd = toLeft ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_lmove_win(source, destination, fromEnd, toEnd, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_lmove_linux(source, destination, fromEnd, toEnd, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_lmove_mac(source, destination, fromEnd, toEnd, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return CopyAndFree(ptr, len);
+    }
+
+    // Pops from the head of the first non-empty list among keys, blocking until one is pushed or
+    // the timeout ends (TimeSpan.Zero or Timeout.InfiniteTimeSpan waits forever). Clients blocked on
+    // the same key are served in arrival order. Returns null on timeout.
+    public static (string Key, byte[] Value)? BLPop(IEnumerable<string> keys, TimeSpan timeout) =>
+        BlockingPop(keys, timeout, left: true);
+
+    // Like BLPop but pops from the tail.
+    public static (string Key, byte[] Value)? BRPop(IEnumerable<string> keys, TimeSpan timeout) =>
+        BlockingPop(keys, timeout, left: false);
+
+    private static (string Key, byte[] Value)? BlockingPop(IEnumerable<string> keys, TimeSpan timeout, bool left)
+    {
+        var blob = PackKeys(keys);
+        var timeoutMs = BlockTimeoutMs(timeout);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = left ? cache_blpop_win(blob, (UIntPtr)blob.Length, timeoutMs, out len) : cache_brpop_win(blob, (UIntPtr)blob.Length, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = left ? cache_blpop_linux(blob, (UIntPtr)blob.Length, timeoutMs, out len) : cache_brpop_linux(blob, (UIntPtr)blob.Length, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = left ? cache_blpop_mac(blob, (UIntPtr)blob.Length, timeoutMs, out len) : cache_brpop_mac(blob, (UIntPtr)blob.Length, timeoutMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        var items = ParseListRangeBlob(CopyAndFree(ptr, len));
+        return (Encoding.UTF8.GetString(items[0]), items[1]);
+    }
+
+    private static byte[] PackKeys(IEnumerable<string> keys)
+    {
+        ArgumentNullException.ThrowIfNull(keys);
+        return PackItems(keys.Select(k => Encoding.UTF8.GetBytes(k ?? throw new ArgumentNullException(nameof(keys)))));
+    }
+
+    // 0 means block forever on the native side.
+    private static ulong BlockTimeoutMs(TimeSpan timeout)
+    {
+        if (timeout == Timeout.InfiniteTimeSpan)
+            return 0;
+        if (timeout < TimeSpan.Zero)
+            throw new ArgumentOutOfRangeException(nameof(timeout));
+        return (ulong)Math.Ceiling(timeout.TotalMilliseconds);
+    }
+
+    private static byte[] PackItems(IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(values);
+
+        // format: [Count (u32)] [ItemLen (u32)] [Item] ...
+        var items = values.ToList();
+        var size = 4;
+        foreach (var item in items)
+        {
+            ArgumentNullException.ThrowIfNull(item, nameof(values));
+            size += 4 + item.Length;
+        }
+
+        var blob = new byte[size];
+        BinaryPrimitives.WriteUInt32LittleEndian(blob.AsSpan(0, 4), (uint)items.Count);
+        var offset = 4;
+        foreach (var item in items)
+        {
+            BinaryPrimitives.WriteUInt32LittleEndian(blob.AsSpan(offset, 4), (uint)item.Length);
+            offset += 4;
+            item.CopyTo(blob, offset);
+            offset += item.Length;
+        }
+        return blob;
+    }
+
     private static List<byte[]> ParseListRangeBlob(byte[] blob)
     {
         // format: [Count (u32)] [ItemLen (u32)] [Item] ...
@@ -37,15 +37,55 @@ public static partial class JustCache
     private static extern UIntPtr cache_len_mac();
 
 
+    // Memory limit / eviction policy
+    [DllImport(WindowsLib, EntryPoint = "cache_set_eviction_policy", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_eviction_policy_win(int policy);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_set_eviction_policy", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_eviction_policy_linux(int policy);
+
+    [DllImport(MacLib, EntryPoint = "cache_set_eviction_policy", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_eviction_policy_mac(int policy);
+
+    [DllImport(WindowsLib, EntryPoint = "cache_set_type_overwrite", CallingConvention = CallingConvention.Cdecl)]
+    private static extern void cache_set_type_overwrite_win(int enabled);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_set_type_overwrite", CallingConvention = CallingConvention.Cdecl)]
+    private static extern void cache_set_type_overwrite_linux(int enabled);
+
+    [DllImport(MacLib, EntryPoint = "cache_set_type_overwrite", CallingConvention = CallingConvention.Cdecl)]
+    private static extern void cache_set_type_overwrite_mac(int enabled);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_set_max_memory_bytes", CallingConvention = CallingConvention.Cdecl)]
+    private static extern void cache_set_max_memory_bytes_win(ulong maxBytes);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_set_max_memory_bytes", CallingConvention = CallingConvention.Cdecl)]
+    private static extern void cache_set_max_memory_bytes_linux(ulong maxBytes);
+
+    [DllImport(MacLib, EntryPoint = "cache_set_max_memory_bytes", CallingConvention = CallingConvention.Cdecl)]
+    private static extern void cache_set_max_memory_bytes_mac(ulong maxBytes);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_used_memory", CallingConvention = CallingConvention.Cdecl)]
+    private static extern ulong cache_used_memory_win();
+
+    [DllImport(LinuxLib, EntryPoint = "cache_used_memory", CallingConvention = CallingConvention.Cdecl)]
+    private static extern ulong cache_used_memory_linux();
+
+    [DllImport(MacLib, EntryPoint = "cache_used_memory", CallingConvention = CallingConvention.Cdecl)]
+    private static extern ulong cache_used_memory_mac();
+
+
     // TTL
     [DllImport(WindowsLib, EntryPoint = "cache_set_with_ttl", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_with_ttl_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, ulong ttlMs);
+    private static extern int cache_set_with_ttl_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, ulong ttlMs);
 
     [DllImport(LinuxLib, EntryPoint = "cache_set_with_ttl", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_with_ttl_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, ulong ttlMs);
+    private static extern int cache_set_with_ttl_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, ulong ttlMs);
 
     [DllImport(MacLib, EntryPoint = "cache_set_with_ttl", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_with_ttl_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, ulong ttlMs);
+    private static extern int cache_set_with_ttl_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, ulong ttlMs);
 
 
     [DllImport(WindowsLib, EntryPoint = "cache_expire", CallingConvention = CallingConvention.Cdecl)]
@@ -101,13 +141,13 @@ public static partial class JustCache
 
     // Binary-safe keys
     [DllImport(WindowsLib, EntryPoint = "cache_set_b", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+    private static extern int cache_set_b_win(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
 
     [DllImport(LinuxLib, EntryPoint = "cache_set_b", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+    private static extern int cache_set_b_linux(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
 
     [DllImport(MacLib, EntryPoint = "cache_set_b", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
+    private static extern int cache_set_b_mac(byte[] key, UIntPtr keyLen, byte[] val, UIntPtr len);
 
 
     [DllImport(WindowsLib, EntryPoint = "cache_get_b", CallingConvention = CallingConvention.Cdecl)]
@@ -192,6 +232,78 @@ public static partial class JustCache
         return (int)u;
     }
 
+    public enum EvictionPolicy
+    {
+        AllKeysLru = 0,
+        AllKeysLfu = 1,
+        WTinyLfu = 2,
+        VolatileLru = 3,
+        VolatileTtl = 4,
+        NoEviction = 5,
+    }
+
+    // Under NoEviction, and under the volatile policies when no key has a TTL, writes
+    // that need room throw JustCacheException (Status = OutOfMemory).
+    public static void SetEvictionPolicy(EvictionPolicy policy)
+    {
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_set_eviction_policy_win((int)policy);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_set_eviction_policy_linux((int)policy);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_set_eviction_policy_mac((int)policy);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (res == (int)CacheStatus.InvalidArgument)
+            throw new ArgumentOutOfRangeException(nameof(policy), policy, "Unknown eviction policy.");
+        ThrowIfRejected(res);
+    }
+
+    // By default HSet/LPush/SAdd/ZAdd/XAdd/JsonSet on a key holding another type throw
+    // JustCacheException (Status = WrongType) and leave the key untouched; enabling this
+    // replaces the old value instead.
+    public static void SetTypeOverwrite(bool enabled)
+    {
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            cache_set_type_overwrite_win(enabled ? 1 : 0);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            cache_set_type_overwrite_linux(enabled ? 1 : 0);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            cache_set_type_overwrite_mac(enabled ? 1 : 0);
+        else
+            throw new PlatformNotSupportedException();
+    }
+
+    // 0 disables the memory limit.
+    public static void SetMaxMemoryBytes(ulong maxBytes)
+    {
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            cache_set_max_memory_bytes_win(maxBytes);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            cache_set_max_memory_bytes_linux(maxBytes);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            cache_set_max_memory_bytes_mac(maxBytes);
+        else
+            throw new PlatformNotSupportedException();
+    }
+
+    // Estimated bytes used by keys and values.
+    public static ulong UsedMemory
+    {
+        get
+        {
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+                return cache_used_memory_win();
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+                return cache_used_memory_linux();
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+                return cache_used_memory_mac();
+            throw new PlatformNotSupportedException();
+        }
+    }
+
     public static int Count
     {
         get
@@ -218,14 +330,16 @@ public static partial class JustCache
         ulong ttlMs = (ulong)Math.Max(0, (long)ttl.TotalMilliseconds);
         var len = (UIntPtr)val.Length;
 
+        int res;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            cache_set_with_ttl_win(key, val, len, ttlMs);
+            res = cache_set_with_ttl_win(key, val, len, ttlMs);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            cache_set_with_ttl_linux(key, val, len, ttlMs);
+            res = cache_set_with_ttl_linux(key, val, len, ttlMs);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            cache_set_with_ttl_mac(key, val, len, ttlMs);
+            res = cache_set_with_ttl_mac(key, val, len, ttlMs);
         else
             throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
     }
 
     public static void SetStringWithTtl(string key, string val, TimeSpan ttl)
@@ -250,7 +364,10 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return res != 0;
+        if (res == (int)CacheStatus.NotFound)
+            return false;
+        ThrowIfRejected(res);
+        return true;
     }
 
     // Redis-style TTL semantics:
@@ -285,7 +402,7 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return res != 0;
+        return res == 1;
     }
 
     public static void DisableAof()
@@ -314,7 +431,7 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return res != 0;
+        return res == 1;
     }
 
     public static void Set(byte[] key, byte[] val)
@@ -325,14 +442,16 @@ public static partial class JustCache
         var klen = (UIntPtr)key.Length;
         var vlen = (UIntPtr)val.Length;
 
+        int res;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            cache_set_b_win(key, klen, val, vlen);
+            res = cache_set_b_win(key, klen, val, vlen);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            cache_set_b_linux(key, klen, val, vlen);
+            res = cache_set_b_linux(key, klen, val, vlen);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            cache_set_b_mac(key, klen, val, vlen);
+            res = cache_set_b_mac(key, klen, val, vlen);
         else
             throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
 
         JustCacheEventSource.Log.ReportSet(val.Length);
     }
@@ -356,6 +475,7 @@ public static partial class JustCache
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
         {
+            ThrowIfFailed();
             JustCacheEventSource.Log.ReportGetMiss();
             return null;
         }
@@ -393,8 +513,10 @@ public static partial class JustCache
                 throw new PlatformNotSupportedException();
         }
 
-        if (ret == -1)
+        // -1 is also "destination too small for a 1-byte value"; the status tells them apart.
+        if (ret == -1 && LastStatus != CacheStatus.Ok)
         {
+            ThrowIfFailed();
             JustCacheEventSource.Log.ReportGetMiss();
             return false;
         }
@@ -485,6 +607,7 @@ public static partial class JustCache
 
         if (handle == IntPtr.Zero)
         {
+            ThrowIfFailed();
             JustCacheEventSource.Log.ReportGetMiss();
             return default;
         }
@@ -32,7 +32,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return null;
+        }
 
         return CopyAndFree(ptr, len);
     }
@@ -69,7 +72,11 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return rc != 0;
+        // An invalid path or document is a "no"; other failures (e.g. a full cache) throw.
+        if (rc == (int)CacheStatus.InvalidArgument)
+            return false;
+        ThrowIfRejected(rc);
+        return true;
     }
 
     #endregion
@@ -97,7 +104,7 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return rc != 0;
+        return rc == 1;
     }
 
     [DllImport(WindowsLib, EntryPoint = "cache_find", CallingConvention = CallingConvention.Cdecl)]
@@ -124,7 +131,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return Array.Empty<string>();
+        }
 
         var bytes = CopyAndFree(ptr, len);
         return DecodeKeyList(bytes);
@@ -188,7 +198,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return null;
+        }
 
         return CopyAndFree(ptr, len);
     }
@@ -122,7 +122,10 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return false;
+        }
 
         var blob = CopyAndFree(ptr, len);
         if (blob.Length < 8)
@@ -5,7 +5,7 @@ namespace LiteAPI.Cache;
 
 public static partial class JustCache
 {
-    #region Sets (SADD/SISMEMBER)
+    #region Sets
 
     [DllImport(WindowsLib, EntryPoint = "cache_sadd", CallingConvention = CallingConvention.Cdecl)]
     private static extern int cache_sadd_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
@@ -27,6 +27,126 @@ public static partial class JustCache
     private static extern int cache_sismember_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
 
+    [DllImport(WindowsLib, EntryPoint = "cache_srem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_srem_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_srem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_srem_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_srem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_srem_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_smembers", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smembers_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_smembers", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smembers_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_smembers", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smembers_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_scard", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_scard_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_scard", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_scard_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(MacLib, EntryPoint = "cache_scard", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_scard_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_spop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_spop_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, UIntPtr count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_spop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_spop_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, UIntPtr count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_spop", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_spop_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, UIntPtr count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_srandmember", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_srandmember_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_srandmember", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_srandmember_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_srandmember", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_srandmember_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_smismember", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smismember_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] members, UIntPtr membersLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_smismember", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smismember_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] members, UIntPtr membersLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_smismember", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_smismember_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] members, UIntPtr membersLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sunion", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sunion_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sunion", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sunion_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sunion", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sunion_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sinter", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sinter_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sinter", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sinter_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sinter", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sinter_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sdiff", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sdiff_win(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sdiff", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sdiff_linux(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_sdiff", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_sdiff_mac(byte[] keys, UIntPtr keysLen, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sunionstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sunionstore_win([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sunionstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sunionstore_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_sunionstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sunionstore_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sinterstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sinterstore_win([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sinterstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sinterstore_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_sinterstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sinterstore_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_sdiffstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sdiffstore_win([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_sdiffstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sdiffstore_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_sdiffstore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_sdiffstore_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string dst, byte[] keys, UIntPtr keysLen);
+
+
     public static bool SAdd(string key, byte[] value)
     {
         ArgumentNullException.ThrowIfNull(key);
@@ -43,7 +163,8 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return res != 0;
+        ThrowIfRejected(res);
+        return res > 0;
     }
 
     public static bool SAddString(string key, string value)
@@ -68,7 +189,8 @@ public static partial class JustCache
         else
             throw new PlatformNotSupportedException();
 
-        return res != 0;
+        ThrowIfRejected(res);
+        return res == 1;
     }
 
     public static bool SIsMemberString(string key, string value)
@@ -77,5 +199,273 @@ public static partial class JustCache
         return SIsMember(key, Encoding.UTF8.GetBytes(value));
     }
 
+    // Returns true if the member was removed. A set left empty is deleted.
+    public static bool SRem(string key, byte[] value)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(value);
+
+        var len = (UIntPtr)value.Length;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_srem_win(key, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_srem_linux(key, value, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_srem_mac(key, value, len);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    public static bool SRemString(string key, string value)
+    {
+        ArgumentNullException.ThrowIfNull(value);
+        return SRem(key, Encoding.UTF8.GetBytes(value));
+    }
+
+    // Membership of each value, in order, checked under one lock.
+    public static bool[] SMIsMember(string key, IEnumerable<byte[]> values)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        var blob = PackItems(values);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_smismember_win(key, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_smismember_linux(key, blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_smismember_mac(key, blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return Array.Empty<bool>();
+        }
+
+        return CopyAndFree(ptr, len).Select(b => b != 0).ToArray();
+    }
+
+    public static long SCard(string key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_scard_win(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_scard_linux(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_scard_mac(key);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static List<byte[]> SMembers(string key)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_smembers_win(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_smembers_linux(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_smembers_mac(key, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // Removes and returns up to count random members.
+    public static List<byte[]> SPop(string key, int count = 1)
+    {
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_spop_win(key, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_spop_linux(key, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_spop_mac(key, (UIntPtr)count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // Up to count distinct random members, or -count members that may repeat for a negative count.
+    public static List<byte[]> SRandMember(string key, long count = 1)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_srandmember_win(key, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_srandmember_linux(key, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_srandmember_mac(key, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // Set algebra over several keys; missing keys count as empty sets.
+    public static List<byte[]> SUnion(IEnumerable<string> keys)
+    {
+        var blob = PackKeys(keys);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_sunion_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_sunion_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_sunion_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SInter(IEnumerable<string> keys)
+    {
+        var blob = PackKeys(keys);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_sinter_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_sinter_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_sinter_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<byte[]> SDiff(IEnumerable<string> keys)
+    {
+        var blob = PackKeys(keys);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_sdiff_win(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_sdiff_linux(blob, (UIntPtr)blob.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_sdiff_mac(blob, (UIntPtr)blob.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<byte[]>();
+        }
+
+        return ParseListRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // The *Store variants replace destination with the result atomically and return its size;
+    // an empty result deletes destination.
+    public static long SUnionStore(string destination, IEnumerable<string> keys)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        var blob = PackKeys(keys);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sunionstore_win(destination, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sunionstore_linux(destination, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sunionstore_mac(destination, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long SInterStore(string destination, IEnumerable<string> keys)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        var blob = PackKeys(keys);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sinterstore_win(destination, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sinterstore_linux(destination, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sinterstore_mac(destination, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long SDiffStore(string destination, IEnumerable<string> keys)
+    {
+        ArgumentNullException.ThrowIfNull(destination);
+        var blob = PackKeys(keys);
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_sdiffstore_win(destination, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_sdiffstore_linux(destination, blob, (UIntPtr)blob.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_sdiffstore_mac(destination, blob, (UIntPtr)blob.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
     #endregion
 }
@@ -6,16 +6,16 @@ namespace LiteAPI.Cache;
 
 public static partial class JustCache
 {
-    #region Sorted Sets (ZADD/ZRANGE)
+    #region Sorted Sets
 
     [DllImport(WindowsLib, EntryPoint = "cache_zadd", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_zadd_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+    private static extern int cache_zadd_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
 
     [DllImport(LinuxLib, EntryPoint = "cache_zadd", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_zadd_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+    private static extern int cache_zadd_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
 
     [DllImport(MacLib, EntryPoint = "cache_zadd", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_zadd_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+    private static extern int cache_zadd_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
 
 
     [DllImport(WindowsLib, EntryPoint = "cache_zrange", CallingConvention = CallingConvention.Cdecl)]
@@ -28,19 +28,133 @@ public static partial class JustCache
     private static extern IntPtr cache_zrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, out UIntPtr len);
 
 
+    [DllImport(WindowsLib, EntryPoint = "cache_zadd_flags", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int flags);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zadd_flags", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int flags);
+
+    [DllImport(MacLib, EntryPoint = "cache_zadd_flags", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zadd_flags_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double score, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int flags);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrem", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zrem_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zincrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double increment, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zincrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double increment, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(MacLib, EntryPoint = "cache_zincrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zincrby_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double increment, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zscore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zscore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+    [DllImport(MacLib, EntryPoint = "cache_zscore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_zscore_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, out double score);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrank", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int rev);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrank", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int rev);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrank", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zrank_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string member, int rev);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zcard", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zcard_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zcard", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zcard_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(MacLib, EntryPoint = "cache_zcard", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_zcard_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrevrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrevrange_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrevrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrevrange_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrevrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrevrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrange_withscores", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_withscores_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, int rev, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrange_withscores", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_withscores_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, int rev, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrange_withscores", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrange_withscores_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, int start, int end, int rev, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrangebyscore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebyscore_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double min, double max, int flags, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrangebyscore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebyscore_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double min, double max, int flags, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrangebyscore", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebyscore_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double min, double max, int flags, UIntPtr offset, long count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_zrangebylex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string min, [MarshalAs(UnmanagedType.LPUTF8Str)] string max, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_zrangebylex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string min, [MarshalAs(UnmanagedType.LPUTF8Str)] string max, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_zrangebylex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_zrangebylex_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string min, [MarshalAs(UnmanagedType.LPUTF8Str)] string max, int rev, UIntPtr offset, long count, out UIntPtr len);
+
+
+    // Conditions for ZAdd, as in Redis: Nx only adds new members, Xx only updates existing ones,
+    // Gt / Lt only update a member when the new score is greater / less than the current one.
+    [Flags]
+    public enum ZAddFlags
+    {
+        None = 0,
+        Nx = 1,
+        Xx = 2,
+        Gt = 4,
+        Lt = 8,
+    }
+
     public static void ZAdd(string key, double score, string member)
     {
         ArgumentNullException.ThrowIfNull(key);
         ArgumentNullException.ThrowIfNull(member);
 
+        int res;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            cache_zadd_win(key, score, member);
+            res = cache_zadd_win(key, score, member);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            cache_zadd_linux(key, score, member);
+            res = cache_zadd_linux(key, score, member);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            cache_zadd_mac(key, score, member);
+            res = cache_zadd_mac(key, score, member);
         else
             throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
     }
 
     public static List<string> ZRange(string key, int start, int end)
@@ -58,12 +172,274 @@ public static partial class JustCache
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return new List<string>();
+        }
 
         var blob = CopyAndFree(ptr, len);
         return ParseZRangeBlob(blob);
     }
 
+    // Returns true if the member was added or its score changed.
+    public static bool ZAdd(string key, double score, string member, ZAddFlags flags)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zadd_flags_win(key, score, member, (int)flags);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zadd_flags_linux(key, score, member, (int)flags);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zadd_flags_mac(key, score, member, (int)flags);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    // Returns true if the member was removed. A set left empty is deleted.
+    public static bool ZRem(string key, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zrem_win(key, member);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zrem_linux(key, member);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zrem_mac(key, member);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    // Adds increment to the member's score (a new member starts at 0); returns the new score.
+    public static double ZIncrBy(string key, double increment, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        double score;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zincrby_win(key, increment, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zincrby_linux(key, increment, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zincrby_mac(key, increment, member, out score);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return score;
+    }
+
+    // Returns null if the key or member is missing.
+    public static double? ZScore(string key, string member)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        int res;
+        double score;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zscore_win(key, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zscore_linux(key, member, out score);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zscore_mac(key, member, out score);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res == 1 ? score : null;
+    }
+
+    // 0-based rank by ascending score (descending when reverse); null if the key or member is missing.
+    public static long? ZRank(string key, string member, bool reverse = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(member);
+
+        var rev = reverse ? 1 : 0;
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zrank_win(key, member, rev);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zrank_linux(key, member, rev);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zrank_mac(key, member, rev);
+        else
+            throw new PlatformNotSupportedException();
+        if (res == (long)CacheStatus.NotFound)
+            return null;
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    public static long ZCard(string key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_zcard_win(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_zcard_linux(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_zcard_mac(key);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Like ZRange, from the highest score down.
+    public static List<string> ZRevRange(string key, int start, int end)
+    {
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrevrange_win(key, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrevrange_linux(key, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrevrange_mac(key, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        return ParseZRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    public static List<(string Member, double Score)> ZRangeWithScores(string key, int start, int end, bool reverse = false)
+    {
+        var rev = reverse ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrange_withscores_win(key, start, end, rev, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrange_withscores_linux(key, start, end, rev, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrange_withscores_mac(key, start, end, rev, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<(string, double)>();
+        }
+
+        return ParseScoredBlob(CopyAndFree(ptr, len));
+    }
+
+    // Members with min <= score <= max (either end optionally exclusive), ascending or, when
+    // reverse, from max down; skips offset and returns at most count (negative = all).
+    public static List<(string Member, double Score)> ZRangeByScore(
+        string key,
+        double min,
+        double max,
+        bool minExclusive = false,
+        bool maxExclusive = false,
+        bool reverse = false,
+        int offset = 0,
+        int count = -1)
+    {
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        var flags = (minExclusive ? 1 : 0) | (maxExclusive ? 2 : 0) | (reverse ? 4 : 0);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrangebyscore_win(key, min, max, flags, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrangebyscore_linux(key, min, max, flags, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrangebyscore_mac(key, min, max, flags, (UIntPtr)offset, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<(string, double)>();
+        }
+
+        return ParseScoredBlob(CopyAndFree(ptr, len));
+    }
+
+    // Members between the Redis-style lex bounds min and max ("-", "+", "[member", "(member"),
+    // for sets whose members all share one score.
+    public static List<string> ZRangeByLex(string key, string min, string max, bool reverse = false, int offset = 0, int count = -1)
+    {
+        ArgumentNullException.ThrowIfNull(min);
+        ArgumentNullException.ThrowIfNull(max);
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        var rev = reverse ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_zrangebylex_win(key, min, max, rev, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_zrangebylex_linux(key, min, max, rev, (UIntPtr)offset, count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_zrangebylex_mac(key, min, max, rev, (UIntPtr)offset, count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<string>();
+        }
+
+        return ParseZRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    private static List<(string Member, double Score)> ParseScoredBlob(byte[] blob)
+    {
+        // format: [Count (u32)] [MemberLen (u32)] [Member] [Score (f64)] ...
+        var result = new List<(string, double)>();
+        if (blob.Length < 4)
+            return result;
+
+        int offset = 0;
+        uint count = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+
+        for (uint i = 0; i < count; i++)
+        {
+            if (offset + 4 > blob.Length) break;
+            uint mlen = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (offset + mlen + 8 > blob.Length) break;
+
+            string member = Encoding.UTF8.GetString(blob, offset, (int)mlen);
+            offset += (int)mlen;
+            double score = BinaryPrimitives.ReadDoubleLittleEndian(blob.AsSpan(offset, 8));
+            offset += 8;
+            result.Add((member, score));
+        }
+
+        return result;
+    }
+
     private static List<string> ParseZRangeBlob(byte[] blob)
     {
         // format: [Count (u32)] [MemberLen (u32)] [Member] ...
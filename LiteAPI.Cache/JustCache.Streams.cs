@@ -1,80 +1,465 @@
 using System.Buffers.Binary;
 using System.Runtime.InteropServices;
+using System.Text;
 
 namespace LiteAPI.Cache;
 
 public static partial class JustCache
 {
-    #region Phase3: Streams (XADD/XRANGE)
+    #region Phase3: Streams (XADD/XRANGE/XREAD/XTRIM/XDEL)
 
     [DllImport(WindowsLib, EntryPoint = "cache_xadd", CallingConvention = CallingConvention.Cdecl)]
-    private static extern ulong cache_xadd_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] payload, UIntPtr len);
+    private static extern StreamId cache_xadd_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] payload, UIntPtr len);
 
     [DllImport(LinuxLib, EntryPoint = "cache_xadd", CallingConvention = CallingConvention.Cdecl)]
-    private static extern ulong cache_xadd_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] payload, UIntPtr len);
+    private static extern StreamId cache_xadd_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] payload, UIntPtr len);
 
     [DllImport(MacLib, EntryPoint = "cache_xadd", CallingConvention = CallingConvention.Cdecl)]
-    private static extern ulong cache_xadd_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] payload, UIntPtr len);
+    private static extern StreamId cache_xadd_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] payload, UIntPtr len);
 
 
     [DllImport(WindowsLib, EntryPoint = "cache_xrange", CallingConvention = CallingConvention.Cdecl)]
-    private static extern IntPtr cache_xrange_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, ulong startId, ulong endId, out UIntPtr len);
+    private static extern IntPtr cache_xrange_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId startId, StreamId endId, UIntPtr count, out UIntPtr len);
 
     [DllImport(LinuxLib, EntryPoint = "cache_xrange", CallingConvention = CallingConvention.Cdecl)]
-    private static extern IntPtr cache_xrange_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, ulong startId, ulong endId, out UIntPtr len);
+    private static extern IntPtr cache_xrange_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId startId, StreamId endId, UIntPtr count, out UIntPtr len);
 
     [DllImport(MacLib, EntryPoint = "cache_xrange", CallingConvention = CallingConvention.Cdecl)]
-    private static extern IntPtr cache_xrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, ulong startId, ulong endId, out UIntPtr len);
+    private static extern IntPtr cache_xrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId startId, StreamId endId, UIntPtr count, out UIntPtr len);
 
 
-    public readonly record struct StreamItem(ulong Id, byte[] Payload);
+    [DllImport(WindowsLib, EntryPoint = "cache_xrevrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrevrange_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId endId, StreamId startId, UIntPtr count, out UIntPtr len);
 
-    public static ulong XAdd(string key, byte[] payload)
+    [DllImport(LinuxLib, EntryPoint = "cache_xrevrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrevrange_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId endId, StreamId startId, UIntPtr count, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xrevrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xrevrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId endId, StreamId startId, UIntPtr count, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xadd_ex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe int cache_xadd_ex_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] fields, UIntPtr fieldsLen, StreamId* id, long maxLen, StreamId* minId, out StreamId outId);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xadd_ex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe int cache_xadd_ex_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] fields, UIntPtr fieldsLen, StreamId* id, long maxLen, StreamId* minId, out StreamId outId);
+
+    [DllImport(MacLib, EntryPoint = "cache_xadd_ex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe int cache_xadd_ex_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] fields, UIntPtr fieldsLen, StreamId* id, long maxLen, StreamId* minId, out StreamId outId);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xtrim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe long cache_xtrim_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long maxLen, StreamId* minId);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xtrim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe long cache_xtrim_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long maxLen, StreamId* minId);
+
+    [DllImport(MacLib, EntryPoint = "cache_xtrim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern unsafe long cache_xtrim_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long maxLen, StreamId* minId);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xdel_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId[] ids, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xdel_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId[] ids, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xdel_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, StreamId[] ids, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xlen_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xlen_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(MacLib, EntryPoint = "cache_xlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xlen_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xread_block", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xread_block_win(byte[] keys, UIntPtr keysLen, StreamId[] afterIds, UIntPtr count, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xread_block", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xread_block_linux(byte[] keys, UIntPtr keysLen, StreamId[] afterIds, UIntPtr count, ulong timeoutMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xread_block", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xread_block_mac(byte[] keys, UIntPtr keysLen, StreamId[] afterIds, UIntPtr count, ulong timeoutMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xgroup_create", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_create_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId lastId, int mkStream);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xgroup_create", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_create_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId lastId, int mkStream);
+
+    [DllImport(MacLib, EntryPoint = "cache_xgroup_create", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_create_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId lastId, int mkStream);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xgroup_destroy", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_destroy_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xgroup_destroy", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_destroy_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group);
+
+    [DllImport(MacLib, EntryPoint = "cache_xgroup_destroy", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_xgroup_destroy_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xreadgroup", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xreadgroup_win([MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, byte[] keys, UIntPtr keysLen, StreamId[] ids, UIntPtr count, int noAck, long blockMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xreadgroup", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xreadgroup_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, byte[] keys, UIntPtr keysLen, StreamId[] ids, UIntPtr count, int noAck, long blockMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xreadgroup", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xreadgroup_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, byte[] keys, UIntPtr keysLen, StreamId[] ids, UIntPtr count, int noAck, long blockMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xack", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xack_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId[] ids, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xack", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xack_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId[] ids, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xack", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_xack_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId[] ids, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xpending", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xpending_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId startId, StreamId endId, UIntPtr count, [MarshalAs(UnmanagedType.LPUTF8Str)] string? consumer, ulong minIdleMs, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xpending", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xpending_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId startId, StreamId endId, UIntPtr count, [MarshalAs(UnmanagedType.LPUTF8Str)] string? consumer, ulong minIdleMs, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xpending", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xpending_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, StreamId startId, StreamId endId, UIntPtr count, [MarshalAs(UnmanagedType.LPUTF8Str)] string? consumer, ulong minIdleMs, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xclaim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xclaim_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId[] ids, UIntPtr idsLen, int justId, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xclaim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xclaim_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId[] ids, UIntPtr idsLen, int justId, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xclaim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xclaim_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId[] ids, UIntPtr idsLen, int justId, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_xautoclaim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xautoclaim_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId startId, UIntPtr count, int justId, out StreamId nextId, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_xautoclaim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xautoclaim_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId startId, UIntPtr count, int justId, out StreamId nextId, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_xautoclaim", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_xautoclaim_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, [MarshalAs(UnmanagedType.LPUTF8Str)] string group, [MarshalAs(UnmanagedType.LPUTF8Str)] string consumer, ulong minIdleMs, StreamId startId, UIntPtr count, int justId, out StreamId nextId, out UIntPtr len);
+
+
+    // A stream entry id, <ms>-<seq>. Ids order by Ms, then Seq; the layout matches the native StreamId.
+    [StructLayout(LayoutKind.Sequential)]
+    public readonly record struct StreamId(ulong Ms, ulong Seq) : IComparable<StreamId>
+    {
+        public static readonly StreamId Min = new(0, 0);
+        public static readonly StreamId Max = new(ulong.MaxValue, ulong.MaxValue);
+
+        public int CompareTo(StreamId other)
+        {
+            var c = Ms.CompareTo(other.Ms);
+            return c != 0 ? c : Seq.CompareTo(other.Seq);
+        }
+
+        public static bool operator <(StreamId a, StreamId b) => a.CompareTo(b) < 0;
+        public static bool operator >(StreamId a, StreamId b) => a.CompareTo(b) > 0;
+        public static bool operator <=(StreamId a, StreamId b) => a.CompareTo(b) <= 0;
+        public static bool operator >=(StreamId a, StreamId b) => a.CompareTo(b) >= 0;
+
+        public override string ToString() => $"{Ms}-{Seq}";
+    }
+
+    // An entry's field/value pairs in the order they were added. Fields is null for an entry deleted
+    // after it was delivered (XReadGroup history reads).
+    public readonly record struct StreamItem(StreamId Id, IReadOnlyList<KeyValuePair<string, byte[]>>? Fields)
+    {
+        // The value of field "payload", which the single-payload XAdd overloads write.
+        public byte[]? Payload => Fields?.FirstOrDefault(f => f.Key == PayloadField).Value;
+    }
+
+    private const string PayloadField = "payload";
+
+    // A pending entry of a consumer group: who it was delivered to, how long ago, and how many times.
+    public readonly record struct StreamPendingEntry(StreamId Id, string Consumer, TimeSpan Idle, long Deliveries);
+
+    // Pass as an XReadBlock or XGroupCreate id to read only entries added after the call (Redis `$`),
+    // or as an XReadGroup id for entries never delivered to the group (Redis `>`).
+    public static readonly StreamId NewEntriesOnly = StreamId.Max;
+
+    // Appends an entry holding payload as field "payload", with an id above every id the stream has
+    // issued (the current time in ms when the clock is ahead of them).
+    public static StreamId XAdd(string key, byte[] payload)
     {
         ArgumentNullException.ThrowIfNull(key);
         ArgumentNullException.ThrowIfNull(payload);
 
         var len = (UIntPtr)payload.Length;
 
+        StreamId id;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            return cache_xadd_win(key, payload, len);
-        if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            return cache_xadd_linux(key, payload, len);
-        if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            return cache_xadd_mac(key, payload, len);
+            id = cache_xadd_win(key, payload, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            id = cache_xadd_linux(key, payload, len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            id = cache_xadd_mac(key, payload, len);
+        else
+            throw new PlatformNotSupportedException();
 
-        throw new PlatformNotSupportedException();
+        if (id == StreamId.Min)
+            throw ToException(LastStatus);
+        return id;
     }
 
-    public static List<StreamItem> XRange(string key, ulong startId, ulong endId)
+    // XAdd with an explicit id (null = generated), which must be above the stream's last one, and a
+    // cap applied after the add: keep the newest maxLen entries or drop those below minId (not both).
+    public static StreamId XAdd(string key, byte[] payload, StreamId? id = null, long? maxLen = null, StreamId? minId = null)
     {
+        ArgumentNullException.ThrowIfNull(payload);
+        return XAdd(key, new[] { new KeyValuePair<string, byte[]>(PayloadField, payload) }, id, maxLen, minId);
+    }
+
+    // XAdd of an entry with the given field/value pairs (at least one), kept in order.
+    public static unsafe StreamId XAdd(
+        string key,
+        IEnumerable<KeyValuePair<string, byte[]>> fields,
+        StreamId? id = null,
+        long? maxLen = null,
+        StreamId? minId = null)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(fields);
+        if (maxLen is < 0)
+            throw new ArgumentOutOfRangeException(nameof(maxLen));
+
+        var items = new List<byte[]>();
+        foreach (var (field, value) in fields)
+        {
+            ArgumentNullException.ThrowIfNull(field, nameof(fields));
+            items.Add(Encoding.UTF8.GetBytes(field));
+            items.Add(value ?? throw new ArgumentNullException(nameof(fields)));
+        }
+        if (items.Count == 0)
+            throw new ArgumentException("A stream entry needs at least one field.", nameof(fields));
+
+        var blob = PackItems(items);
+        var len = (UIntPtr)blob.Length;
+        var idValue = id.GetValueOrDefault();
+        var minIdValue = minId.GetValueOrDefault();
+        var idPtr = id.HasValue ? &idValue : null;
+        var minIdPtr = minId.HasValue ? &minIdValue : null;
+        var max = maxLen ?? -1;
+
+        int res;
+        StreamId added;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xadd_ex_win(key, blob, len, idPtr, max, minIdPtr, out added);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xadd_ex_linux(key, blob, len, idPtr, max, minIdPtr, out added);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xadd_ex_mac(key, blob, len, idPtr, max, minIdPtr, out added);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return added;
+    }
+
+    // Entries from startId to endId inclusive, oldest first, at most count (0 = all).
+    public static List<StreamItem> XRange(string key, StreamId startId, StreamId endId, int count = 0)
+    {
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
         UIntPtr len;
         IntPtr ptr;
 
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            ptr = cache_xrange_win(key, startId, endId, out len);
+            ptr = cache_xrange_win(key, startId, endId, (UIntPtr)count, out len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            ptr = cache_xrange_linux(key, startId, endId, out len);
+            ptr = cache_xrange_linux(key, startId, endId, (UIntPtr)count, out len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            ptr = cache_xrange_mac(key, startId, endId, out len);
+            ptr = cache_xrange_mac(key, startId, endId, (UIntPtr)count, out len);
         else
             throw new PlatformNotSupportedException();
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
             return new List<StreamItem>();
+        }
 
         var blob = CopyAndFree(ptr, len);
         return ParseXRangeBlob(blob);
     }
 
+    // Entries from endId down to startId inclusive, newest first, at most count (0 = all).
+    public static List<StreamItem> XRevRange(string key, StreamId endId, StreamId startId, int count = 0)
+    {
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xrevrange_win(key, endId, startId, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xrevrange_linux(key, endId, startId, (UIntPtr)count, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xrevrange_mac(key, endId, startId, (UIntPtr)count, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamItem>();
+        }
+
+        return ParseXRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // Keeps the newest maxLen entries or drops those below minId (exactly one of them); returns how
+    // many entries were removed. The stream keeps issuing ids above the trimmed ones.
+    public static unsafe long XTrim(string key, long? maxLen = null, StreamId? minId = null)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        if (maxLen is < 0)
+            throw new ArgumentOutOfRangeException(nameof(maxLen));
+
+        var minIdValue = minId.GetValueOrDefault();
+        var minIdPtr = minId.HasValue ? &minIdValue : null;
+        var max = maxLen ?? -1;
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xtrim_win(key, max, minIdPtr);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xtrim_linux(key, max, minIdPtr);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xtrim_mac(key, max, minIdPtr);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Removes the entries with the given ids; returns how many existed.
+    public static long XDel(string key, params StreamId[] ids)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(ids);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xdel_win(key, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xdel_linux(key, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xdel_mac(key, ids, (UIntPtr)ids.Length);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Number of entries; 0 for a missing key.
+    public static long XLen(string key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xlen_win(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xlen_linux(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xlen_mac(key);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Reads up to count entries (0 = all) newer than the given id from each stream, blocking until
+    // at least one stream has some or the timeout ends (TimeSpan.Zero or Timeout.InfiniteTimeSpan
+    // waits forever). Returns only the streams that had entries, or null on timeout.
+    public static Dictionary<string, List<StreamItem>>? XReadBlock(
+        IReadOnlyList<(string Key, StreamId AfterId)> streams,
+        int count,
+        TimeSpan timeout)
+    {
+        ArgumentNullException.ThrowIfNull(streams);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        var keys = PackKeys(streams.Select(s => s.Key));
+        var ids = streams.Select(s => s.AfterId).ToArray();
+        var timeoutMs = BlockTimeoutMs(timeout);
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xread_block_win(keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xread_block_linux(keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, timeoutMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xread_block_mac(keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, timeoutMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return ParseStreamReadsBlob(CopyAndFree(ptr, len));
+    }
+
+    private static Dictionary<string, List<StreamItem>> ParseStreamReadsBlob(byte[] blob)
+    {
+        // format: [StreamCount (u32)] { [KeyLen (u32)] [Key] <XRange format> } ...
+        var result = new Dictionary<string, List<StreamItem>>();
+        int offset = 0;
+        uint streamCount = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+        for (uint i = 0; i < streamCount && offset + 4 <= blob.Length; i++)
+        {
+            int keyLen = (int)BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            var key = Encoding.UTF8.GetString(blob, offset, keyLen);
+            offset += keyLen;
+            result[key] = ParseStreamItems(blob, ref offset);
+        }
+        return result;
+    }
+
     private static List<StreamItem> ParseXRangeBlob(byte[] blob)
     {
-        // format: [Count (u32)] [Id (u64)] [PayloadLen (u32)] [Payload] ...
+        int offset = 0;
+        return ParseStreamItems(blob, ref offset);
+    }
+
+    private static List<StreamItem> ParseStreamItems(byte[] blob, ref int offset)
+    {
+        // format: [Count (u32)] { [Ms (u64)] [Seq (u64)] [FieldCount (u32)] { [NameLen (u32)] [Name]
+        // [ValueLen (u32)] [Value] } } ...
+        // A FieldCount of uint.MaxValue (and no fields) marks an entry deleted since delivery.
         var result = new List<StreamItem>();
-        if (blob.Length < 4)
+        if (blob.Length - offset < 4)
             return result;
 
-        int offset = 0;
         uint count = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
         offset += 4;
 
@@ -82,25 +467,318 @@ public static partial class JustCache
 
         for (uint i = 0; i < count; i++)
         {
-            if (offset + 8 + 4 > blob.Length) break;
+            if (offset + 16 + 4 > blob.Length) break;
 
-            ulong id = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset, 8));
-            offset += 8;
+            var id = ReadStreamId(blob, ref offset);
 
-            uint plen = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            uint fieldCount = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
             offset += 4;
 
-            if (offset + plen > blob.Length) break;
+            if (fieldCount == uint.MaxValue)
+            {
+                result.Add(new StreamItem(id, null));
+                continue;
+            }
+
+            var fields = new List<KeyValuePair<string, byte[]>>((int)Math.Min(fieldCount, 1024));
+            for (uint f = 0; f < fieldCount; f++)
+            {
+                if (!TryReadItem(blob, ref offset, out var name) || !TryReadItem(blob, ref offset, out var value))
+                    return result;
+                fields.Add(new KeyValuePair<string, byte[]>(Encoding.UTF8.GetString(name), value));
+            }
+
+            result.Add(new StreamItem(id, fields));
+        }
+
+        return result;
+    }
+
+    private static bool TryReadItem(byte[] blob, ref int offset, out byte[] item)
+    {
+        item = Array.Empty<byte>();
+        if (offset + 4 > blob.Length) return false;
+
+        uint len = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+        if (offset + len > blob.Length) return false;
+
+        item = new byte[len];
+        Buffer.BlockCopy(blob, offset, item, 0, (int)len);
+        offset += (int)len;
+        return true;
+    }
+
+    private static StreamId ReadStreamId(byte[] blob, ref int offset)
+    {
+        ulong ms = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset, 8));
+        ulong seq = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset + 8, 8));
+        offset += 16;
+        return new StreamId(ms, seq);
+    }
+
+    #endregion
+
+    #region Streams: consumer groups
+
+    // Creates a consumer group that delivers entries after lastDeliveredId (null or NewEntriesOnly =
+    // only new ones). With mkStream a missing key gets an empty stream; otherwise it throws, as does an
+    // existing group (BUSYGROUP).
+    public static void XGroupCreate(string key, string group, StreamId? lastDeliveredId = null, bool mkStream = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+
+        var lastId = lastDeliveredId ?? NewEntriesOnly;
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xgroup_create_win(key, group, lastId, mkStream ? 1 : 0);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xgroup_create_linux(key, group, lastId, mkStream ? 1 : 0);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xgroup_create_mac(key, group, lastId, mkStream ? 1 : 0);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+    }
+
+    // Returns false if the stream had no such group.
+    public static bool XGroupDestroy(string key, string group)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+
+        int res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xgroup_destroy_win(key, group);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xgroup_destroy_linux(key, group);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xgroup_destroy_mac(key, group);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res == 1;
+    }
+
+    // Reads as consumer of group. A NewEntriesOnly id hands out entries no consumer of the group has
+    // received yet, each to exactly one reader; any other id re-reads this consumer's pending entries
+    // after it. Up to count entries per stream (0 = all); noAck skips the pending list. When every id
+    // is NewEntriesOnly and nothing is new, waits up to block (TimeSpan.Zero or Timeout.InfiniteTimeSpan
+    // = forever; null = do not wait). History reads return entries deleted since delivery with null
+    // Fields. Returns null if nothing was read.
+    public static Dictionary<string, List<StreamItem>>? XReadGroup(
+        string group,
+        string consumer,
+        IReadOnlyList<(string Key, StreamId AfterId)> streams,
+        int count = 0,
+        bool noAck = false,
+        TimeSpan? block = null)
+    {
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(consumer);
+        ArgumentNullException.ThrowIfNull(streams);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        var keys = PackKeys(streams.Select(s => s.Key));
+        var ids = streams.Select(s => s.AfterId).ToArray();
+        long blockMs = block is { } timeout ? (long)BlockTimeoutMs(timeout) : -1;
+        var flag = noAck ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xreadgroup_win(group, consumer, keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, flag, blockMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xreadgroup_linux(group, consumer, keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, flag, blockMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xreadgroup_mac(group, consumer, keys, (UIntPtr)keys.Length, ids, (UIntPtr)count, flag, blockMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return null;
+        }
+
+        return ParseStreamReadsBlob(CopyAndFree(ptr, len));
+    }
+
+    // Removes ids from the group's pending list; returns how many were pending.
+    public static long XAck(string key, string group, params StreamId[] ids)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(ids);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_xack_win(key, group, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_xack_linux(key, group, ids, (UIntPtr)ids.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_xack_mac(key, group, ids, (UIntPtr)ids.Length);
+        else
+            throw new PlatformNotSupportedException();
+
+        ThrowIfRejected(res);
+        return res;
+    }
 
-            byte[] payload = new byte[plen];
-            Buffer.BlockCopy(blob, offset, payload, 0, (int)plen);
-            offset += (int)plen;
+    // Pending entries of group with ids in [startId, endId] (null = unbounded), optionally only those of consumer and
+    // idle for at least minIdle.
+    public static List<StreamPendingEntry> XPending(
+        string key,
+        string group,
+        StreamId? startId = null,
+        StreamId? endId = null,
+        int count = int.MaxValue,
+        string? consumer = null,
+        TimeSpan? minIdle = null)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentOutOfRangeException.ThrowIfNegative(count);
+
+        var minIdleMs = IdleMs(minIdle ?? TimeSpan.Zero);
+        var start = startId ?? StreamId.Min;
+        var end = endId ?? StreamId.Max;
+        UIntPtr len;
+        IntPtr ptr;
 
-            result.Add(new StreamItem(id, payload));
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xpending_win(key, group, start, end, (UIntPtr)count, consumer, minIdleMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xpending_linux(key, group, start, end, (UIntPtr)count, consumer, minIdleMs, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xpending_mac(key, group, start, end, (UIntPtr)count, consumer, minIdleMs, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamPendingEntry>();
         }
 
+        // format: [Count (u32)] { [Ms (u64)] [Seq (u64)] [ConsumerLen (u32)] [Consumer] [IdleMs (u64)] [Deliveries (u64)] } ...
+        var blob = CopyAndFree(ptr, len);
+        var result = new List<StreamPendingEntry>();
+        int offset = 0;
+        uint n = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+        offset += 4;
+        for (uint i = 0; i < n && offset + 20 <= blob.Length; i++)
+        {
+            var id = ReadStreamId(blob, ref offset);
+            int nameLen = (int)BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            if (offset + nameLen + 16 > blob.Length) break;
+            var name = Encoding.UTF8.GetString(blob, offset, nameLen);
+            offset += nameLen;
+            ulong idleMs = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset, 8));
+            offset += 8;
+            ulong deliveries = BinaryPrimitives.ReadUInt64LittleEndian(blob.AsSpan(offset, 8));
+            offset += 8;
+            result.Add(new StreamPendingEntry(id, name, TimeSpan.FromMilliseconds(idleMs), (long)deliveries));
+        }
         return result;
     }
 
+    // Gives consumer the pending entries among ids that have been idle for at least minIdle and returns
+    // them (with no fields when justId, which also leaves delivery counts alone).
+    public static List<StreamItem> XClaim(string key, string group, string consumer, TimeSpan minIdle, IEnumerable<StreamId> ids, bool justId = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(consumer);
+        ArgumentNullException.ThrowIfNull(ids);
+
+        var idArray = ids.ToArray();
+        var minIdleMs = IdleMs(minIdle);
+        var flag = justId ? 1 : 0;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xclaim_win(key, group, consumer, minIdleMs, idArray, (UIntPtr)idArray.Length, flag, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xclaim_linux(key, group, consumer, minIdleMs, idArray, (UIntPtr)idArray.Length, flag, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xclaim_mac(key, group, consumer, minIdleMs, idArray, (UIntPtr)idArray.Length, flag, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return new List<StreamItem>();
+        }
+
+        return ParseXRangeBlob(CopyAndFree(ptr, len));
+    }
+
+    // Claims up to count pending entries idle for at least minIdle, scanning the pending list from
+    // startId. Pass NextStartId to the next call; it is StreamId.Min once the whole list has been
+    // scanned. Deleted lists pending ids whose entries were deleted; they leave the pending list.
+    public static (StreamId NextStartId, List<StreamItem> Claimed, List<StreamId> Deleted) XAutoClaim(
+        string key,
+        string group,
+        string consumer,
+        TimeSpan minIdle,
+        StreamId startId = default,
+        int count = 100,
+        bool justId = false)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(group);
+        ArgumentNullException.ThrowIfNull(consumer);
+        ArgumentOutOfRangeException.ThrowIfNegativeOrZero(count);
+
+        var minIdleMs = IdleMs(minIdle);
+        var flag = justId ? 1 : 0;
+        StreamId next;
+        UIntPtr len;
+        IntPtr ptr;
+
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_xautoclaim_win(key, group, consumer, minIdleMs, startId, (UIntPtr)count, flag, out next, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_xautoclaim_linux(key, group, consumer, minIdleMs, startId, (UIntPtr)count, flag, out next, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_xautoclaim_mac(key, group, consumer, minIdleMs, startId, (UIntPtr)count, flag, out next, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return (StreamId.Min, new List<StreamItem>(), new List<StreamId>());
+        }
+
+        // format: <XRange format> [DeletedCount (u32)] { [Ms (u64)] [Seq (u64)] } ...
+        var blob = CopyAndFree(ptr, len);
+        int offset = 0;
+        var claimed = ParseStreamItems(blob, ref offset);
+        var deleted = new List<StreamId>();
+        if (offset + 4 <= blob.Length)
+        {
+            uint n = BinaryPrimitives.ReadUInt32LittleEndian(blob.AsSpan(offset, 4));
+            offset += 4;
+            for (uint i = 0; i < n && offset + 16 <= blob.Length; i++)
+                deleted.Add(ReadStreamId(blob, ref offset));
+        }
+        return (next, claimed, deleted);
+    }
+
+    private static ulong IdleMs(TimeSpan idle)
+    {
+        if (idle < TimeSpan.Zero)
+            throw new ArgumentOutOfRangeException(nameof(idle));
+        return (ulong)idle.TotalMilliseconds;
+    }
+
     #endregion
 }
@@ -0,0 +1,346 @@
+using System.Runtime.InteropServices;
+
+namespace LiteAPI.Cache;
+
+public static partial class JustCache
+{
+    #region Strings (SET options/INCR/APPEND/SETRANGE/GETRANGE/GETSET/GETDEL)
+
+    [DllImport(WindowsLib, EntryPoint = "cache_incrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrby_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long incr, out long value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_incrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrby_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long incr, out long value);
+
+    [DllImport(MacLib, EntryPoint = "cache_incrby", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrby_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long incr, out long value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_incrbyfloat", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrbyfloat_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double incr, out double value);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_incrbyfloat", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrbyfloat_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double incr, out double value);
+
+    [DllImport(MacLib, EntryPoint = "cache_incrbyfloat", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_incrbyfloat_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, double incr, out double value);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_append", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_append_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_append", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_append_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_append", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_append_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_setrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_setrange_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, ulong offset, byte[] val, UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_setrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_setrange_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, ulong offset, byte[] val, UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_setrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_setrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, ulong offset, byte[] val, UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_getrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getrange_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long start, long end, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_getrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getrange_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long start, long end, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_getrange", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getrange_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, long start, long end, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_strlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_strlen_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_strlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_strlen_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+    [DllImport(MacLib, EntryPoint = "cache_strlen", CallingConvention = CallingConvention.Cdecl)]
+    private static extern long cache_strlen_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_getset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getset_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, out UIntPtr outLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_getset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getset_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, out UIntPtr outLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_getset", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getset_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, out UIntPtr outLen);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_getdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getdel_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_getdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getdel_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+    [DllImport(MacLib, EntryPoint = "cache_getdel", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_getdel_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, out UIntPtr len);
+
+
+    [DllImport(WindowsLib, EntryPoint = "cache_set_ex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_ex_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, int flags, ulong ttl, out IntPtr oldValue, out UIntPtr oldLen);
+
+    [DllImport(LinuxLib, EntryPoint = "cache_set_ex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_ex_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, int flags, ulong ttl, out IntPtr oldValue, out UIntPtr oldLen);
+
+    [DllImport(MacLib, EntryPoint = "cache_set_ex", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_set_ex_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len, int flags, ulong ttl, out IntPtr oldValue, out UIntPtr oldLen);
+
+    // Atomically adds increment to the integer stored at key (a missing key counts as 0) and returns
+    // the new value. The value must be a base-10 64-bit integer and the result must not overflow.
+    public static long IncrBy(string key, long increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        int res;
+        long value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_incrby_win(key, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_incrby_linux(key, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_incrby_mac(key, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    public static long Incr(string key) => IncrBy(key, 1);
+
+    public static long Decr(string key) => IncrBy(key, -1);
+
+    public static long DecrBy(string key, long decrement)
+    {
+        if (decrement == long.MinValue)
+            throw new ArgumentException("decrement would overflow", nameof(decrement));
+        return IncrBy(key, -decrement);
+    }
+
+    // Atomically adds increment to the float stored at key (a missing key counts as 0) and returns the
+    // new value, which must stay finite.
+    public static double IncrByFloat(string key, double increment)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        int res;
+        double value;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_incrbyfloat_win(key, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_incrbyfloat_linux(key, increment, out value);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_incrbyfloat_mac(key, increment, out value);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return value;
+    }
+
+    // Appends val to the string at key (creating it if missing); returns the new length. The key keeps
+    // its TTL.
+    public static long Append(string key, byte[] val)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_append_win(key, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_append_linux(key, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_append_mac(key, val, (UIntPtr)val.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Overwrites the string at key from offset on, zero-padding it if it is shorter; returns the new
+    // length. An empty val changes nothing. The key keeps its TTL.
+    public static long SetRange(string key, long offset, byte[] val)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+        ArgumentOutOfRangeException.ThrowIfNegative(offset);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_setrange_win(key, (ulong)offset, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_setrange_linux(key, (ulong)offset, val, (UIntPtr)val.Length);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_setrange_mac(key, (ulong)offset, val, (UIntPtr)val.Length);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Bytes start..end (inclusive) of the string at key; negative offsets count from the end. Empty for
+    // a missing key.
+    public static byte[] GetRange(string key, long start, long end)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_getrange_win(key, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_getrange_linux(key, start, end, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_getrange_mac(key, start, end, out len);
+        else
+            throw new PlatformNotSupportedException();
+
+        if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+        {
+            ThrowIfFailed();
+            return Array.Empty<byte>();
+        }
+        return CopyAndFree(ptr, len);
+    }
+
+    // Length of the string at key, 0 if it is missing.
+    public static long StrLen(string key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        long res;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_strlen_win(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_strlen_linux(key);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_strlen_mac(key);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+        return res;
+    }
+
+    // Sets key to val (dropping any TTL) and returns the previous value, or null if it was missing.
+    public static byte[]? GetSet(string key, byte[] val)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_getset_win(key, val, (UIntPtr)val.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_getset_linux(key, val, (UIntPtr)val.Length, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_getset_mac(key, val, (UIntPtr)val.Length, out len);
+        else
+            throw new PlatformNotSupportedException();
+        return TakeStringResult(ptr, len);
+    }
+
+    // Removes key and returns its value, or null if it was missing.
+    public static byte[]? GetDel(string key)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+
+        UIntPtr len;
+        IntPtr ptr;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            ptr = cache_getdel_win(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            ptr = cache_getdel_linux(key, out len);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            ptr = cache_getdel_mac(key, out len);
+        else
+            throw new PlatformNotSupportedException();
+        return TakeStringResult(ptr, len);
+    }
+
+    // Options of SetEx, matching SET's NX / XX / GET / KEEPTTL. ExAt is implied by the expireAt overload.
+    [Flags]
+    public enum SetFlags
+    {
+        None = 0,
+        Nx = 1,
+        Xx = 2,
+        Get = 4,
+        KeepTtl = 8,
+        ExAt = 16,
+    }
+
+    // SET key val with options in one atomic step: Nx writes only if key is missing, Xx only if it
+    // exists, KeepTtl keeps the current TTL instead of clearing it, and ttl sets a new one. Returns
+    // whether the value was written.
+    public static bool SetEx(string key, byte[] val, SetFlags flags = SetFlags.None, TimeSpan? ttl = null)
+    {
+        if (ttl is { } t && t <= TimeSpan.Zero)
+            throw new ArgumentOutOfRangeException(nameof(ttl));
+        return SetExCore(key, val, flags & ~(SetFlags.Get | SetFlags.ExAt), ttl is { } d ? (ulong)d.TotalMilliseconds : 0).Written;
+    }
+
+    // Like SetEx, but the key expires at the given point in time (a past one removes it).
+    public static bool SetEx(string key, byte[] val, SetFlags flags, DateTimeOffset expireAt)
+    {
+        var at = expireAt.ToUnixTimeMilliseconds();
+        if (at <= 0)
+            throw new ArgumentOutOfRangeException(nameof(expireAt));
+        return SetExCore(key, val, (flags & ~SetFlags.Get) | SetFlags.ExAt, (ulong)at).Written;
+    }
+
+    // SetEx that also returns the previous value (SET ... GET): null if the key was missing. A key
+    // holding another type fails with WRONGTYPE before anything is written.
+    public static (bool Written, byte[]? Old) SetExGet(string key, byte[] val, SetFlags flags = SetFlags.None, TimeSpan? ttl = null)
+    {
+        if (ttl is { } t && t <= TimeSpan.Zero)
+            throw new ArgumentOutOfRangeException(nameof(ttl));
+        return SetExCore(key, val, (flags & ~SetFlags.ExAt) | SetFlags.Get, ttl is { } d ? (ulong)d.TotalMilliseconds : 0);
+    }
+
+    private static (bool Written, byte[]? Old) SetExCore(string key, byte[] val, SetFlags flags, ulong ttl)
+    {
+        ArgumentNullException.ThrowIfNull(key);
+        ArgumentNullException.ThrowIfNull(val);
+
+        int res;
+        IntPtr old;
+        UIntPtr oldLen;
+        if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+            res = cache_set_ex_win(key, val, (UIntPtr)val.Length, (int)flags, ttl, out old, out oldLen);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+            res = cache_set_ex_linux(key, val, (UIntPtr)val.Length, (int)flags, ttl, out old, out oldLen);
+        else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+            res = cache_set_ex_mac(key, val, (UIntPtr)val.Length, (int)flags, ttl, out old, out oldLen);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
+
+        if (!flags.HasFlag(SetFlags.Get))
+            return (res == 1, null);
+        if (old != IntPtr.Zero && oldLen != UIntPtr.Zero)
+            return (res == 1, CopyAndFree(old, oldLen));
+        return (res == 1, oldLen == UIntPtr.MaxValue ? null : Array.Empty<byte>());
+    }
+
+    // A returned value: null only when the key was missing, empty for an empty value.
+    private static byte[]? TakeStringResult(IntPtr ptr, UIntPtr len)
+    {
+        if (ptr != IntPtr.Zero && len != UIntPtr.Zero)
+            return CopyAndFree(ptr, len);
+
+        ThrowIfFailed();
+        return LastStatus == CacheStatus.NotFound ? null : Array.Empty<byte>();
+    }
+
+    #endregion
+}
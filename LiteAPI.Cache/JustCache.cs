@@ -95,27 +95,124 @@ public static partial class JustCache
     
     #endregion
 
+    #region Errors
+
+    // Every native call records its outcome per thread; failed calls return a negative status
+    // (or null/0) and leave a Redis-style message such as "WRONGTYPE ..." behind.
+    [DllImport(WindowsLib, EntryPoint = "cache_last_status", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_last_status_win();
+    [DllImport(LinuxLib, EntryPoint = "cache_last_status", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_last_status_linux();
+    [DllImport(MacLib, EntryPoint = "cache_last_status", CallingConvention = CallingConvention.Cdecl)]
+    private static extern int cache_last_status_mac();
+
+    [DllImport(WindowsLib, EntryPoint = "cache_last_error", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_last_error_win(out UIntPtr len);
+    [DllImport(LinuxLib, EntryPoint = "cache_last_error", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_last_error_linux(out UIntPtr len);
+    [DllImport(MacLib, EntryPoint = "cache_last_error", CallingConvention = CallingConvention.Cdecl)]
+    private static extern IntPtr cache_last_error_mac(out UIntPtr len);
+
+    // Outcome of the last JustCache call on this thread.
+    public static CacheStatus LastStatus
+    {
+        get
+        {
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+                return (CacheStatus)cache_last_status_win();
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+                return (CacheStatus)cache_last_status_linux();
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+                return (CacheStatus)cache_last_status_mac();
+
+            throw new PlatformNotSupportedException();
+        }
+    }
+
+    // Error message of the last JustCache call on this thread, or null if it succeeded.
+    public static string? LastError
+    {
+        get
+        {
+            UIntPtr len;
+            IntPtr ptr;
+            if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+                ptr = cache_last_error_win(out len);
+            else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
+                ptr = cache_last_error_linux(out len);
+            else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+                ptr = cache_last_error_mac(out len);
+            else
+                throw new PlatformNotSupportedException();
+
+            if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
+                return null;
+            return System.Text.Encoding.UTF8.GetString(CopyAndFree(ptr, len));
+        }
+    }
+
+    private static Exception ToException(CacheStatus status)
+    {
+        var message = LastError ?? $"Cache call failed with status {status}.";
+        return status switch
+        {
+            CacheStatus.NotFound => new KeyNotFoundException(message),
+            CacheStatus.InvalidArgument => new ArgumentException(message),
+            CacheStatus.Io => new IOException(message),
+            CacheStatus.InvalidHandle => new ObjectDisposedException(nameof(JustCache), message),
+            _ => new JustCacheException(status, message),
+        };
+    }
+
+    // For calls returning a status: throws for any negative one.
+    private static void ThrowIfRejected(int res)
+    {
+        if (res < 0)
+            throw ToException((CacheStatus)res);
+    }
+
+    private static void ThrowIfRejected(long res)
+    {
+        if (res < 0)
+            throw ToException((CacheStatus)res);
+    }
+
+    // For lookups that returned nothing: a missing key is not an error, anything else
+    // (e.g. WRONGTYPE) throws.
+    private static void ThrowIfFailed()
+    {
+        var status = LastStatus;
+        if (status != CacheStatus.Ok && status != CacheStatus.NotFound)
+            throw ToException(status);
+    }
+
+    #endregion
+
     #region  Set Method
 
     [DllImport(WindowsLib, EntryPoint = "cache_set", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+    private static extern int cache_set_win([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
     [DllImport(LinuxLib, EntryPoint = "cache_set", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+    private static extern int cache_set_linux([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
     [DllImport(MacLib, EntryPoint = "cache_set", CallingConvention = CallingConvention.Cdecl)]
-    private static extern void cache_set_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
+    private static extern int cache_set_mac([MarshalAs(UnmanagedType.LPUTF8Str)] string key, byte[] val, UIntPtr len);
 
     public static void Set(string key, byte[] val)
     {
         var len = (UIntPtr)val.Length;
 
+        int res;
         if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
-            cache_set_win(key, val, len);
+            res = cache_set_win(key, val, len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.Linux))
-            cache_set_linux(key, val, len);
+            res = cache_set_linux(key, val, len);
         else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
-            cache_set_mac(key, val, len);
+            res = cache_set_mac(key, val, len);
+        else
+            throw new PlatformNotSupportedException();
+        ThrowIfRejected(res);
 
         JustCacheEventSource.Log.ReportSet(val.Length);
     }
@@ -167,6 +264,7 @@ public static partial class JustCache
 
         if (ptr == IntPtr.Zero || len == UIntPtr.Zero)
         {
+            ThrowIfFailed();
             JustCacheEventSource.Log.ReportGetMiss();
             return null;
         }
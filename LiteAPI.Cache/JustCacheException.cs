@@ -0,0 +1,26 @@
+namespace LiteAPI.Cache;
+
+// Status codes returned by the native library (see CacheStatus in RustLib).
+public enum CacheStatus
+{
+    Ok = 0,
+    OutOfMemory = -1,
+    WrongType = -2,
+    NotFound = -3,
+    InvalidArgument = -4,
+    Io = -5,
+    InvalidHandle = -6,
+}
+
+// Thrown when the cache rejects a command: the write needs room the eviction policy cannot
+// make (OutOfMemory), or the key holds another kind of value (WrongType).
+public sealed class JustCacheException : InvalidOperationException
+{
+    public JustCacheException(CacheStatus status, string message)
+        : base(message)
+    {
+        Status = status;
+    }
+
+    public CacheStatus Status { get; }
+}
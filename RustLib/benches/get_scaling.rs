@@ -0,0 +1,84 @@
+// Multi-threaded GET throughput over the FFI hot path (`cache_get_into`).
+//
+// Run with `cargo bench --bench get_scaling`. Each thread count reads random keys from a
+// preloaded keyspace for a fixed duration; with shared-lock reads, throughput should grow
+// with the thread count up to the number of cores.
+
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rust_cache::{cache_clear_all, cache_get_into, cache_init, cache_set};
+
+const KEYS: usize = 100_000;
+const VALUE_LEN: usize = 128;
+const RUN_FOR: Duration = Duration::from_secs(2);
+
+fn main() {
+    cache_init();
+    cache_clear_all();
+
+    let keys: Arc<Vec<CString>> = Arc::new((0..KEYS).map(|i| CString::new(format!("key:{}", i)).unwrap()).collect());
+    let value = [b'x'; VALUE_LEN];
+    for key in keys.iter() {
+        cache_set(key.as_ptr(), value.as_ptr(), value.len());
+    }
+
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut thread_counts = vec![1];
+    while *thread_counts.last().unwrap() < cores.max(8) {
+        thread_counts.push(thread_counts.last().unwrap() * 2);
+    }
+
+    println!("get_scaling: {} keys, {} byte values, {} cores", KEYS, VALUE_LEN, cores);
+    let mut baseline = 0.0;
+    for threads in thread_counts {
+        let ops_per_sec = run(&keys, threads);
+        if baseline == 0.0 {
+            baseline = ops_per_sec;
+        }
+        println!(
+            "{:>3} threads: {:>12.0} GET/s  ({:.2}x)",
+            threads,
+            ops_per_sec,
+            ops_per_sec / baseline
+        );
+    }
+}
+
+fn run(keys: &Arc<Vec<CString>>, threads: usize) -> f64 {
+    let stop = Arc::new(AtomicBool::new(false));
+    let total = Arc::new(AtomicU64::new(0));
+    let workers: Vec<_> = (0..threads)
+        .map(|t| {
+            let (keys, stop, total) = (keys.clone(), stop.clone(), total.clone());
+            std::thread::spawn(move || {
+                let mut buf = [0u8; VALUE_LEN];
+                let mut x = 0x9E37_79B9_7F4A_7C15u64 ^ (t as u64 + 1);
+                let mut ops = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    for _ in 0..1024 {
+                        // xorshift64: cheap key selection that does not contend across threads.
+                        x ^= x << 13;
+                        x ^= x >> 7;
+                        x ^= x << 17;
+                        let key = &keys[(x % KEYS as u64) as usize];
+                        let n = cache_get_into(key.as_ptr(), buf.as_mut_ptr(), buf.len());
+                        assert_eq!(n, VALUE_LEN as i64);
+                    }
+                    ops += 1024;
+                }
+                total.fetch_add(ops, Ordering::Relaxed);
+            })
+        })
+        .collect();
+
+    let started = Instant::now();
+    std::thread::sleep(RUN_FOR);
+    stop.store(true, Ordering::Relaxed);
+    for w in workers {
+        w.join().unwrap();
+    }
+    total.load(Ordering::Relaxed) as f64 / started.elapsed().as_secs_f64()
+}
@@ -0,0 +1,148 @@
+// Standalone RESP server around the cache library.
+//
+// Usage: rust_cache_server [--bind ADDR] [--unix PATH] [--aof PATH] [--appendfsync always|everysec|no]
+//                          [--max-items N] [--maxmemory BYTES] [--maxmemory-policy POLICY]
+
+use std::ffi::CString;
+use std::process::ExitCode;
+
+use rust_cache::{
+    cache_aof_enable, cache_aof_load, cache_aof_set_fsync_policy, cache_free, cache_init, cache_last_error,
+    cache_set_max_items, cache_set_eviction_policy, cache_set_max_memory_bytes, server,
+};
+
+const USAGE: &str = "usage: rust_cache_server [--bind ADDR] [--unix PATH] [--aof PATH] \
+                         [--appendfsync always|everysec|no] [--max-items N] [--maxmemory BYTES] \
+                         [--maxmemory-policy allkeys-lru|allkeys-lfu|w-tinylfu|volatile-lru|volatile-ttl|noeviction]";
+
+// Message of the last failed library call on this thread.
+fn last_error() -> String {
+    let mut len = 0;
+    let ptr = cache_last_error(&mut len);
+    if ptr.is_null() {
+        return String::new();
+    }
+    let msg = String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(ptr, len) }).into_owned();
+    cache_free(ptr, len);
+    msg
+}
+
+struct Options {
+    bind: String,
+    unix: Option<String>,
+    aof: Option<String>,
+    fsync_policy: Option<i32>,
+    max_items: Option<usize>,
+    max_memory: Option<u64>,
+    eviction_policy: Option<i32>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        bind: "127.0.0.1:6379".to_string(),
+        unix: None,
+        aof: None,
+        fsync_policy: None,
+        max_items: None,
+        max_memory: None,
+        eviction_policy: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("missing value for {}", name));
+        match arg.as_str() {
+            "--bind" => opts.bind = value("--bind")?,
+            "--unix" => opts.unix = Some(value("--unix")?),
+            "--aof" => opts.aof = Some(value("--aof")?),
+            "--appendfsync" => {
+                opts.fsync_policy = Some(match value("--appendfsync")?.as_str() {
+                    "no" => 0,
+                    "everysec" => 1,
+                    "always" => 2,
+                    other => return Err(format!("invalid --appendfsync: {}", other)),
+                });
+            }
+            "--max-items" => {
+                let v = value("--max-items")?;
+                opts.max_items = Some(v.parse().map_err(|_| format!("invalid --max-items: {}", v))?);
+            }
+            "--maxmemory" => {
+                let v = value("--maxmemory")?;
+                opts.max_memory = Some(v.parse().map_err(|_| format!("invalid --maxmemory: {}", v))?);
+            }
+            "--maxmemory-policy" => {
+                opts.eviction_policy = Some(match value("--maxmemory-policy")?.as_str() {
+                    "allkeys-lru" => 0,
+                    "allkeys-lfu" => 1,
+                    "w-tinylfu" => 2,
+                    "volatile-lru" => 3,
+                    "volatile-ttl" => 4,
+                    "noeviction" => 5,
+                    other => return Err(format!("invalid --maxmemory-policy: {}", other)),
+                });
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other => return Err(format!("unknown argument: {}\n{}", other, USAGE)),
+        }
+    }
+    Ok(opts)
+}
+
+fn main() -> ExitCode {
+    let opts = match parse_args() {
+        Ok(o) => o,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            return ExitCode::from(2);
+        }
+    };
+
+    cache_init();
+    if let Some(n) = opts.max_items {
+        cache_set_max_items(n);
+    }
+    if let Some(policy) = opts.eviction_policy {
+        cache_set_eviction_policy(policy);
+    }
+    if let Some(bytes) = opts.max_memory {
+        cache_set_max_memory_bytes(bytes);
+    }
+    if let Some(policy) = opts.fsync_policy {
+        cache_aof_set_fsync_policy(policy);
+    }
+    if let Some(path) = &opts.aof {
+        let Ok(cpath) = CString::new(path.as_str()) else {
+            eprintln!("invalid --aof path");
+            return ExitCode::from(2);
+        };
+        if std::path::Path::new(path).exists() && cache_aof_load(cpath.as_ptr()) != 1 {
+            eprintln!("failed to load AOF {}: {}", path, last_error());
+            return ExitCode::FAILURE;
+        }
+        if cache_aof_enable(cpath.as_ptr()) != 1 {
+            eprintln!("failed to open AOF {}: {}", path, last_error());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(path) = &opts.unix {
+        #[cfg(unix)]
+        if let Err(e) = server::spawn_unix(path) {
+            eprintln!("failed to bind {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+        #[cfg(not(unix))]
+        {
+            eprintln!("--unix is not supported on this platform ({})", path);
+            return ExitCode::from(2);
+        }
+    }
+
+    eprintln!("rust_cache_server listening on {}", opts.bind);
+    if let Err(e) = server::serve_tcp(&opts.bind) {
+        eprintln!("failed to bind {}: {}", opts.bind, e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
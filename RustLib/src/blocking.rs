@@ -0,0 +1,310 @@
+// Blocking list pops (BLPOP / BRPOP) and stream reads (XREAD / XREADGROUP BLOCK).
+//
+// A blocked caller registers on each of its keys and parks on the instance's `blocked_signal`;
+// pushes and XADD wake the waiters of the key they wrote once the shard lock is released.
+// List waiters are queued per key and only the oldest one may pop from it, so clients blocked
+// on the same list are served first come, first served (a new caller also queues behind them).
+// Stream readers only need the wake-up: XREAD does not consume entries, and XREADGROUP hands
+// each new entry to whichever group reader gets to it first.
+//
+// Every attempt re-reads the key: a list or stream that is deleted, expires or is replaced by
+// another type while clients wait keeps them waiting until a new one appears or the timeout
+// ends. Waits are sliced so that destroyed instances and cancelled callers (a RESP client that
+// disconnected) are noticed.
+//
+// Attempts run without the `waiters` lock: a pop or read takes shard locks and may wait for the
+// AOF (fsync under `always`), which must not hold up pushes calling `wake`. A caller notes the
+// wake-up count before an attempt and tries again instead of parking if it changed meanwhile.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::{cmd_pop, cmd_xread, CacheError, CacheKey, Instance, StreamId, StreamItems};
+
+const WAIT_SLICE: Duration = Duration::from_millis(100);
+
+// A waited-on key, by its bytes.
+type WaitKey = Vec<u8>;
+
+fn wait_key<K: CacheKey + ?Sized>(key: &K) -> WaitKey {
+    key.as_bytes().to_vec()
+}
+
+#[derive(Default)]
+pub(crate) struct Waiters {
+    next_id: u64,
+    // List waiters per key, oldest first.
+    queues: HashMap<WaitKey, VecDeque<u64>>,
+    // Number of blocked stream readers per key.
+    readers: HashMap<WaitKey, usize>,
+    // Wake-ups so far, so an attempt made without the lock can tell it missed one.
+    wakes: u64,
+}
+
+impl Waiters {
+    fn notify(&mut self, db: &Instance) {
+        self.wakes += 1;
+        db.blocked_signal.notify_all();
+    }
+}
+
+// Called after a push or XADD on `key` (without holding its shard lock).
+pub(crate) fn wake<K: CacheKey + ?Sized>(db: &Instance, key: &K) {
+    let mut waiters = db.waiters.lock().unwrap();
+    if waiters.queues.is_empty() && waiters.readers.is_empty() {
+        return;
+    }
+    let key = wait_key(key);
+    if waiters.queues.contains_key(&key) || waiters.readers.contains_key(&key) {
+        waiters.notify(db);
+    }
+}
+
+fn pop_one<K: CacheKey + ?Sized>(db: &Instance, key: &K, left: bool) -> Result<Option<Vec<u8>>, CacheError> {
+    Ok(cmd_pop(db, key, left, 1)?.and_then(|items| items.into_iter().next()))
+}
+
+// Time left before `deadline` (None = wait forever), capped at one slice; None once it passed.
+fn next_wait(deadline: Option<Instant>) -> Option<Duration> {
+    match deadline {
+        Some(d) => d.checked_duration_since(Instant::now()).filter(|t| !t.is_zero()).map(|t| t.min(WAIT_SLICE)),
+        None => Some(WAIT_SLICE),
+    }
+}
+
+// Pops from the head (`left`) or tail of the first of `keys` holding a list, waiting up to
+// `timeout` (None = forever) for one to appear. Returns the key and the item, or None on
+// timeout, cancellation or when the instance is destroyed. A key of another type fails with
+// WrongType only if found before blocking.
+pub(crate) fn blocking_pop<K: CacheKey + Clone>(
+    db: &Instance,
+    keys: &[K],
+    left: bool,
+    timeout: Option<Duration>,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<Option<(K, Vec<u8>)>, CacheError> {
+    let _alive = db.arc();
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let wait_keys: Vec<WaitKey> = keys.iter().map(wait_key).collect();
+    let free: Vec<bool> = {
+        let waiters = db.waiters.lock().unwrap();
+        wait_keys.iter().map(|k| !waiters.queues.contains_key(k)).collect()
+    };
+    for (key, _) in keys.iter().zip(&free).filter(|(_, free)| **free) {
+        if let Some(item) = pop_one(db, key, left)? {
+            return Ok(Some((key.clone(), item)));
+        }
+    }
+
+    let mut waiters = db.waiters.lock().unwrap();
+    let id = waiters.next_id;
+    waiters.next_id += 1;
+    for wait_key in &wait_keys {
+        waiters.queues.entry(wait_key.clone()).or_default().push_back(id);
+    }
+    let served = loop {
+        let wakes = waiters.wakes;
+        let ours: Vec<bool> = wait_keys.iter().map(|k| waiters.queues.get(k).and_then(|q| q.front()) == Some(&id)).collect();
+        drop(waiters);
+        let mut hit = None;
+        for (key, _) in keys.iter().zip(&ours).filter(|(_, ours)| **ours) {
+            // Errors (the key now holds another type) just mean nothing to pop yet.
+            if let Ok(Some(item)) = pop_one(db, key, left) {
+                hit = Some((key.clone(), item));
+                break;
+            }
+        }
+        waiters = db.waiters.lock().unwrap();
+        if hit.is_some() {
+            break hit;
+        }
+        if db.closed.load(Ordering::Relaxed) || cancelled() {
+            break None;
+        }
+        let Some(wait) = next_wait(deadline) else { break None; };
+        if waiters.wakes == wakes {
+            waiters = db.blocked_signal.wait_timeout(waiters, wait).unwrap().0;
+        }
+    };
+
+    for wait_key in &wait_keys {
+        if let Some(queue) = waiters.queues.get_mut(wait_key) {
+            queue.retain(|w| *w != id);
+            if queue.is_empty() {
+                waiters.queues.remove(wait_key);
+            }
+        }
+    }
+    // The next waiter in line may be able to pop now.
+    waiters.notify(db);
+    Ok(served)
+}
+
+// Reads entries after the given ids from every stream, waiting up to `timeout` (None =
+// forever) until at least one has some. Returns only the streams with entries, or None on
+// timeout, cancellation or when the instance is destroyed.
+pub(crate) fn blocking_xread<K: CacheKey + Clone>(
+    db: &Instance,
+    streams: &[(K, StreamId)],
+    count: Option<usize>,
+    timeout: Option<Duration>,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<Option<Vec<(K, StreamItems)>>, CacheError> {
+    let keys: Vec<K> = streams.iter().map(|(key, _)| key.clone()).collect();
+    blocking_stream_read(db, &keys, timeout, cancelled, &|| cmd_xread(db, streams, count))
+}
+
+// Runs `read` over the streams at `keys` until it returns something, retrying whenever one
+// of them is written, for up to `timeout` (None = forever). XREAD and XREADGROUP share it.
+// An error is returned only from the first attempt; later ones just mean nothing to read yet.
+pub(crate) fn blocking_stream_read<K: CacheKey, T>(
+    db: &Instance,
+    keys: &[K],
+    timeout: Option<Duration>,
+    cancelled: &dyn Fn() -> bool,
+    read: &dyn Fn() -> Result<Vec<T>, CacheError>,
+) -> Result<Option<Vec<T>>, CacheError> {
+    let _alive = db.arc();
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let found = read()?;
+    if !found.is_empty() {
+        return Ok(Some(found));
+    }
+
+    let wait_keys: Vec<WaitKey> = keys.iter().map(wait_key).collect();
+    let mut waiters = db.waiters.lock().unwrap();
+    for wait_key in &wait_keys {
+        *waiters.readers.entry(wait_key.clone()).or_default() += 1;
+    }
+    // Read once more after registering: an XADD since the first read did not wake us.
+    let found = loop {
+        let wakes = waiters.wakes;
+        drop(waiters);
+        let found = read();
+        waiters = db.waiters.lock().unwrap();
+        match found {
+            Ok(found) if !found.is_empty() => break Some(found),
+            _ if db.closed.load(Ordering::Relaxed) || cancelled() => break None,
+            _ => {}
+        }
+        let Some(wait) = next_wait(deadline) else { break None; };
+        if waiters.wakes == wakes {
+            waiters = db.blocked_signal.wait_timeout(waiters, wait).unwrap().0;
+        }
+    };
+
+    for wait_key in &wait_keys {
+        if let Some(n) = waiters.readers.get_mut(wait_key) {
+            *n -= 1;
+            if *n == 0 {
+                waiters.readers.remove(wait_key);
+            }
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd_expire, cmd_remove, cmd_rpush, cmd_set, cmd_xadd, db, shard_index, test_reset};
+
+    fn queued(key: &str) -> usize {
+        db().waiters.lock().unwrap().queues.get(&wait_key(key)).map_or(0, |q| q.len())
+    }
+
+    fn wait_until(f: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if f() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("condition not reached");
+    }
+
+    fn spawn_pop(keys: &[&str]) -> std::thread::JoinHandle<Option<(String, Vec<u8>)>> {
+        let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+        std::thread::spawn(move || blocking_pop(db(), &keys, true, Some(Duration::from_secs(10)), &|| false).unwrap())
+    }
+
+    #[test]
+    fn waiters_are_served_in_arrival_order() {
+        let _guard = test_reset();
+        let first = spawn_pop(&["q", "other"]);
+        wait_until(|| queued("q") == 1);
+        let second = spawn_pop(&["q"]);
+        wait_until(|| queued("q") == 2);
+
+        cmd_rpush(db(), "q", vec![b"1".to_vec(), b"2".to_vec()]).unwrap();
+        assert_eq!(first.join().unwrap(), Some(("q".to_string(), b"1".to_vec())));
+        assert_eq!(second.join().unwrap(), Some(("q".to_string(), b"2".to_vec())));
+        assert_eq!(queued("q"), 0);
+        assert_eq!(queued("other"), 0);
+    }
+
+    #[test]
+    fn waiters_outlive_removed_replaced_and_expired_keys() {
+        let _guard = test_reset();
+        let short = Some(Duration::from_millis(30));
+        assert_eq!(blocking_pop(db(), &["missing".to_string()], true, short, &|| false), Ok(None));
+        cmd_set(db(), "s", b"v".to_vec()).unwrap();
+        assert_eq!(blocking_pop(db(), &["s".to_string()], true, short, &|| false), Err(CacheError::WrongType));
+
+        cmd_rpush(db(), "e", vec![b"old".to_vec()]).unwrap();
+        assert!(cmd_expire(db(), "e", 1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(blocking_pop(db(), &["e".to_string()], true, short, &|| false), Ok(None));
+
+        let waiter = spawn_pop(&["k"]);
+        wait_until(|| queued("k") == 1);
+        cmd_set(db(), "k", b"not a list".to_vec()).unwrap();
+        assert!(cmd_remove(db(), "k"));
+        cmd_rpush(db(), "k", vec![b"new".to_vec()]).unwrap();
+        assert_eq!(waiter.join().unwrap(), Some(("k".to_string(), b"new".to_vec())));
+
+        // Cancellation ends an indefinite wait.
+        assert_eq!(blocking_pop(db(), &["none".to_string()], true, None, &|| true), Ok(None));
+        assert_eq!(queued("none"), 0);
+    }
+
+    #[test]
+    fn attempts_do_not_hold_the_waiters_lock() {
+        let _guard = test_reset();
+        let waiter = spawn_pop(&["q"]);
+        wait_until(|| queued("q") == 1);
+        // The waiter's next attempt blocks on the shard lock held here...
+        let shard = db().cache.shards[shard_index("q")].write().unwrap();
+        wake(db(), "q");
+        std::thread::sleep(Duration::from_millis(20));
+        // ...without keeping other callers from waking it.
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            wake(db(), "q");
+            tx.send(queued("q")).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+        drop(shard);
+
+        cmd_rpush(db(), "q", vec![b"1".to_vec()]).unwrap();
+        assert_eq!(waiter.join().unwrap(), Some(("q".to_string(), b"1".to_vec())));
+    }
+
+    #[test]
+    fn stream_readers_wake_on_xadd() {
+        let _guard = test_reset();
+        let first = cmd_xadd(db(), "s", b"a".to_vec()).unwrap();
+        let reader = std::thread::spawn(move || {
+            let streams = [("s".to_string(), first)];
+            blocking_xread(db(), &streams, None, Some(Duration::from_secs(10)), &|| false).unwrap()
+        });
+        wait_until(|| db().waiters.lock().unwrap().readers.contains_key(&wait_key("s")));
+        let id = cmd_xadd(db(), "s", b"b".to_vec()).unwrap();
+        assert_eq!(reader.join().unwrap(), Some(vec![("s".to_string(), vec![(id, vec![(b"payload".to_vec(), b"b".to_vec())])])]));
+        assert!(db().waiters.lock().unwrap().readers.is_empty());
+
+        let streams = [("s".to_string(), id)];
+        assert_eq!(blocking_xread(db(), &streams, None, Some(Duration::from_millis(30)), &|| false), Ok(None));
+    }
+}
@@ -0,0 +1,330 @@
+// Eviction policies applied when the cache reaches its item or memory limit.
+//
+//   allkeys-lru   least recently used key (default)
+//   allkeys-lfu   least frequently used key; counters are logarithmic and decay over time
+//   w-tinylfu     new keys enter a small recency window; keys leaving it must beat the main
+//                 LRU victim on estimated access frequency to stay
+//   volatile-lru  least recently used key that has a TTL; writes that need room are rejected
+//                 when no key has one
+//   volatile-ttl  key with the nearest expiry (same rule)
+//   noeviction    nothing is evicted; writes that need room are rejected instead
+//
+// Like Redis, LFU and volatile-* pick their victim from a sample (here: the SAMPLES least
+// recently used keys), so choosing a victim does not scan the whole keyspace. The volatile-*
+// policies sample from each shard's list of keys with a TTL (kept in the same LRU order, like
+// Redis' expires dict), so keys without one never fill their sample. Victims are picked within
+// one shard (the writer's own first), so every policy approximates its global order.
+//
+// Reads only hold a shared shard lock, so they cannot reorder the LRU list. Instead they set
+// the entry's `accessed` bit, and LRU victims are chosen CLOCK-style: walking from the LRU
+// end, recently accessed keys get a second chance (bit cleared, moved to the front).
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use lru::LruCache;
+
+use crate::{now_ms, Entry};
+
+pub(crate) const POLICY_ALLKEYS_LRU: u8 = 0;
+pub(crate) const POLICY_ALLKEYS_LFU: u8 = 1;
+pub(crate) const POLICY_W_TINYLFU: u8 = 2;
+pub(crate) const POLICY_VOLATILE_LRU: u8 = 3;
+pub(crate) const POLICY_VOLATILE_TTL: u8 = 4;
+pub(crate) const POLICY_NOEVICTION: u8 = 5;
+
+pub(crate) fn policy_name(policy: u8) -> &'static str {
+    match policy {
+        POLICY_ALLKEYS_LFU => "allkeys-lfu",
+        POLICY_W_TINYLFU => "w-tinylfu",
+        POLICY_VOLATILE_LRU => "volatile-lru",
+        POLICY_VOLATILE_TTL => "volatile-ttl",
+        POLICY_NOEVICTION => "noeviction",
+        _ => "allkeys-lru",
+    }
+}
+
+// Number of least recently used keys considered by the sampling policies.
+const SAMPLES: usize = 16;
+
+// --- LFU counters ---
+
+pub(crate) const LFU_INIT: u8 = 5;
+const LFU_LOG_FACTOR: f64 = 10.0;
+const LFU_DECAY_MINUTES: u16 = 1;
+
+pub(crate) fn now_minutes() -> u16 {
+    (now_ms() / 60_000) as u16
+}
+
+fn decayed_counter(entry: &Entry) -> u8 {
+    let touched = entry.lfu_touched_min.load(Ordering::Relaxed);
+    let periods = now_minutes().wrapping_sub(touched) / LFU_DECAY_MINUTES;
+    entry.lfu_counter.load(Ordering::Relaxed).saturating_sub(periods.min(u8::MAX as u16) as u8)
+}
+
+// Records an access: decays the counter, then increments it with a probability that falls
+// as the counter grows (so 255 represents roughly a million hits). Concurrent readers may
+// race on the update; a lost increment only makes the estimate slightly lower.
+pub(crate) fn touch(entry: &Entry) {
+    let mut counter = decayed_counter(entry);
+    if counter < u8::MAX {
+        let base = counter.saturating_sub(LFU_INIT) as f64;
+        if random_unit() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+            counter += 1;
+        }
+    }
+    entry.lfu_counter.store(counter, Ordering::Relaxed);
+    entry.lfu_touched_min.store(now_minutes(), Ordering::Relaxed);
+}
+
+// xorshift64*, seeded per thread; drives the LFU increment probability and random set members.
+pub(crate) fn random_u64() -> u64 {
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new({
+            let mut h = DefaultHasher::new();
+            std::thread::current().id().hash(&mut h);
+            now_ms().hash(&mut h);
+            h.finish() | 1
+        });
+    }
+    STATE.with(|s| {
+        let mut x = s.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        s.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+fn random_unit() -> f64 {
+    (random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// --- W-TinyLFU ---
+
+pub(crate) fn key_hash<K: Hash + ?Sized>(key: &K) -> u64 {
+    let mut h = DefaultHasher::new();
+    key.hash(&mut h);
+    h.finish()
+}
+
+// Count-min sketch of access frequencies: four rows of counters saturating at 15. Every
+// counter is halved after `sample_size` increments so past popularity fades. Counters are
+// atomic so reads can record accesses under a shared lock.
+pub(crate) struct FrequencySketch {
+    table: Vec<AtomicU8>,
+    width: usize,
+    additions: AtomicUsize,
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    pub(crate) fn new(capacity: usize) -> FrequencySketch {
+        let width = capacity.clamp(64, 1 << 24).next_power_of_two();
+        FrequencySketch {
+            table: (0..width * 4).map(|_| AtomicU8::new(0)).collect(),
+            width,
+            additions: AtomicUsize::new(0),
+            sample_size: width * 10,
+        }
+    }
+
+    fn slots(&self, hash: u64) -> [usize; 4] {
+        let h1 = hash as usize;
+        let h2 = (hash >> 32) as usize | 1;
+        std::array::from_fn(|row| row * self.width + (h1.wrapping_add(row.wrapping_mul(h2)) & (self.width - 1)))
+    }
+
+    pub(crate) fn increment(&self, hash: u64) {
+        for slot in self.slots(hash) {
+            let _ = self.table[slot].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| (c < 15).then_some(c + 1));
+        }
+        // Only the thread that reaches the sample size performs the reset.
+        if self.additions.fetch_add(1, Ordering::Relaxed) + 1 == self.sample_size {
+            for c in &self.table {
+                c.store(c.load(Ordering::Relaxed) / 2, Ordering::Relaxed);
+            }
+            self.additions.fetch_sub(self.sample_size / 2, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn estimate(&self, hash: u64) -> u8 {
+        self.slots(hash).iter().map(|&s| self.table[s].load(Ordering::Relaxed)).min().unwrap_or(0)
+    }
+}
+
+// The recency window holds about 1% of the shard's share of the item limit.
+pub(crate) fn window_cap(shard_capacity: usize) -> usize {
+    (shard_capacity / 100).max(1)
+}
+
+// Adds a newly inserted key to the window; keys pushed out while there is still room in the
+// cache join the main space without competing.
+pub(crate) fn admit_to_window<K: Hash + Eq + Clone>(
+    map: &mut LruCache<K, Entry>,
+    window: &mut VecDeque<K>,
+    key: K,
+    cap: usize,
+) {
+    let Some(entry) = map.peek_mut(&key) else { return; };
+    if entry.in_window {
+        return;
+    }
+    entry.in_window = true;
+    window.push_back(key);
+    while window.len() > cap {
+        if let Some(k) = window.pop_front() {
+            if let Some(e) = map.peek_mut(&k) {
+                e.in_window = false;
+            }
+        }
+    }
+}
+
+fn pick_tinylfu<K: Hash + Eq + Clone>(
+    map: &mut LruCache<K, Entry>,
+    volatile: &mut LruCache<K, ()>,
+    window: &mut VecDeque<K>,
+    sketch: &FrequencySketch,
+    window_cap: usize,
+) -> Option<K> {
+    while window.front().is_some_and(|k| !map.contains(k)) {
+        window.pop_front();
+    }
+    let victim = lru_victim(map, volatile, false);
+    if window.len() < window_cap {
+        return victim;
+    }
+    // The oldest window key leaves the window: it stays only if it is used more than the victim.
+    let candidate = window.pop_front()?;
+    match victim {
+        Some(v) if v != candidate && sketch.estimate(key_hash(&candidate)) > sketch.estimate(key_hash(&v)) => {
+            if let Some(e) = map.peek_mut(&candidate) {
+                e.in_window = false;
+            }
+            Some(v)
+        }
+        _ => Some(candidate),
+    }
+}
+
+// --- Victim selection ---
+
+// Second-chance walk from the LRU end (of all keys, or of the keys with a TTL only): accessed
+// keys are moved to the front with their bit cleared, the first key not accessed since the
+// last walk is the victim. The walk stops after SAMPLES keys, then the oldest second chance
+// is taken instead.
+fn lru_victim<K: Hash + Eq + Clone>(
+    map: &mut LruCache<K, Entry>,
+    volatile: &mut LruCache<K, ()>,
+    volatile_only: bool,
+) -> Option<K> {
+    let sample: Vec<K> = if volatile_only {
+        volatile.iter().rev().take(SAMPLES).map(|(k, _)| k.clone()).collect()
+    } else {
+        map.iter().rev().take(SAMPLES).map(|(k, _)| k.clone()).collect()
+    };
+    let mut spared = Vec::new();
+    let mut victim = None;
+    for k in sample {
+        let Some(e) = map.peek(&k) else { continue; };
+        if !e.accessed.swap(false, Ordering::Relaxed) {
+            victim = Some(k);
+            break;
+        }
+        spared.push(k);
+    }
+    if victim.is_none() && !spared.is_empty() {
+        victim = Some(spared.remove(0));
+    }
+    for k in &spared {
+        map.promote(k);
+        volatile.promote(k);
+    }
+    victim
+}
+
+// Nearest expiry among the SAMPLES least recently used keys with a TTL.
+fn ttl_victim<K: Hash + Eq + Clone>(map: &LruCache<K, Entry>, volatile: &LruCache<K, ()>) -> Option<K> {
+    volatile
+        .iter()
+        .rev()
+        .take(SAMPLES)
+        .filter_map(|(k, _)| map.peek(k)?.expires_at_ms.map(|t| (t, k)))
+        .min_by_key(|(t, _)| *t)
+        .map(|(_, k)| k.clone())
+}
+
+// Chooses the key to evict from one keyspace, or None when the policy allows no victim
+// (noeviction, or volatile-* without keys that carry a TTL). `volatile` holds the keys of
+// `map` that have a TTL, in the same LRU order.
+pub(crate) fn pick_victim<K: Hash + Eq + Clone>(
+    map: &mut LruCache<K, Entry>,
+    volatile: &mut LruCache<K, ()>,
+    window: &mut VecDeque<K>,
+    sketch: Option<&FrequencySketch>,
+    policy: u8,
+    window_cap: usize,
+) -> Option<K> {
+    match policy {
+        POLICY_ALLKEYS_LFU => map
+            .iter()
+            .rev()
+            .take(SAMPLES)
+            .min_by_key(|(_, e)| decayed_counter(e))
+            .map(|(k, _)| k.clone()),
+        POLICY_W_TINYLFU => match sketch {
+            Some(sketch) => pick_tinylfu(map, volatile, window, sketch, window_cap),
+            None => lru_victim(map, volatile, false),
+        },
+        POLICY_VOLATILE_LRU => lru_victim(map, volatile, true),
+        POLICY_VOLATILE_TTL => ttl_victim(map, volatile),
+        POLICY_NOEVICTION => None,
+        _ => lru_victim(map, volatile, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+    use std::sync::Arc;
+
+    // The LRU end holds `untimed` keys without a TTL, followed by keys with the given expiries.
+    fn maps_with_ttl_keys_after(untimed: usize, expiries: &[u64]) -> (LruCache<String, Entry>, LruCache<String, ()>) {
+        let mut map = LruCache::unbounded();
+        let mut volatile = LruCache::unbounded();
+        for i in 0..untimed {
+            map.put(format!("k{}", i), Entry::new(Value::Bytes(Arc::new(Vec::new())), None));
+        }
+        for (i, &at) in expiries.iter().enumerate() {
+            map.put(format!("t{}", i), Entry::new(Value::Bytes(Arc::new(Vec::new())), Some(at)));
+            volatile.put(format!("t{}", i), ());
+        }
+        (map, volatile)
+    }
+
+    #[test]
+    fn volatile_victims_are_sampled_from_keys_with_a_ttl() {
+        let at = now_ms() + 60_000;
+        // However many keys without a TTL sit at the LRU end, they never take up the sample.
+        let (mut map, mut volatile) = maps_with_ttl_keys_after(SAMPLES * 4, &[at + 2, at, at + 1]);
+        assert_eq!(ttl_victim(&map, &volatile).as_deref(), Some("t1"));
+        assert_eq!(lru_victim(&mut map, &mut volatile, true).as_deref(), Some("t0"));
+
+        // Accessed keys get a second chance in both LRU orders.
+        map.peek("t0").unwrap().accessed.store(true, Ordering::Relaxed);
+        assert_eq!(lru_victim(&mut map, &mut volatile, true).as_deref(), Some("t1"));
+        assert_eq!(volatile.iter().next().map(|(k, _)| k.as_str()), Some("t0"));
+        assert_eq!(map.iter().next().map(|(k, _)| k.as_str()), Some("t0"));
+
+        let (mut map, mut volatile) = maps_with_ttl_keys_after(SAMPLES, &[]);
+        assert_eq!(ttl_victim(&map, &volatile), None);
+        assert_eq!(lru_victim(&mut map, &mut volatile, true), None);
+    }
+}
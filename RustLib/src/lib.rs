@@ -1,68 +1,528 @@
+// FFI exports take raw pointers from the .NET side by design.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::ffi::CStr;
 use std::io::{Read, Write};
-use std::num::NonZeroUsize;
+use std::hash::Hash;
 use std::os::raw::{c_char, c_uchar};
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::cell::Cell;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use lru::LruCache;
 use once_cell::sync::Lazy;
 use serde_json::Value as JsonValue;
 
+mod blocking;
+mod eviction;
+pub mod server;
+mod snapshot;
+mod zset;
+
+use zset::{LexBound, ScoreBound, ScoredMembers, SortedSet};
+
+// Field -> value.
+type HashData = HashMap<Vec<u8>, Vec<u8>>;
+
 // Define the Value enum to support multiple data structures
 #[derive(Clone)]
 enum Value {
     Bytes(Arc<Vec<u8>>),
-    Hash(HashMap<String, Vec<u8>>),
-    List(Vec<Vec<u8>>),
+    Hash(HashData),
+    List(VecDeque<Vec<u8>>),
     Set(HashSet<Vec<u8>>),
-    SortedSet(HashMap<String, f64>), // Member -> Score
+    SortedSet(SortedSet),
     Stream(StreamData),
 }
 
-#[derive(Clone)]
+/// Stream entry id, `<ms>-<seq>`: a millisecond timestamp and a sequence number within it.
+/// Ids order by `ms`, then `seq`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    const MAX: StreamId = StreamId { ms: u64::MAX, seq: u64::MAX };
+
+    fn new(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    // The next id up, None after MAX.
+    fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+
+    // The next id down, None before MIN.
+    fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_sub(1)?, u64::MAX)),
+        }
+    }
+}
+
+impl std::fmt::Display for StreamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+// Field/value pairs of one stream entry, in the order they were added.
+type StreamFields = Vec<(Vec<u8>, Vec<u8>)>;
+
+// The field written by the single-payload forms (`XADD key * <payload>`, cache_xadd).
+const STREAM_PAYLOAD_FIELD: &[u8] = b"payload";
+
+// A stored entry. Producers tend to write the same fields every time, so an entry whose field
+// names match the previous entry's shares them with it; runs of sharing entries stay
+// contiguous, since entries are only ever appended.
+#[derive(Clone, Debug, PartialEq)]
 struct StreamEntry {
-    id: u64,
-    payload: Vec<u8>,
+    names: Arc<[Vec<u8>]>,
+    values: Vec<Vec<u8>>,
 }
 
-#[derive(Clone)]
+impl StreamEntry {
+    // Takes `prev`'s field names if they are the same as those of `fields`.
+    fn new(prev: Option<&StreamEntry>, fields: StreamFields) -> StreamEntry {
+        let (names, values): (Vec<Vec<u8>>, Vec<Vec<u8>>) = fields.into_iter().unzip();
+        let names = match prev {
+            Some(prev) if *prev.names == names[..] => prev.names.clone(),
+            _ => names.into(),
+        };
+        StreamEntry { names, values }
+    }
+
+    fn fields(&self) -> StreamFields {
+        self.names.iter().cloned().zip(self.values.iter().cloned()).collect()
+    }
+}
+
+#[derive(Clone, Default)]
 struct StreamData {
-    entries: Vec<StreamEntry>,
+    // Entries by id; ranges, trims and deletes are O(log n) seeks.
+    entries: BTreeMap<StreamId, StreamEntry>,
+    // Highest id ever added. New ids stay above it even after the newest entries are deleted.
+    last_id: StreamId,
+    // Consumer groups by name.
+    groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl StreamData {
+    // Adds entry `id` and raises the last id. Returns the memory it takes: its field names
+    // count unless it shares them with the entry before it.
+    fn insert(&mut self, id: StreamId, fields: StreamFields) -> usize {
+        let prev = self.entries.range(..id).next_back().map(|(_, e)| e);
+        let e = StreamEntry::new(prev, fields);
+        let mut mem = stream_entry_mem(&e);
+        if !prev.is_some_and(|p| Arc::ptr_eq(&p.names, &e.names)) {
+            mem += stream_names_mem(&e.names);
+        }
+        self.entries.insert(id, e);
+        self.last_id = self.last_id.max(id);
+        mem
+    }
+
+    // Removes entry `id`. Returns the memory it freed, its field names included unless a
+    // neighbour still shares them.
+    fn remove(&mut self, id: StreamId) -> Option<usize> {
+        let e = self.entries.remove(&id)?;
+        let shares = |n: Option<(&StreamId, &StreamEntry)>| n.is_some_and(|(_, n)| Arc::ptr_eq(&n.names, &e.names));
+        let mut freed = stream_entry_mem(&e);
+        if !shares(self.entries.range(..id).next_back()) && !shares(self.entries.range(id..).next()) {
+            freed += stream_names_mem(&e.names);
+        }
+        Some(freed)
+    }
+
+    // Drops every entry below `below`. Returns how many went and the memory they freed.
+    fn trim(&mut self, below: StreamId) -> (usize, usize) {
+        let kept = self.entries.split_off(&below);
+        let removed = std::mem::replace(&mut self.entries, kept);
+        let mut freed = stream_entries_mem(removed.values());
+        let last = removed.values().next_back();
+        if let (Some(last), Some(first)) = (last, self.entries.values().next()) {
+            if Arc::ptr_eq(&last.names, &first.names) {
+                freed -= stream_names_mem(&first.names);
+            }
+        }
+        (removed.len(), freed)
+    }
+}
+
+// A consumer group: the newest entry it has handed out and its pending entries list (PEL),
+// the deliveries no consumer has acknowledged yet.
+#[derive(Clone, Default)]
+struct ConsumerGroup {
+    last_delivered: StreamId,
+    pending: BTreeMap<StreamId, PendingEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct PendingEntry {
+    consumer: String,
+    delivered_at_ms: u64,
+    deliveries: u64,
 }
 
-#[derive(Clone)]
 struct Entry {
     value: Value,
     expires_at_ms: Option<u64>,
+    // Estimated size of `value` in bytes; mutations keep it up to date incrementally.
+    mem: usize,
+    // Eviction bookkeeping: read since the last LRU walk, LFU access counter, minute it was
+    // last updated, and whether the key is still in the W-TinyLFU recency window. The access
+    // fields are atomic because reads update them under a shared lock.
+    accessed: AtomicBool,
+    lfu_counter: AtomicU8,
+    lfu_touched_min: AtomicU16,
+    in_window: bool,
+}
+
+impl Entry {
+    fn new(value: Value, expires_at_ms: Option<u64>) -> Entry {
+        let mem = value_mem(&value);
+        Entry {
+            value,
+            expires_at_ms,
+            mem,
+            accessed: AtomicBool::new(false),
+            lfu_counter: AtomicU8::new(eviction::LFU_INIT),
+            lfu_touched_min: AtomicU16::new(eviction::now_minutes()),
+            in_window: false,
+        }
+    }
+}
+
+impl Clone for Entry {
+    fn clone(&self) -> Entry {
+        Entry {
+            value: self.value.clone(),
+            expires_at_ms: self.expires_at_ms,
+            mem: self.mem,
+            accessed: AtomicBool::new(self.accessed.load(Ordering::Relaxed)),
+            lfu_counter: AtomicU8::new(self.lfu_counter.load(Ordering::Relaxed)),
+            lfu_touched_min: AtomicU16::new(self.lfu_touched_min.load(Ordering::Relaxed)),
+            in_window: self.in_window,
+        }
+    }
+}
+
+// --- Memory accounting ---
+// Sizes are estimates: payload bytes plus a fixed allowance for allocator and container overhead.
+
+const ENTRY_OVERHEAD: usize = 64; // LRU node, Entry and key allocation
+const ELEM_OVERHEAD: usize = 32; // per collection element
+
+fn item_mem(item: &[u8]) -> usize {
+    ELEM_OVERHEAD + item.len()
+}
+
+fn hash_field_mem(field: &[u8], val: &[u8]) -> usize {
+    2 * ELEM_OVERHEAD + field.len() + val.len()
+}
+
+// The member is kept both in the score map and in its skiplist node.
+fn zset_member_mem(member: &[u8]) -> usize {
+    2 * (ELEM_OVERHEAD + member.len()) + std::mem::size_of::<f64>()
+}
+
+// An entry without its field names, which are counted once per run of entries sharing them.
+fn stream_entry_mem(e: &StreamEntry) -> usize {
+    ELEM_OVERHEAD + std::mem::size_of::<StreamId>() + e.values.iter().map(|v| item_mem(v)).sum::<usize>()
+}
+
+fn stream_names_mem(names: &[Vec<u8>]) -> usize {
+    names.iter().map(|n| item_mem(n)).sum()
+}
+
+// Entries in id order, with each run's shared field names counted once.
+fn stream_entries_mem<'a>(entries: impl Iterator<Item = &'a StreamEntry>) -> usize {
+    let mut prev: Option<&Arc<[Vec<u8>]>> = None;
+    let mut mem = 0;
+    for e in entries {
+        mem += stream_entry_mem(e);
+        if !prev.is_some_and(|p| Arc::ptr_eq(p, &e.names)) {
+            mem += stream_names_mem(&e.names);
+        }
+        prev = Some(&e.names);
+    }
+    mem
+}
+
+fn stream_group_mem(name: &str) -> usize {
+    ELEM_OVERHEAD + name.len() + std::mem::size_of::<StreamId>()
+}
+
+fn pending_entry_mem(p: &PendingEntry) -> usize {
+    ELEM_OVERHEAD + std::mem::size_of::<StreamId>() + 2 * std::mem::size_of::<u64>() + p.consumer.len()
+}
+
+fn stream_mem(stream: &StreamData) -> usize {
+    let groups = stream.groups.iter().map(|(name, g)| stream_group_mem(name) + g.pending.values().map(pending_entry_mem).sum::<usize>());
+    stream_entries_mem(stream.entries.values()) + groups.sum::<usize>()
+}
+
+fn value_mem(value: &Value) -> usize {
+    match value {
+        Value::Bytes(b) => b.len(),
+        Value::Hash(hmap) => hmap.iter().map(|(f, v)| hash_field_mem(f, v)).sum(),
+        Value::List(list) => list.iter().map(|i| item_mem(i)).sum(),
+        Value::Set(set) => set.iter().map(|m| item_mem(m)).sum(),
+        Value::SortedSet(ss) => ss.iter().map(|(m, _)| zset_member_mem(m)).sum(),
+        Value::Stream(stream) => stream_mem(stream),
+    }
+}
+
+fn entry_mem(key_len: usize, entry: &Entry) -> usize {
+    ENTRY_OVERHEAD + key_len + entry.mem
+}
+
+// One independently locked slice of the keyspace. Each shard keeps its own LRU order;
+// the item and memory limits apply to the cache as a whole.
+struct Shard {
+    idx: usize,
+    map: LruCache<Vec<u8>, Entry>,
+    // The keys of `map` that have a TTL, in the same LRU order; volatile-* victims come from here.
+    volatile: LruCache<Vec<u8>, ()>,
+    // W-TinyLFU state, only kept while that policy is active.
+    sketch: Option<eviction::FrequencySketch>,
+    window: VecDeque<Vec<u8>>,
+}
+
+// A key of the keyspace, which is keyed by bytes. `[u8]` keys (the `_b` exports) are used as
+// they are and `str` keys are a view of their UTF-8 bytes, so "abc" written through `cache_set`
+// is the key `b"abc"` of `cache_get_b`. `String` and `Vec<u8>` forward to them.
+pub(crate) trait CacheKey {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl CacheKey for str {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl CacheKey for [u8] {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl CacheKey for String {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl CacheKey for Vec<u8> {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<K: CacheKey + ?Sized> CacheKey for &K {
+    fn as_bytes(&self) -> &[u8] {
+        (**self).as_bytes()
+    }
+}
+
+// Name of a key in keyspace notifications: the key itself, or "b:<hex>" when it is not UTF-8.
+fn key_name<K: CacheKey + ?Sized>(key: &K) -> String {
+    match std::str::from_utf8(key.as_bytes()) {
+        Ok(name) => name.to_string(),
+        Err(_) => bytes_to_hex_key(key.as_bytes()),
+    }
 }
 
-struct CacheState {
-    map: LruCache<String, Entry>,
-    map_b: LruCache<Vec<u8>, Entry>,
-    // Phase4: optional numeric secondary indexes for JSON (top-level fields)
-    numeric_indexes: HashMap<String, BTreeMap<i64, HashSet<String>>>,
+// Keys whose JSON value has a given number in the indexed field.
+type NumericIndex = BTreeMap<i64, HashSet<Vec<u8>>>;
+
+struct Cache {
+    shards: Vec<RwLock<Shard>>,
+    // Phase4: optional numeric secondary indexes for JSON (top-level fields).
+    // Lock order: shard(s) first, then the indexes.
+    numeric_indexes: RwLock<HashMap<String, NumericIndex>>,
+    // Lets writers skip the index lock while no index exists.
+    has_indexes: AtomicBool,
+    // Sum of entry_mem() over every shard.
+    used_memory: AtomicUsize,
+    // Number of keys over all shards.
+    len: AtomicUsize,
 }
 
+const SHARD_BITS: u32 = 4;
+const SHARD_COUNT: usize = 1 << SHARD_BITS;
+
 const DEFAULT_MAX_ITEMS: usize = 100_000;
-static MAX_ITEMS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_ITEMS);
-
-// Global Cache Storage (Phase2: LRU-backed)
-static CACHE: Lazy<RwLock<CacheState>> = Lazy::new(|| {
-    let cap = NonZeroUsize::new(DEFAULT_MAX_ITEMS).unwrap();
-    RwLock::new(CacheState {
-        map: LruCache::new(cap),
-        map_b: LruCache::new(cap),
-        numeric_indexes: HashMap::new(),
-    })
-});
 
-static EXPIRY_THREAD_STARTED: OnceLock<()> = OnceLock::new();
-static AOF_FILE: Lazy<Mutex<Option<std::fs::File>>> = Lazy::new(|| Mutex::new(None));
+/// One independent cache: its own keyspace, limits, eviction policy, AOF, stream ids,
+/// keyspace notifications and pub/sub channels. `cache_instance_create` hands out
+/// `Arc::into_raw` pointers to these; the plain `cache_*` exports use the default instance.
+pub struct Instance {
+    cache: Cache,
+    max_items: AtomicUsize,
+    // Memory limit in bytes (0 = unlimited).
+    max_memory: AtomicUsize,
+    policy: AtomicU8,
+    aof: Mutex<AofState>,
+    // Wakes the background writer (new records or a sync request).
+    aof_writer_signal: Condvar,
+    // Wakes callers waiting for records to become durable.
+    aof_durable_signal: Condvar,
+    aof_writer_started: OnceLock<()>,
+    aof_fsync_policy: AtomicU8,
+    aof_rewrite_in_progress: AtomicBool,
+    // Auto-rewrite when the file grows past base_size * (1 + pct/100); 0 disables it.
+    aof_auto_rewrite_pct: AtomicU64,
+    aof_auto_rewrite_min_size: AtomicU64,
+    // Data-structure writes replace a value of another type instead of failing with WRONGTYPE.
+    overwrite_types: AtomicBool,
+    notify_queue: Mutex<VecDeque<NotifyEvent>>,
+    pubsub: Mutex<PubSubState>,
+    // Signalled whenever a subscription queue receives a message (paired with `pubsub`).
+    pubsub_signal: Condvar,
+    // Clients blocked in BLPOP / BRPOP / XREAD BLOCK, woken through `blocked_signal`.
+    waiters: Mutex<blocking::Waiters>,
+    blocked_signal: Condvar,
+    // Set by cache_instance_destroy so the instance's background threads exit.
+    closed: AtomicBool,
+    // Background threads (AOF writer, rewrites) hold a strong reference while they run.
+    me: Weak<Instance>,
+}
+
+impl Instance {
+    fn new() -> Arc<Instance> {
+        let db = Arc::new_cyclic(|me| Instance {
+            cache: Cache {
+                shards: (0..SHARD_COUNT)
+                    .map(|idx| {
+                        RwLock::new(Shard {
+                            idx,
+                            map: LruCache::unbounded(),
+                            volatile: LruCache::unbounded(),
+                            sketch: None,
+                            window: VecDeque::new(),
+                        })
+                    })
+                    .collect(),
+                numeric_indexes: RwLock::new(HashMap::new()),
+                has_indexes: AtomicBool::new(false),
+                used_memory: AtomicUsize::new(0),
+                len: AtomicUsize::new(0),
+            },
+            max_items: AtomicUsize::new(DEFAULT_MAX_ITEMS),
+            max_memory: AtomicUsize::new(0),
+            policy: AtomicU8::new(eviction::POLICY_ALLKEYS_LRU),
+            aof: Mutex::new(AofState {
+                file: None,
+                path: String::new(),
+                pending: Vec::new(),
+                appended: 0,
+                written: 0,
+                synced: 0,
+                sync_requested: false,
+                writer_busy: false,
+                failed: false,
+                rewrite_buf: None,
+                rewrite_failed_at: None,
+                base_size: 0,
+                size: 0,
+            }),
+            aof_writer_signal: Condvar::new(),
+            aof_durable_signal: Condvar::new(),
+            aof_writer_started: OnceLock::new(),
+            aof_fsync_policy: AtomicU8::new(AOF_FSYNC_EVERYSEC),
+            aof_rewrite_in_progress: AtomicBool::new(false),
+            aof_auto_rewrite_pct: AtomicU64::new(0),
+            aof_auto_rewrite_min_size: AtomicU64::new(64 * 1024 * 1024),
+            overwrite_types: AtomicBool::new(false),
+            notify_queue: Mutex::new(VecDeque::new()),
+            pubsub: Mutex::new(PubSubState {
+                next_id: 1,
+                subs: HashMap::new(),
+                channels: HashMap::new(),
+                queues: HashMap::new(),
+            }),
+            pubsub_signal: Condvar::new(),
+            waiters: Mutex::new(blocking::Waiters::default()),
+            blocked_signal: Condvar::new(),
+            closed: AtomicBool::new(false),
+            me: me.clone(),
+        });
+        INSTANCES.lock().unwrap().push(Arc::downgrade(&db));
+        start_expiry_thread_once();
+        db
+    }
+
+    fn arc(&self) -> Arc<Instance> {
+        self.me.upgrade().expect("instance used after it was destroyed")
+    }
+
+    fn policy(&self) -> u8 {
+        self.policy.load(Ordering::Relaxed)
+    }
+
+    fn len(&self) -> usize {
+        self.cache.len.load(Ordering::Relaxed)
+    }
+}
+
+// Backs the plain `cache_*` exports, the RESP server binary and the .NET wrapper.
+static DEFAULT_INSTANCE: Lazy<Arc<Instance>> = Lazy::new(Instance::new);
+
+// Every instance created so far; the expiry thread sweeps the ones still alive.
+static INSTANCES: Mutex<Vec<Weak<Instance>>> = Mutex::new(Vec::new());
+
+fn default_instance() -> *const Instance {
+    Arc::as_ptr(&DEFAULT_INSTANCE)
+}
+
+// Uses the top bits of the hash so the low bits, which the frequency sketch indexes by,
+// stay uniformly spread within a shard.
+fn shard_index<K: CacheKey + ?Sized>(key: &K) -> usize {
+    (eviction::key_hash(key.as_bytes()) >> (64 - SHARD_BITS)) as usize
+}
+
+// A shard's share of the item limit; sizes the W-TinyLFU sketch and window.
+fn shard_capacity(db: &Instance) -> usize {
+    (db.max_items.load(Ordering::Relaxed) / SHARD_COUNT).max(1)
+}
+
+fn window_cap(db: &Instance) -> usize {
+    eviction::window_cap(shard_capacity(db))
+}
 
-static STREAM_ID: AtomicU64 = AtomicU64::new(1);
+fn shard_read<'a, K: CacheKey + ?Sized>(db: &'a Instance, key: &K) -> RwLockReadGuard<'a, Shard> {
+    db.cache.shards[shard_index(key)].read().unwrap()
+}
+
+fn shard_write<'a, K: CacheKey + ?Sized>(db: &'a Instance, key: &K) -> CacheWriteGuard<'a> {
+    CacheWriteGuard(Some(db.cache.shards[shard_index(key)].write().unwrap()), db)
+}
+
+// Shared locks on every shard (in index order), for consistent whole-cache copies.
+fn cache_read_all(db: &Instance) -> Vec<RwLockReadGuard<'_, Shard>> {
+    db.cache.shards.iter().map(|s| s.read().unwrap()).collect()
+}
+
+fn cache_write_all(db: &Instance) -> AllShardsGuard<'_> {
+    AllShardsGuard(db.cache.shards.iter().map(|s| s.write().unwrap()).collect(), db)
+}
+
+static EXPIRY_THREAD_STARTED: OnceLock<()> = OnceLock::new();
+// After a failed rewrite, automatic rewrites wait this long before trying again.
+const AOF_REWRITE_RETRY_DELAY: Duration = Duration::from_secs(60);
 
 // Helper for string conversion
 unsafe fn to_string(ptr: *const c_char) -> String {
@@ -72,6 +532,14 @@ unsafe fn to_string(ptr: *const c_char) -> String {
     CStr::from_ptr(ptr).to_string_lossy().into_owned()
 }
 
+// The bytes of a C string as they are, without UTF-8 decoding.
+unsafe fn to_c_bytes(ptr: *const c_char) -> Vec<u8> {
+    if ptr.is_null() {
+        return Vec::new();
+    }
+    CStr::from_ptr(ptr).to_bytes().to_vec()
+}
+
 unsafe fn to_bytes(ptr: *const c_uchar, len: usize) -> Vec<u8> {
     if ptr.is_null() || len == 0 {
         return Vec::new();
@@ -106,34 +574,17 @@ fn bytes_to_hex_key(key_bytes: &[u8]) -> String {
     s
 }
 
-fn bytes_key_to_string(key_ptr: *const c_uchar, key_len: usize) -> String {
-    if key_ptr.is_null() || key_len == 0 {
-        return String::new();
-    }
-    let key_bytes = unsafe { std::slice::from_raw_parts(key_ptr, key_len) };
-    bytes_to_hex_key(key_bytes)
-}
-
-fn maybe_remove_if_expired(state: &mut CacheState, key: &String) -> bool {
-    if let Some(entry) = state.map.peek(key) {
-        if is_expired(entry) {
-            if let Some(evicted) = state.map.pop(key) {
-                index_remove_for_entry(state, key, &evicted);
-            }
-            notify_expired(key);
-            return true;
-        }
+// Removes the key if it has expired. The removal is logged, so a replay that sees the key's
+// past expiry also sees it go away before any later write recreates it.
+fn maybe_remove_if_expired<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K) -> bool {
+    if aof_replaying() {
+        return false;
     }
-    false
-}
-
-fn maybe_remove_if_expired_b(state: &mut CacheState, key: &Vec<u8>) -> bool {
-    if let Some(entry) = state.map_b.peek(key) {
+    if let Some(entry) = state.map.peek(key.as_bytes()) {
         if is_expired(entry) {
-            let _ = state.map_b.pop(key);
-            // Preserve historical notification key formatting for binary keys.
-            let key_str = bytes_to_hex_key(key);
-            notify_expired(&key_str);
+            pop_entry(db, state, key);
+            aof_write_remove(db, key);
+            notify_expired(db, &key_name(key));
             return true;
         }
     }
@@ -157,16 +608,17 @@ fn extract_numeric_field(json: &JsonValue, field: &str) -> Option<i64> {
     None
 }
 
-fn index_remove_for_entry(state: &mut CacheState, key: &str, entry: &Entry) {
-    if state.numeric_indexes.is_empty() {
+fn index_remove_for_entry<K: CacheKey + ?Sized>(db: &Instance, key: &K, entry: &Entry) {
+    if !db.cache.has_indexes.load(Ordering::Acquire) {
         return;
     }
     let Some(json) = try_parse_json_from_entry(entry) else { return; };
 
-    for (field, idx) in state.numeric_indexes.iter_mut() {
+    let mut indexes = db.cache.numeric_indexes.write().unwrap();
+    for (field, idx) in indexes.iter_mut() {
         if let Some(num) = extract_numeric_field(&json, field) {
             if let Some(keys) = idx.get_mut(&num) {
-                keys.remove(key);
+                keys.remove(key.as_bytes());
                 if keys.is_empty() {
                     idx.remove(&num);
                 }
@@ -175,84 +627,341 @@ fn index_remove_for_entry(state: &mut CacheState, key: &str, entry: &Entry) {
     }
 }
 
-fn index_add_for_entry(state: &mut CacheState, key: &str, entry: &Entry) {
-    if state.numeric_indexes.is_empty() {
+fn index_add_for_entry<K: CacheKey + ?Sized>(db: &Instance, key: &K, entry: &Entry) {
+    if !db.cache.has_indexes.load(Ordering::Acquire) {
         return;
     }
     let Some(json) = try_parse_json_from_entry(entry) else { return; };
 
-    for (field, idx) in state.numeric_indexes.iter_mut() {
+    let mut indexes = db.cache.numeric_indexes.write().unwrap();
+    for (field, idx) in indexes.iter_mut() {
         if let Some(num) = extract_numeric_field(&json, field) {
-            idx.entry(num).or_default().insert(key.to_string());
+            idx.entry(num).or_default().insert(key.as_bytes().to_vec());
+        }
+    }
+}
+
+// Removes a key, keeping indexes, counts and memory accounting consistent.
+fn pop_entry<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K) -> Option<Entry> {
+    let old = state.map.pop(key.as_bytes())?;
+    if old.expires_at_ms.is_some() {
+        state.volatile.pop(key.as_bytes());
+    }
+    db.cache.len.fetch_sub(1, Ordering::Relaxed);
+    db.cache.used_memory.fetch_sub(entry_mem(key.as_bytes().len(), &old), Ordering::Relaxed);
+    index_remove_for_entry(db, key, &old);
+    Some(old)
+}
+
+// Evicts one key chosen by the eviction policy. With `force` (making room under the item
+// limit) plain LRU is used when the policy itself offers no victim.
+fn evict_one(db: &Instance, state: &mut Shard, force: bool) -> bool {
+    let cap = window_cap(db);
+    let victim = eviction::pick_victim(&mut state.map, &mut state.volatile, &mut state.window, state.sketch.as_ref(), db.policy(), cap)
+        .or_else(|| if force { state.map.peek_lru().map(|(k, _)| k.clone()) } else { None });
+    let Some(key) = victim else { return false; };
+    pop_entry(db, state, key.as_slice());
+    notify_evicted(db, &key_name(key.as_slice()));
+    true
+}
+
+// Runs `evict` on the first other shard that is not locked and has a victim. Uses try_write
+// so a writer holding its own shard lock never waits on another shard (no lock ordering issue).
+fn evict_elsewhere(db: &Instance, from: usize, evict: impl Fn(&mut Shard) -> bool) -> bool {
+    (1..SHARD_COUNT).any(|i| match db.cache.shards[(from + i) % SHARD_COUNT].try_write() {
+        Ok(mut shard) => evict(&mut shard),
+        Err(_) => false,
+    })
+}
+
+// Evicts one entry per shard per round until `over_limit` is false, so shrinking the
+// limits does not empty the first shards before touching the others.
+fn evict_round_robin(db: &Instance, over_limit: impl Fn() -> bool, evict: impl Fn(&mut Shard) -> bool) {
+    while over_limit() {
+        let mut progress = false;
+        for shard in &db.cache.shards {
+            let mut shard = shard.write().unwrap();
+            progress |= over_limit() && evict(&mut shard);
+        }
+        if !progress {
+            break;
         }
     }
 }
 
-fn put_entry_with_lru(state: &mut CacheState, key: String, entry: Entry) {
-    // Capture eviction for keyspace notifications.
-    let cap = state.map.cap().get();
-    if !state.map.contains(&key) && state.map.len() >= cap {
-        if let Some((evicted_key, evicted_entry)) = state.map.pop_lru() {
-            index_remove_for_entry(state, &evicted_key, &evicted_entry);
-            notify_evicted(&evicted_key);
+fn allkeys_policy(db: &Instance) -> bool {
+    matches!(
+        db.policy(),
+        eviction::POLICY_ALLKEYS_LRU | eviction::POLICY_ALLKEYS_LFU | eviction::POLICY_W_TINYLFU
+    )
+}
+
+// Makes room for one key under the item limit: the policy's victim from this shard or
+// another one. Only the allkeys-* policies fall back to plain LRU when no shard offers one;
+// volatile-* and noeviction leave the cache full (`check_oom` rejects the write instead).
+fn evict_for_items(db: &Instance, state: &mut Shard) -> bool {
+    let force = allkeys_policy(db);
+    evict_one(db, state, false)
+        || evict_elsewhere(db, state.idx, |s| evict_one(db, s, false))
+        || (force && (evict_one(db, state, true) || evict_elsewhere(db, state.idx, |s| evict_one(db, s, true))))
+}
+
+fn memory_over_limit(db: &Instance) -> bool {
+    let max = db.max_memory.load(Ordering::Relaxed);
+    max != 0 && db.cache.used_memory.load(Ordering::Relaxed) > max
+}
+
+// Evicts entries until usage is back under the memory limit (or the policy runs out of
+// victims), preferring the writer's own shard.
+fn evict_for_memory(db: &Instance, state: &mut Shard) {
+    while memory_over_limit(db) {
+        if !(evict_one(db, state, false) || evict_elsewhere(db, state.idx, |s| evict_one(db, s, false))) {
+            break;
         }
     }
-    // If overwriting an existing key, remove old index entries first.
-    if let Some(old) = state.map.pop(&key) {
-        index_remove_for_entry(state, &key, &old);
+}
+
+// --- Errors ---
+
+/// Status codes of the FFI. Exports returning an i32 give 1 on success (0 where the call
+/// answers "no", e.g. not a member) and one of the negative codes on failure; every export
+/// also records its outcome for `cache_last_status` / `cache_last_error` on the calling thread.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Ok = 0,
+    /// The cache is full and the eviction policy cannot make room.
+    OutOfMemory = -1,
+    /// The key holds a value of another type.
+    WrongType = -2,
+    NotFound = -3,
+    InvalidArgument = -4,
+    Io = -5,
+    /// Null or destroyed instance handle.
+    InvalidHandle = -6,
+}
+
+/// Why a command failed. The Display text is what `cache_last_error` and the RESP server report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CacheError {
+    OutOfMemory,
+    WrongType,
+    NotFound,
+    InvalidArgument(String),
+    Io(String),
+    InvalidHandle,
+    // Consumer-group failures, reported with Redis' NOGROUP / BUSYGROUP prefixes.
+    NoGroup,
+    BusyGroup,
+}
+
+impl CacheError {
+    fn status(&self) -> CacheStatus {
+        match self {
+            CacheError::OutOfMemory => CacheStatus::OutOfMemory,
+            CacheError::WrongType => CacheStatus::WrongType,
+            CacheError::NotFound => CacheStatus::NotFound,
+            CacheError::InvalidArgument(_) => CacheStatus::InvalidArgument,
+            CacheError::Io(_) => CacheStatus::Io,
+            CacheError::InvalidHandle => CacheStatus::InvalidHandle,
+            CacheError::NoGroup | CacheError::BusyGroup => CacheStatus::InvalidArgument,
+        }
+    }
+
+    fn invalid(msg: &str) -> CacheError {
+        CacheError::InvalidArgument(msg.to_string())
     }
+}
+
+impl From<std::io::Error> for CacheError {
+    fn from(e: std::io::Error) -> CacheError {
+        CacheError::Io(e.to_string())
+    }
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheError::OutOfMemory => f.write_str("OOM command not allowed when used memory > 'maxmemory'."),
+            CacheError::WrongType => f.write_str("WRONGTYPE Operation against a key holding the wrong kind of value"),
+            CacheError::NotFound => f.write_str("ERR no such key"),
+            CacheError::InvalidArgument(msg) => write!(f, "ERR {}", msg),
+            CacheError::Io(msg) => write!(f, "ERR I/O error: {}", msg),
+            CacheError::InvalidHandle => f.write_str("ERR invalid cache instance handle"),
+            CacheError::NoGroup => f.write_str("NOGROUP No such key or consumer group"),
+            CacheError::BusyGroup => f.write_str("BUSYGROUP Consumer Group name already exists"),
+        }
+    }
+}
 
-    if !state.numeric_indexes.is_empty() {
-        index_add_for_entry(state, &key, &entry);
+thread_local! {
+    // Outcome of the last export called on this thread (None = success).
+    static LAST_ERROR: std::cell::RefCell<Option<CacheError>> = const { std::cell::RefCell::new(None) };
+}
+
+// Records `err` as this thread's last error and returns its status code.
+fn fail(err: CacheError) -> i32 {
+    let code = err.status() as i32;
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(err));
+    code
+}
+
+// i32 result of a command: `ok(value)` on success, the recorded error's code otherwise.
+fn status<T>(res: Result<T, CacheError>, ok: impl FnOnce(T) -> i32) -> i32 {
+    match res {
+        Ok(v) => ok(v),
+        Err(e) => fail(e),
     }
-    state.map.put(key, entry);
 }
 
-fn put_entry_with_lru_b(state: &mut CacheState, key: Vec<u8>, entry: Entry) {
-    // Capture eviction for keyspace notifications.
-    let cap = state.map_b.cap().get();
-    if !state.map_b.contains(&key) && state.map_b.len() >= cap {
-        if let Some((evicted_key, _evicted_entry)) = state.map_b.pop_lru() {
-            let evicted_key_str = bytes_to_hex_key(&evicted_key);
-            notify_evicted(&evicted_key_str);
+// Buffer result of a lookup; a missing key or an error returns null with the reason recorded.
+fn return_found(res: Result<Option<Vec<u8>>, CacheError>, out_len: *mut usize) -> *mut c_uchar {
+    match res {
+        Ok(Some(v)) => prepare_return(v, out_len),
+        Ok(None) => {
+            fail(CacheError::NotFound);
+            prepare_return(Vec::new(), out_len)
         }
+        Err(e) => {
+            fail(e);
+            prepare_return(Vec::new(), out_len)
+        }
+    }
+}
+
+// Resolves the instance handle of an export and clears this thread's last error.
+fn instance<'a>(handle: *const Instance) -> Option<&'a Instance> {
+    LAST_ERROR.with(|e| e.borrow_mut().take());
+    let db = unsafe { handle.as_ref() };
+    if db.is_none() {
+        fail(CacheError::InvalidHandle);
+    }
+    db
+}
+
+/// Status of the last export called on this thread (0 = success).
+#[no_mangle]
+pub extern "C" fn cache_last_status() -> i32 {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(CacheStatus::Ok as i32, |err| err.status() as i32))
+}
+
+/// Message of the last export's error on this thread (UTF-8, free with cache_free), or null
+/// if it succeeded. Reading it does not clear it.
+#[no_mangle]
+pub extern "C" fn cache_last_error(out_len: *mut usize) -> *mut c_uchar {
+    let msg = LAST_ERROR.with(|e| e.borrow().as_ref().map(|err| err.to_string()));
+    prepare_return(msg.map(String::into_bytes).unwrap_or_default(), out_len)
+}
+
+// Eviction runs after every write, so usage only stays above the limit when the policy found
+// nothing to evict (allkeys-* policies always can; a concurrent writer may just not have
+// caught up yet).
+fn memory_exhausted(db: &Instance) -> bool {
+    !allkeys_policy(db) && memory_over_limit(db)
+}
+
+// A new key at the item limit needs a victim up front unless the policy always has one:
+// noeviction refuses it, volatile-* refuses it when no key with a TTL is left to evict.
+fn check_oom<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K) -> Result<(), CacheError> {
+    let items_full = !allkeys_policy(db)
+        && db.cache.len.load(Ordering::Relaxed) >= db.max_items.load(Ordering::Relaxed)
+        && !state.map.contains(key.as_bytes())
+        && !evict_for_items(db, state);
+    if items_full || memory_exhausted(db) { Err(CacheError::OutOfMemory) } else { Ok(()) }
+}
+
+// Records a read for the eviction policy. Only atomics are touched, so this works under a
+// shared lock; the `accessed` bit is only written when it changes to keep hot keys cheap.
+fn record_access<K: CacheKey + ?Sized>(db: &Instance, state: &Shard, key: &K, entry: &Entry) {
+    if let Some(sketch) = &state.sketch {
+        sketch.increment(eviction::key_hash(key.as_bytes()));
+    }
+    if !entry.accessed.load(Ordering::Relaxed) {
+        entry.accessed.store(true, Ordering::Relaxed);
+    }
+    if db.policy() == eviction::POLICY_ALLKEYS_LFU {
+        eviction::touch(entry);
+    }
+}
+
+// Looks up a live key for reading (shared lock) and records the access. Expired entries
+// read as missing and are left for the expiry sweep to remove.
+fn get_entry<'a, K: CacheKey + ?Sized>(db: &Instance, state: &'a Shard, key: &K) -> Option<&'a Entry> {
+    let entry = state.map.peek(key.as_bytes()).filter(|e| !is_expired(e))?;
+    record_access(db, state, key, entry);
+    Some(entry)
+}
+
+// Looks up a key for in-place mutation under the shard's write lock.
+fn get_entry_mut<'a, K: CacheKey + ?Sized>(state: &'a mut Shard, key: &K) -> Option<&'a mut Entry> {
+    let entry = state.map.get_mut(key.as_bytes())?;
+    if entry.expires_at_ms.is_some() {
+        state.volatile.promote(key.as_bytes());
+    }
+    eviction::touch(entry);
+    Some(entry)
+}
+
+fn put_entry_with_lru<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, mut entry: Entry) {
+    // If overwriting an existing key, remove old index entries first.
+    if let Some(old) = pop_entry(db, state, key) {
+        entry.in_window |= old.in_window;
     }
-    let _ = state.map_b.pop(&key);
-    state.map_b.put(key, entry);
+    // Make room under the item limit; concurrent writers in other shards can push the count
+    // past it, so keep evicting until it is back below or no victim is left.
+    while db.cache.len.load(Ordering::Relaxed) >= db.max_items.load(Ordering::Relaxed) && evict_for_items(db, state) {}
+
+    index_add_for_entry(db, key, &entry);
+    if let Some(sketch) = &state.sketch {
+        sketch.increment(eviction::key_hash(key.as_bytes()));
+    }
+    eviction::touch(&entry);
+    db.cache.len.fetch_add(1, Ordering::Relaxed);
+    db.cache.used_memory.fetch_add(entry_mem(key.as_bytes().len(), &entry), Ordering::Relaxed);
+    if entry.expires_at_ms.is_some() {
+        state.volatile.put(key.as_bytes().to_vec(), ());
+    }
+    state.map.put(key.as_bytes().to_vec(), entry);
+    if state.sketch.is_some() {
+        eviction::admit_to_window(&mut state.map, &mut state.window, key.as_bytes().to_vec(), window_cap(db));
+    }
+    evict_for_memory(db, state);
 }
 
 fn start_expiry_thread_once() {
     let _ = EXPIRY_THREAD_STARTED.get_or_init(|| {
         std::thread::spawn(|| loop {
             std::thread::sleep(Duration::from_millis(250));
-            let mut state = CACHE.write().unwrap();
-
-            let mut expired_keys: Vec<String> = Vec::new();
-            for (k, v) in state.map.iter() {
-                if is_expired(v) {
-                    expired_keys.push(k.clone());
-                }
-            }
-            for k in expired_keys {
-                state.map.pop(&k);
-                notify_expired(&k);
-            }
-
-            let mut expired_keys_b: Vec<Vec<u8>> = Vec::new();
-            for (k, v) in state.map_b.iter() {
-                if is_expired(v) {
-                    expired_keys_b.push(k.clone());
+            let live: Vec<Arc<Instance>> = {
+                let mut instances = INSTANCES.lock().unwrap();
+                instances.retain(|w| w.strong_count() > 0);
+                instances.iter().filter_map(Weak::upgrade).collect()
+            };
+            for db in live.iter().filter(|db| !db.closed.load(Ordering::Relaxed)) {
+                for shard in &db.cache.shards {
+                    sweep_expired(db, shard);
                 }
             }
-            for k in expired_keys_b {
-                state.map_b.pop(&k);
-                let key_str = bytes_to_hex_key(&k);
-                notify_expired(&key_str);
-            }
         });
     });
 }
 
+// Scans one shard under a shared lock and only takes the write lock if something expired.
+fn sweep_expired(db: &Instance, shard: &RwLock<Shard>) {
+    let expired_keys: Vec<Vec<u8>> = {
+        let state = shard.read().unwrap();
+        state.map.iter().filter(|(_, v)| is_expired(v)).map(|(k, _)| k.clone()).collect()
+    };
+    if expired_keys.is_empty() {
+        return;
+    }
+
+    let mut state = shard.write().unwrap();
+    for k in expired_keys {
+        maybe_remove_if_expired(db, &mut state, k.as_slice());
+    }
+}
+
 // --- AOF (Append Only File) ---
 
 const AOF_OP_SET: u8 = 1;
@@ -263,1722 +972,11134 @@ const AOF_OP_HSET: u8 = 5;
 const AOF_OP_LPUSH: u8 = 6;
 const AOF_OP_SADD: u8 = 7;
 const AOF_OP_ZADD: u8 = 8;
-const AOF_OP_XADD: u8 = 9;
+const AOF_OP_XADD_U64: u8 = 9;
 
-// Binary-key variants (avoid encoding key bytes into strings)
+// SET / REMOVE of binary keys in files written while those had a keyspace of their own.
+// Replayed as AOF_OP_SET / AOF_OP_REMOVE.
 const AOF_OP_SET_B: u8 = 10;
 const AOF_OP_REMOVE_B: u8 = 11;
 
-fn aof_write(buf: &[u8]) {
-    let mut guard = AOF_FILE.lock().unwrap();
-    let Some(file) = guard.as_mut() else { return; };
-    let _ = file.write_all(buf);
-    let _ = file.flush();
-}
-
-fn aof_write_set(key: &str, val: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 4 + val.len());
-    buf.push(AOF_OP_SET);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&(val.len() as u32).to_le_bytes());
-    buf.extend_from_slice(val);
-    aof_write(&buf);
-}
-
-fn aof_write_set_b(key: &[u8], val: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 4 + val.len());
-    buf.push(AOF_OP_SET_B);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key);
-    buf.extend_from_slice(&(val.len() as u32).to_le_bytes());
-    buf.extend_from_slice(val);
-    aof_write(&buf);
-}
-
-fn aof_write_remove(key: &str) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len());
-    buf.push(AOF_OP_REMOVE);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    aof_write(&buf);
-}
-
-fn aof_write_remove_b(key: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len());
-    buf.push(AOF_OP_REMOVE_B);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key);
-    aof_write(&buf);
-}
-
-fn aof_write_clear() {
-    aof_write(&[AOF_OP_CLEAR]);
-}
-
-fn aof_write_expire(key: &str, ttl_ms: u64) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 8);
-    buf.push(AOF_OP_EXPIRE);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&ttl_ms.to_le_bytes());
-    aof_write(&buf);
-}
-
-fn aof_write_hset(key: &str, field: &str, val: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 4 + field.len() + 4 + val.len());
-    buf.push(AOF_OP_HSET);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&(field.len() as u32).to_le_bytes());
-    buf.extend_from_slice(field.as_bytes());
-    buf.extend_from_slice(&(val.len() as u32).to_le_bytes());
-    buf.extend_from_slice(val);
-    aof_write(&buf);
-}
-
-fn aof_write_lpush(key: &str, val: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 4 + val.len());
-    buf.push(AOF_OP_LPUSH);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&(val.len() as u32).to_le_bytes());
-    buf.extend_from_slice(val);
-    aof_write(&buf);
-}
-
-fn aof_write_sadd(key: &str, val: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 4 + val.len());
-    buf.push(AOF_OP_SADD);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&(val.len() as u32).to_le_bytes());
-    buf.extend_from_slice(val);
-    aof_write(&buf);
-}
-
-fn aof_write_zadd(key: &str, score: f64, member: &str) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 8 + 4 + member.len());
-    buf.push(AOF_OP_ZADD);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&score.to_le_bytes());
-    buf.extend_from_slice(&(member.len() as u32).to_le_bytes());
-    buf.extend_from_slice(member.as_bytes());
-    aof_write(&buf);
+// Absolute expiry (unix ms). AOF_OP_EXPIRE is still read from older files.
+const AOF_OP_EXPIRE_AT: u8 = 12;
+
+// List edits. Each record carries the resolved effect (pop count, insert position), so
+// replaying it against the same list gives the same result.
+const AOF_OP_RPUSH: u8 = 13;
+const AOF_OP_LPOP: u8 = 14;
+const AOF_OP_RPOP: u8 = 15;
+const AOF_OP_LSET: u8 = 16;
+const AOF_OP_LINSERT: u8 = 17;
+const AOF_OP_LREM: u8 = 18;
+const AOF_OP_LTRIM: u8 = 19;
+
+// Sorted-set removal. ZINCRBY and flagged ZADDs are logged as plain ZADDs of the resulting score.
+const AOF_OP_ZREM: u8 = 20;
+
+// Hash field removal. HINCRBY / HINCRBYFLOAT are logged as HSETs of the resulting value.
+const AOF_OP_HDEL: u8 = 21;
+
+// Set member removal. SPOP is logged as SREMs of the popped members, and the *STORE commands
+// as a removal of the destination followed by SADDs of the result.
+const AOF_OP_SREM: u8 = 22;
+
+// Stream consumer groups. XREADGROUP and XCLAIM are logged with their outcome (new owner,
+// delivery time, ids), so replay does not depend on the clock.
+const AOF_OP_XGROUP_CREATE_U64: u8 = 23;
+const AOF_OP_XGROUP_DESTROY: u8 = 24;
+const AOF_OP_XCLAIM_U64: u8 = 25;
+const AOF_OP_XACK_U64: u8 = 26;
+
+// Stream records with `<ms>-<seq>` ids, each written as [Ms u64] [Seq u64]. The ops above
+// (and AOF_OP_XADD_U64) carry the single u64 ids of older files, read back as `<id>-0`.
+// AOF_OP_XADD_PAYLOAD and AOF_OP_XADD_U64 hold one payload, replayed as its `payload` field.
+const AOF_OP_XADD_PAYLOAD: u8 = 27;
+const AOF_OP_XGROUP_CREATE: u8 = 28;
+const AOF_OP_XCLAIM: u8 = 29;
+const AOF_OP_XACK: u8 = 30;
+const AOF_OP_XDEL: u8 = 31;
+// Drops every entry below the id: XTRIM and capped XADDs log where the trim stopped.
+const AOF_OP_XTRIM: u8 = 32;
+// Creates the stream if missing and raises its last id (rewrites of streams whose newest
+// entries were deleted, or that are empty).
+const AOF_OP_XSETID: u8 = 33;
+// [Key] [Ms u64] [Seq u64] [FieldCount u32] { [NameLen u32] [Name] [ValueLen u32] [Value] }
+const AOF_OP_XADD: u8 = 34;
+
+// Marked the other records of binary keys in those files (same layout). Keys are bytes in
+// every record, so the mark is dropped on replay: a file holding both AOF_OP_SET and
+// AOF_OP_SET_B replays into the one keyspace in file order, and a key once written both ways
+// keeps the later write. A rewrite leaves only unmarked records.
+const AOF_OP_BINARY_KEY: u8 = 0x80;
+
+// fsync policies (same meaning as Redis' appendfsync).
+const AOF_FSYNC_NO: u8 = 0;
+const AOF_FSYNC_EVERYSEC: u8 = 1;
+const AOF_FSYNC_ALWAYS: u8 = 2;
+
+struct AofState {
+    file: Option<Arc<std::fs::File>>,
+    path: String,
+    // Records not yet picked up by the writer thread.
+    pending: Vec<u8>,
+    // Positions in the logical record stream: enqueued, written to the OS, fsynced.
+    appended: u64,
+    written: u64,
+    synced: u64,
+    sync_requested: bool,
+    writer_busy: bool,
+    // Set when a background write or fsync fails; reported by cache_aof_flush.
+    failed: bool,
+    // Records appended while a rewrite is running; copied onto the new file at swap time.
+    rewrite_buf: Option<Vec<u8>>,
+    // When the last rewrite failed, cleared by a successful one; delays automatic retries.
+    rewrite_failed_at: Option<std::time::Instant>,
+    // File size after the last enable/rewrite, and the current size.
+    base_size: u64,
+    size: u64,
 }
 
-fn aof_write_xadd(key: &str, id: u64, payload: &[u8]) {
-    let mut buf = Vec::with_capacity(1 + 4 + key.len() + 8 + 4 + payload.len());
-    buf.push(AOF_OP_XADD);
-    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-    buf.extend_from_slice(key.as_bytes());
-    buf.extend_from_slice(&id.to_le_bytes());
-    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
-    buf.extend_from_slice(payload);
-    aof_write(&buf);
+thread_local! {
+    // Under the "always" policy, the position this thread must see fsynced before
+    // returning to its caller. Waited on after the shard lock is released.
+    static AOF_DURABLE_TARGET: Cell<u64> = const { Cell::new(0) };
+    // Set while this thread replays an AOF. Expiry is then judged only once the whole log is
+    // applied: a key whose logged expiry has passed may still receive later records.
+    static AOF_REPLAYING: Cell<bool> = const { Cell::new(false) };
 }
 
-// --- Phase3: Pub/Sub + Keyspace Notifications ---
-
-#[derive(Clone)]
-struct NotifyEvent {
-    kind: u8,
-    key: String,
-    at_ms: u64,
+fn aof_replaying() -> bool {
+    AOF_REPLAYING.with(Cell::get)
 }
 
-const NOTIFY_KIND_EXPIRED: u8 = 1;
-const NOTIFY_KIND_EVICTED: u8 = 2;
+// Write guard for a shard that, once the lock is released, waits for this thread's AOF
+// records to be fsynced when the "always" policy is active. Waiting outside the lock lets
+// concurrent writers share one fsync (group commit).
+struct CacheWriteGuard<'a>(Option<RwLockWriteGuard<'a, Shard>>, &'a Instance);
 
-static NOTIFY_QUEUE: Lazy<Mutex<VecDeque<NotifyEvent>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+impl std::ops::Deref for CacheWriteGuard<'_> {
+    type Target = Shard;
+    fn deref(&self) -> &Shard {
+        self.0.as_ref().unwrap()
+    }
+}
 
-fn notify_expired(key: &str) {
-    let mut q = NOTIFY_QUEUE.lock().unwrap();
-    q.push_back(NotifyEvent {
-        kind: NOTIFY_KIND_EXPIRED,
-        key: key.to_string(),
-        at_ms: now_ms(),
-    });
+impl std::ops::DerefMut for CacheWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Shard {
+        self.0.as_mut().unwrap()
+    }
 }
 
-fn notify_evicted(key: &str) {
-    let mut q = NOTIFY_QUEUE.lock().unwrap();
-    q.push_back(NotifyEvent {
-        kind: NOTIFY_KIND_EVICTED,
-        key: key.to_string(),
-        at_ms: now_ms(),
-    });
+impl Drop for CacheWriteGuard<'_> {
+    fn drop(&mut self) {
+        self.0.take();
+        aof_wait_own_records(self.1);
+    }
 }
 
-#[derive(Clone)]
-struct PubMessage {
-    channel: String,
-    payload: Vec<u8>,
-}
+// Write locks on every shard, for operations that replace the whole state (clear, loads).
+struct AllShardsGuard<'a>(Vec<RwLockWriteGuard<'a, Shard>>, &'a Instance);
 
-struct PubSubState {
-    next_id: u64,
-    subs: HashMap<u64, String>,
-    channels: HashMap<String, Vec<u64>>,
-    queues: HashMap<u64, VecDeque<PubMessage>>,
+impl AllShardsGuard<'_> {
+    fn shard<K: CacheKey + ?Sized>(&mut self, key: &K) -> &mut Shard {
+        &mut self.0[shard_index(key)]
+    }
 }
 
-static PUBSUB: Lazy<Mutex<PubSubState>> = Lazy::new(|| {
-    Mutex::new(PubSubState {
-        next_id: 1,
-        subs: HashMap::new(),
-        channels: HashMap::new(),
-        queues: HashMap::new(),
-    })
-});
-
-fn read_exact_u8(r: &mut impl Read) -> Option<u8> {
-    let mut b = [0u8; 1];
-    r.read_exact(&mut b).ok()?;
-    Some(b[0])
+// Sorted, distinct shard indexes of `keys`: the order multi-key operations lock them in.
+fn shard_indexes<K: CacheKey>(keys: &[K]) -> Vec<usize> {
+    let mut idxs: Vec<usize> = keys.iter().map(shard_index).collect();
+    idxs.sort_unstable();
+    idxs.dedup();
+    idxs
 }
 
-fn read_exact_u32(r: &mut impl Read) -> Option<u32> {
-    let mut b = [0u8; 4];
-    r.read_exact(&mut b).ok()?;
-    Some(u32::from_le_bytes(b))
-}
+// Write locks on the shards of several keys, taken in index order (each shard once).
+struct ShardsGuard<'a>(Vec<RwLockWriteGuard<'a, Shard>>, &'a Instance);
 
-fn read_exact_u64(r: &mut impl Read) -> Option<u64> {
-    let mut b = [0u8; 8];
-    r.read_exact(&mut b).ok()?;
-    Some(u64::from_le_bytes(b))
+fn shard_write_keys<'a, K: CacheKey>(db: &'a Instance, keys: &[K]) -> ShardsGuard<'a> {
+    ShardsGuard(shard_indexes(keys).into_iter().map(|i| db.cache.shards[i].write().unwrap()).collect(), db)
 }
 
-fn read_exact_f64(r: &mut impl Read) -> Option<f64> {
-    let mut b = [0u8; 8];
-    r.read_exact(&mut b).ok()?;
-    Some(f64::from_le_bytes(b))
-}
+impl ShardsGuard<'_> {
+    fn shard<K: CacheKey + ?Sized>(&mut self, key: &K) -> &mut Shard {
+        let idx = shard_index(key);
+        self.0.iter_mut().find(|s| s.idx == idx).expect("key's shard is locked")
+    }
 
-fn read_exact_vec(r: &mut impl Read, len: usize) -> Option<Vec<u8>> {
-    let mut b = vec![0u8; len];
-    r.read_exact(&mut b).ok()?;
-    Some(b)
+    fn shard_ref<K: CacheKey + ?Sized>(&self, key: &K) -> &Shard {
+        let idx = shard_index(key);
+        self.0.iter().find(|s| s.idx == idx).expect("key's shard is locked")
+    }
 }
 
-fn read_exact_string(r: &mut impl Read, len: usize) -> Option<String> {
-    let bytes = read_exact_vec(r, len)?;
-    Some(String::from_utf8_lossy(&bytes).into_owned())
+impl Drop for ShardsGuard<'_> {
+    fn drop(&mut self) {
+        self.0.clear();
+        aof_wait_own_records(self.1);
+    }
 }
 
-fn apply_set_internal(state: &mut CacheState, key: String, val: Vec<u8>) {
-    put_entry_with_lru(
-        state,
-        key,
-        Entry {
-            value: Value::Bytes(Arc::new(val)),
-            expires_at_ms: None,
-        },
-    );
-}
+// Shared locks on the shards of several keys, taken in index order.
+struct ShardsReadGuard<'a>(Vec<RwLockReadGuard<'a, Shard>>);
 
-fn apply_set_internal_b(state: &mut CacheState, key: Vec<u8>, val: Vec<u8>) {
-    put_entry_with_lru_b(
-        state,
-        key,
-        Entry {
-            value: Value::Bytes(Arc::new(val)),
-            expires_at_ms: None,
-        },
-    );
+fn shard_read_keys<'a, K: CacheKey>(db: &'a Instance, keys: &[K]) -> ShardsReadGuard<'a> {
+    ShardsReadGuard(shard_indexes(keys).into_iter().map(|i| db.cache.shards[i].read().unwrap()).collect())
 }
 
-fn apply_remove_internal(state: &mut CacheState, key: &String) {
-    if let Some(old) = state.map.pop(key) {
-        index_remove_for_entry(state, key, &old);
+impl ShardsReadGuard<'_> {
+    fn shard<K: CacheKey + ?Sized>(&self, key: &K) -> &Shard {
+        let idx = shard_index(key);
+        self.0.iter().find(|s| s.idx == idx).expect("key's shard is locked")
     }
 }
 
-fn apply_remove_internal_b(state: &mut CacheState, key: &Vec<u8>) {
-    let _ = state.map_b.pop(key);
-}
-
-fn apply_clear_internal(state: &mut CacheState) {
-    state.map.clear();
-    state.map_b.clear();
-    state.numeric_indexes.clear();
-}
-
-fn apply_expire_internal(state: &mut CacheState, key: &String, ttl_ms: u64) -> bool {
-    if maybe_remove_if_expired(state, key) {
-        return false;
+impl Drop for AllShardsGuard<'_> {
+    fn drop(&mut self) {
+        self.0.clear();
+        aof_wait_own_records(self.1);
     }
-    let Some(mut entry) = state.map.pop(key) else { return false; };
-    let expires_at = now_ms().saturating_add(ttl_ms);
-    entry.expires_at_ms = Some(expires_at);
-    put_entry_with_lru(state, key.clone(), entry);
-    true
 }
 
-// Prepares a vector for FFI return: shrinks to fit (cap=len), forgets it, returns ptr/len
-fn prepare_return(mut vec: Vec<u8>, out_len: *mut usize) -> *mut c_uchar {
-    vec.shrink_to_fit();
-    let len = vec.len();
-    unsafe { *out_len = len };
-    if len == 0 {
-        return std::ptr::null_mut();
+fn aof_wait_own_records(db: &Instance) {
+    let target = AOF_DURABLE_TARGET.with(|t| t.replace(0));
+    if target > 0 {
+        aof_wait_synced(db, target);
     }
-    let ptr = vec.as_mut_ptr();
-    std::mem::forget(vec);
-    ptr
 }
 
-// --- Common ---
-
-#[no_mangle]
-pub extern "C" fn cache_init() {
-    Lazy::force(&CACHE);
-    start_expiry_thread_once();
+fn aof_enabled(db: &Instance) -> bool {
+    db.aof.lock().unwrap().file.is_some()
 }
 
-#[no_mangle]
-pub extern "C" fn cache_remove(key: *const c_char) {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
-    apply_remove_internal(&mut state, &key_str);
-    aof_write_remove(&key_str);
+fn aof_write(db: &Instance, buf: &[u8]) {
+    let mut guard = db.aof.lock().unwrap();
+    let aof = &mut *guard;
+    if aof.file.is_none() {
+        return;
+    }
+    aof.pending.extend_from_slice(buf);
+    aof.appended += buf.len() as u64;
+    aof.size += buf.len() as u64;
+    if let Some(pending) = aof.rewrite_buf.as_mut() {
+        pending.extend_from_slice(buf);
+    }
+    if db.aof_fsync_policy.load(Ordering::Relaxed) == AOF_FSYNC_ALWAYS {
+        let target = aof.appended;
+        AOF_DURABLE_TARGET.with(|t| t.set(target));
+    }
+    db.aof_writer_signal.notify_one();
+
+    let pct = db.aof_auto_rewrite_pct.load(Ordering::Relaxed);
+    let due = pct > 0
+        && aof.size >= db.aof_auto_rewrite_min_size.load(Ordering::Relaxed)
+        && aof.size >= aof.base_size.saturating_add(aof.base_size.saturating_mul(pct) / 100)
+        && aof.rewrite_failed_at.is_none_or(|t| t.elapsed() >= AOF_REWRITE_RETRY_DELAY);
+    drop(guard);
+    // Claiming the flag here means concurrent writers start at most one rewrite thread.
+    if due && db.aof_rewrite_in_progress.compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+        let db = db.arc();
+        std::thread::spawn(move || aof_rewrite_claimed(&db));
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn cache_clear_all() {
-    let mut state = CACHE.write().unwrap();
-    apply_clear_internal(&mut state);
-    aof_write_clear();
+fn start_aof_writer_once(db: &Instance) {
+    let _ = db.aof_writer_started.get_or_init(|| {
+        let db = db.arc();
+        std::thread::spawn(move || aof_writer_loop(&db));
+    });
 }
 
-// --- Core / String (Value::Bytes) ---
+// Batches pending records to disk and applies the fsync policy (group commit).
+fn aof_writer_loop(db: &Instance) {
+    let mut last_sync = std::time::Instant::now();
+    let mut aof = db.aof.lock().unwrap();
+    loop {
+        let policy = db.aof_fsync_policy.load(Ordering::Relaxed);
+        let since_sync = last_sync.elapsed();
+        let unsynced = aof.written > aof.synced || !aof.pending.is_empty();
+        let sync = unsynced
+            && (aof.sync_requested
+                || policy == AOF_FSYNC_ALWAYS
+                || (policy == AOF_FSYNC_EVERYSEC && since_sync >= Duration::from_secs(1)));
+
+        if aof.pending.is_empty() && !(sync && aof.written > aof.synced) {
+            // A destroyed instance has drained and closed its AOF already.
+            if db.closed.load(Ordering::Relaxed) {
+                return;
+            }
+            let wait = if policy == AOF_FSYNC_EVERYSEC && unsynced {
+                Duration::from_secs(1).saturating_sub(since_sync).max(Duration::from_millis(1))
+            } else {
+                Duration::from_secs(1)
+            };
+            aof = db.aof_writer_signal.wait_timeout(aof, wait).unwrap().0;
+            continue;
+        }
 
-#[no_mangle]
-pub extern "C" fn cache_set(key: *const c_char, value: *const c_uchar, len: usize) {
-    let key_str = unsafe { to_string(key) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    // Write AOF without holding the cache lock.
-    aof_write_set(&key_str, &val_vec);
-    let mut state = CACHE.write().unwrap();
-    apply_set_internal(&mut state, key_str, val_vec);
-}
+        let Some(file) = aof.file.clone() else {
+            aof.pending.clear();
+            continue;
+        };
+        let batch = std::mem::take(&mut aof.pending);
+        let batch_end = aof.appended;
+        if sync {
+            aof.sync_requested = false;
+        }
+        aof.writer_busy = true;
+        drop(aof);
 
-#[no_mangle]
-pub extern "C" fn cache_get(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
+        let mut ok = (&*file).write_all(&batch).is_ok();
+        if sync {
+            ok = ok && file.sync_data().is_ok();
+            last_sync = std::time::Instant::now();
+        }
 
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+        aof = db.aof.lock().unwrap();
+        aof.writer_busy = false;
+        aof.failed |= !ok;
+        aof.written = aof.written.max(batch_end);
+        if sync || !ok {
+            // Failed batches are not retried; waiters are released and see `failed`.
+            aof.synced = aof.synced.max(batch_end);
+        }
+        db.aof_durable_signal.notify_all();
     }
+}
 
-    if let Some(entry) = state.map.get(&key_str) {
-        if let Value::Bytes(val) = &entry.value {
-            return prepare_return((**val).clone(), out_len);
+// Blocks until every record up to `target` is fsynced (or the AOF is disabled).
+fn aof_wait_synced(db: &Instance, target: u64) -> bool {
+    let mut aof = db.aof.lock().unwrap();
+    while aof.file.is_some() && aof.synced < target {
+        if !aof.sync_requested {
+            aof.sync_requested = true;
+            db.aof_writer_signal.notify_one();
         }
+        aof = db.aof_durable_signal.wait(aof).unwrap();
     }
-    
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
+    !aof.failed
 }
 
-// Copy value bytes into a caller-provided buffer.
-// Return semantics:
-//  -1  => key missing (or expired)
-//  <0  => buffer too small; required length is -ret
-//  >0  => bytes written
-//   0  => value exists but is empty
-#[no_mangle]
-pub extern "C" fn cache_get_into(key: *const c_char, dst: *mut c_uchar, dst_len: usize) -> i64 {
-    let key_str = unsafe { to_string(key) };
-    if key_str.is_empty() {
-        return -1;
+// Synchronously writes and fsyncs whatever is pending for the current file, so the file
+// can be switched or closed without losing or reordering records.
+fn aof_drain_locked<'a>(db: &'a Instance, mut aof: MutexGuard<'a, AofState>) -> MutexGuard<'a, AofState> {
+    while aof.writer_busy {
+        aof = db.aof_durable_signal.wait(aof).unwrap();
     }
-
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        return -1;
+    if let Some(file) = aof.file.clone() {
+        let batch = std::mem::take(&mut aof.pending);
+        let ok = (&*file).write_all(&batch).is_ok() && file.sync_data().is_ok();
+        aof.failed |= !ok;
     }
+    aof.pending.clear();
+    aof.written = aof.appended;
+    aof.synced = aof.appended;
+    db.aof_durable_signal.notify_all();
+    aof
+}
 
-    let Some(entry) = state.map.get(&key_str) else {
-        return -1;
-    };
-
-    let Value::Bytes(val) = &entry.value else {
-        return -1;
-    };
+// Starts a record: the op and the key.
+fn aof_put_key<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, op: u8, key: &K) {
+    buf.push(op);
+    aof_put_bytes(buf, key.as_bytes());
+}
 
-    let value_len = val.len();
-    if value_len == 0 {
-        return 0;
+// The op of a record read back from the file, with the binary-key forms of older files
+// (see AOF_OP_BINARY_KEY) mapped to the plain ones.
+fn aof_record_op(op: u8) -> u8 {
+    match op {
+        AOF_OP_SET_B => AOF_OP_SET,
+        AOF_OP_REMOVE_B => AOF_OP_REMOVE,
+        op => op & !AOF_OP_BINARY_KEY,
     }
+}
 
-    if dst.is_null() || dst_len < value_len {
-        return -(value_len as i64);
-    }
+fn aof_encode_set<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, val: &[u8]) {
+    buf.reserve(1 + 4 + key.as_bytes().len() + 4 + val.len());
+    aof_put_key(buf, AOF_OP_SET, key);
+    aof_put_bytes(buf, val);
+}
 
-    unsafe {
-        std::ptr::copy_nonoverlapping(val.as_ptr(), dst, value_len);
-    }
-    value_len as i64
+fn aof_write_set<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: &[u8]) {
+    let mut buf = Vec::new();
+    aof_encode_set(&mut buf, key, val);
+    aof_write(db, &buf);
 }
 
-// --- Hashes ---
+fn aof_encode_remove<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K) {
+    aof_put_key(buf, AOF_OP_REMOVE, key);
+}
 
-#[no_mangle]
-pub extern "C" fn cache_hset(key: *const c_char, field: *const c_char, value: *const c_uchar, len: usize) {
-    let key_str = unsafe { to_string(key) };
-    let field_str = unsafe { to_string(field) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        // fallthrough to create fresh
-    }
+fn aof_write_remove<K: CacheKey + ?Sized>(db: &Instance, key: &K) {
+    let mut buf = Vec::new();
+    aof_encode_remove(&mut buf, key);
+    aof_write(db, &buf);
+}
 
-    let mut entry = state
-        .map
-        .pop(&key_str)
-        .unwrap_or(Entry { value: Value::Hash(HashMap::new()), expires_at_ms: None });
+fn aof_write_clear(db: &Instance) {
+    aof_write(db, &[AOF_OP_CLEAR]);
+}
 
-    match &mut entry.value {
-        Value::Hash(hmap) => {
-            hmap.insert(field_str.clone(), val_vec.clone());
-        }
-        _ => {
-            let mut h = HashMap::new();
-            h.insert(field_str.clone(), val_vec.clone());
-            entry.value = Value::Hash(h);
-        }
-    }
+fn aof_encode_expire_at<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, expires_at_ms: u64) {
+    aof_put_key(buf, AOF_OP_EXPIRE_AT, key);
+    buf.extend_from_slice(&expires_at_ms.to_le_bytes());
+}
 
-    put_entry_with_lru(&mut state, key_str.clone(), entry);
-    aof_write_hset(&key_str, &field_str, &val_vec);
+fn aof_write_expire_at<K: CacheKey + ?Sized>(db: &Instance, key: &K, expires_at_ms: u64) {
+    let mut buf = Vec::new();
+    aof_encode_expire_at(&mut buf, key, expires_at_ms);
+    aof_write(db, &buf);
 }
 
-#[no_mangle]
-pub extern "C" fn cache_hget(key: *const c_char, field: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let field_str = unsafe { to_string(field) };
-    
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    }
+fn aof_encode_hset<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, field: &[u8], val: &[u8]) {
+    buf.reserve(1 + 4 + key.as_bytes().len() + 4 + field.len() + 4 + val.len());
+    aof_put_key(buf, AOF_OP_HSET, key);
+    aof_put_bytes(buf, field);
+    aof_put_bytes(buf, val);
+}
 
-    if let Some(entry) = state.map.get(&key_str) {
-        if let Value::Hash(hmap) = &entry.value {
-            if let Some(val) = hmap.get(&field_str) {
-                return prepare_return(val.clone(), out_len);
-            }
-        }
-    }
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
+fn aof_write_hset<K: CacheKey + ?Sized>(db: &Instance, key: &K, field: &[u8], val: &[u8]) {
+    let mut buf = Vec::new();
+    aof_encode_hset(&mut buf, key, field, val);
+    aof_write(db, &buf);
 }
 
-#[no_mangle]
-pub extern "C" fn cache_hgetall(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
+fn aof_encode_push<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, left: bool, val: &[u8]) {
+    buf.reserve(1 + 4 + key.as_bytes().len() + 4 + val.len());
+    aof_put_key(buf, if left { AOF_OP_LPUSH } else { AOF_OP_RPUSH }, key);
+    aof_put_bytes(buf, val);
+}
 
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    }
+fn aof_write_push<K: CacheKey + ?Sized>(db: &Instance, key: &K, left: bool, val: &[u8]) {
+    let mut buf = Vec::new();
+    aof_encode_push(&mut buf, key, left, val);
+    aof_write(db, &buf);
+}
 
-    if let Some(entry) = state.map.get(&key_str) {
-        if let Value::Hash(hmap) = &entry.value {
-        let mut flat = Vec::new();
-        // format: [Count (u32)] [KeyLen (u32)] [Key] [ValLen (u32)] [Val] ...
-        flat.extend_from_slice(&(hmap.len() as u32).to_le_bytes());
-        for (k, v) in hmap {
-            let k_bytes = k.as_bytes();
-            flat.extend_from_slice(&(k_bytes.len() as u32).to_le_bytes());
-            flat.extend_from_slice(k_bytes);
-            flat.extend_from_slice(&(v.len() as u32).to_le_bytes());
-            flat.extend_from_slice(v);
-        }
-        return prepare_return(flat, out_len);
-    }
-    }
-    
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
+fn aof_write_pop<K: CacheKey + ?Sized>(db: &Instance, key: &K, left: bool, count: usize) {
+    let mut buf = Vec::with_capacity(1 + 4 + key.as_bytes().len() + 4);
+    aof_put_key(&mut buf, if left { AOF_OP_LPOP } else { AOF_OP_RPOP }, key);
+    buf.extend_from_slice(&(count as u32).to_le_bytes());
+    aof_write(db, &buf);
 }
 
-// --- Lists ---
+// LSET (index), LINSERT (resolved position) and LREM (count) share a layout: [Key] [i64] [Val].
+fn aof_write_list_at<K: CacheKey + ?Sized>(db: &Instance, op: u8, key: &K, index: i64, val: &[u8]) {
+    let mut buf = Vec::with_capacity(1 + 4 + key.as_bytes().len() + 8 + 4 + val.len());
+    aof_put_key(&mut buf, op, key);
+    buf.extend_from_slice(&index.to_le_bytes());
+    aof_put_bytes(&mut buf, val);
+    aof_write(db, &buf);
+}
 
-#[no_mangle]
-pub extern "C" fn cache_lpush(key: *const c_char, value: *const c_uchar, len: usize) {
-    let key_str = unsafe { to_string(key) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        // create fresh
-    }
+fn aof_write_lrem<K: CacheKey + ?Sized>(db: &Instance, key: &K, count: i64, val: &[u8]) {
+    aof_write_list_at(db, AOF_OP_LREM, key, count, val);
+}
 
-    let mut entry = state
-        .map
-        .pop(&key_str)
-        .unwrap_or(Entry { value: Value::List(Vec::new()), expires_at_ms: None });
+fn aof_write_ltrim<K: CacheKey + ?Sized>(db: &Instance, key: &K, start: i64, end: i64) {
+    let mut buf = Vec::with_capacity(1 + 4 + key.as_bytes().len() + 16);
+    aof_put_key(&mut buf, AOF_OP_LTRIM, key);
+    buf.extend_from_slice(&start.to_le_bytes());
+    buf.extend_from_slice(&end.to_le_bytes());
+    aof_write(db, &buf);
+}
 
-    match &mut entry.value {
-        Value::List(list) => list.insert(0, val_vec.clone()),
-        _ => entry.value = Value::List(vec![val_vec.clone()]),
-    }
+fn aof_encode_sadd<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, val: &[u8]) {
+    buf.reserve(1 + 4 + key.as_bytes().len() + 4 + val.len());
+    aof_put_key(buf, AOF_OP_SADD, key);
+    aof_put_bytes(buf, val);
+}
 
-    put_entry_with_lru(&mut state, key_str.clone(), entry);
-    aof_write_lpush(&key_str, &val_vec);
+fn aof_write_sadd<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: &[u8]) {
+    let mut buf = Vec::new();
+    aof_encode_sadd(&mut buf, key, val);
+    aof_write(db, &buf);
 }
 
-#[no_mangle]
-pub extern "C" fn cache_rpop(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
+fn aof_encode_zadd<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, score: f64, member: &[u8]) {
+    buf.reserve(1 + 4 + key.as_bytes().len() + 8 + 4 + member.len());
+    aof_put_key(buf, AOF_OP_ZADD, key);
+    buf.extend_from_slice(&score.to_le_bytes());
+    aof_put_bytes(buf, member);
+}
 
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    }
+fn aof_write_zadd<K: CacheKey + ?Sized>(db: &Instance, key: &K, score: f64, member: &[u8]) {
+    let mut buf = Vec::new();
+    aof_encode_zadd(&mut buf, key, score, member);
+    aof_write(db, &buf);
+}
 
-    let Some(mut entry) = state.map.pop(&key_str) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+// Records an op naming a single field or member of `key` (HDEL, SREM, ZREM).
+fn aof_write_key_item<K: CacheKey + ?Sized>(db: &Instance, op: u8, key: &K, item: &[u8]) {
+    let mut buf = Vec::with_capacity(1 + 4 + key.as_bytes().len() + 4 + item.len());
+    aof_put_key(&mut buf, op, key);
+    aof_put_bytes(&mut buf, item);
+    aof_write(db, &buf);
+}
 
-    let mut popped: Option<Vec<u8>> = None;
-    if let Value::List(list) = &mut entry.value {
-        popped = list.pop();
+fn aof_encode_xadd<'a, K: CacheKey + ?Sized>(
+    buf: &mut Vec<u8>,
+    key: &K,
+    id: StreamId,
+    fields: impl ExactSizeIterator<Item = (&'a [u8], &'a [u8])>,
+) {
+    aof_put_key(buf, AOF_OP_XADD, key);
+    aof_put_id(buf, id);
+    buf.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+    for (name, value) in fields {
+        aof_put_bytes(buf, name);
+        aof_put_bytes(buf, value);
     }
+}
 
-    // Keep key if list still exists (even empty) to match current behavior
-    put_entry_with_lru(&mut state, key_str, entry);
+fn aof_write_xadd<K: CacheKey + ?Sized>(db: &Instance, key: &K, id: StreamId, fields: &StreamFields) {
+    let mut buf = Vec::new();
+    aof_encode_xadd(&mut buf, key, id, fields.iter().map(|(n, v)| (&n[..], &v[..])));
+    aof_write(db, &buf);
+}
 
-    if let Some(val) = popped {
-        return prepare_return(val, out_len);
-    }
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
+fn aof_put_bytes(buf: &mut Vec<u8>, b: &[u8]) {
+    buf.extend_from_slice(&(b.len() as u32).to_le_bytes());
+    buf.extend_from_slice(b);
 }
 
-#[no_mangle]
-pub extern "C" fn cache_lrange(key: *const c_char, start: i32, end: i32, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
+fn aof_put_id(buf: &mut Vec<u8>, id: StreamId) {
+    buf.extend_from_slice(&id.ms.to_le_bytes());
+    buf.extend_from_slice(&id.seq.to_le_bytes());
+}
 
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+fn aof_put_ids(buf: &mut Vec<u8>, ids: &[StreamId]) {
+    buf.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+    for id in ids {
+        aof_put_id(buf, *id);
     }
-
-    if let Some(entry) = state.map.get(&key_str) {
-        if let Value::List(list) = &entry.value {
-         let len = list.len() as i32;
-         let mut low = start;
-         let mut high = end;
-         
-         if low < 0 { low += len; }
-         if high < 0 { high += len; }
-         if low < 0 { low = 0; }
-         if high >= len { high = len - 1; }
-         
-         let mut flat = Vec::new();
-         if low <= high {
-             let count = (high - low + 1) as u32;
-             flat.extend_from_slice(&count.to_le_bytes());
-             for i in low..=high {
-                 let item = &list[i as usize];
-                 flat.extend_from_slice(&(item.len() as u32).to_le_bytes());
-                 flat.extend_from_slice(item);
-             }
-         } else {
-             flat.extend_from_slice(&(0u32).to_le_bytes());
-         }
-         return prepare_return(flat, out_len);
-    }
-        }
-    
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
 }
 
-// --- Sets ---
+// Records an op naming a single stream id of `key` (XTRIM, XSETID).
+fn aof_encode_key_id<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, op: u8, key: &K, id: StreamId) {
+    aof_put_key(buf, op, key);
+    aof_put_id(buf, id);
+}
 
-#[no_mangle]
-pub extern "C" fn cache_sadd(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
-    let key_str = unsafe { to_string(key) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        // create fresh
-    }
+fn aof_write_key_id<K: CacheKey + ?Sized>(db: &Instance, op: u8, key: &K, id: StreamId) {
+    let mut buf = Vec::new();
+    aof_encode_key_id(&mut buf, op, key, id);
+    aof_write(db, &buf);
+}
 
-    let mut entry = state
-        .map
-        .pop(&key_str)
-        .unwrap_or(Entry { value: Value::Set(HashSet::new()), expires_at_ms: None });
+fn aof_write_xdel<K: CacheKey + ?Sized>(db: &Instance, key: &K, ids: &[StreamId]) {
+    let mut buf = Vec::new();
+    aof_put_key(&mut buf, AOF_OP_XDEL, key);
+    aof_put_ids(&mut buf, ids);
+    aof_write(db, &buf);
+}
 
-    let inserted = match &mut entry.value {
-        Value::Set(set) => set.insert(val_vec.clone()),
-        _ => {
-            let mut s = HashSet::new();
-            let inserted = s.insert(val_vec.clone());
-            entry.value = Value::Set(s);
-            inserted
-        }
-    };
+fn aof_encode_xgroup_create<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, group: &str, last_id: StreamId) {
+    aof_put_key(buf, AOF_OP_XGROUP_CREATE, key);
+    aof_put_bytes(buf, group.as_bytes());
+    aof_put_id(buf, last_id);
+}
 
-    put_entry_with_lru(&mut state, key_str.clone(), entry);
-    if inserted {
-        aof_write_sadd(&key_str, &val_vec);
-        1
-    } else {
-        0
-    }
+fn aof_encode_xclaim<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, group: &str, d: &Delivery) {
+    aof_put_key(buf, AOF_OP_XCLAIM, key);
+    aof_put_bytes(buf, group.as_bytes());
+    aof_put_bytes(buf, d.consumer.as_bytes());
+    buf.extend_from_slice(&d.at_ms.to_le_bytes());
+    aof_put_id(buf, d.last_id);
+    buf.extend_from_slice(&d.deliveries.to_le_bytes());
+    aof_put_ids(buf, &d.ids);
 }
 
-#[no_mangle]
-pub extern "C" fn cache_sismember(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
-    let key_str = unsafe { to_string(key) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        return 0;
-    }
+fn aof_write_xclaim<K: CacheKey + ?Sized>(db: &Instance, key: &K, group: &str, d: &Delivery) {
+    let mut buf = Vec::new();
+    aof_encode_xclaim(&mut buf, key, group, d);
+    aof_write(db, &buf);
+}
 
-    if let Some(entry) = state.map.get(&key_str) {
-        if let Value::Set(set) = &entry.value {
-            return if set.contains(&val_vec) { 1 } else { 0 };
-        }
-    }
-    0
+fn aof_write_xack<K: CacheKey + ?Sized>(db: &Instance, key: &K, group: &str, ids: &[StreamId]) {
+    let mut buf = Vec::new();
+    aof_put_key(&mut buf, AOF_OP_XACK, key);
+    aof_put_bytes(&mut buf, group.as_bytes());
+    aof_put_ids(&mut buf, ids);
+    aof_write(db, &buf);
 }
 
-// --- Sorted Sets ---
+// --- AOF rewrite ---
 
-#[no_mangle]
-pub extern "C" fn cache_zadd(key: *const c_char, score: f64, member: *const c_char) {
-    let key_str = unsafe { to_string(key) };
-    let member_str = unsafe { to_string(member) };
-    
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        // create fresh
-    }
-
-    let mut entry = state
-        .map
-        .pop(&key_str)
-        .unwrap_or(Entry { value: Value::SortedSet(HashMap::new()), expires_at_ms: None });
-
-    match &mut entry.value {
+// Encodes the minimal record sequence that rebuilds one live entry.
+fn aof_encode_entry<K: CacheKey + ?Sized>(buf: &mut Vec<u8>, key: &K, entry: &Entry) {
+    match &entry.value {
+        Value::Bytes(val) => aof_encode_set(buf, key, val),
+        Value::Hash(hmap) => {
+            for (field, val) in hmap {
+                aof_encode_hset(buf, key, field, val);
+            }
+        }
+        Value::List(list) => {
+            for val in list {
+                aof_encode_push(buf, key, false, val);
+            }
+        }
+        Value::Set(set) => {
+            for member in set {
+                aof_encode_sadd(buf, key, member);
+            }
+        }
         Value::SortedSet(ss) => {
-            ss.insert(member_str.clone(), score);
+            for (member, score) in ss.iter() {
+                aof_encode_zadd(buf, key, score, member);
+            }
         }
-        _ => {
-            let mut ss = HashMap::new();
-            ss.insert(member_str.clone(), score);
-            entry.value = Value::SortedSet(ss);
+        Value::Stream(stream) => {
+            for (id, e) in &stream.entries {
+                let fields = e.names.iter().zip(&e.values).map(|(n, v)| (&n[..], &v[..]));
+                aof_encode_xadd(buf, key, *id, fields);
+            }
+            // Also recreates a stream left empty by XDEL / XTRIM.
+            aof_encode_key_id(buf, AOF_OP_XSETID, key, stream.last_id);
+            for (name, group) in &stream.groups {
+                aof_encode_xgroup_create(buf, key, name, group.last_delivered);
+                for (id, p) in &group.pending {
+                    let d = Delivery {
+                        consumer: p.consumer.clone(),
+                        at_ms: p.delivered_at_ms,
+                        last_id: StreamId::MIN,
+                        deliveries: p.deliveries,
+                        ids: vec![*id],
+                    };
+                    aof_encode_xclaim(buf, key, name, &d);
+                }
+            }
         }
     }
+    if let Some(t) = entry.expires_at_ms {
+        aof_encode_expire_at(buf, key, t);
+    }
+}
 
-    put_entry_with_lru(&mut state, key_str.clone(), entry);
-    aof_write_zadd(&key_str, score, &member_str);
+// Replaces the AOF with the minimal log for the current state. Writes keep flowing
+// during the rewrite; they are appended to the old file and buffered, and the buffer is
+// copied onto the new file right before it is renamed into place.
+fn aof_rewrite(db: &Instance) -> Result<(), CacheError> {
+    if db.aof.lock().unwrap().file.is_none() {
+        return Err(CacheError::invalid("AOF is not enabled"));
+    }
+    if db.aof_rewrite_in_progress.swap(true, Ordering::AcqRel) {
+        return Err(CacheError::invalid("AOF rewrite already in progress"));
+    }
+    aof_rewrite_claimed(db)
 }
 
-#[no_mangle]
-pub extern "C" fn cache_zrange(key: *const c_char, start: i32, end: i32, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
+// Runs a rewrite for a caller that has already set db.aof_rewrite_in_progress.
+fn aof_rewrite_claimed(db: &Instance) -> Result<(), CacheError> {
+    let res = aof_rewrite_inner(db);
+    db.aof_rewrite_in_progress.store(false, Ordering::Release);
+    Ok(res?)
+}
 
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    }
+fn aof_rewrite_inner(db: &Instance) -> std::io::Result<()> {
+    // Snapshot under the shard locks so no mutation falls between the copy and the buffer.
+    let (path, entries) = {
+        let shards = cache_read_all(db);
+        let mut aof = db.aof.lock().unwrap();
+        if aof.file.is_none() {
+            return Err(std::io::Error::other("AOF is not enabled"));
+        }
+        aof.rewrite_buf = Some(Vec::new());
+        // Oldest first, so replay restores each shard's LRU order.
+        let entries: Vec<(Vec<u8>, Entry)> =
+            shards.iter().flat_map(|s| s.map.iter().rev()).map(|(k, v)| (k.clone(), v.clone())).collect();
+        (aof.path.clone(), entries)
+    };
 
-    if let Some(entry) = state.map.get(&key_str) {
-        if let Value::SortedSet(ss) = &entry.value {
-        let mut entries: Vec<(&String, &f64)> = ss.iter().collect();
-        // Sort by score (asc)
-        entries.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal));
-        
-        let len = entries.len() as i32;
-        let mut low = start;
-        let mut high = end;
-        if low < 0 { low += len; }
-        if high < 0 { high += len; }
-        if low < 0 { low = 0; }
-        if high >= len { high = len - 1; }
-        
-        let mut flat = Vec::new();
-        if low <= high {
-            let count = (high - low + 1) as u32;
-            flat.extend_from_slice(&count.to_le_bytes());
-            for i in low..=high {
-                let (member, _) = entries[i as usize];
-                let b = member.as_bytes();
-                flat.extend_from_slice(&(b.len() as u32).to_le_bytes());
-                flat.extend_from_slice(b);
-            }
-        } else {
-             flat.extend_from_slice(&(0u32).to_le_bytes());
+    let tmp_path = format!("{}.rewrite", path);
+    let result = aof_write_rewrite_file(&tmp_path, &entries).and_then(|mut tmp| {
+        let mut aof = db.aof.lock().unwrap();
+        // The writer thread drops its handle on the old file before it clears writer_busy.
+        while aof.writer_busy {
+            aof = db.aof_durable_signal.wait(aof).unwrap();
         }
-        return prepare_return(flat, out_len);
+        // Bail out if the AOF was disabled or re-pointed while we were writing.
+        let pending = match aof.rewrite_buf.take() {
+            Some(p) if aof.file.is_some() && aof.path == path => p,
+            _ => return Err(std::io::Error::other("AOF changed during rewrite")),
+        };
+        tmp.write_all(&pending)?;
+        tmp.sync_all()?;
+        drop(tmp);
+        // Windows cannot rename over a file that is still open, so the old handle is closed
+        // first (and reopened if the rename fails; if that fails too, the AOF ends up disabled).
+        aof.file = None;
+        let reopen = || std::fs::OpenOptions::new().append(true).open(&path);
+        if let Err(e) = std::fs::rename(&tmp_path, &path) {
+            aof.file = reopen().ok().map(Arc::new);
+            aof.failed |= aof.file.is_none();
+            return Err(e);
+        }
+        let file = reopen().inspect_err(|_| aof.failed = true)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        aof.file = Some(Arc::new(file));
+        aof.base_size = size;
+        aof.size = size;
+        aof.rewrite_failed_at = None;
+        // Everything still pending is already part of the new file.
+        aof.pending.clear();
+        aof.written = aof.appended;
+        aof.synced = aof.appended;
+        db.aof_durable_signal.notify_all();
+        Ok(())
+    });
+
+    if result.is_err() {
+        let mut aof = db.aof.lock().unwrap();
+        aof.rewrite_buf = None;
+        aof.rewrite_failed_at = Some(std::time::Instant::now());
+        drop(aof);
+        let _ = std::fs::remove_file(&tmp_path);
     }
+    result
+}
+
+fn aof_write_rewrite_file(tmp_path: &str, entries: &[(Vec<u8>, Entry)]) -> std::io::Result<std::fs::File> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(tmp_path)?);
+    let mut buf = Vec::new();
+    for (key, entry) in entries {
+        if is_expired(entry) {
+            continue;
+        }
+        buf.clear();
+        aof_encode_entry(&mut buf, key.as_slice(), entry);
+        out.write_all(&buf)?;
     }
+    out.into_inner().map_err(|e| e.into_error())
+}
+
+// --- Phase3: Pub/Sub + Keyspace Notifications ---
 
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
+#[derive(Clone)]
+struct NotifyEvent {
+    kind: u8,
+    key: String,
+    at_ms: u64,
 }
 
-#[no_mangle]
-pub extern "C" fn cache_free(ptr: *mut c_uchar, len: usize) {
-    if ptr.is_null() || len == 0 {
-        return;
-    }
-    unsafe {
-        // Correctly free vector assuming cap == len
-        let _ = Vec::from_raw_parts(ptr, len, len);
-    }
+const NOTIFY_KIND_EXPIRED: u8 = 1;
+const NOTIFY_KIND_EVICTED: u8 = 2;
+
+fn notify_expired(db: &Instance, key: &str) {
+    let mut q = db.notify_queue.lock().unwrap();
+    q.push_back(NotifyEvent {
+        kind: NOTIFY_KIND_EXPIRED,
+        key: key.to_string(),
+        at_ms: now_ms(),
+    });
 }
 
-// --- Phase2: LRU + TTL + AOF + Binary-Safe Keys ---
+fn notify_evicted(db: &Instance, key: &str) {
+    let mut q = db.notify_queue.lock().unwrap();
+    q.push_back(NotifyEvent {
+        kind: NOTIFY_KIND_EVICTED,
+        key: key.to_string(),
+        at_ms: now_ms(),
+    });
+}
 
-#[no_mangle]
-pub extern "C" fn cache_set_max_items(max_items: usize) {
-    let max_items = max_items.max(1);
-    MAX_ITEMS.store(max_items, Ordering::Relaxed);
-    let mut state = CACHE.write().unwrap();
-    state.map.resize(NonZeroUsize::new(max_items).unwrap());
-    state.map_b.resize(NonZeroUsize::new(max_items).unwrap());
+#[derive(Clone)]
+struct PubMessage {
+    channel: String,
+    payload: Vec<u8>,
 }
 
-#[no_mangle]
-pub extern "C" fn cache_get_max_items() -> usize {
-    MAX_ITEMS.load(Ordering::Relaxed)
+struct PubSubState {
+    next_id: u64,
+    subs: HashMap<u64, String>,
+    channels: HashMap<String, Vec<u64>>,
+    queues: HashMap<u64, VecDeque<PubMessage>>,
 }
 
-#[no_mangle]
-pub extern "C" fn cache_len() -> usize {
-    let state = CACHE.read().unwrap();
-    state.map.len() + state.map_b.len()
+fn read_exact_u8(r: &mut impl Read) -> Option<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b).ok()?;
+    Some(b[0])
 }
 
-#[no_mangle]
-pub extern "C" fn cache_set_with_ttl(key: *const c_char, value: *const c_uchar, len: usize, ttl_ms: u64) {
-    let key_str = unsafe { to_string(key) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    let expires_at = now_ms().saturating_add(ttl_ms);
+fn read_exact_u32(r: &mut impl Read) -> Option<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b).ok()?;
+    Some(u32::from_le_bytes(b))
+}
+
+fn read_exact_u64(r: &mut impl Read) -> Option<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b).ok()?;
+    Some(u64::from_le_bytes(b))
+}
+
+fn read_exact_f64(r: &mut impl Read) -> Option<f64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b).ok()?;
+    Some(f64::from_le_bytes(b))
+}
+
+// [Count u32] { [Id u64] }
+// A stream id: [Ms u64] [Seq u64], or a single u64 read as `<id>-0` in records of older files.
+fn read_exact_stream_id(r: &mut impl Read, u64_id: bool) -> Option<StreamId> {
+    let ms = read_exact_u64(r)?;
+    let seq = if u64_id { 0 } else { read_exact_u64(r)? };
+    Some(StreamId::new(ms, seq))
+}
+
+fn read_exact_stream_ids(r: &mut impl Read, u64_ids: bool) -> Option<Vec<StreamId>> {
+    let count = read_exact_u32(r)?;
+    (0..count).map(|_| read_exact_stream_id(r, u64_ids)).collect()
+}
+
+// [Count u32] { [NameLen u32] [Name] [ValueLen u32] [Value] }
+fn read_exact_stream_fields(r: &mut impl Read) -> Option<StreamFields> {
+    let count = read_exact_u32(r)?;
+    let mut fields = Vec::new();
+    for _ in 0..count {
+        let nlen = read_exact_u32(r)? as usize;
+        let name = read_exact_vec(r, nlen)?;
+        let vlen = read_exact_u32(r)? as usize;
+        fields.push((name, read_exact_vec(r, vlen)?));
+    }
+    Some(fields)
+}
+
+fn read_exact_vec(r: &mut impl Read, len: usize) -> Option<Vec<u8>> {
+    let mut b = vec![0u8; len];
+    r.read_exact(&mut b).ok()?;
+    Some(b)
+}
+
+fn read_exact_string(r: &mut impl Read, len: usize) -> Option<String> {
+    let bytes = read_exact_vec(r, len)?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
 
-    let mut state = CACHE.write().unwrap();
+fn apply_set_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, val: Vec<u8>) {
     put_entry_with_lru(
-        &mut state,
-        key_str.clone(),
-        Entry {
-            value: Value::Bytes(Arc::new(val_vec.clone())),
-            expires_at_ms: Some(expires_at),
-        },
+        db,
+        state,
+        key,
+        Entry::new(Value::Bytes(Arc::new(val)), None),
     );
+}
 
-    aof_write_set(&key_str, &val_vec);
-    aof_write_expire(&key_str, ttl_ms);
+fn apply_remove_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K) -> bool {
+    pop_entry(db, state, key).is_some()
 }
 
-#[no_mangle]
-pub extern "C" fn cache_expire(key: *const c_char, ttl_ms: u64) -> i32 {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
-    let ok = apply_expire_internal(&mut state, &key_str, ttl_ms);
-    if ok {
-        aof_write_expire(&key_str, ttl_ms);
-        1
-    } else {
-        0
+fn apply_clear_internal(db: &Instance, all: &mut AllShardsGuard) {
+    for state in all.0.iter_mut() {
+        state.map.clear();
+        state.volatile.clear();
+        state.window.clear();
     }
+    db.cache.numeric_indexes.write().unwrap().clear();
+    db.cache.has_indexes.store(false, Ordering::Release);
+    db.cache.used_memory.store(0, Ordering::Relaxed);
+    db.cache.len.store(0, Ordering::Relaxed);
 }
 
-#[no_mangle]
-pub extern "C" fn cache_ttl(key: *const c_char) -> i64 {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
+fn apply_expire_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, ttl_ms: u64) -> bool {
+    apply_expire_at_internal(db, state, key, now_ms().saturating_add(ttl_ms))
+}
 
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        return -2;
+fn apply_expire_at_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, expires_at_ms: u64) -> bool {
+    if maybe_remove_if_expired(db, state, key) {
+        return false;
     }
+    let Some(mut entry) = pop_entry(db, state, key) else { return false; };
+    entry.expires_at_ms = Some(expires_at_ms);
+    put_entry_with_lru(db, state, key, entry);
+    true
+}
 
-    let Some(entry) = state.map.get(&key_str) else { return -2; };
-    match entry.expires_at_ms {
-        None => -1,
-        Some(t) => {
-            let now = now_ms();
-            if t <= now { 0 } else { (t - now) as i64 }
-        }
+fn same_type(a: &Value, b: &Value) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+// Pops the live entry so it can be mutated and re-inserted (keeps indexes consistent), or
+// starts one holding `empty`. A value of another type is left untouched: WrongType.
+// During replay the entry is taken even if expired, keeping its expiry for the final purge.
+fn take_typed_entry<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, empty: Value) -> Result<Entry, CacheError> {
+    maybe_remove_if_expired(db, state, key);
+    match state.map.peek(key.as_bytes()) {
+        Some(e) if !same_type(&e.value, &empty) => Err(CacheError::WrongType),
+        Some(_) => Ok(pop_entry(db, state, key).expect("entry was just peeked")),
+        None => Ok(Entry::new(empty, None)),
     }
 }
 
-#[no_mangle]
-pub extern "C" fn cache_aof_enable(path: *const c_char) -> i32 {
-    let path_str = unsafe { to_string(path) };
-    if path_str.is_empty() {
-        return 0;
+// Readies `key` for a data-structure write of `empty`'s type: an expired key is dropped first
+// (so its removal is logged ahead of the new records), then room is checked. A live value of
+// another type fails with WrongType, or is removed (and logged) under the overwrite option.
+fn prepare_typed_write<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, empty: Value) -> Result<(), CacheError> {
+    maybe_remove_if_expired(db, state, key);
+    let mismatch = state.map.peek(key.as_bytes()).is_some_and(|e| !same_type(&e.value, &empty));
+    if mismatch && !db.overwrite_types.load(Ordering::Relaxed) {
+        return Err(CacheError::WrongType);
     }
-    match std::fs::OpenOptions::new().create(true).append(true).open(&path_str) {
-        Ok(f) => {
-            let mut guard = AOF_FILE.lock().unwrap();
-            *guard = Some(f);
-            1
+    check_oom(db, state, key)?;
+    if mismatch {
+        aof_write_remove(db, key);
+        apply_remove_internal(db, state, key);
+    }
+    Ok(())
+}
+
+// Returns true if the field was newly created.
+fn apply_hset_internal<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    field: Vec<u8>,
+    val: Vec<u8>,
+) -> Result<bool, CacheError> {
+    let mut entry = take_typed_entry(db, state, key, Value::Hash(HashMap::new()))?;
+    let Value::Hash(hmap) = &mut entry.value else { unreachable!("type checked on take") };
+
+    let added = hash_field_mem(&field, &val);
+    let val_len = val.len();
+    let created = match hmap.insert(field, val) {
+        Some(old) => {
+            entry.mem = entry.mem - old.len() + val_len;
+            false
         }
-        Err(_) => 0,
+        None => {
+            entry.mem += added;
+            true
+        }
+    };
+
+    put_entry_with_lru(db, state, key, entry);
+    Ok(created)
+}
+
+// Returns true if the field was removed.
+fn apply_hdel_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, field: &[u8]) -> Result<bool, CacheError> {
+    let removed = update_collection(db, state, key, |hmap: &mut HashData| match hmap.remove(field) {
+        Some(val) => (true, -(hash_field_mem(field, &val) as isize)),
+        None => (false, 0),
+    })?;
+    Ok(removed.unwrap_or(false))
+}
+
+// Pushes onto the head (`left`) or tail. Returns the list length after the push.
+fn apply_push_internal<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    left: bool,
+    val: Vec<u8>,
+) -> Result<usize, CacheError> {
+    let mut entry = take_typed_entry(db, state, key, Value::List(VecDeque::new()))?;
+    let Value::List(list) = &mut entry.value else { unreachable!("type checked on take") };
+
+    entry.mem += item_mem(&val);
+    if left {
+        list.push_front(val);
+    } else {
+        list.push_back(val);
     }
+    let len = list.len();
+
+    put_entry_with_lru(db, state, key, entry);
+    Ok(len)
 }
 
-#[no_mangle]
-pub extern "C" fn cache_aof_disable() {
-    let mut guard = AOF_FILE.lock().unwrap();
-    *guard = None;
+// Collection values that `update_collection` edits in place.
+trait Collection {
+    fn of(value: &mut Value) -> Option<&mut Self>;
+    fn is_empty(&self) -> bool;
 }
 
-#[no_mangle]
-pub extern "C" fn cache_aof_load(path: *const c_char) -> i32 {
-    let path_str = unsafe { to_string(path) };
-    if path_str.is_empty() {
-        return 0;
+impl Collection for VecDeque<Vec<u8>> {
+    fn of(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
     }
-    let mut file = match std::fs::File::open(&path_str) {
-        Ok(f) => f,
-        Err(_) => return 0,
-    };
 
-    let mut state = CACHE.write().unwrap();
+    fn is_empty(&self) -> bool {
+        VecDeque::is_empty(self)
+    }
+}
 
-    loop {
-        let Some(op) = read_exact_u8(&mut file) else { break; };
-        match op {
-            AOF_OP_SET => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let vlen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let val = match read_exact_vec(&mut file, vlen) { Some(v) => v, None => break };
-                apply_set_internal(&mut state, key, val);
-            }
-            AOF_OP_SET_B => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_vec(&mut file, klen) { Some(v) => v, None => break };
-                let vlen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let val = match read_exact_vec(&mut file, vlen) { Some(v) => v, None => break };
-                apply_set_internal_b(&mut state, key, val);
-            }
-            AOF_OP_REMOVE => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                apply_remove_internal(&mut state, &key);
-            }
-            AOF_OP_REMOVE_B => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_vec(&mut file, klen) { Some(v) => v, None => break };
-                apply_remove_internal_b(&mut state, &key);
-            }
-            AOF_OP_CLEAR => {
-                apply_clear_internal(&mut state);
-            }
-            AOF_OP_EXPIRE => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let ttl_ms = match read_exact_u64(&mut file) { Some(v) => v, None => break };
-                let _ = apply_expire_internal(&mut state, &key, ttl_ms);
-            }
-            AOF_OP_HSET => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let flen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let field = match read_exact_string(&mut file, flen) { Some(v) => v, None => break };
-                let vlen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let val = match read_exact_vec(&mut file, vlen) { Some(v) => v, None => break };
-
-                let mut entry = state
-                    .map
-                    .pop(&key)
-                    .unwrap_or(Entry { value: Value::Hash(HashMap::new()), expires_at_ms: None });
-                match &mut entry.value {
-                    Value::Hash(hmap) => {
-                        hmap.insert(field, val);
-                    }
-                    _ => {
-                        let mut h = HashMap::new();
-                        h.insert(field, val);
-                        entry.value = Value::Hash(h);
-                    }
-                }
-                put_entry_with_lru(&mut state, key, entry);
-            }
-            AOF_OP_LPUSH => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let vlen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let val = match read_exact_vec(&mut file, vlen) { Some(v) => v, None => break };
-
-                let mut entry = state
-                    .map
-                    .pop(&key)
-                    .unwrap_or(Entry { value: Value::List(Vec::new()), expires_at_ms: None });
-                match &mut entry.value {
-                    Value::List(list) => list.insert(0, val),
-                    _ => entry.value = Value::List(vec![val]),
-                }
-                put_entry_with_lru(&mut state, key, entry);
-            }
-            AOF_OP_SADD => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let vlen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let val = match read_exact_vec(&mut file, vlen) { Some(v) => v, None => break };
-
-                let mut entry = state
-                    .map
-                    .pop(&key)
-                    .unwrap_or(Entry { value: Value::Set(HashSet::new()), expires_at_ms: None });
-                match &mut entry.value {
-                    Value::Set(set) => {
-                        let _ = set.insert(val);
-                    }
-                    _ => {
-                        let mut s = HashSet::new();
-                        let _ = s.insert(val);
-                        entry.value = Value::Set(s);
-                    }
-                }
-                put_entry_with_lru(&mut state, key, entry);
-            }
-            AOF_OP_ZADD => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let score = match read_exact_f64(&mut file) { Some(v) => v, None => break };
-                let mlen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let member = match read_exact_string(&mut file, mlen) { Some(v) => v, None => break };
-
-                let mut entry = state
-                    .map
-                    .pop(&key)
-                    .unwrap_or(Entry { value: Value::SortedSet(HashMap::new()), expires_at_ms: None });
-                match &mut entry.value {
-                    Value::SortedSet(ss) => {
-                        ss.insert(member, score);
-                    }
-                    _ => {
-                        let mut ss = HashMap::new();
-                        ss.insert(member, score);
-                        entry.value = Value::SortedSet(ss);
-                    }
-                }
-                put_entry_with_lru(&mut state, key, entry);
-            }
-            AOF_OP_XADD => {
-                let klen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let key = match read_exact_string(&mut file, klen) { Some(v) => v, None => break };
-                let id = match read_exact_u64(&mut file) { Some(v) => v, None => break };
-                let plen = match read_exact_u32(&mut file) { Some(v) => v as usize, None => break };
-                let payload = match read_exact_vec(&mut file, plen) { Some(v) => v, None => break };
-
-                let mut entry = state
-                    .map
-                    .pop(&key)
-                    .unwrap_or(Entry { value: Value::Stream(StreamData { entries: Vec::new() }), expires_at_ms: None });
-                match &mut entry.value {
-                    Value::Stream(stream) => {
-                        stream.entries.push(StreamEntry { id, payload });
-                    }
-                    _ => {
-                        entry.value = Value::Stream(StreamData { entries: vec![StreamEntry { id, payload }] });
-                    }
-                }
-                put_entry_with_lru(&mut state, key, entry);
-            }
-            _ => break,
+impl Collection for HashData {
+    fn of(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::Hash(hmap) => Some(hmap),
+            _ => None,
         }
     }
 
-    1
+    fn is_empty(&self) -> bool {
+        HashMap::is_empty(self)
+    }
 }
 
-// Binary keys (byte-for-byte): stored separately to avoid key encoding overhead.
+impl Collection for HashSet<Vec<u8>> {
+    fn of(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::Set(set) => Some(set),
+            _ => None,
+        }
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_set_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) {
-    let key_vec = unsafe { to_bytes(key, key_len) };
-    let val_vec = unsafe { to_bytes(value, len) };
-    // Write AOF without holding the cache lock.
-    aof_write_set_b(&key_vec, &val_vec);
-    let mut state = CACHE.write().unwrap();
-    apply_set_internal_b(&mut state, key_vec, val_vec);
+    fn is_empty(&self) -> bool {
+        HashSet::is_empty(self)
+    }
 }
 
-// --- Phase4: JSON Path Support (basic) ---
+// Streams stay when their last entry goes, as in Redis (their groups live on).
+impl Collection for StreamData {
+    fn of(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::Stream(stream) => Some(stream),
+            _ => None,
+        }
+    }
 
-#[derive(Debug)]
-enum JsonPathToken {
-    Field(String),
-    Index(usize),
+    fn is_empty(&self) -> bool {
+        false
+    }
 }
 
-fn parse_json_path(path: &str) -> Option<Vec<JsonPathToken>> {
-    let p = path.trim();
-    if p.is_empty() {
-        return None;
+impl Collection for SortedSet {
+    fn of(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::SortedSet(ss) => Some(ss),
+            _ => None,
+        }
     }
 
-    let mut i = 0usize;
-    let chars: Vec<char> = p.chars().collect();
+    fn is_empty(&self) -> bool {
+        SortedSet::is_empty(self)
+    }
+}
 
-    if chars.get(0) == Some(&'$') {
-        i += 1;
+// Runs `f` on the collection at `key` in place; `f` returns its result and the change in the
+// value's memory estimate. A collection left empty is removed, as in Redis. Ok(None) if the
+// key is missing.
+fn update_collection<T: Collection, R, K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    f: impl FnOnce(&mut T) -> (R, isize),
+) -> Result<Option<R>, CacheError> {
+    maybe_remove_if_expired(db, state, key);
+    let Some(entry) = get_entry_mut(state, key) else { return Ok(None); };
+    let Some(coll) = T::of(&mut entry.value) else { return Err(CacheError::WrongType); };
+    let (res, delta) = f(coll);
+    let emptied = coll.is_empty();
+    entry.mem = entry.mem.wrapping_add_signed(delta);
+    if delta >= 0 {
+        db.cache.used_memory.fetch_add(delta as usize, Ordering::Relaxed);
+    } else {
+        db.cache.used_memory.fetch_sub(delta.unsigned_abs(), Ordering::Relaxed);
     }
+    if emptied {
+        pop_entry(db, state, key);
+    } else if delta > 0 {
+        evict_for_memory(db, state);
+    }
+    Ok(Some(res))
+}
 
-    let mut tokens = Vec::new();
+fn update_list<R, K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    f: impl FnOnce(&mut VecDeque<Vec<u8>>) -> (R, isize),
+) -> Result<Option<R>, CacheError> {
+    update_collection(db, state, key, f)
+}
 
-    while i < chars.len() {
-        match chars[i] {
-            '.' => {
-                i += 1;
-                let start = i;
-                while i < chars.len() {
-                    let c = chars[i];
-                    if c == '.' || c == '[' {
-                        break;
-                    }
-                    i += 1;
-                }
-                if i == start {
-                    return None;
-                }
-                let field: String = chars[start..i].iter().collect();
-                tokens.push(JsonPathToken::Field(field));
-            }
-            '[' => {
-                i += 1;
-                let start = i;
-                while i < chars.len() && chars[i].is_ascii_digit() {
-                    i += 1;
-                }
-                if i == start || i >= chars.len() || chars[i] != ']' {
-                    return None;
-                }
-                let num_str: String = chars[start..i].iter().collect();
-                i += 1; // consume ']'
-                let idx = num_str.parse::<usize>().ok()?;
-                tokens.push(JsonPathToken::Index(idx));
-            }
-            _ => {
-                // allow path starting with field name without leading dot
-                let start = i;
-                while i < chars.len() {
-                    let c = chars[i];
-                    if c == '.' || c == '[' {
-                        break;
-                    }
-                    i += 1;
-                }
-                if i == start {
-                    return None;
-                }
-                let field: String = chars[start..i].iter().collect();
-                tokens.push(JsonPathToken::Field(field));
-            }
+// Resolves a Redis-style list index (negative counts from the tail).
+fn list_index(index: i64, len: usize) -> Option<usize> {
+    let idx = if index < 0 { index + len as i64 } else { index };
+    (0..len as i64).contains(&idx).then_some(idx as usize)
+}
+
+// Pops up to `count` items from the head (`left`) or tail, in pop order.
+fn apply_pop_internal<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    left: bool,
+    count: usize,
+) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    update_list(db, state, key, |list| {
+        let n = count.min(list.len());
+        let popped: Vec<Vec<u8>> = if left {
+            list.drain(..n).collect()
+        } else {
+            (0..n).filter_map(|_| list.pop_back()).collect()
+        };
+        let freed: usize = popped.iter().map(|i| item_mem(i)).sum();
+        (popped, -(freed as isize))
+    })
+}
+
+fn apply_lset_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, index: i64, val: Vec<u8>) -> Result<(), CacheError> {
+    update_list(db, state, key, |list| match list_index(index, list.len()) {
+        Some(idx) => {
+            let delta = val.len() as isize - list[idx].len() as isize;
+            list[idx] = val;
+            (Ok(()), delta)
         }
-    }
+        None => (Err(CacheError::invalid("index out of range")), 0),
+    })?
+    .ok_or(CacheError::NotFound)?
+}
 
-    Some(tokens)
+// Inserts at an already resolved position (clamped to the list length). Returns the new length.
+fn apply_linsert_internal<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    pos: usize,
+    val: Vec<u8>,
+) -> Result<Option<usize>, CacheError> {
+    update_list(db, state, key, |list| {
+        let added = item_mem(&val) as isize;
+        list.insert(pos.min(list.len()), val);
+        (list.len(), added)
+    })
 }
 
-fn json_get_at_path<'a>(root: &'a JsonValue, path: &[JsonPathToken]) -> Option<&'a JsonValue> {
-    let mut cur = root;
-    for t in path {
-        match t {
-            JsonPathToken::Field(f) => {
-                cur = cur.get(f)?;
+// Removes `count` occurrences of `val` from the head (count > 0), the tail (count < 0) or
+// all of them (0). Returns how many were removed.
+fn apply_lrem_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, count: i64, val: &[u8]) -> Result<usize, CacheError> {
+    let removed = update_list(db, state, key, |list| {
+        let limit = if count == 0 { usize::MAX } else { count.unsigned_abs() as usize };
+        // From the tail: keep the first (matches - limit) occurrences.
+        let skip = if count < 0 { list.iter().filter(|i| i.as_slice() == val).count().saturating_sub(limit) } else { 0 };
+        let (mut seen, mut removed) = (0, 0);
+        list.retain(|i| {
+            if i.as_slice() != val {
+                return true;
             }
-            JsonPathToken::Index(idx) => {
-                cur = cur.get(*idx)?;
+            seen += 1;
+            if seen <= skip || removed >= limit {
+                return true;
             }
-        }
-    }
-    Some(cur)
+            removed += 1;
+            false
+        });
+        (removed, -((removed * item_mem(val)) as isize))
+    })?;
+    Ok(removed.unwrap_or(0))
 }
 
-fn json_set_at_path(root: &mut JsonValue, path: &[JsonPathToken], new_val: JsonValue) -> bool {
-    if path.is_empty() {
-        *root = new_val;
-        return true;
+fn apply_ltrim_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, start: i64, end: i64) -> Result<(), CacheError> {
+    update_list(db, state, key, |list| {
+        // Keep [from, to); an empty range removes the whole list.
+        let (from, to) = normalize_range(start, end, list.len()).map_or((0, 0), |(low, high)| (low, high + 1));
+        let mut freed: usize = list.drain(to..).map(|i| item_mem(&i)).sum();
+        freed += list.drain(..from).map(|i| item_mem(&i)).sum::<usize>();
+        ((), -(freed as isize))
+    })?;
+    Ok(())
+}
+
+// Returns true if the member was newly added.
+fn apply_sadd_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, val: Vec<u8>) -> Result<bool, CacheError> {
+    let mut entry = take_typed_entry(db, state, key, Value::Set(HashSet::new()))?;
+    let Value::Set(set) = &mut entry.value else { unreachable!("type checked on take") };
+
+    let added = item_mem(&val);
+    let inserted = set.insert(val);
+    if inserted {
+        entry.mem += added;
     }
 
-    let mut cur = root;
-    for (pos, t) in path.iter().enumerate() {
-        let last = pos == path.len() - 1;
-        match t {
-            JsonPathToken::Field(f) => {
-                if last {
-                    if !cur.is_object() {
-                        *cur = JsonValue::Object(Default::default());
-                    }
-                    if let Some(obj) = cur.as_object_mut() {
-                        obj.insert(f.clone(), new_val);
-                        return true;
-                    }
-                    return false;
-                }
+    put_entry_with_lru(db, state, key, entry);
+    Ok(inserted)
+}
 
-                if !cur.is_object() {
-                    *cur = JsonValue::Object(Default::default());
-                }
-                let obj = cur.as_object_mut().unwrap();
-                cur = obj.entry(f.clone()).or_insert(JsonValue::Object(Default::default()));
-            }
-            JsonPathToken::Index(idx) => {
-                if !cur.is_array() {
-                    *cur = JsonValue::Array(Vec::new());
-                }
-                let arr = cur.as_array_mut().unwrap();
-                while arr.len() <= *idx {
-                    arr.push(JsonValue::Null);
-                }
-                if last {
-                    arr[*idx] = new_val;
-                    return true;
-                }
-                cur = &mut arr[*idx];
-            }
-        }
+// Returns true if the member was removed. A set left empty is removed.
+fn apply_srem_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, member: &[u8]) -> Result<bool, CacheError> {
+    let removed = update_collection(db, state, key, |set: &mut HashSet<Vec<u8>>| {
+        if set.remove(member) { (true, -(item_mem(member) as isize)) } else { (false, 0) }
+    })?;
+    Ok(removed.unwrap_or(false))
+}
+
+// Returns true if the member was newly added.
+fn apply_zadd_internal<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    score: f64,
+    member: Vec<u8>,
+) -> Result<bool, CacheError> {
+    let mut entry = take_typed_entry(db, state, key, Value::SortedSet(SortedSet::default()))?;
+    let Value::SortedSet(ss) = &mut entry.value else { unreachable!("type checked on take") };
+
+    let added = zset_member_mem(&member);
+    let created = ss.insert(member, score).is_none();
+    if created {
+        entry.mem += added;
     }
-    false
+
+    put_entry_with_lru(db, state, key, entry);
+    Ok(created)
 }
 
-#[no_mangle]
-pub extern "C" fn cache_json_get(key: *const c_char, path: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let path_str = unsafe { to_string(path) };
-    let Some(tokens) = parse_json_path(&path_str) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+// Returns true if the member was removed.
+fn apply_zrem_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, member: &[u8]) -> Result<bool, CacheError> {
+    let removed = update_collection(db, state, key, |ss: &mut SortedSet| match ss.remove(member) {
+        Some(_) => (true, -(zset_member_mem(member) as isize)),
+        None => (false, 0),
+    })?;
+    Ok(removed.unwrap_or(false))
+}
 
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+fn apply_xadd_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, id: StreamId, fields: StreamFields) -> Result<(), CacheError> {
+    let mut entry = take_typed_entry(db, state, key, Value::Stream(StreamData::default()))?;
+    let Value::Stream(stream) = &mut entry.value else { unreachable!("type checked on take") };
+
+    if let Some(freed) = stream.remove(id) {
+        entry.mem -= freed;
     }
+    entry.mem += stream.insert(id, fields);
 
-    let Some(entry) = state.map.get(&key_str) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+    put_entry_with_lru(db, state, key, entry);
+    Ok(())
+}
 
-    let Some(json) = try_parse_json_from_entry(entry) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+// Removes every entry below `below`. Returns how many went.
+fn apply_xtrim_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, below: StreamId) -> Result<usize, CacheError> {
+    let trimmed = update_collection(db, state, key, |stream: &mut StreamData| {
+        let (trimmed, freed) = stream.trim(below);
+        (trimmed, -(freed as isize))
+    })?;
+    Ok(trimmed.unwrap_or(0))
+}
 
-    let Some(v) = json_get_at_path(&json, &tokens) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+// Returns how many of `ids` were entries.
+fn apply_xdel_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, ids: &[StreamId]) -> Result<usize, CacheError> {
+    let deleted = update_collection(db, state, key, |stream: &mut StreamData| {
+        let mut deleted = 0;
+        let mut freed = 0;
+        for id in ids {
+            if let Some(mem) = stream.remove(*id) {
+                deleted += 1;
+                freed += mem;
+            }
+        }
+        (deleted, -(freed as isize))
+    })?;
+    Ok(deleted.unwrap_or(0))
+}
 
-    let bytes = match serde_json::to_vec(v) {
-        Ok(b) => b,
-        Err(_) => {
-            unsafe { *out_len = 0 };
-            return std::ptr::null_mut();
+// Raises the stream's last id to at least `id`, creating an empty stream if the key is missing.
+fn apply_xsetid_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, id: StreamId) -> Result<(), CacheError> {
+    maybe_remove_if_expired(db, state, key);
+    if !state.map.contains(key.as_bytes()) {
+        put_entry_with_lru(db, state, key, Entry::new(Value::Stream(StreamData::default()), None));
+    }
+    update_collection(db, state, key, |stream: &mut StreamData| {
+        stream.last_id = stream.last_id.max(id);
+        ((), 0)
+    })?;
+    Ok(())
+}
+
+// Adds group `group` delivering entries after `last_id`. Returns false if it already exists. A
+// missing stream fails with NoGroup unless `mkstream`, which creates an empty one.
+fn apply_xgroup_create_internal<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    group: &str,
+    last_id: StreamId,
+    mkstream: bool,
+) -> Result<bool, CacheError> {
+    maybe_remove_if_expired(db, state, key);
+    if !state.map.contains(key.as_bytes()) {
+        if !mkstream {
+            return Err(CacheError::invalid(
+                "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.",
+            ));
         }
-    };
+        put_entry_with_lru(db, state, key, Entry::new(Value::Stream(StreamData::default()), None));
+    }
+    let created = update_collection(db, state, key, |stream: &mut StreamData| {
+        if stream.groups.contains_key(group) {
+            return (false, 0);
+        }
+        stream.groups.insert(group.to_string(), ConsumerGroup { last_delivered: last_id, pending: BTreeMap::new() });
+        (true, stream_group_mem(group) as isize)
+    })?;
+    Ok(created.unwrap_or(false))
+}
 
-    prepare_return(bytes, out_len)
+// Returns true if the group existed.
+fn apply_xgroup_destroy_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, group: &str) -> Result<bool, CacheError> {
+    let removed = update_collection(db, state, key, |stream: &mut StreamData| match stream.groups.remove(group) {
+        Some(g) => {
+            let freed = stream_group_mem(group) + g.pending.values().map(pending_entry_mem).sum::<usize>();
+            (true, -(freed as isize))
+        }
+        None => (false, 0),
+    })?;
+    Ok(removed.unwrap_or(false))
 }
 
-#[no_mangle]
-pub extern "C" fn cache_json_set(key: *const c_char, path: *const c_char, json_value: *const c_uchar, len: usize) -> i32 {
-    let key_str = unsafe { to_string(key) };
-    let path_str = unsafe { to_string(path) };
-    let Some(tokens) = parse_json_path(&path_str) else {
-        return 0;
-    };
+// One hand-off of entries to a consumer (XREADGROUP, XCLAIM), as logged to the AOF: `ids` now
+// belong to `consumer`, delivered at `at_ms`, their delivery counts raised by `deliveries`
+// (a new pending entry starts at 0), and the group's last delivered id moves up to `last_id`.
+struct Delivery {
+    consumer: String,
+    at_ms: u64,
+    last_id: StreamId,
+    deliveries: u64,
+    ids: Vec<StreamId>,
+}
 
-    let new_bytes = unsafe { to_bytes(json_value, len) };
-    let new_val: JsonValue = match serde_json::from_slice(&new_bytes) {
-        Ok(v) => v,
-        Err(_) => return 0,
-    };
+// Returns false if the group is missing.
+fn apply_xclaim_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, group: &str, d: &Delivery) -> Result<bool, CacheError> {
+    let found = update_collection(db, state, key, |stream: &mut StreamData| {
+        let Some(g) = stream.groups.get_mut(group) else { return (false, 0); };
+        g.last_delivered = g.last_delivered.max(d.last_id);
+        let mut delta = 0;
+        for id in &d.ids {
+            let p = g.pending.entry(*id).or_insert_with(|| {
+                let p = PendingEntry { consumer: String::new(), delivered_at_ms: 0, deliveries: 0 };
+                delta += pending_entry_mem(&p) as isize;
+                p
+            });
+            delta += d.consumer.len() as isize - p.consumer.len() as isize;
+            p.consumer.clone_from(&d.consumer);
+            p.delivered_at_ms = d.at_ms;
+            p.deliveries += d.deliveries;
+        }
+        (true, delta)
+    })?;
+    Ok(found.unwrap_or(false))
+}
+
+// Drops acknowledged ids from the group's PEL. Returns how many were pending.
+fn apply_xack_internal<K: CacheKey + ?Sized>(db: &Instance, state: &mut Shard, key: &K, group: &str, ids: &[StreamId]) -> Result<usize, CacheError> {
+    let acked = update_collection(db, state, key, |stream: &mut StreamData| {
+        let Some(g) = stream.groups.get_mut(group) else { return (0, 0); };
+        let mut acked = 0;
+        let mut freed = 0;
+        for id in ids {
+            if let Some(p) = g.pending.remove(id) {
+                acked += 1;
+                freed += pending_entry_mem(&p);
+            }
+        }
+        (acked, -(freed as isize))
+    })?;
+    Ok(acked.unwrap_or(0))
+}
 
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        // create fresh
+// Resolves Redis-style inclusive (start, end) indexes, negative counting from the tail.
+fn normalize_range(start: i64, end: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let mut low = start;
+    let mut high = end;
+    if low < 0 { low += len; }
+    if high < 0 { high += len; }
+    if low < 0 { low = 0; }
+    if high >= len { high = len - 1; }
+    if low > high {
+        return None;
     }
+    Some((low as usize, high as usize))
+}
 
-    let mut entry = state
-        .map
-        .pop(&key_str)
-        .unwrap_or(Entry { value: Value::Bytes(Arc::new(b"{}".to_vec())), expires_at_ms: None });
+// Serializes optional items as [Count u32] { [Len u32] [Bytes] }, where a Len of u32::MAX
+// (and no bytes) marks a missing one.
+fn pack_optional_items(items: &[Option<Vec<u8>>]) -> Vec<u8> {
+    let mut flat = Vec::new();
+    flat.extend_from_slice(&(items.len() as u32).to_le_bytes());
+    for item in items {
+        match item {
+            Some(item) => {
+                flat.extend_from_slice(&(item.len() as u32).to_le_bytes());
+                flat.extend_from_slice(item);
+            }
+            None => flat.extend_from_slice(&u32::MAX.to_le_bytes()),
+        }
+    }
+    flat
+}
 
-    let mut json = try_parse_json_from_entry(&entry).unwrap_or(JsonValue::Object(Default::default()));
-    let ok = json_set_at_path(&mut json, &tokens, new_val);
-    if !ok {
-        // restore old entry
-        put_entry_with_lru(&mut state, key_str, entry);
-        return 0;
+// Serializes items as: [Count u32][Len u32][Bytes]...
+fn pack_items<'a>(items: impl ExactSizeIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut flat = Vec::new();
+    flat.extend_from_slice(&(items.len() as u32).to_le_bytes());
+    for item in items {
+        flat.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        flat.extend_from_slice(item);
     }
+    flat
+}
 
-    let updated_bytes = match serde_json::to_vec(&json) {
-        Ok(b) => b,
-        Err(_) => {
-            put_entry_with_lru(&mut state, key_str, entry);
-            return 0;
-        }
+// Reverses pack_items; a truncated or oversized buffer is an InvalidArgument.
+fn unpack_items(buf: &[u8]) -> Result<Vec<Vec<u8>>, CacheError> {
+    let malformed = || CacheError::invalid("malformed item buffer");
+    let read_u32 = |at: usize| -> Option<usize> {
+        Some(u32::from_le_bytes(buf.get(at..at + 4)?.try_into().ok()?) as usize)
     };
+    let count = read_u32(0).ok_or_else(malformed)?;
+    let mut items = Vec::with_capacity(count.min(buf.len() / 4));
+    let mut at = 4;
+    for _ in 0..count {
+        let len = read_u32(at).ok_or_else(malformed)?;
+        at += 4;
+        let item = buf.get(at..at.checked_add(len).ok_or_else(malformed)?).ok_or_else(malformed)?;
+        items.push(item.to_vec());
+        at += len;
+    }
+    if at != buf.len() {
+        return Err(malformed());
+    }
+    Ok(items)
+}
 
-    entry.value = Value::Bytes(Arc::new(updated_bytes.clone()));
-    put_entry_with_lru(&mut state, key_str.clone(), entry);
-
-    // AOF logs as SET of the full updated JSON document.
-    aof_write_set(&key_str, &updated_bytes);
-    1
+// Prepares a vector for FFI return: shrinks to fit (cap=len), forgets it, returns ptr/len
+fn prepare_return(mut vec: Vec<u8>, out_len: *mut usize) -> *mut c_uchar {
+    vec.shrink_to_fit();
+    let len = vec.len();
+    unsafe { *out_len = len };
+    if len == 0 {
+        return std::ptr::null_mut();
+    }
+    let ptr = vec.as_mut_ptr();
+    std::mem::forget(vec);
+    ptr
 }
 
-// --- Phase4: Secondary indexing + Find ---
+// --- Common ---
 
 #[no_mangle]
-pub extern "C" fn cache_index_create_numeric(field: *const c_char) -> i32 {
-    let field_str = unsafe { to_string(field) };
-    if field_str.is_empty() {
-        return 0;
-    }
+pub extern "C" fn cache_init() {
+    Lazy::force(&DEFAULT_INSTANCE);
+}
 
-    let mut state = CACHE.write().unwrap();
-    state.numeric_indexes.entry(field_str.clone()).or_insert_with(BTreeMap::new);
+// --- Instances ---
+//
+// Every export below also exists as `cache_instance_<name>`, taking a handle from
+// cache_instance_create first; the plain form works on the default instance. A null
+// handle gets the export's "missing" result (InvalidHandle, -1, null, ...) and records
+// InvalidHandle as the last error.
+
+/// Settings for `cache_instance_create`; a zeroed struct (or a null pointer) gives the defaults.
+#[repr(C)]
+pub struct CacheConfig {
+    /// Item limit; 0 = the default (100 000).
+    pub max_items: usize,
+    /// Memory limit in bytes; 0 = unlimited.
+    pub max_memory_bytes: u64,
+    /// Same values as `cache_set_eviction_policy`.
+    pub eviction_policy: i32,
+    /// AOF file, or null for none. An existing file is replayed before logging resumes.
+    pub aof_path: *const c_char,
+}
 
-    // rebuild this index from current state
-    let mut idx_map = BTreeMap::<i64, HashSet<String>>::new();
-    for (k, v) in state.map.iter() {
-        if let Some(json) = try_parse_json_from_entry(v) {
-            if let Some(num) = extract_numeric_field(&json, &field_str) {
-                idx_map.entry(num).or_default().insert(k.clone());
+// Creates an empty instance with its own keyspace, limits, AOF, notifications and pub/sub.
+// Returns its handle, or null for an unknown eviction policy or an AOF that cannot be
+// loaded or opened (the reason is left in cache_last_error).
+#[no_mangle]
+pub extern "C" fn cache_instance_create(config: *const CacheConfig) -> *const Instance {
+    let db = Instance::new();
+    if let Some(config) = unsafe { config.as_ref() } {
+        let handle = Arc::as_ptr(&db);
+        if config.max_items > 0 {
+            cache_instance_set_max_items(handle, config.max_items);
+        }
+        cache_instance_set_max_memory_bytes(handle, config.max_memory_bytes);
+        if cache_instance_set_eviction_policy(handle, config.eviction_policy) != 1 {
+            return std::ptr::null();
+        }
+        if !config.aof_path.is_null() {
+            let path = unsafe { to_string(config.aof_path) };
+            let replayed = !std::path::Path::new(&path).exists() || cache_instance_aof_load(handle, config.aof_path) == 1;
+            if !replayed || cache_instance_aof_enable(handle, config.aof_path) != 1 {
+                return std::ptr::null();
             }
         }
     }
-    state.numeric_indexes.insert(field_str, idx_map);
-    1
+    Arc::into_raw(db)
 }
 
-fn parse_find_query(q: &str) -> Option<(String, String, String)> {
-    // very small grammar: <field> <op> <value>
-    // op: > >= < <= ==
-    let s = q.trim();
-    if s.is_empty() {
-        return None;
+// Destroys an instance: its AOF is flushed and closed and its background threads stop; the
+// memory is released once no RESP server started on it is left. The handle is invalid
+// afterwards. Returns 1, InvalidHandle for a null handle, or InvalidArgument for the default
+// instance.
+#[no_mangle]
+pub extern "C" fn cache_instance_destroy(db: *const Instance) -> i32 {
+    let Some(handle) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    if std::ptr::eq(handle, default_instance()) {
+        return fail(CacheError::invalid("the default instance cannot be destroyed"));
     }
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    if parts.len() < 3 {
-        return None;
+    let db = unsafe { Arc::from_raw(db) };
+    db.closed.store(true, Ordering::Relaxed);
+    {
+        let mut aof = aof_drain_locked(&db, db.aof.lock().unwrap());
+        aof.file = None;
+        aof.rewrite_buf = None;
     }
-    Some((parts[0].to_string(), parts[1].to_string(), parts[2..].join(" ")))
+    db.aof_writer_signal.notify_all();
+    db.aof_durable_signal.notify_all();
+    db.pubsub_signal.notify_all();
+    db.blocked_signal.notify_all();
+    1
+}
+
+// Returns true if a live key was removed.
+fn cmd_remove<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> bool {
+    let mut state = shard_write(db, key);
+    let existed = !maybe_remove_if_expired(db, &mut state, key) && apply_remove_internal(db, &mut state, key);
+    if existed {
+        aof_write_remove(db, key);
+    }
+    existed
 }
 
 #[no_mangle]
-pub extern "C" fn cache_find(query: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let query_str = unsafe { to_string(query) };
-    let Some((field, op, value_str)) = parse_find_query(&query_str) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+pub extern "C" fn cache_remove(key: *const c_char) {
+    cache_instance_remove(default_instance(), key)
+}
 
-    let value_num: Option<i64> = value_str.parse::<i64>().ok();
-    let mut keys: Vec<String> = Vec::new();
+#[no_mangle]
+pub extern "C" fn cache_instance_remove(db: *const Instance, key: *const c_char) {
+    let Some(db) = instance(db) else { return; };
+    let key_str = unsafe { to_string(key) };
+    cmd_remove(db, &key_str);
+}
 
-    let state = CACHE.read().unwrap();
+fn cmd_clear_all(db: &Instance) {
+    let mut state = cache_write_all(db);
+    apply_clear_internal(db, &mut state);
+    aof_write_clear(db);
+}
 
-    if let (Some(vnum), Some(idx)) = (value_num, state.numeric_indexes.get(&field)) {
-        match op.as_str() {
-            ">" => {
-                for (_k, set) in idx.range((vnum + 1)..) {
-                    keys.extend(set.iter().cloned());
-                }
-            }
-            ">=" => {
-                for (_k, set) in idx.range(vnum..) {
-                    keys.extend(set.iter().cloned());
-                }
-            }
-            "<" => {
-                for (_k, set) in idx.range(..vnum) {
-                    keys.extend(set.iter().cloned());
-                }
-            }
-            "<=" => {
-                for (_k, set) in idx.range(..=vnum) {
-                    keys.extend(set.iter().cloned());
-                }
-            }
+#[no_mangle]
+pub extern "C" fn cache_clear_all() {
+    cache_instance_clear_all(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_clear_all(db: *const Instance) {
+    let Some(db) = instance(db) else { return; };
+    cmd_clear_all(db);
+}
+
+// --- Core / String (Value::Bytes) ---
+
+fn cmd_set<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: Vec<u8>) -> Result<(), CacheError> {
+    let mut state = shard_write(db, key);
+    check_oom(db, &mut state, key)?;
+    // Log under the shard lock so an AOF rewrite snapshot cannot miss the record.
+    aof_write_set(db, key, &val);
+    apply_set_internal(db, &mut state, key, val);
+    Ok(())
+}
+
+// Returns 1, or a negative CacheStatus (OutOfMemory if the write was rejected).
+#[no_mangle]
+pub extern "C" fn cache_set(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_set(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_set(db, &key_str, val_vec), |_| 1)
+}
+
+fn cmd_get<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Arc<Vec<u8>>>, CacheError> {
+    let state = shard_read(db, key);
+    match get_entry(db, &state, key).map(|e| &e.value) {
+        Some(Value::Bytes(val)) => Ok(Some(val.clone())),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(None),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_get(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_get(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    return_found(cmd_get(db, &key_str).map(|v| v.map(|v| (*v).clone())), out_len)
+}
+
+// Copy value bytes into a caller-provided buffer.
+// Return semantics:
+//  -1  => key missing (or expired), wrong type or empty key; see cache_last_status
+//  <0  => buffer too small; required length is -ret
+//  >0  => bytes written
+//   0  => value exists but is empty
+#[no_mangle]
+pub extern "C" fn cache_get_into(key: *const c_char, dst: *mut c_uchar, dst_len: usize) -> i64 {
+    cache_instance_get_into(default_instance(), key, dst, dst_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_into(
+    db: *const Instance,
+    key: *const c_char,
+    dst: *mut c_uchar,
+    dst_len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return -1; };
+    let key_str = unsafe { to_string(key) };
+    if key_str.is_empty() {
+        fail(CacheError::invalid("empty key"));
+        return -1;
+    }
+
+    let val = match cmd_get(db, &key_str) {
+        Ok(Some(val)) => val,
+        Ok(None) => {
+            fail(CacheError::NotFound);
+            return -1;
+        }
+        Err(e) => {
+            fail(e);
+            return -1;
+        }
+    };
+
+    let value_len = val.len();
+    if value_len == 0 {
+        return 0;
+    }
+
+    if dst.is_null() || dst_len < value_len {
+        return -(value_len as i64);
+    }
+
+    unsafe {
+        std::ptr::copy_nonoverlapping(val.as_ptr(), dst, value_len);
+    }
+    value_len as i64
+}
+
+// Longest string SETRANGE and APPEND may produce, Redis' proto-max-bulk-len default.
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+// Read-modify-write of a string under one lock. `f` gets the current value (None if the key is
+// missing) and returns the new value (None = leave the key as is) and the command's result. The
+// key keeps its TTL. The new value is logged, not the operation, so replay is deterministic.
+fn update_string<R, K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    f: impl FnOnce(Option<&[u8]>) -> Result<(Option<Vec<u8>>, R), CacheError>,
+) -> Result<R, CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let (current, expires_at_ms) = match state.map.peek(key.as_bytes()) {
+        Some(Entry { value: Value::Bytes(val), expires_at_ms, .. }) => (Some(val.clone()), *expires_at_ms),
+        Some(_) => return Err(CacheError::WrongType),
+        None => (None, None),
+    };
+    let (next, res) = f(current.as_deref().map(|v| v.as_slice()))?;
+    let Some(next) = next else { return Ok(res) };
+    check_oom(db, &mut state, key)?;
+    aof_write_set(db, key, &next);
+    if let Some(at) = expires_at_ms {
+        aof_write_expire_at(db, key, at);
+    }
+    put_entry_with_lru(db, &mut state, key, Entry::new(Value::Bytes(Arc::new(next)), expires_at_ms));
+    Ok(res)
+}
+
+// Integers are parsed as Redis does: optional '-', then digits without leading zeros.
+fn parse_string_int(val: &[u8]) -> Option<i64> {
+    let digits = val.strip_prefix(b"-").unwrap_or(val);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) || (digits[0] == b'0' && val.len() > 1) {
+        return None;
+    }
+    std::str::from_utf8(val).ok()?.parse().ok()
+}
+
+// Adds `incr` to the integer stored at the key (a missing key counts as 0).
+fn cmd_incrby<K: CacheKey + ?Sized>(db: &Instance, key: &K, incr: i64) -> Result<i64, CacheError> {
+    update_string(db, key, |current| {
+        let current = match current {
+            Some(val) => parse_string_int(val).ok_or_else(|| CacheError::invalid("value is not an integer or out of range"))?,
+            None => 0,
+        };
+        let next = current
+            .checked_add(incr)
+            .ok_or_else(|| CacheError::invalid("increment or decrement would overflow"))?;
+        Ok((Some(next.to_string().into_bytes()), next))
+    })
+}
+
+// Writes the new value to `value_out`. Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_incrby(key: *const c_char, incr: i64, value_out: *mut i64) -> i32 {
+    cache_instance_incrby(default_instance(), key, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_incrby(db: *const Instance, key: *const c_char, incr: i64, value_out: *mut i64) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    status(cmd_incrby(db, &key_str, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_incrby_b(key: *const c_uchar, key_len: usize, incr: i64, value_out: *mut i64) -> i32 {
+    cache_instance_incrby_b(default_instance(), key, key_len, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_incrby_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    incr: i64,
+    value_out: *mut i64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    status(cmd_incrby(db, &key_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+// Adds `incr` to the float stored at the key (a missing key counts as 0).
+fn cmd_incrbyfloat<K: CacheKey + ?Sized>(db: &Instance, key: &K, incr: f64) -> Result<f64, CacheError> {
+    update_string(db, key, |current| {
+        let current = match current {
+            Some(val) => std::str::from_utf8(val)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| CacheError::invalid("value is not a valid float"))?,
+            None => 0.0,
+        };
+        let next = current + incr;
+        if !next.is_finite() {
+            return Err(CacheError::invalid("increment would produce NaN or Infinity"));
+        }
+        Ok((Some(next.to_string().into_bytes()), next))
+    })
+}
+
+// Writes the new value to `value_out`. Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_incrbyfloat(key: *const c_char, incr: f64, value_out: *mut f64) -> i32 {
+    cache_instance_incrbyfloat(default_instance(), key, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_incrbyfloat(db: *const Instance, key: *const c_char, incr: f64, value_out: *mut f64) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    status(cmd_incrbyfloat(db, &key_str, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_incrbyfloat_b(key: *const c_uchar, key_len: usize, incr: f64, value_out: *mut f64) -> i32 {
+    cache_instance_incrbyfloat_b(default_instance(), key, key_len, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_incrbyfloat_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    incr: f64,
+    value_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    status(cmd_incrbyfloat(db, &key_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+// Appends to the string (a missing key counts as empty). Returns the new length.
+fn cmd_append<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: &[u8]) -> Result<usize, CacheError> {
+    update_string(db, key, |current| {
+        let current = current.unwrap_or_default();
+        if current.len() + val.len() > MAX_STRING_LEN {
+            return Err(CacheError::invalid("string exceeds maximum allowed size (proto-max-bulk-len)"));
+        }
+        let next = [current, val].concat();
+        let len = next.len();
+        Ok((Some(next), len))
+    })
+}
+
+// Returns the new length, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_append(key: *const c_char, value: *const c_uchar, len: usize) -> i64 {
+    cache_instance_append(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_append(db: *const Instance, key: *const c_char, value: *const c_uchar, len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    match cmd_append(db, &key_str, &val_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_append_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i64 {
+    cache_instance_append_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_append_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    match cmd_append(db, &key_vec, &val_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Overwrites the bytes from `offset` on, zero-padding a shorter string (a missing key counts as
+// empty). An empty `val` changes nothing and creates no key. Returns the new length.
+fn cmd_setrange<K: CacheKey + ?Sized>(db: &Instance, key: &K, offset: usize, val: &[u8]) -> Result<usize, CacheError> {
+    update_string(db, key, |current| {
+        let current = current.unwrap_or_default();
+        if val.is_empty() {
+            return Ok((None, current.len()));
+        }
+        let end = offset.checked_add(val.len()).filter(|&end| end <= MAX_STRING_LEN);
+        let Some(end) = end else {
+            return Err(CacheError::invalid("string exceeds maximum allowed size (proto-max-bulk-len)"));
+        };
+        let mut next = current.to_vec();
+        if next.len() < end {
+            next.resize(end, 0);
+        }
+        next[offset..end].copy_from_slice(val);
+        let len = next.len();
+        Ok((Some(next), len))
+    })
+}
+
+// Returns the new length, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_setrange(key: *const c_char, offset: u64, value: *const c_uchar, len: usize) -> i64 {
+    cache_instance_setrange(default_instance(), key, offset, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_setrange(
+    db: *const Instance,
+    key: *const c_char,
+    offset: u64,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+    match cmd_setrange(db, &key_str, offset, &val_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_setrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    offset: u64,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    cache_instance_setrange_b(default_instance(), key, key_len, offset, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_setrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    offset: u64,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+    match cmd_setrange(db, &key_vec, offset, &val_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Bytes `start..=end` of the string; negative indexes count from the end. Empty for a missing key.
+fn cmd_getrange<K: CacheKey + ?Sized>(db: &Instance, key: &K, start: i64, end: i64) -> Result<Vec<u8>, CacheError> {
+    let Some(val) = cmd_get(db, key)? else { return Ok(Vec::new()) };
+    Ok(normalize_range(start, end, val.len()).map_or_else(Vec::new, |(low, high)| val[low..=high].to_vec()))
+}
+
+// The bytes (null with length 0 if empty or missing), or null with a recorded error.
+#[no_mangle]
+pub extern "C" fn cache_getrange(key: *const c_char, start: i64, end: i64, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_getrange(default_instance(), key, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_getrange(
+    db: *const Instance,
+    key: *const c_char,
+    start: i64,
+    end: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    return_found(cmd_getrange(db, &key_str, start, end).map(Some), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_getrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    start: i64,
+    end: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_getrange_b(default_instance(), key, key_len, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_getrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start: i64,
+    end: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    return_found(cmd_getrange(db, &key_vec, start, end).map(Some), out_len)
+}
+
+// Length of the string, 0 if the key is missing.
+fn cmd_strlen<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<usize, CacheError> {
+    Ok(cmd_get(db, key)?.map_or(0, |val| val.len()))
+}
+
+// Returns the length, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_strlen(key: *const c_char) -> i64 {
+    cache_instance_strlen(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_strlen(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    match cmd_strlen(db, &key_str) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_strlen_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_strlen_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_strlen_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    match cmd_strlen(db, &key_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Sets the value, dropping any TTL, and returns the previous one.
+fn cmd_getset<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: Vec<u8>) -> Result<Option<Vec<u8>>, CacheError> {
+    let (_, old) = cmd_set_with(db, key, val, SetOptions { get: true, ..SetOptions::default() })?;
+    Ok(old)
+}
+
+// The previous value, or null (NotFound) if the key was missing.
+#[no_mangle]
+pub extern "C" fn cache_getset(key: *const c_char, value: *const c_uchar, len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_getset(default_instance(), key, value, len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_getset(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    return_found(cmd_getset(db, &key_str, val_vec), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_getset_b(
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_getset_b(default_instance(), key, key_len, value, len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_getset_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    return_found(cmd_getset(db, &key_vec, val_vec), out_len)
+}
+
+// Removes the key and returns its value. A value of another type is left in place: WrongType.
+fn cmd_getdel<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Vec<u8>>, CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    match state.map.peek(key.as_bytes()).map(|e| &e.value) {
+        Some(Value::Bytes(_)) => {}
+        Some(_) => return Err(CacheError::WrongType),
+        None => return Ok(None),
+    }
+    aof_write_remove(db, key);
+    let Some(Entry { value: Value::Bytes(val), .. }) = pop_entry(db, &mut state, key) else {
+        unreachable!("type checked on peek")
+    };
+    Ok(Some(Arc::unwrap_or_clone(val)))
+}
+
+// The removed value, or null (NotFound) if the key was missing.
+#[no_mangle]
+pub extern "C" fn cache_getdel(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_getdel(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_getdel(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    return_found(cmd_getdel(db, &key_str), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_getdel_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_getdel_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_getdel_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    return_found(cmd_getdel(db, &key_vec), out_len)
+}
+
+// --- Multi-key (MGET / MSET / MSETNX / DEL) ---
+//
+// Each command takes the locks of all its keys' shards once, in index order, so MGET reads a
+// consistent snapshot and MSET / MSETNX / DEL apply as a whole. The `_b` exports take the
+// same keys as raw bytes.
+
+type KeyValues = Vec<(Vec<u8>, Vec<u8>)>;
+
+// Values of the given keys; None for missing keys and for keys holding another type, as in Redis.
+fn cmd_mget<K: CacheKey>(db: &Instance, keys: &[K]) -> Vec<Option<Vec<u8>>> {
+    let shards = shard_read_keys(db, keys);
+    keys.iter()
+        .map(|k| match get_entry(db, shards.shard(k), k).map(|e| &e.value) {
+            Some(Value::Bytes(val)) => Some((**val).clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Writes every pair under the locks already held, dropping their TTLs. Nothing is written
+// (OutOfMemory) unless every key has room. A key given twice ends up with its last value.
+fn mset_locked<K: CacheKey>(db: &Instance, shards: &mut ShardsGuard, pairs: Vec<(K, Vec<u8>)>) -> Result<(), CacheError> {
+    for (key, _) in &pairs {
+        check_oom(db, shards.shard(key), key)?;
+    }
+    // One write, so a replay sees all of the pairs or none.
+    let mut buf = Vec::new();
+    for (key, val) in &pairs {
+        aof_encode_set(&mut buf, key, val);
+    }
+    aof_write(db, &buf);
+    for (key, val) in pairs {
+        apply_set_internal(db, shards.shard(&key), &key, val);
+    }
+    Ok(())
+}
+
+fn cmd_mset<K: CacheKey>(db: &Instance, pairs: Vec<(K, Vec<u8>)>) -> Result<(), CacheError> {
+    let keys: Vec<&K> = pairs.iter().map(|(k, _)| k).collect();
+    let mut shards = shard_write_keys(db, &keys);
+    mset_locked(db, &mut shards, pairs)
+}
+
+// Like MSET, but only if none of the keys exists (of any type). Returns whether they were set.
+fn cmd_msetnx<K: CacheKey>(db: &Instance, pairs: Vec<(K, Vec<u8>)>) -> Result<bool, CacheError> {
+    let keys: Vec<&K> = pairs.iter().map(|(k, _)| k).collect();
+    let mut shards = shard_write_keys(db, &keys);
+    for key in keys {
+        let state = shards.shard(key);
+        if !maybe_remove_if_expired(db, state, key) && state.map.contains(key.as_bytes()) {
+            return Ok(false);
+        }
+    }
+    mset_locked(db, &mut shards, pairs).map(|_| true)
+}
+
+// Removes the keys (of any type); returns how many of them existed.
+fn cmd_del<K: CacheKey>(db: &Instance, keys: &[K]) -> usize {
+    let mut shards = shard_write_keys(db, keys);
+    let mut buf = Vec::new();
+    let mut removed = 0;
+    for key in keys {
+        let state = shards.shard(key);
+        if !maybe_remove_if_expired(db, state, key) && apply_remove_internal(db, state, key) {
+            aof_encode_remove(&mut buf, key);
+            removed += 1;
+        }
+    }
+    if !buf.is_empty() {
+        aof_write(db, &buf);
+    }
+    removed
+}
+
+// Items alternating key and value, packed as cache_hmset takes them.
+fn unpack_pairs(packed: &[u8]) -> Result<KeyValues, CacheError> {
+    let items = unpack_items(packed)?;
+    if items.is_empty() || items.len() % 2 != 0 {
+        return Err(CacheError::invalid("expected key/value pairs"));
+    }
+    let mut pairs = Vec::with_capacity(items.len() / 2);
+    let mut items = items.into_iter();
+    while let (Some(key), Some(val)) = (items.next(), items.next()) {
+        pairs.push((key, val));
+    }
+    Ok(pairs)
+}
+
+fn unpack_string_pairs(packed: &[u8]) -> Result<Vec<(String, Vec<u8>)>, CacheError> {
+    Ok(unpack_pairs(packed)?.into_iter().map(|(k, v)| (String::from_utf8_lossy(&k).into_owned(), v)).collect())
+}
+
+// Keys packed like cache_blpop's. Replies with one entry per key in the pack_optional_items
+// layout; missing keys and keys of another type have no value.
+#[no_mangle]
+pub extern "C" fn cache_mget(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_mget(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_mget(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    return_found(unpack_keys(&packed).map(|keys| Some(pack_optional_items(&cmd_mget(db, &keys)))), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_mget_b(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_mget_b(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_mget_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    return_found(unpack_keys_b(&packed).map(|keys| Some(pack_optional_items(&cmd_mget(db, &keys)))), out_len)
+}
+
+// Pairs packed as items alternating key and value. Returns 1, or a negative CacheStatus
+// (OutOfMemory if the write was rejected, in which case no key was written).
+#[no_mangle]
+pub extern "C" fn cache_mset(items: *const c_uchar, items_len: usize) -> i32 {
+    cache_instance_mset(default_instance(), items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_mset(db: *const Instance, items: *const c_uchar, items_len: usize) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let packed = unsafe { to_bytes(items, items_len) };
+    status(unpack_string_pairs(&packed).and_then(|pairs| cmd_mset(db, pairs)), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_mset_b(items: *const c_uchar, items_len: usize) -> i32 {
+    cache_instance_mset_b(default_instance(), items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_mset_b(db: *const Instance, items: *const c_uchar, items_len: usize) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let packed = unsafe { to_bytes(items, items_len) };
+    status(unpack_pairs(&packed).and_then(|pairs| cmd_mset(db, pairs)), |_| 1)
+}
+
+// Returns 1 if every pair was set, 0 if one of the keys already existed (nothing is written),
+// or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_msetnx(items: *const c_uchar, items_len: usize) -> i32 {
+    cache_instance_msetnx(default_instance(), items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_msetnx(db: *const Instance, items: *const c_uchar, items_len: usize) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let packed = unsafe { to_bytes(items, items_len) };
+    status(unpack_string_pairs(&packed).and_then(|pairs| cmd_msetnx(db, pairs)), |set| set as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_msetnx_b(items: *const c_uchar, items_len: usize) -> i32 {
+    cache_instance_msetnx_b(default_instance(), items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_msetnx_b(db: *const Instance, items: *const c_uchar, items_len: usize) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let packed = unsafe { to_bytes(items, items_len) };
+    status(unpack_pairs(&packed).and_then(|pairs| cmd_msetnx(db, pairs)), |set| set as i32)
+}
+
+// Keys packed like cache_mget's. Returns the number of keys removed, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_del(keys: *const c_uchar, keys_len: usize) -> i64 {
+    cache_instance_del(default_instance(), keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_del(db: *const Instance, keys: *const c_uchar, keys_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    match unpack_keys(&packed) {
+        Ok(keys) => cmd_del(db, &keys) as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_del_b(keys: *const c_uchar, keys_len: usize) -> i64 {
+    cache_instance_del_b(default_instance(), keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_del_b(db: *const Instance, keys: *const c_uchar, keys_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    match unpack_keys_b(&packed) {
+        Ok(keys) => cmd_del(db, &keys) as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// --- Hashes ---
+
+// Sets every field under one lock, so the command applies fully or (on OOM) not at all.
+// Returns the number of newly created fields.
+fn cmd_hset<K: CacheKey + ?Sized>(db: &Instance, key: &K, fields: HashFields) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::Hash(HashMap::new()))?;
+    let mut created = 0;
+    for (field, val) in fields {
+        aof_write_hset(db, key, &field, &val);
+        created += apply_hset_internal(db, &mut state, key, field, val)? as usize;
+    }
+    Ok(created)
+}
+
+// Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hset(key: *const c_char, field: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_hset(default_instance(), key, field, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hset(
+    db: *const Instance,
+    key: *const c_char,
+    field: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_hset(db, &key_str, vec![(field_vec, val_vec)]), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hset_b(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    cache_instance_hset_b(default_instance(), key, key_len, field, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hset_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_hset(db, &key_vec, vec![(field_vec, val_vec)]), |_| 1)
+}
+
+// Like cache_hset_b, with the field also passed as a pointer and a length so it may hold NULs
+// or bytes that are not UTF-8. The other `_bf` hash exports take their fields the same way.
+#[no_mangle]
+pub extern "C" fn cache_hset_bf(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    cache_instance_hset_bf(default_instance(), key, key_len, field, field_len, value, len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_hset_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_hset(db, &key_vec, vec![(field_vec, val_vec)]), |_| 1)
+}
+
+fn read_hash<'a, K: CacheKey + ?Sized>(db: &Instance, state: &'a Shard, key: &K) -> Result<Option<&'a HashData>, CacheError> {
+    match get_entry(db, state, key).map(|e| &e.value) {
+        Some(Value::Hash(hmap)) => Ok(Some(hmap)),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn cmd_hget<K: CacheKey + ?Sized>(db: &Instance, key: &K, field: &[u8]) -> Result<Option<Vec<u8>>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_hash(db, &state, key)?.and_then(|hmap| hmap.get(field).cloned()))
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hget(key: *const c_char, field: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hget(default_instance(), key, field, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hget(
+    db: *const Instance,
+    key: *const c_char,
+    field: *const c_char,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    return_found(cmd_hget(db, &key_str, &field_vec), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hget_b(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_hget_b(default_instance(), key, key_len, field, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hget_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    return_found(cmd_hget(db, &key_vec, &field_vec), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hget_bf(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_hget_bf(default_instance(), key, key_len, field, field_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hget_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    return_found(cmd_hget(db, &key_vec, &field_vec), out_len)
+}
+
+// Field/value pairs of a hash.
+type HashFields = Vec<(Vec<u8>, Vec<u8>)>;
+
+fn cmd_hgetall<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<HashFields>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_hash(db, &state, key)?.map(|hmap| hmap.iter().map(|(k, v)| (k.clone(), v.clone())).collect()))
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hgetall(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hgetall(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hgetall(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let key_str = unsafe { to_string(key) };
+    hgetall_export(db, key_str.as_str(), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hgetall_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hgetall_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hgetall_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    hgetall_export(db, key_vec.as_slice(), out_len)
+}
+
+fn hgetall_export<K: CacheKey + ?Sized>(db: *const Instance, key: &K, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let packed = cmd_hgetall(db, key).map(|fields| {
+        fields.map(|fields| {
+            let mut flat = Vec::new();
+            // format: [Count (u32)] [KeyLen (u32)] [Key] [ValLen (u32)] [Val] ...
+            flat.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+            for (k, v) in &fields {
+                flat.extend_from_slice(&(k.len() as u32).to_le_bytes());
+                flat.extend_from_slice(k);
+                flat.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                flat.extend_from_slice(v);
+            }
+            flat
+        })
+    });
+    return_found(packed, out_len)
+}
+
+// Sets fields from packed items alternating field and value ([Count u32] { [Len u32] [Bytes] }).
+// Returns the number of newly created fields, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hmset(key: *const c_char, items: *const c_uchar, items_len: usize) -> i64 {
+    cache_instance_hmset(default_instance(), key, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hmset(
+    db: *const Instance,
+    key: *const c_char,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    let key_str = unsafe { to_string(key) };
+    hmset_export(db, key_str.as_str(), items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hmset_b(key: *const c_uchar, key_len: usize, items: *const c_uchar, items_len: usize) -> i64 {
+    cache_instance_hmset_b(default_instance(), key, key_len, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hmset_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    hmset_export(db, key_vec.as_slice(), items, items_len)
+}
+
+fn hmset_export<K: CacheKey + ?Sized>(db: *const Instance, key: &K, items: *const c_uchar, items_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let packed = unsafe { to_bytes(items, items_len) };
+    let res = unpack_items(&packed).and_then(|items| {
+        if items.is_empty() || items.len() % 2 != 0 {
+            return Err(CacheError::invalid("expected field/value pairs"));
+        }
+        let mut fields = Vec::with_capacity(items.len() / 2);
+        let mut items = items.into_iter();
+        while let (Some(field), Some(val)) = (items.next(), items.next()) {
+            fields.push((field, val));
+        }
+        cmd_hset(db, key, fields)
+    });
+    match res {
+        Ok(created) => created as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Sets the field only if it does not exist yet. Returns true if it was set.
+fn cmd_hsetnx<K: CacheKey + ?Sized>(db: &Instance, key: &K, field: Vec<u8>, val: Vec<u8>) -> Result<bool, CacheError> {
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::Hash(HashMap::new()))?;
+    if read_hash(db, &state, key)?.is_some_and(|hmap| hmap.contains_key(&field)) {
+        return Ok(false);
+    }
+    aof_write_hset(db, key, &field, &val);
+    apply_hset_internal(db, &mut state, key, field, val)
+}
+
+// Returns 1 if set, 0 if the field already exists, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hsetnx(key: *const c_char, field: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_hsetnx(default_instance(), key, field, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hsetnx(
+    db: *const Instance,
+    key: *const c_char,
+    field: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_hsetnx(db, &key_str, field_vec, val_vec), |set| set as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hsetnx_b(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    cache_instance_hsetnx_b(default_instance(), key, key_len, field, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hsetnx_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_hsetnx(db, &key_vec, field_vec, val_vec), |set| set as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hsetnx_bf(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    cache_instance_hsetnx_bf(default_instance(), key, key_len, field, field_len, value, len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_hsetnx_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_hsetnx(db, &key_vec, field_vec, val_vec), |set| set as i32)
+}
+
+// Removes fields under one lock; a hash left empty is removed. Returns how many existed.
+fn cmd_hdel<K: CacheKey + ?Sized>(db: &Instance, key: &K, fields: &[Vec<u8>]) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    let mut removed = 0;
+    for field in fields {
+        if apply_hdel_internal(db, &mut state, key, field)? {
+            aof_write_key_item(db, AOF_OP_HDEL, key, field);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// Returns 1 if removed, 0 if the field is missing, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hdel(key: *const c_char, field: *const c_char) -> i32 {
+    cache_instance_hdel(default_instance(), key, field)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hdel(db: *const Instance, key: *const c_char, field: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hdel(db, &key_str, &[field_vec]), |n| n as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hdel_b(key: *const c_uchar, key_len: usize, field: *const c_char) -> i32 {
+    cache_instance_hdel_b(default_instance(), key, key_len, field)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hdel_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hdel(db, &key_vec, &[field_vec]), |n| n as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hdel_bf(key: *const c_uchar, key_len: usize, field: *const c_uchar, field_len: usize) -> i32 {
+    cache_instance_hdel_bf(default_instance(), key, key_len, field, field_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hdel_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    status(cmd_hdel(db, &key_vec, &[field_vec]), |n| n as i32)
+}
+
+fn cmd_hexists<K: CacheKey + ?Sized>(db: &Instance, key: &K, field: &[u8]) -> Result<bool, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_hash(db, &state, key)?.is_some_and(|hmap| hmap.contains_key(field)))
+}
+
+// Returns 1 if the field exists, 0 if not, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hexists(key: *const c_char, field: *const c_char) -> i32 {
+    cache_instance_hexists(default_instance(), key, field)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hexists(db: *const Instance, key: *const c_char, field: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hexists(db, &key_str, &field_vec), |found| found as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hexists_b(key: *const c_uchar, key_len: usize, field: *const c_char) -> i32 {
+    cache_instance_hexists_b(default_instance(), key, key_len, field)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hexists_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hexists(db, &key_vec, &field_vec), |found| found as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hexists_bf(key: *const c_uchar, key_len: usize, field: *const c_uchar, field_len: usize) -> i32 {
+    cache_instance_hexists_bf(default_instance(), key, key_len, field, field_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hexists_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    status(cmd_hexists(db, &key_vec, &field_vec), |found| found as i32)
+}
+
+fn cmd_hlen<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<usize, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_hash(db, &state, key)?.map_or(0, |hmap| hmap.len()))
+}
+
+// Returns the number of fields, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hlen(key: *const c_char) -> i64 {
+    cache_instance_hlen(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hlen(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    match cmd_hlen(db, &key_str) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hlen_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_hlen_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hlen_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    match cmd_hlen(db, &key_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+fn cmd_hkeys<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_hash(db, &state, key)?.map(|hmap| hmap.keys().cloned().collect()))
+}
+
+fn cmd_hvals<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_hash(db, &state, key)?.map(|hmap| hmap.values().cloned().collect()))
+}
+
+// Field names as packed items, or null (NotFound) for a missing key.
+#[no_mangle]
+pub extern "C" fn cache_hkeys(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hkeys(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hkeys(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_hkeys(db, &key_str).map(|keys| keys.map(|keys| pack_items(keys.iter().map(|k| k.as_slice()))));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hkeys_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hkeys_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hkeys_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_hkeys(db, &key_vec).map(|keys| keys.map(|keys| pack_items(keys.iter().map(|k| k.as_slice()))));
+    return_found(packed, out_len)
+}
+
+// Values as packed items, or null (NotFound) for a missing key.
+#[no_mangle]
+pub extern "C" fn cache_hvals(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hvals(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hvals(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_hvals(db, &key_str).map(|vals| vals.map(|vals| pack_items(vals.iter().map(|v| &v[..]))));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hvals_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hvals_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hvals_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_hvals(db, &key_vec).map(|vals| vals.map(|vals| pack_items(vals.iter().map(|v| &v[..]))));
+    return_found(packed, out_len)
+}
+
+// Values of the given fields, None for missing ones (all None for a missing key).
+fn cmd_hmget<K: CacheKey + ?Sized>(db: &Instance, key: &K, fields: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+    let state = shard_read(db, key);
+    let hmap = read_hash(db, &state, key)?;
+    Ok(fields.iter().map(|f| hmap.and_then(|hmap| hmap.get(f).cloned())).collect())
+}
+
+// Fields packed as items; replies with one entry per field in the pack_optional_items layout.
+#[no_mangle]
+pub extern "C" fn cache_hmget(key: *const c_char, fields: *const c_uchar, fields_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_hmget(default_instance(), key, fields, fields_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hmget(
+    db: *const Instance,
+    key: *const c_char,
+    fields: *const c_uchar,
+    fields_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = unsafe { to_bytes(fields, fields_len) };
+    let res = unpack_keys_b(&packed).and_then(|fields| cmd_hmget(db, &key_str, &fields));
+    return_found(res.map(|vals| Some(pack_optional_items(&vals))), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hmget_b(
+    key: *const c_uchar,
+    key_len: usize,
+    fields: *const c_uchar,
+    fields_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_hmget_b(default_instance(), key, key_len, fields, fields_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hmget_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    fields: *const c_uchar,
+    fields_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = unsafe { to_bytes(fields, fields_len) };
+    let res = unpack_keys_b(&packed).and_then(|fields| cmd_hmget(db, &key_vec, &fields));
+    return_found(res.map(|vals| Some(pack_optional_items(&vals))), out_len)
+}
+
+// Adds `incr` to the integer stored in the field (a missing field counts as 0).
+fn cmd_hincrby<K: CacheKey + ?Sized>(db: &Instance, key: &K, field: Vec<u8>, incr: i64) -> Result<i64, CacheError> {
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::Hash(HashMap::new()))?;
+    let current = match read_hash(db, &state, key)?.and_then(|hmap| hmap.get(&field)) {
+        Some(val) => std::str::from_utf8(val)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(|| CacheError::invalid("hash value is not an integer"))?,
+        None => 0,
+    };
+    let next = current
+        .checked_add(incr)
+        .ok_or_else(|| CacheError::invalid("increment or decrement would overflow"))?;
+    let val = next.to_string().into_bytes();
+    aof_write_hset(db, key, &field, &val);
+    apply_hset_internal(db, &mut state, key, field, val)?;
+    Ok(next)
+}
+
+// Writes the new value to `value_out`. Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hincrby(key: *const c_char, field: *const c_char, incr: i64, value_out: *mut i64) -> i32 {
+    cache_instance_hincrby(default_instance(), key, field, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hincrby(
+    db: *const Instance,
+    key: *const c_char,
+    field: *const c_char,
+    incr: i64,
+    value_out: *mut i64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hincrby(db, &key_str, field_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hincrby_b(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    incr: i64,
+    value_out: *mut i64,
+) -> i32 {
+    cache_instance_hincrby_b(default_instance(), key, key_len, field, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hincrby_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    incr: i64,
+    value_out: *mut i64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hincrby(db, &key_vec, field_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hincrby_bf(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    incr: i64,
+    value_out: *mut i64,
+) -> i32 {
+    cache_instance_hincrby_bf(default_instance(), key, key_len, field, field_len, incr, value_out)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_hincrby_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    incr: i64,
+    value_out: *mut i64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    status(cmd_hincrby(db, &key_vec, field_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+// Adds `incr` to the float stored in the field (a missing field counts as 0).
+fn cmd_hincrbyfloat<K: CacheKey + ?Sized>(db: &Instance, key: &K, field: Vec<u8>, incr: f64) -> Result<f64, CacheError> {
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::Hash(HashMap::new()))?;
+    let current = match read_hash(db, &state, key)?.and_then(|hmap| hmap.get(&field)) {
+        Some(val) => std::str::from_utf8(val)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| CacheError::invalid("hash value is not a float"))?,
+        None => 0.0,
+    };
+    let next = current + incr;
+    if !next.is_finite() {
+        return Err(CacheError::invalid("increment would produce NaN or Infinity"));
+    }
+    let val = next.to_string().into_bytes();
+    aof_write_hset(db, key, &field, &val);
+    apply_hset_internal(db, &mut state, key, field, val)?;
+    Ok(next)
+}
+
+// Writes the new value to `value_out`. Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_hincrbyfloat(key: *const c_char, field: *const c_char, incr: f64, value_out: *mut f64) -> i32 {
+    cache_instance_hincrbyfloat(default_instance(), key, field, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hincrbyfloat(
+    db: *const Instance,
+    key: *const c_char,
+    field: *const c_char,
+    incr: f64,
+    value_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hincrbyfloat(db, &key_str, field_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hincrbyfloat_b(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    incr: f64,
+    value_out: *mut f64,
+) -> i32 {
+    cache_instance_hincrbyfloat_b(default_instance(), key, key_len, field, incr, value_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_hincrbyfloat_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_char,
+    incr: f64,
+    value_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_c_bytes(field) };
+    status(cmd_hincrbyfloat(db, &key_vec, field_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_hincrbyfloat_bf(
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    incr: f64,
+    value_out: *mut f64,
+) -> i32 {
+    cache_instance_hincrbyfloat_bf(default_instance(), key, key_len, field, field_len, incr, value_out)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_hincrbyfloat_bf(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    field: *const c_uchar,
+    field_len: usize,
+    incr: f64,
+    value_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let field_vec = unsafe { to_bytes(field, field_len) };
+    status(cmd_hincrbyfloat(db, &key_vec, field_vec, incr), |value| {
+        if !value_out.is_null() {
+            unsafe { *value_out = value };
+        }
+        1
+    })
+}
+
+// --- Lists ---
+
+// Pushes the values in order under one lock onto the head (`left`) or tail. Returns the list
+// length after the push.
+fn cmd_push<K: CacheKey + ?Sized>(db: &Instance, key: &K, left: bool, vals: Vec<Vec<u8>>) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::List(VecDeque::new()))?;
+    let mut len = 0;
+    for val in vals {
+        aof_write_push(db, key, left, &val);
+        len = apply_push_internal(db, &mut state, key, left, val)?;
+    }
+    drop(state);
+    blocking::wake(db, key);
+    Ok(len)
+}
+
+fn cmd_lpush<K: CacheKey + ?Sized>(db: &Instance, key: &K, vals: Vec<Vec<u8>>) -> Result<usize, CacheError> {
+    cmd_push(db, key, true, vals)
+}
+
+fn cmd_rpush<K: CacheKey + ?Sized>(db: &Instance, key: &K, vals: Vec<Vec<u8>>) -> Result<usize, CacheError> {
+    cmd_push(db, key, false, vals)
+}
+
+// Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_lpush(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_lpush(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lpush(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_lpush(db, &key_str, vec![val_vec]), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lpush_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_lpush_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lpush_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_lpush(db, &key_vec, vec![val_vec]), |_| 1)
+}
+
+// Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_rpush(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_rpush(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_rpush(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_rpush(db, &key_str, vec![val_vec]), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_rpush_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_rpush_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_rpush_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_rpush(db, &key_vec, vec![val_vec]), |_| 1)
+}
+
+// Multi-value pushes take the values packed as [Count u32] { [Len u32] [Bytes] } (the
+// cache_lrange layout) and push them in order. Returns the list length after the push, or a
+// negative CacheStatus (InvalidArgument for a malformed buffer).
+#[no_mangle]
+pub extern "C" fn cache_lpush_multi(key: *const c_char, items: *const c_uchar, items_len: usize) -> i64 {
+    cache_instance_lpush_multi(default_instance(), key, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lpush_multi(
+    db: *const Instance,
+    key: *const c_char,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    let key_str = unsafe { to_string(key) };
+    push_multi(db, key_str.as_str(), true, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lpush_multi_b(
+    key: *const c_uchar,
+    key_len: usize,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    cache_instance_lpush_multi_b(default_instance(), key, key_len, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lpush_multi_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    push_multi(db, key_vec.as_slice(), true, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_rpush_multi(key: *const c_char, items: *const c_uchar, items_len: usize) -> i64 {
+    cache_instance_rpush_multi(default_instance(), key, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_rpush_multi(
+    db: *const Instance,
+    key: *const c_char,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    let key_str = unsafe { to_string(key) };
+    push_multi(db, key_str.as_str(), false, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_rpush_multi_b(
+    key: *const c_uchar,
+    key_len: usize,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    cache_instance_rpush_multi_b(default_instance(), key, key_len, items, items_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_rpush_multi_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    items: *const c_uchar,
+    items_len: usize,
+) -> i64 {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    push_multi(db, key_vec.as_slice(), false, items, items_len)
+}
+
+fn push_multi<K: CacheKey + ?Sized>(db: *const Instance, key: &K, left: bool, items: *const c_uchar, items_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let packed = unsafe { to_bytes(items, items_len) };
+    match unpack_items(&packed).and_then(|vals| cmd_push(db, key, left, vals)) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Pops up to `count` items from one end, logging the pop. Ok(None) if the key is missing.
+fn cmd_pop<K: CacheKey + ?Sized>(db: &Instance, key: &K, left: bool, count: usize) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    let mut state = shard_write(db, key);
+    let popped = apply_pop_internal(db, &mut state, key, left, count)?;
+    if let Some(items) = popped.as_ref().filter(|items| !items.is_empty()) {
+        aof_write_pop(db, key, left, items.len());
+    }
+    Ok(popped)
+}
+
+fn cmd_lpop<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Vec<u8>>, CacheError> {
+    Ok(cmd_pop(db, key, true, 1)?.and_then(|items| items.into_iter().next()))
+}
+
+fn cmd_rpop<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Vec<u8>>, CacheError> {
+    Ok(cmd_pop(db, key, false, 1)?.and_then(|items| items.into_iter().next()))
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lpop(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_lpop(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lpop(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    return_found(cmd_lpop(db, &key_str), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lpop_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_lpop_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lpop_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    return_found(cmd_lpop(db, &key_vec), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_rpop(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_rpop(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_rpop(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    return_found(cmd_rpop(db, &key_str), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_rpop_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_rpop_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_rpop_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    return_found(cmd_rpop(db, &key_vec), out_len)
+}
+
+// Looks up a live list for reading; None if the key is missing.
+fn read_list<'a, K: CacheKey + ?Sized>(db: &Instance, state: &'a Shard, key: &K) -> Result<Option<&'a VecDeque<Vec<u8>>>, CacheError> {
+    match get_entry(db, state, key).map(|e| &e.value) {
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn cmd_lrange<K: CacheKey + ?Sized>(db: &Instance, key: &K, start: i64, end: i64) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    let state = shard_read(db, key);
+    let Some(list) = read_list(db, &state, key)? else { return Ok(None); };
+    match normalize_range(start, end, list.len()) {
+        Some((low, high)) => Ok(Some(list.range(low..=high).cloned().collect())),
+        None => Ok(Some(Vec::new())),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lrange(key: *const c_char, start: i32, end: i32, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_lrange(default_instance(), key, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lrange(
+    db: *const Instance,
+    key: *const c_char,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_lrange(db, &key_str, start as i64, end as i64)
+        .map(|items| items.map(|items| pack_items(items.iter().map(|i| i.as_slice()))));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_lrange_b(default_instance(), key, key_len, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_lrange(db, &key_vec, start as i64, end as i64)
+        .map(|items| items.map(|items| pack_items(items.iter().map(|i| i.as_slice()))));
+    return_found(packed, out_len)
+}
+
+// Length of the list, 0 if the key is missing.
+fn cmd_llen<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<usize, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_list(db, &state, key)?.map_or(0, |list| list.len()))
+}
+
+// Returns the length, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_llen(key: *const c_char) -> i64 {
+    cache_instance_llen(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_llen(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    match cmd_llen(db, &key_str) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_llen_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_llen_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_llen_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    match cmd_llen(db, &key_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+fn cmd_lindex<K: CacheKey + ?Sized>(db: &Instance, key: &K, index: i64) -> Result<Option<Vec<u8>>, CacheError> {
+    let state = shard_read(db, key);
+    let Some(list) = read_list(db, &state, key)? else { return Ok(None); };
+    Ok(list_index(index, list.len()).map(|idx| list[idx].clone()))
+}
+
+// Null when the key is missing or the index is out of range.
+#[no_mangle]
+pub extern "C" fn cache_lindex(key: *const c_char, index: i32, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_lindex(default_instance(), key, index, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lindex(
+    db: *const Instance,
+    key: *const c_char,
+    index: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    return_found(cmd_lindex(db, &key_str, index as i64), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lindex_b(key: *const c_uchar, key_len: usize, index: i32, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_lindex_b(default_instance(), key, key_len, index, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lindex_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    index: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    return_found(cmd_lindex(db, &key_vec, index as i64), out_len)
+}
+
+fn cmd_lset<K: CacheKey + ?Sized>(db: &Instance, key: &K, index: i64, val: Vec<u8>) -> Result<(), CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    check_oom(db, &mut state, key)?;
+    apply_lset_internal(db, &mut state, key, index, val.clone())?;
+    aof_write_list_at(db, AOF_OP_LSET, key, index, &val);
+    Ok(())
+}
+
+// Returns 1, NotFound for a missing key, InvalidArgument for an index out of range, or
+// another negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_lset(key: *const c_char, index: i32, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_lset(default_instance(), key, index, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lset(
+    db: *const Instance,
+    key: *const c_char,
+    index: i32,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_lset(db, &key_str, index as i64, val_vec), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lset_b(
+    key: *const c_uchar,
+    key_len: usize,
+    index: i32,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    cache_instance_lset_b(default_instance(), key, key_len, index, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lset_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    index: i32,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_lset(db, &key_vec, index as i64, val_vec), |_| 1)
+}
+
+// Inserts `val` before or after the first occurrence of `pivot`. Returns the new length,
+// 0 if the key is missing, or -1 if the pivot is not in the list (as Redis does).
+fn cmd_linsert<K: CacheKey + ?Sized>(db: &Instance, key: &K, before: bool, pivot: &[u8], val: Vec<u8>) -> Result<i64, CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let pos = match state.map.peek(key.as_bytes()).map(|e| &e.value) {
+        Some(Value::List(list)) => list.iter().position(|i| i.as_slice() == pivot),
+        Some(_) => return Err(CacheError::WrongType),
+        None => return Ok(0),
+    };
+    let Some(pos) = pos else { return Ok(-1); };
+    check_oom(db, &mut state, key)?;
+    let pos = if before { pos } else { pos + 1 };
+    aof_write_list_at(db, AOF_OP_LINSERT, key, pos as i64, &val);
+    Ok(apply_linsert_internal(db, &mut state, key, pos, val)?.map_or(0, |len| len as i64))
+}
+
+// Returns the new length, 0 if the key is missing, NotFound if the pivot is not in the list,
+// or another negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_linsert(
+    key: *const c_char,
+    before: i32,
+    pivot: *const c_uchar,
+    pivot_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    cache_instance_linsert(default_instance(), key, before, pivot, pivot_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_linsert(
+    db: *const Instance,
+    key: *const c_char,
+    before: i32,
+    pivot: *const c_uchar,
+    pivot_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let pivot_vec = unsafe { to_bytes(pivot, pivot_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    match cmd_linsert(db, &key_str, before != 0, &pivot_vec, val_vec) {
+        Ok(-1) => fail(CacheError::NotFound) as i64,
+        Ok(len) => len,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_linsert_b(
+    key: *const c_uchar,
+    key_len: usize,
+    before: i32,
+    pivot: *const c_uchar,
+    pivot_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    cache_instance_linsert_b(default_instance(), key, key_len, before, pivot, pivot_len, value, len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_linsert_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    before: i32,
+    pivot: *const c_uchar,
+    pivot_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let pivot_vec = unsafe { to_bytes(pivot, pivot_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    match cmd_linsert(db, &key_vec, before != 0, &pivot_vec, val_vec) {
+        Ok(-1) => fail(CacheError::NotFound) as i64,
+        Ok(len) => len,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+fn cmd_lrem<K: CacheKey + ?Sized>(db: &Instance, key: &K, count: i64, val: &[u8]) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    let removed = apply_lrem_internal(db, &mut state, key, count, val)?;
+    if removed > 0 {
+        aof_write_lrem(db, key, count, val);
+    }
+    Ok(removed)
+}
+
+// Returns the number of removed items, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_lrem(key: *const c_char, count: i64, value: *const c_uchar, len: usize) -> i64 {
+    cache_instance_lrem(default_instance(), key, count, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lrem(
+    db: *const Instance,
+    key: *const c_char,
+    count: i64,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    match cmd_lrem(db, &key_str, count, &val_vec) {
+        Ok(removed) => removed as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lrem_b(
+    key: *const c_uchar,
+    key_len: usize,
+    count: i64,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    cache_instance_lrem_b(default_instance(), key, key_len, count, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lrem_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    count: i64,
+    value: *const c_uchar,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    match cmd_lrem(db, &key_vec, count, &val_vec) {
+        Ok(removed) => removed as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+fn cmd_ltrim<K: CacheKey + ?Sized>(db: &Instance, key: &K, start: i64, end: i64) -> Result<(), CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    if state.map.contains(key.as_bytes()) {
+        apply_ltrim_internal(db, &mut state, key, start, end)?;
+        aof_write_ltrim(db, key, start, end);
+    }
+    Ok(())
+}
+
+// Returns 1 (also for a missing key), or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_ltrim(key: *const c_char, start: i32, end: i32) -> i32 {
+    cache_instance_ltrim(default_instance(), key, start, end)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_ltrim(db: *const Instance, key: *const c_char, start: i32, end: i32) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    status(cmd_ltrim(db, &key_str, start as i64, end as i64), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_ltrim_b(key: *const c_uchar, key_len: usize, start: i32, end: i32) -> i32 {
+    cache_instance_ltrim_b(default_instance(), key, key_len, start, end)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_ltrim_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    status(cmd_ltrim(db, &key_vec, start as i64, end as i64), |_| 1)
+}
+
+// Atomically pops from one end of `src` and pushes onto one end of `dst` (which may be the
+// same list). Logged as the pop followed by the push. Ok(None) if `src` is missing.
+fn cmd_lmove<K: CacheKey + ?Sized>(db: &Instance, src: &K, dst: &K, from_left: bool, to_left: bool) -> Result<Option<Vec<u8>>, CacheError> {
+    let mut shards = shard_write_keys(db, &[src, dst]);
+    maybe_remove_if_expired(db, shards.shard(src), src);
+    match shards.shard(src).map.peek(src.as_bytes()).map(|e| &e.value) {
+        Some(Value::List(_)) => {}
+        Some(_) => return Err(CacheError::WrongType),
+        None => return Ok(None),
+    }
+    prepare_typed_write(db, shards.shard(dst), dst, Value::List(VecDeque::new()))?;
+    let Some(val) = apply_pop_internal(db, shards.shard(src), src, from_left, 1)?.and_then(|v| v.into_iter().next()) else {
+        return Ok(None);
+    };
+    aof_write_pop(db, src, from_left, 1);
+    aof_write_push(db, dst, to_left, &val);
+    apply_push_internal(db, shards.shard(dst), dst, to_left, val.clone())?;
+    drop(shards);
+    blocking::wake(db, dst);
+    Ok(Some(val))
+}
+
+// `from_left` / `to_left`: 1 for the head, 0 for the tail. Returns the moved item, or null
+// when `src` is missing.
+#[no_mangle]
+pub extern "C" fn cache_lmove(
+    src: *const c_char,
+    dst: *const c_char,
+    from_left: i32,
+    to_left: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_lmove(default_instance(), src, dst, from_left, to_left, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_lmove(
+    db: *const Instance,
+    src: *const c_char,
+    dst: *const c_char,
+    from_left: i32,
+    to_left: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let src_str = unsafe { to_string(src) };
+    let dst_str = unsafe { to_string(dst) };
+    return_found(cmd_lmove(db, &src_str, &dst_str, from_left != 0, to_left != 0), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_lmove_b(
+    src: *const c_uchar,
+    src_len: usize,
+    dst: *const c_uchar,
+    dst_len: usize,
+    from_left: i32,
+    to_left: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_lmove_b(default_instance(), src, src_len, dst, dst_len, from_left, to_left, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_lmove_b(
+    db: *const Instance,
+    src: *const c_uchar,
+    src_len: usize,
+    dst: *const c_uchar,
+    dst_len: usize,
+    from_left: i32,
+    to_left: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let src_vec = unsafe { to_bytes(src, src_len) };
+    let dst_vec = unsafe { to_bytes(dst, dst_len) };
+    return_found(cmd_lmove(db, &src_vec, &dst_vec, from_left != 0, to_left != 0), out_len)
+}
+
+// FFI timeouts: 0 blocks until data arrives, as in Redis.
+fn block_timeout(timeout_ms: u64) -> Option<Duration> {
+    (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms))
+}
+
+// Blocking pops take the keys packed like cache_lpush_multi's values and wait up to
+// `timeout_ms` for one of them to hold a list; waiters are served in arrival order. Returns
+// [2 u32] [KeyLen u32] [Key] [ValLen u32] [Val] (the cache_lrange layout), or null on timeout
+// (NotFound).
+#[no_mangle]
+pub extern "C" fn cache_blpop(keys: *const c_uchar, keys_len: usize, timeout_ms: u64, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_blpop(default_instance(), keys, keys_len, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_blpop(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    blocking_pop_ffi(db, unpack_keys, keys, keys_len, true, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_blpop_b(
+    keys: *const c_uchar,
+    keys_len: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_blpop_b(default_instance(), keys, keys_len, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_blpop_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    blocking_pop_ffi(db, unpack_keys_b, keys, keys_len, true, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_brpop(keys: *const c_uchar, keys_len: usize, timeout_ms: u64, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_brpop(default_instance(), keys, keys_len, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_brpop(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    blocking_pop_ffi(db, unpack_keys, keys, keys_len, false, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_brpop_b(
+    keys: *const c_uchar,
+    keys_len: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_brpop_b(default_instance(), keys, keys_len, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_brpop_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    blocking_pop_ffi(db, unpack_keys_b, keys, keys_len, false, timeout_ms, out_len)
+}
+
+fn blocking_pop_ffi<K: CacheKey + Clone>(
+    db: *const Instance,
+    unpack: fn(&[u8]) -> Result<Vec<K>, CacheError>,
+    keys: *const c_uchar,
+    keys_len: usize,
+    left: bool,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    let res = unpack(&packed).and_then(|keys| {
+        let popped = blocking::blocking_pop(db, &keys, left, block_timeout(timeout_ms), &|| false)?;
+        Ok(popped.map(|(key, val)| pack_items([key.as_bytes(), &val[..]].into_iter())))
+    });
+    return_found(res, out_len)
+}
+
+// Keys packed as [Count u32] { [Len u32] [Key] }.
+fn unpack_keys(packed: &[u8]) -> Result<Vec<String>, CacheError> {
+    Ok(unpack_keys_b(packed)?.into_iter().map(|k| String::from_utf8_lossy(&k).into_owned()).collect())
+}
+
+fn unpack_keys_b(packed: &[u8]) -> Result<Vec<Vec<u8>>, CacheError> {
+    let keys = unpack_items(packed)?;
+    if keys.is_empty() {
+        return Err(CacheError::invalid("no keys given"));
+    }
+    Ok(keys)
+}
+
+// --- Sets ---
+
+// Adds every member under one lock. Returns the number of members that were new.
+fn cmd_sadd<K: CacheKey + ?Sized>(db: &Instance, key: &K, members: Vec<Vec<u8>>) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::Set(HashSet::new()))?;
+    let mut added = 0;
+    for member in members {
+        if apply_sadd_internal(db, &mut state, key, member.clone())? {
+            aof_write_sadd(db, key, &member);
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+// Returns 1 if added, 0 if already a member, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_sadd(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_sadd(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sadd(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_sadd(db, &key_str, vec![val_vec]), |added| added as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sadd_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_sadd_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sadd_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_sadd(db, &key_vec, vec![val_vec]), |added| added as i32)
+}
+
+fn read_set<'a, K: CacheKey + ?Sized>(db: &Instance, state: &'a Shard, key: &K) -> Result<Option<&'a HashSet<Vec<u8>>>, CacheError> {
+    match get_entry(db, state, key).map(|e| &e.value) {
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn cmd_sismember<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: &[u8]) -> Result<bool, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_set(db, &state, key)?.is_some_and(|set| set.contains(val)))
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sismember(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_sismember(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sismember(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_sismember(db, &key_str, &val_vec), |found| found as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sismember_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_sismember_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sismember_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_sismember(db, &key_vec, &val_vec), |found| found as i32)
+}
+
+fn cmd_smismember<K: CacheKey + ?Sized>(db: &Instance, key: &K, members: &[Vec<u8>]) -> Result<Vec<bool>, CacheError> {
+    let state = shard_read(db, key);
+    let set = read_set(db, &state, key)?;
+    Ok(members.iter().map(|m| set.is_some_and(|set| set.contains(m))).collect())
+}
+
+// Members packed like cache_lpush_multi's values; replies with one byte per member, 1 if it
+// belongs to the set and 0 if not.
+#[no_mangle]
+pub extern "C" fn cache_smismember(
+    key: *const c_char,
+    members: *const c_uchar,
+    members_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_smismember(default_instance(), key, members, members_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_smismember(
+    db: *const Instance,
+    key: *const c_char,
+    members: *const c_uchar,
+    members_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = unsafe { to_bytes(members, members_len) };
+    let res = unpack_members(&packed).and_then(|members| cmd_smismember(db, &key_str, &members));
+    return_found(res.map(|found| Some(found.into_iter().map(u8::from).collect())), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_smismember_b(
+    key: *const c_uchar,
+    key_len: usize,
+    members: *const c_uchar,
+    members_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_smismember_b(default_instance(), key, key_len, members, members_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_smismember_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    members: *const c_uchar,
+    members_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = unsafe { to_bytes(members, members_len) };
+    let res = unpack_members(&packed).and_then(|members| cmd_smismember(db, &key_vec, &members));
+    return_found(res.map(|found| Some(found.into_iter().map(u8::from).collect())), out_len)
+}
+
+// Members packed as [Count u32] { [Len u32] [Member] }; at least one is required.
+fn unpack_members(packed: &[u8]) -> Result<Vec<Vec<u8>>, CacheError> {
+    let members = unpack_items(packed)?;
+    if members.is_empty() {
+        return Err(CacheError::invalid("no members given"));
+    }
+    Ok(members)
+}
+
+// Removes members under one lock; a set left empty is removed. Returns how many were members.
+fn cmd_srem<K: CacheKey + ?Sized>(db: &Instance, key: &K, members: &[Vec<u8>]) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    let mut removed = 0;
+    for member in members {
+        if apply_srem_internal(db, &mut state, key, member)? {
+            aof_write_key_item(db, AOF_OP_SREM, key, member);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// Returns 1 if removed, 0 if not a member, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_srem(key: *const c_char, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_srem(default_instance(), key, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_srem(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_srem(db, &key_str, &[val_vec]), |n| n as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_srem_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_srem_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_srem_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_srem(db, &key_vec, &[val_vec]), |n| n as i32)
+}
+
+fn cmd_smembers<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_set(db, &state, key)?.map(|set| set.iter().cloned().collect()))
+}
+
+// Members as packed items (in no particular order), or null (NotFound) for a missing key.
+#[no_mangle]
+pub extern "C" fn cache_smembers(key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_smembers(default_instance(), key, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_smembers(db: *const Instance, key: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_smembers(db, &key_str).map(|members| members.map(|m| pack_items(m.iter().map(|m| &m[..]))));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_smembers_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_smembers_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_smembers_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_smembers(db, &key_vec).map(|members| members.map(|m| pack_items(m.iter().map(|m| &m[..]))));
+    return_found(packed, out_len)
+}
+
+fn cmd_scard<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<usize, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_set(db, &state, key)?.map_or(0, |set| set.len()))
+}
+
+// Returns the number of members, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_scard(key: *const c_char) -> i64 {
+    cache_instance_scard(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_scard(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    match cmd_scard(db, &key_str) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_scard_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_scard_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_scard_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    match cmd_scard(db, &key_vec) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Up to `count` distinct members picked uniformly at random (reservoir sampling). A HashSet
+// has no random access, so this walks the whole set.
+fn sample_members(set: &HashSet<Vec<u8>>, count: usize) -> Vec<&Vec<u8>> {
+    if count >= set.len() {
+        return set.iter().collect();
+    }
+    let mut picked = Vec::with_capacity(count);
+    for (i, member) in set.iter().enumerate() {
+        if i < count {
+            picked.push(member);
+        } else {
+            let j = (eviction::random_u64() % (i as u64 + 1)) as usize;
+            if j < count {
+                picked[j] = member;
+            }
+        }
+    }
+    picked
+}
+
+// Removes and returns up to `count` random members; a set left empty is removed. None if the
+// key is missing. The popped members are logged as SREMs, so replay does not depend on chance.
+fn cmd_spop<K: CacheKey + ?Sized>(db: &Instance, key: &K, count: usize) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    let mut state = shard_write(db, key);
+    let popped = update_collection(db, &mut state, key, |set: &mut HashSet<Vec<u8>>| {
+        let picked: Vec<Vec<u8>> = sample_members(set, count).into_iter().cloned().collect();
+        let mut freed = 0;
+        for member in &picked {
+            set.remove(member);
+            freed += item_mem(member);
+        }
+        (picked, -(freed as isize))
+    })?;
+    for member in popped.iter().flatten() {
+        aof_write_key_item(db, AOF_OP_SREM, key, member);
+    }
+    Ok(popped)
+}
+
+// Pops up to `count` random members. Returns them as packed items, or null (NotFound) for a
+// missing key.
+#[no_mangle]
+pub extern "C" fn cache_spop(key: *const c_char, count: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_spop(default_instance(), key, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_spop(
+    db: *const Instance,
+    key: *const c_char,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_spop(db, &key_str, count).map(|members| members.map(|m| pack_items(m.iter().map(|m| &m[..]))));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_spop_b(key: *const c_uchar, key_len: usize, count: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_spop_b(default_instance(), key, key_len, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_spop_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_spop(db, &key_vec, count).map(|members| members.map(|m| pack_items(m.iter().map(|m| &m[..]))));
+    return_found(packed, out_len)
+}
+
+// Random members without removing them: up to `count` distinct ones for a positive count, or
+// exactly -`count` picks that may repeat for a negative one (Redis' SRANDMEMBER). None if the
+// key is missing.
+fn cmd_srandmember<K: CacheKey + ?Sized>(db: &Instance, key: &K, count: i64) -> Result<Option<Vec<Vec<u8>>>, CacheError> {
+    // Replies count their items in a u32.
+    if count.unsigned_abs() > u32::MAX as u64 {
+        return Err(CacheError::invalid("value is out of range"));
+    }
+    let state = shard_read(db, key);
+    let Some(set) = read_set(db, &state, key)? else { return Ok(None); };
+    if count >= 0 {
+        return Ok(Some(sample_members(set, count as usize).into_iter().cloned().collect()));
+    }
+    let members: Vec<&Vec<u8>> = set.iter().collect();
+    let pick = || members[(eviction::random_u64() % members.len() as u64) as usize].clone();
+    Ok(Some((0..count.unsigned_abs()).map(|_| pick()).collect()))
+}
+
+// Returns the picked members as packed items, or null (NotFound) for a missing key.
+#[no_mangle]
+pub extern "C" fn cache_srandmember(key: *const c_char, count: i64, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_srandmember(default_instance(), key, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_srandmember(
+    db: *const Instance,
+    key: *const c_char,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_srandmember(db, &key_str, count).map(|members| members.map(|m| pack_items(m.iter().map(|m| &m[..]))));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_srandmember_b(
+    key: *const c_uchar,
+    key_len: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_srandmember_b(default_instance(), key, key_len, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_srandmember_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_srandmember(db, &key_vec, count).map(|members| members.map(|m| pack_items(m.iter().map(|m| &m[..]))));
+    return_found(packed, out_len)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SetOp {
+    Union,
+    Inter,
+    Diff,
+}
+
+// Folds the sets of SUNION / SINTER / SDIFF (missing keys are empty sets); SDIFF subtracts the
+// others from the first.
+fn combine_sets<'a>(op: SetOp, sets: impl IntoIterator<Item = Option<&'a HashSet<Vec<u8>>>>) -> HashSet<Vec<u8>> {
+    let mut sets = sets.into_iter();
+    let mut result = sets.next().flatten().cloned().unwrap_or_default();
+    for set in sets {
+        match (op, set) {
+            (SetOp::Union, Some(set)) => result.extend(set.iter().cloned()),
+            (SetOp::Inter, Some(set)) => result.retain(|m| set.contains(m)),
+            (SetOp::Inter, None) => result.clear(),
+            (SetOp::Diff, Some(set)) => result.retain(|m| !set.contains(m)),
+            (SetOp::Union | SetOp::Diff, None) => {}
+        }
+    }
+    result
+}
+
+// Reads every set under one set of shared locks, so the result is a consistent snapshot.
+fn cmd_set_op<K: CacheKey>(db: &Instance, op: SetOp, keys: &[K]) -> Result<Vec<Vec<u8>>, CacheError> {
+    let shards = shard_read_keys(db, keys);
+    let sets = keys.iter().map(|k| read_set(db, shards.shard(k), k)).collect::<Result<Vec<_>, _>>()?;
+    Ok(combine_sets(op, sets).into_iter().collect())
+}
+
+// Stores the result at `dst` under the write locks of every key involved, replacing whatever
+// `dst` held (its TTL included); an empty result removes `dst`. Returns the result's size.
+fn cmd_set_op_store<K: CacheKey>(db: &Instance, op: SetOp, dst: &K, keys: &[K]) -> Result<usize, CacheError> {
+    let names: Vec<&K> = keys.iter().chain([dst]).collect();
+    let mut shards = shard_write_keys(db, &names);
+    let sets = keys.iter().map(|k| read_set(db, shards.shard_ref(k), k)).collect::<Result<Vec<_>, _>>()?;
+    let result = combine_sets(op, sets);
+
+    let state = shards.shard(dst);
+    maybe_remove_if_expired(db, state, dst);
+    if result.is_empty() {
+        if apply_remove_internal(db, state, dst) {
+            aof_write_remove(db, dst);
+        }
+        return Ok(0);
+    }
+    check_oom(db, state, dst)?;
+    // One write, so the removal and the new members reach the log together.
+    let mut buf = Vec::new();
+    aof_encode_remove(&mut buf, dst);
+    for member in &result {
+        aof_encode_sadd(&mut buf, dst, member);
+    }
+    aof_write(db, &buf);
+    let len = result.len();
+    put_entry_with_lru(db, state, dst, Entry::new(Value::Set(result), None));
+    Ok(len)
+}
+
+// Set algebra takes the keys packed like cache_blpop's; missing keys count as empty sets and a
+// key of another type fails with WrongType. Returns the resulting members as packed items.
+#[no_mangle]
+pub extern "C" fn cache_sunion(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_sunion(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sunion(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    set_op_ffi(db, SetOp::Union, unpack_keys, keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sunion_b(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_sunion_b(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sunion_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    set_op_ffi(db, SetOp::Union, unpack_keys_b, keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sinter(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_sinter(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sinter(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    set_op_ffi(db, SetOp::Inter, unpack_keys, keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sinter_b(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_sinter_b(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sinter_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    set_op_ffi(db, SetOp::Inter, unpack_keys_b, keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sdiff(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_sdiff(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sdiff(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    set_op_ffi(db, SetOp::Diff, unpack_keys, keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sdiff_b(keys: *const c_uchar, keys_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_sdiff_b(default_instance(), keys, keys_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sdiff_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    set_op_ffi(db, SetOp::Diff, unpack_keys_b, keys, keys_len, out_len)
+}
+
+fn set_op_ffi<K: CacheKey>(
+    db: *const Instance,
+    op: SetOp,
+    unpack: fn(&[u8]) -> Result<Vec<K>, CacheError>,
+    keys: *const c_uchar,
+    keys_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    let res = unpack(&packed).and_then(|keys| cmd_set_op(db, op, &keys));
+    return_found(res.map(|members| Some(pack_items(members.iter().map(|m| &m[..])))), out_len)
+}
+
+// The *STORE variants write the result to `dst` atomically. Return the size of the stored
+// set, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_sunionstore(dst: *const c_char, keys: *const c_uchar, keys_len: usize) -> i64 {
+    cache_instance_sunionstore(default_instance(), dst, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sunionstore(
+    db: *const Instance,
+    dst: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let dst_str = unsafe { to_string(dst) };
+    set_op_store_ffi(db, SetOp::Union, &dst_str, unpack_keys, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sunionstore_b(
+    dst: *const c_uchar,
+    dst_len: usize,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    cache_instance_sunionstore_b(default_instance(), dst, dst_len, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sunionstore_b(
+    db: *const Instance,
+    dst: *const c_uchar,
+    dst_len: usize,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let dst_vec = unsafe { to_bytes(dst, dst_len) };
+    set_op_store_ffi(db, SetOp::Union, &dst_vec, unpack_keys_b, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sinterstore(dst: *const c_char, keys: *const c_uchar, keys_len: usize) -> i64 {
+    cache_instance_sinterstore(default_instance(), dst, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sinterstore(
+    db: *const Instance,
+    dst: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let dst_str = unsafe { to_string(dst) };
+    set_op_store_ffi(db, SetOp::Inter, &dst_str, unpack_keys, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sinterstore_b(
+    dst: *const c_uchar,
+    dst_len: usize,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    cache_instance_sinterstore_b(default_instance(), dst, dst_len, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sinterstore_b(
+    db: *const Instance,
+    dst: *const c_uchar,
+    dst_len: usize,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let dst_vec = unsafe { to_bytes(dst, dst_len) };
+    set_op_store_ffi(db, SetOp::Inter, &dst_vec, unpack_keys_b, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sdiffstore(dst: *const c_char, keys: *const c_uchar, keys_len: usize) -> i64 {
+    cache_instance_sdiffstore(default_instance(), dst, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sdiffstore(
+    db: *const Instance,
+    dst: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let dst_str = unsafe { to_string(dst) };
+    set_op_store_ffi(db, SetOp::Diff, &dst_str, unpack_keys, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_sdiffstore_b(
+    dst: *const c_uchar,
+    dst_len: usize,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    cache_instance_sdiffstore_b(default_instance(), dst, dst_len, keys, keys_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_sdiffstore_b(
+    db: *const Instance,
+    dst: *const c_uchar,
+    dst_len: usize,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let dst_vec = unsafe { to_bytes(dst, dst_len) };
+    set_op_store_ffi(db, SetOp::Diff, &dst_vec, unpack_keys_b, keys, keys_len)
+}
+
+fn set_op_store_ffi<K: CacheKey>(
+    db: *const Instance,
+    op: SetOp,
+    dst: &K,
+    unpack: fn(&[u8]) -> Result<Vec<K>, CacheError>,
+    keys: *const c_uchar,
+    keys_len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    match unpack(&packed).and_then(|keys| cmd_set_op_store(db, op, dst, &keys)) {
+        Ok(len) => len as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// --- Sorted Sets ---
+
+// ZADD condition bits for `cache_zadd_flags` (Redis' NX / XX / GT / LT).
+pub const ZADD_NX: i32 = 1;
+pub const ZADD_XX: i32 = 2;
+pub const ZADD_GT: i32 = 4;
+pub const ZADD_LT: i32 = 8;
+
+// Range option bits for `cache_zrangebyscore`.
+pub const ZRANGE_MIN_EXCLUSIVE: i32 = 1;
+pub const ZRANGE_MAX_EXCLUSIVE: i32 = 2;
+pub const ZRANGE_REV: i32 = 4;
+
+// NX only adds new members, XX only updates existing ones; GT / LT only update a member
+// when the new score is greater / less than the current one (new members are still added).
+#[derive(Clone, Copy, Default)]
+pub(crate) struct ZAddFlags {
+    pub(crate) nx: bool,
+    pub(crate) xx: bool,
+    pub(crate) gt: bool,
+    pub(crate) lt: bool,
+}
+
+impl ZAddFlags {
+    fn from_bits(bits: i32) -> ZAddFlags {
+        ZAddFlags {
+            nx: bits & ZADD_NX != 0,
+            xx: bits & ZADD_XX != 0,
+            gt: bits & ZADD_GT != 0,
+            lt: bits & ZADD_LT != 0,
+        }
+    }
+
+    fn validate(&self) -> Result<(), CacheError> {
+        if self.nx && self.xx {
+            return Err(CacheError::invalid("XX and NX options at the same time are not compatible"));
+        }
+        if (self.gt && self.lt) || (self.nx && (self.gt || self.lt)) {
+            return Err(CacheError::invalid("GT, LT, and/or NX options at the same time are not compatible"));
+        }
+        Ok(())
+    }
+
+    // Whether a member currently scored `old` (None = absent) may be set to `score`.
+    fn allows(&self, old: Option<f64>, score: f64) -> bool {
+        match old {
+            None => !self.xx,
+            Some(_) if self.nx => false,
+            Some(old) => !(self.gt && score <= old || self.lt && score >= old),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ZAddOutcome {
+    pub(crate) added: usize,
+    // Existing members whose score changed.
+    pub(crate) updated: usize,
+}
+
+fn check_score(score: f64) -> Result<(), CacheError> {
+    if score.is_nan() {
+        return Err(CacheError::invalid("value is not a valid float"));
+    }
+    Ok(())
+}
+
+fn read_zset<'a, K: CacheKey + ?Sized>(db: &Instance, state: &'a Shard, key: &K) -> Result<Option<&'a SortedSet>, CacheError> {
+    match get_entry(db, state, key).map(|e| &e.value) {
+        Some(Value::SortedSet(ss)) => Ok(Some(ss)),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(None),
+    }
+}
+
+// Adds or updates every member under one lock. Returns the number of new members.
+fn cmd_zadd<K: CacheKey + ?Sized>(db: &Instance, key: &K, members: Vec<(f64, Vec<u8>)>) -> Result<usize, CacheError> {
+    Ok(cmd_zadd_flags(db, key, members, ZAddFlags::default())?.added)
+}
+
+// ZADD with conditions. Only members whose score actually changes are logged.
+fn cmd_zadd_flags<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    members: Vec<(f64, Vec<u8>)>,
+    flags: ZAddFlags,
+) -> Result<ZAddOutcome, CacheError> {
+    flags.validate()?;
+    for (score, _) in &members {
+        check_score(*score)?;
+    }
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::SortedSet(SortedSet::default()))?;
+    let mut outcome = ZAddOutcome::default();
+    for (score, member) in members {
+        let old = read_zset(db, &state, key)?.and_then(|ss| ss.score(&member));
+        if !flags.allows(old, score) || old == Some(score) {
+            continue;
+        }
+        aof_write_zadd(db, key, score, &member);
+        apply_zadd_internal(db, &mut state, key, score, member)?;
+        match old {
+            Some(_) => outcome.updated += 1,
+            None => outcome.added += 1,
+        }
+    }
+    Ok(outcome)
+}
+
+// Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_zadd(key: *const c_char, score: f64, member: *const c_char) -> i32 {
+    cache_instance_zadd(default_instance(), key, score, member)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zadd(
+    db: *const Instance,
+    key: *const c_char,
+    score: f64,
+    member: *const c_char,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zadd(db, &key_str, vec![(score, member_vec)]), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zadd_b(key: *const c_uchar, key_len: usize, score: f64, member: *const c_char) -> i32 {
+    cache_instance_zadd_b(default_instance(), key, key_len, score, member)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zadd_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_char,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zadd(db, &key_vec, vec![(score, member_vec)]), |_| 1)
+}
+
+// Like cache_zadd_b, with the member also passed as a pointer and a length so it may hold NULs
+// or bytes that are not UTF-8. The other `_bm` sorted-set exports take their members the same way.
+#[no_mangle]
+pub extern "C" fn cache_zadd_bm(
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_uchar,
+    member_len: usize,
+) -> i32 {
+    cache_instance_zadd_bm(default_instance(), key, key_len, score, member, member_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zadd_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_uchar,
+    member_len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_bytes(member, member_len) };
+    status(cmd_zadd(db, &key_vec, vec![(score, member_vec)]), |_| 1)
+}
+
+// ZADD with ZADD_* condition bits. Returns 1 if the member was added or its score changed,
+// 0 if the conditions left it untouched, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_zadd_flags(key: *const c_char, score: f64, member: *const c_char, flags: i32) -> i32 {
+    cache_instance_zadd_flags(default_instance(), key, score, member, flags)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zadd_flags(
+    db: *const Instance,
+    key: *const c_char,
+    score: f64,
+    member: *const c_char,
+    flags: i32,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    let res = cmd_zadd_flags(db, &key_str, vec![(score, member_vec)], ZAddFlags::from_bits(flags));
+    status(res, |o| (o.added + o.updated) as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zadd_flags_b(
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_char,
+    flags: i32,
+) -> i32 {
+    cache_instance_zadd_flags_b(default_instance(), key, key_len, score, member, flags)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zadd_flags_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_char,
+    flags: i32,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    let res = cmd_zadd_flags(db, &key_vec, vec![(score, member_vec)], ZAddFlags::from_bits(flags));
+    status(res, |o| (o.added + o.updated) as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zadd_flags_bm(
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_uchar,
+    member_len: usize,
+    flags: i32,
+) -> i32 {
+    cache_instance_zadd_flags_bm(default_instance(), key, key_len, score, member, member_len, flags)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_zadd_flags_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    score: f64,
+    member: *const c_uchar,
+    member_len: usize,
+    flags: i32,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_bytes(member, member_len) };
+    let res = cmd_zadd_flags(db, &key_vec, vec![(score, member_vec)], ZAddFlags::from_bits(flags));
+    status(res, |o| (o.added + o.updated) as i32)
+}
+
+// Removes members under one lock. Returns how many were present.
+fn cmd_zrem<K: CacheKey + ?Sized>(db: &Instance, key: &K, members: &[Vec<u8>]) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    let mut removed = 0;
+    for member in members {
+        if apply_zrem_internal(db, &mut state, key, member)? {
+            aof_write_key_item(db, AOF_OP_ZREM, key, member);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// Returns 1 if removed, 0 if not a member, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_zrem(key: *const c_char, member: *const c_char) -> i32 {
+    cache_instance_zrem(default_instance(), key, member)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrem(db: *const Instance, key: *const c_char, member: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zrem(db, &key_str, &[member_vec]), |n| n as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrem_b(key: *const c_uchar, key_len: usize, member: *const c_char) -> i32 {
+    cache_instance_zrem_b(default_instance(), key, key_len, member)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrem_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_char,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zrem(db, &key_vec, &[member_vec]), |n| n as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrem_bm(key: *const c_uchar, key_len: usize, member: *const c_uchar, member_len: usize) -> i32 {
+    cache_instance_zrem_bm(default_instance(), key, key_len, member, member_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrem_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_uchar,
+    member_len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_bytes(member, member_len) };
+    status(cmd_zrem(db, &key_vec, &[member_vec]), |n| n as i32)
+}
+
+// Adds `incr` to the member's score (a missing member starts at 0) and logs the result.
+fn cmd_zincrby<K: CacheKey + ?Sized>(db: &Instance, key: &K, incr: f64, member: Vec<u8>) -> Result<f64, CacheError> {
+    check_score(incr)?;
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::SortedSet(SortedSet::default()))?;
+    let old = read_zset(db, &state, key)?.and_then(|ss| ss.score(&member));
+    let score = old.unwrap_or(0.0) + incr;
+    if score.is_nan() {
+        return Err(CacheError::invalid("resulting score is not a number (NaN)"));
+    }
+    aof_write_zadd(db, key, score, &member);
+    apply_zadd_internal(db, &mut state, key, score, member)?;
+    Ok(score)
+}
+
+// Writes the new score to `score_out`. Returns 1, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_zincrby(key: *const c_char, incr: f64, member: *const c_char, score_out: *mut f64) -> i32 {
+    cache_instance_zincrby(default_instance(), key, incr, member, score_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zincrby(
+    db: *const Instance,
+    key: *const c_char,
+    incr: f64,
+    member: *const c_char,
+    score_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zincrby(db, &key_str, incr, member_vec), |score| {
+        if !score_out.is_null() {
+            unsafe { *score_out = score };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zincrby_b(
+    key: *const c_uchar,
+    key_len: usize,
+    incr: f64,
+    member: *const c_char,
+    score_out: *mut f64,
+) -> i32 {
+    cache_instance_zincrby_b(default_instance(), key, key_len, incr, member, score_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zincrby_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    incr: f64,
+    member: *const c_char,
+    score_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zincrby(db, &key_vec, incr, member_vec), |score| {
+        if !score_out.is_null() {
+            unsafe { *score_out = score };
+        }
+        1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zincrby_bm(
+    key: *const c_uchar,
+    key_len: usize,
+    incr: f64,
+    member: *const c_uchar,
+    member_len: usize,
+    score_out: *mut f64,
+) -> i32 {
+    cache_instance_zincrby_bm(default_instance(), key, key_len, incr, member, member_len, score_out)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_zincrby_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    incr: f64,
+    member: *const c_uchar,
+    member_len: usize,
+    score_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_bytes(member, member_len) };
+    status(cmd_zincrby(db, &key_vec, incr, member_vec), |score| {
+        if !score_out.is_null() {
+            unsafe { *score_out = score };
+        }
+        1
+    })
+}
+
+fn cmd_zscore<K: CacheKey + ?Sized>(db: &Instance, key: &K, member: &[u8]) -> Result<Option<f64>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_zset(db, &state, key)?.and_then(|ss| ss.score(member)))
+}
+
+// Writes the score to `score_out`. Returns 1 if found, 0 if the key or member is missing,
+// or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_zscore(key: *const c_char, member: *const c_char, score_out: *mut f64) -> i32 {
+    cache_instance_zscore(default_instance(), key, member, score_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zscore(
+    db: *const Instance,
+    key: *const c_char,
+    member: *const c_char,
+    score_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zscore(db, &key_str, &member_vec), |score| match score {
+        Some(score) if !score_out.is_null() => {
+            unsafe { *score_out = score };
+            1
+        }
+        Some(_) => 1,
+        None => 0,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zscore_b(
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_char,
+    score_out: *mut f64,
+) -> i32 {
+    cache_instance_zscore_b(default_instance(), key, key_len, member, score_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zscore_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_char,
+    score_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    status(cmd_zscore(db, &key_vec, &member_vec), |score| match score {
+        Some(score) if !score_out.is_null() => {
+            unsafe { *score_out = score };
+            1
+        }
+        Some(_) => 1,
+        None => 0,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zscore_bm(
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_uchar,
+    member_len: usize,
+    score_out: *mut f64,
+) -> i32 {
+    cache_instance_zscore_bm(default_instance(), key, key_len, member, member_len, score_out)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zscore_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_uchar,
+    member_len: usize,
+    score_out: *mut f64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_bytes(member, member_len) };
+    status(cmd_zscore(db, &key_vec, &member_vec), |score| match score {
+        Some(score) if !score_out.is_null() => {
+            unsafe { *score_out = score };
+            1
+        }
+        Some(_) => 1,
+        None => 0,
+    })
+}
+
+// 0-based rank by ascending score (descending when `rev`).
+fn cmd_zrank<K: CacheKey + ?Sized>(db: &Instance, key: &K, member: &[u8], rev: bool) -> Result<Option<usize>, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_zset(db, &state, key)?.and_then(|ss| ss.rank(member, rev)))
+}
+
+// Returns the rank, or a negative CacheStatus (NotFound if the key or member is missing).
+#[no_mangle]
+pub extern "C" fn cache_zrank(key: *const c_char, member: *const c_char, rev: i32) -> i64 {
+    cache_instance_zrank(default_instance(), key, member, rev)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrank(db: *const Instance, key: *const c_char, member: *const c_char, rev: i32) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    match cmd_zrank(db, &key_str, &member_vec, rev != 0) {
+        Ok(Some(rank)) => rank as i64,
+        Ok(None) => fail(CacheError::NotFound) as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrank_b(key: *const c_uchar, key_len: usize, member: *const c_char, rev: i32) -> i64 {
+    cache_instance_zrank_b(default_instance(), key, key_len, member, rev)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrank_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_char,
+    rev: i32,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_c_bytes(member) };
+    match cmd_zrank(db, &key_vec, &member_vec, rev != 0) {
+        Ok(Some(rank)) => rank as i64,
+        Ok(None) => fail(CacheError::NotFound) as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrank_bm(
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_uchar,
+    member_len: usize,
+    rev: i32,
+) -> i64 {
+    cache_instance_zrank_bm(default_instance(), key, key_len, member, member_len, rev)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrank_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    member: *const c_uchar,
+    member_len: usize,
+    rev: i32,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let member_vec = unsafe { to_bytes(member, member_len) };
+    match cmd_zrank(db, &key_vec, &member_vec, rev != 0) {
+        Ok(Some(rank)) => rank as i64,
+        Ok(None) => fail(CacheError::NotFound) as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+fn cmd_zcard<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<usize, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_zset(db, &state, key)?.map_or(0, |ss| ss.len()))
+}
+
+// Returns the number of members, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_zcard(key: *const c_char) -> i64 {
+    cache_instance_zcard(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zcard(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    match cmd_zcard(db, &key_str) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zcard_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_zcard_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zcard_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    match cmd_zcard(db, &key_vec) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Members at Redis-style ranks `start..=end`, counted from the highest score when `rev`.
+fn cmd_zrange<K: CacheKey + ?Sized>(db: &Instance, key: &K, start: i64, end: i64, rev: bool) -> Result<Option<ScoredMembers>, CacheError> {
+    let state = shard_read(db, key);
+    let Some(ss) = read_zset(db, &state, key)? else { return Ok(None); };
+    match normalize_range(start, end, ss.len()) {
+        Some((low, high)) => Ok(Some(ss.range(low, high, rev))),
+        None => Ok(Some(Vec::new())),
+    }
+}
+
+fn cmd_zrangebyscore<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    min: ScoreBound,
+    max: ScoreBound,
+    rev: bool,
+    offset: usize,
+    count: Option<usize>,
+) -> Result<ScoredMembers, CacheError> {
+    check_score(min.value)?;
+    check_score(max.value)?;
+    let state = shard_read(db, key);
+    Ok(read_zset(db, &state, key)?.map_or_else(Vec::new, |ss| ss.range_by_score(min, max, rev, offset, count)))
+}
+
+fn cmd_zrangebylex<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    min: &LexBound,
+    max: &LexBound,
+    rev: bool,
+    offset: usize,
+    count: Option<usize>,
+) -> Result<ScoredMembers, CacheError> {
+    let state = shard_read(db, key);
+    Ok(read_zset(db, &state, key)?.map_or_else(Vec::new, |ss| ss.range_by_lex(min, max, rev, offset, count)))
+}
+
+// Serializes members with scores as: [Count u32] { [Len u32] [Member] [Score f64] }.
+fn pack_scored(members: &[(Vec<u8>, f64)]) -> Vec<u8> {
+    let mut flat = Vec::new();
+    flat.extend_from_slice(&(members.len() as u32).to_le_bytes());
+    for (member, score) in members {
+        flat.extend_from_slice(&(member.len() as u32).to_le_bytes());
+        flat.extend_from_slice(member);
+        flat.extend_from_slice(&score.to_le_bytes());
+    }
+    flat
+}
+
+fn pack_members(members: &[(Vec<u8>, f64)]) -> Vec<u8> {
+    pack_items(members.iter().map(|(m, _)| m.as_slice()))
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrange(key: *const c_char, start: i32, end: i32, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_zrange(default_instance(), key, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrange(
+    db: *const Instance,
+    key: *const c_char,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_zrange(db, &key_str, start as i64, end as i64, false).map(|members| members.map(|m| pack_members(&m)));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrange_b(default_instance(), key, key_len, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_zrange(db, &key_vec, start as i64, end as i64, false).map(|members| members.map(|m| pack_members(&m)));
+    return_found(packed, out_len)
+}
+
+// Like cache_zrange, from the highest score down.
+#[no_mangle]
+pub extern "C" fn cache_zrevrange(key: *const c_char, start: i32, end: i32, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_zrevrange(default_instance(), key, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrevrange(
+    db: *const Instance,
+    key: *const c_char,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_zrange(db, &key_str, start as i64, end as i64, true).map(|members| members.map(|m| pack_members(&m)));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrevrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrevrange_b(default_instance(), key, key_len, start, end, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrevrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_zrange(db, &key_vec, start as i64, end as i64, true).map(|members| members.map(|m| pack_members(&m)));
+    return_found(packed, out_len)
+}
+
+// Members with scores at ranks `start..=end`, in the pack_scored layout.
+#[no_mangle]
+pub extern "C" fn cache_zrange_withscores(
+    key: *const c_char,
+    start: i32,
+    end: i32,
+    rev: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrange_withscores(default_instance(), key, start, end, rev, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrange_withscores(
+    db: *const Instance,
+    key: *const c_char,
+    start: i32,
+    end: i32,
+    rev: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let packed = cmd_zrange(db, &key_str, start as i64, end as i64, rev != 0).map(|members| members.map(|m| pack_scored(&m)));
+    return_found(packed, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_zrange_withscores_b(
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    rev: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrange_withscores_b(default_instance(), key, key_len, start, end, rev, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_zrange_withscores_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start: i32,
+    end: i32,
+    rev: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let packed = cmd_zrange(db, &key_vec, start as i64, end as i64, rev != 0).map(|members| members.map(|m| pack_scored(&m)));
+    return_found(packed, out_len)
+}
+
+// Members with `min <= score <= max` (ZRANGE_* bits make either end exclusive or walk from
+// `max` down), skipping `offset` and returning at most `count` (negative = all), in the
+// pack_scored layout.
+#[no_mangle]
+pub extern "C" fn cache_zrangebyscore(
+    key: *const c_char,
+    min: f64,
+    max: f64,
+    flags: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrangebyscore(default_instance(), key, min, max, flags, offset, count, out_len)
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cache_instance_zrangebyscore(
+    db: *const Instance,
+    key: *const c_char,
+    min: f64,
+    max: f64,
+    flags: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let min = ScoreBound { value: min, exclusive: flags & ZRANGE_MIN_EXCLUSIVE != 0 };
+    let max = ScoreBound { value: max, exclusive: flags & ZRANGE_MAX_EXCLUSIVE != 0 };
+    let rev = flags & ZRANGE_REV != 0;
+    let res = cmd_zrangebyscore(db, &key_str, min, max, rev, offset, usize::try_from(count).ok());
+    return_found(res.map(|members| Some(pack_scored(&members))), out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_zrangebyscore_b(
+    key: *const c_uchar,
+    key_len: usize,
+    min: f64,
+    max: f64,
+    flags: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrangebyscore_b(default_instance(), key, key_len, min, max, flags, offset, count, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_zrangebyscore_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    min: f64,
+    max: f64,
+    flags: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let min = ScoreBound { value: min, exclusive: flags & ZRANGE_MIN_EXCLUSIVE != 0 };
+    let max = ScoreBound { value: max, exclusive: flags & ZRANGE_MAX_EXCLUSIVE != 0 };
+    let rev = flags & ZRANGE_REV != 0;
+    let res = cmd_zrangebyscore(db, &key_vec, min, max, rev, offset, usize::try_from(count).ok());
+    return_found(res.map(|members| Some(pack_scored(&members))), out_len)
+}
+
+// Members between the lex bounds `min` and `max` (`-`, `+`, `[member` or `(member`), walking
+// from `max` down when `rev`, with the same offset / count as cache_zrangebyscore.
+#[no_mangle]
+pub extern "C" fn cache_zrangebylex(
+    key: *const c_char,
+    min: *const c_char,
+    max: *const c_char,
+    rev: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrangebylex(default_instance(), key, min, max, rev, offset, count, out_len)
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn cache_instance_zrangebylex(
+    db: *const Instance,
+    key: *const c_char,
+    min: *const c_char,
+    max: *const c_char,
+    rev: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let bound = |p| {
+        LexBound::parse(&unsafe { to_c_bytes(p) }).ok_or_else(|| CacheError::invalid("min or max not valid string range item"))
+    };
+    let res = bound(min).and_then(|min| {
+        let max = bound(max)?;
+        cmd_zrangebylex(db, &key_str, &min, &max, rev != 0, offset, usize::try_from(count).ok())
+    });
+    return_found(res.map(|members| Some(pack_members(&members))), out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_zrangebylex_b(
+    key: *const c_uchar,
+    key_len: usize,
+    min: *const c_char,
+    max: *const c_char,
+    rev: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrangebylex_b(default_instance(), key, key_len, min, max, rev, offset, count, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_zrangebylex_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    min: *const c_char,
+    max: *const c_char,
+    rev: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let bound = |p| {
+        LexBound::parse(&unsafe { to_c_bytes(p) }).ok_or_else(|| CacheError::invalid("min or max not valid string range item"))
+    };
+    let res = bound(min).and_then(|min| {
+        let max = bound(max)?;
+        cmd_zrangebylex(db, &key_vec, &min, &max, rev != 0, offset, usize::try_from(count).ok())
+    });
+    return_found(res.map(|members| Some(pack_members(&members))), out_len)
+}
+
+// Like cache_zrangebylex_b, with each bound passed as a pointer and a length.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_zrangebylex_bm(
+    key: *const c_uchar,
+    key_len: usize,
+    min: *const c_uchar,
+    min_len: usize,
+    max: *const c_uchar,
+    max_len: usize,
+    rev: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_zrangebylex_bm(default_instance(), key, key_len, min, min_len, max, max_len, rev, offset, count, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_zrangebylex_bm(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    min: *const c_uchar,
+    min_len: usize,
+    max: *const c_uchar,
+    max_len: usize,
+    rev: i32,
+    offset: usize,
+    count: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let bound = |p, len| {
+        LexBound::parse(&unsafe { to_bytes(p, len) }).ok_or_else(|| CacheError::invalid("min or max not valid string range item"))
+    };
+    let res = bound(min, min_len).and_then(|min| {
+        let max = bound(max, max_len)?;
+        cmd_zrangebylex(db, &key_vec, &min, &max, rev != 0, offset, usize::try_from(count).ok())
+    });
+    return_found(res.map(|members| Some(pack_members(&members))), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_free(ptr: *mut c_uchar, len: usize) {
+    if ptr.is_null() || len == 0 {
+        return;
+    }
+    unsafe {
+        // Correctly free vector assuming cap == len
+        let _ = Vec::from_raw_parts(ptr, len, len);
+    }
+}
+
+// --- Phase2: LRU + TTL + AOF + Binary-Safe Keys ---
+
+#[no_mangle]
+pub extern "C" fn cache_set_max_items(max_items: usize) {
+    cache_instance_set_max_items(default_instance(), max_items)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_max_items(db: *const Instance, max_items: usize) {
+    let Some(db) = instance(db) else { return; };
+    db.max_items.store(max_items.max(1), Ordering::Relaxed);
+    // Shrink through the eviction path so notifications and memory accounting stay in sync;
+    // the policy's victims go first, plain LRU only once no shard offers one.
+    for force in [false, true] {
+        evict_round_robin(db, || items_over_limit(db), |s| evict_one(db, s, force));
+    }
+    for shard in &db.cache.shards {
+        let mut state = shard.write().unwrap();
+        if state.sketch.is_some() {
+            state.sketch = Some(eviction::FrequencySketch::new(shard_capacity(db)));
+        }
+    }
+}
+
+fn items_over_limit(db: &Instance) -> bool {
+    let max = db.max_items.load(Ordering::Relaxed);
+    db.cache.len.load(Ordering::Relaxed) > max
+}
+
+/// Selects the eviction policy: 0 = allkeys-lru (default), 1 = allkeys-lfu, 2 = w-tinylfu,
+/// 3 = volatile-lru, 4 = volatile-ttl, 5 = noeviction. Returns InvalidArgument for an unknown policy.
+/// Under noeviction, and under volatile-* when no key has a TTL, writes that need room (item or
+/// memory limit) fail with `CacheStatus::OutOfMemory` instead of evicting other keys.
+#[no_mangle]
+pub extern "C" fn cache_set_eviction_policy(policy: i32) -> i32 {
+    cache_instance_set_eviction_policy(default_instance(), policy)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_eviction_policy(db: *const Instance, policy: i32) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let policy = match u8::try_from(policy) {
+        Ok(p) if p <= eviction::POLICY_NOEVICTION => p,
+        _ => return fail(CacheError::invalid("unknown eviction policy")),
+    };
+    db.policy.store(policy, Ordering::Relaxed);
+    for shard in &db.cache.shards {
+        let mut state = shard.write().unwrap();
+        if policy == eviction::POLICY_W_TINYLFU {
+            if state.sketch.is_none() {
+                state.sketch = Some(eviction::FrequencySketch::new(shard_capacity(db)));
+            }
+        } else if state.sketch.is_some() {
+            state.sketch = None;
+            state.window.clear();
+            for (_, entry) in state.map.iter_mut() {
+                entry.in_window = false;
+            }
+        }
+    }
+    1
+}
+
+#[no_mangle]
+pub extern "C" fn cache_get_eviction_policy() -> i32 {
+    cache_instance_get_eviction_policy(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_eviction_policy(db: *const Instance) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    db.policy() as i32
+}
+
+// What hset/lpush/sadd/zadd/xadd and JSON set do with a key holding another type: by default
+// (0) they fail with WrongType and leave it untouched; non-zero replaces the old value.
+#[no_mangle]
+pub extern "C" fn cache_set_type_overwrite(enabled: i32) {
+    cache_instance_set_type_overwrite(default_instance(), enabled)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_type_overwrite(db: *const Instance, enabled: i32) {
+    let Some(db) = instance(db) else { return; };
+    db.overwrite_types.store(enabled != 0, Ordering::Relaxed);
+}
+
+#[no_mangle]
+pub extern "C" fn cache_get_max_items() -> usize {
+    cache_instance_get_max_items(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_max_items(db: *const Instance) -> usize {
+    let Some(db) = instance(db) else { return 0; };
+    db.max_items.load(Ordering::Relaxed)
+}
+
+/// Sets the memory limit in bytes (0 = unlimited). Least recently used entries are
+/// evicted until the estimated usage fits.
+#[no_mangle]
+pub extern "C" fn cache_set_max_memory_bytes(max_bytes: u64) {
+    cache_instance_set_max_memory_bytes(default_instance(), max_bytes)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_max_memory_bytes(db: *const Instance, max_bytes: u64) {
+    let Some(db) = instance(db) else { return; };
+    db.max_memory.store(max_bytes as usize, Ordering::Relaxed);
+    evict_round_robin(db, || memory_over_limit(db), |s| evict_one(db, s, false));
+}
+
+#[no_mangle]
+pub extern "C" fn cache_get_max_memory_bytes() -> u64 {
+    cache_instance_get_max_memory_bytes(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_max_memory_bytes(db: *const Instance) -> u64 {
+    let Some(db) = instance(db) else { return 0; };
+    db.max_memory.load(Ordering::Relaxed) as u64
+}
+
+/// Estimated bytes used by keys and values (including per-entry overhead).
+#[no_mangle]
+pub extern "C" fn cache_used_memory() -> u64 {
+    cache_instance_used_memory(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_used_memory(db: *const Instance) -> u64 {
+    let Some(db) = instance(db) else { return 0; };
+    db.cache.used_memory.load(Ordering::Relaxed) as u64
+}
+
+#[no_mangle]
+pub extern "C" fn cache_len() -> usize {
+    cache_instance_len(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_len(db: *const Instance) -> usize {
+    let Some(db) = instance(db) else { return 0; };
+    db.cache.len.load(Ordering::Relaxed)
+}
+
+fn cmd_set_with_ttl<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: Vec<u8>, ttl_ms: u64) -> Result<(), CacheError> {
+    let expires_at = now_ms().saturating_add(ttl_ms);
+
+    let mut state = shard_write(db, key);
+    check_oom(db, &mut state, key)?;
+    aof_write_set(db, key, &val);
+    aof_write_expire_at(db, key, expires_at);
+    put_entry_with_lru(
+        db,
+        &mut state,
+        key,
+        Entry::new(Value::Bytes(Arc::new(val)), Some(expires_at)),
+    );
+    Ok(())
+}
+
+// Returns 1, or a negative CacheStatus (OutOfMemory if the write was rejected).
+#[no_mangle]
+pub extern "C" fn cache_set_with_ttl(key: *const c_char, value: *const c_uchar, len: usize, ttl_ms: u64) -> i32 {
+    cache_instance_set_with_ttl(default_instance(), key, value, len, ttl_ms)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_with_ttl(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+    ttl_ms: u64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_set_with_ttl(db, &key_str, val_vec, ttl_ms), |()| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_set_with_ttl_b(
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+    ttl_ms: u64,
+) -> i32 {
+    cache_instance_set_with_ttl_b(default_instance(), key, key_len, value, len, ttl_ms)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_with_ttl_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+    ttl_ms: u64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_set_with_ttl(db, &key_vec, val_vec, ttl_ms), |()| 1)
+}
+
+// Option bits for `cache_set_ex` (Redis' SET NX / XX / GET / KEEPTTL / PXAT).
+pub const SET_NX: i32 = 1;
+pub const SET_XX: i32 = 2;
+pub const SET_GET: i32 = 4;
+pub const SET_KEEPTTL: i32 = 8;
+// The ttl argument is an absolute Unix time in ms instead of a duration.
+pub const SET_EXAT: i32 = 16;
+
+// NX only writes a missing key, XX only an existing one. GET returns the previous value (a
+// value of another type fails with WrongType and nothing is written). The new value expires at
+// `expires_at_ms`, or keeps the key's current TTL under KEEPTTL.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct SetOptions {
+    pub(crate) nx: bool,
+    pub(crate) xx: bool,
+    pub(crate) get: bool,
+    pub(crate) keep_ttl: bool,
+    pub(crate) expires_at_ms: Option<u64>,
+}
+
+impl SetOptions {
+    // `ttl` is 0 for none, else a duration in ms or, under SET_EXAT, a Unix time in ms.
+    fn from_bits(bits: i32, ttl: u64) -> Result<SetOptions, CacheError> {
+        let expires_at_ms = match ttl {
+            0 if bits & SET_EXAT != 0 => return Err(CacheError::invalid("invalid expire time in 'set' command")),
+            0 => None,
+            at if bits & SET_EXAT != 0 => Some(at),
+            ttl => Some(now_ms().saturating_add(ttl)),
+        };
+        let opts = SetOptions {
+            nx: bits & SET_NX != 0,
+            xx: bits & SET_XX != 0,
+            get: bits & SET_GET != 0,
+            keep_ttl: bits & SET_KEEPTTL != 0,
+            expires_at_ms,
+        };
+        if (opts.nx && opts.xx) || (opts.keep_ttl && opts.expires_at_ms.is_some()) {
+            return Err(CacheError::invalid("syntax error"));
+        }
+        Ok(opts)
+    }
+}
+
+// SET with conditions under one lock. Returns whether the value was written and, under `get`,
+// the previous value.
+fn cmd_set_with<K: CacheKey + ?Sized>(db: &Instance, key: &K, val: Vec<u8>, opts: SetOptions) -> Result<(bool, Option<Vec<u8>>), CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let current = state.map.peek(key.as_bytes());
+    let old = match current.map(|e| &e.value) {
+        Some(Value::Bytes(old)) if opts.get => Some((**old).clone()),
+        Some(_) if opts.get => return Err(CacheError::WrongType),
+        _ => None,
+    };
+    let exists = current.is_some();
+    let expires_at_ms = if opts.keep_ttl { current.and_then(|e| e.expires_at_ms) } else { opts.expires_at_ms };
+    if (opts.nx && exists) || (opts.xx && !exists) {
+        return Ok((false, old));
+    }
+    check_oom(db, &mut state, key)?;
+    aof_write_set(db, key, &val);
+    if let Some(at) = expires_at_ms {
+        aof_write_expire_at(db, key, at);
+    }
+    put_entry_with_lru(db, &mut state, key, Entry::new(Value::Bytes(Arc::new(val)), expires_at_ms));
+    Ok((true, old))
+}
+
+// SET with SET_* option bits; `ttl` is 0 for none, else ms from now or (SET_EXAT) a Unix time in
+// ms. Returns 1 if the value was written, 0 if NX / XX prevented it, or a negative CacheStatus.
+// Under SET_GET the previous value goes to `old_value` / `old_len` (free it with cache_free):
+// null with length 0 if it was empty, null with length usize::MAX if the key was missing.
+#[no_mangle]
+pub extern "C" fn cache_set_ex(
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+    flags: i32,
+    ttl: u64,
+    old_value: *mut *mut c_uchar,
+    old_len: *mut usize,
+) -> i32 {
+    cache_instance_set_ex(default_instance(), key, value, len, flags, ttl, old_value, old_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_set_ex(
+    db: *const Instance,
+    key: *const c_char,
+    value: *const c_uchar,
+    len: usize,
+    flags: i32,
+    ttl: u64,
+    old_value: *mut *mut c_uchar,
+    old_len: *mut usize,
+) -> i32 {
+    let key_str = unsafe { to_string(key) };
+    set_ex_export(db, key_str.as_str(), value, len, flags, ttl, old_value, old_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_set_ex_b(
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+    flags: i32,
+    ttl: u64,
+    old_value: *mut *mut c_uchar,
+    old_len: *mut usize,
+) -> i32 {
+    cache_instance_set_ex_b(default_instance(), key, key_len, value, len, flags, ttl, old_value, old_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_set_ex_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+    flags: i32,
+    ttl: u64,
+    old_value: *mut *mut c_uchar,
+    old_len: *mut usize,
+) -> i32 {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    set_ex_export(db, key_vec.as_slice(), value, len, flags, ttl, old_value, old_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn set_ex_export<K: CacheKey + ?Sized>(
+    db: *const Instance,
+    key: &K,
+    value: *const c_uchar,
+    len: usize,
+    flags: i32,
+    ttl: u64,
+    old_value: *mut *mut c_uchar,
+    old_len: *mut usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let val_vec = unsafe { to_bytes(value, len) };
+    let res = SetOptions::from_bits(flags, ttl).and_then(|opts| cmd_set_with(db, key, val_vec, opts));
+    status(res, |(written, old)| {
+        if flags & SET_GET != 0 && !old_value.is_null() && !old_len.is_null() {
+            unsafe {
+                match old {
+                    Some(old) => *old_value = prepare_return(old, old_len),
+                    None => {
+                        *old_value = std::ptr::null_mut();
+                        *old_len = usize::MAX;
+                    }
+                }
+            }
+        }
+        written as i32
+    })
+}
+
+fn cmd_expire<K: CacheKey + ?Sized>(db: &Instance, key: &K, ttl_ms: u64) -> bool {
+    let expires_at = now_ms().saturating_add(ttl_ms);
+    let mut state = shard_write(db, key);
+    let ok = apply_expire_at_internal(db, &mut state, key, expires_at);
+    if ok {
+        aof_write_expire_at(db, key, expires_at);
+    }
+    ok
+}
+
+// Returns 1, or NotFound if the key does not exist.
+#[no_mangle]
+pub extern "C" fn cache_expire(key: *const c_char, ttl_ms: u64) -> i32 {
+    cache_instance_expire(default_instance(), key, ttl_ms)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_expire(db: *const Instance, key: *const c_char, ttl_ms: u64) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    if cmd_expire(db, &key_str, ttl_ms) { 1 } else { fail(CacheError::NotFound) }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_expire_b(key: *const c_uchar, key_len: usize, ttl_ms: u64) -> i32 {
+    cache_instance_expire_b(default_instance(), key, key_len, ttl_ms)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_expire_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    ttl_ms: u64,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    if cmd_expire(db, &key_vec, ttl_ms) { 1 } else { fail(CacheError::NotFound) }
+}
+
+// Remaining TTL in ms; -1 => no expiry, -2 => key missing (or expired).
+fn cmd_ttl<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> i64 {
+    let state = shard_read(db, key);
+    // Peek: asking for the TTL is not an access.
+    let Some(entry) = state.map.peek(key.as_bytes()).filter(|e| !is_expired(e)) else { return -2; };
+    match entry.expires_at_ms {
+        None => -1,
+        Some(t) => {
+            let now = now_ms();
+            if t <= now { 0 } else { (t - now) as i64 }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_ttl(key: *const c_char) -> i64 {
+    cache_instance_ttl(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_ttl(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return -2; };
+    let key_str = unsafe { to_string(key) };
+    let ttl = cmd_ttl(db, &key_str);
+    if ttl == -2 {
+        fail(CacheError::NotFound);
+    }
+    ttl
+}
+
+#[no_mangle]
+pub extern "C" fn cache_ttl_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_ttl_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_ttl_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return -2; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let ttl = cmd_ttl(db, &key_vec);
+    if ttl == -2 {
+        fail(CacheError::NotFound);
+    }
+    ttl
+}
+
+#[no_mangle]
+pub extern "C" fn cache_aof_enable(path: *const c_char) -> i32 {
+    cache_instance_aof_enable(default_instance(), path)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_enable(db: *const Instance, path: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let path_str = unsafe { to_string(path) };
+    if path_str.is_empty() {
+        return fail(CacheError::invalid("empty AOF path"));
+    }
+    match std::fs::OpenOptions::new().create(true).append(true).open(&path_str) {
+        Ok(f) => {
+            start_aof_writer_once(db);
+            let size = f.metadata().map(|m| m.len()).unwrap_or(0);
+            let mut aof = aof_drain_locked(db, db.aof.lock().unwrap());
+            aof.file = Some(Arc::new(f));
+            aof.path = path_str;
+            aof.failed = false;
+            aof.rewrite_buf = None;
+            aof.rewrite_failed_at = None;
+            aof.base_size = size;
+            aof.size = size;
+            1
+        }
+        Err(e) => fail(e.into()),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_aof_disable() {
+    cache_instance_aof_disable(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_disable(db: *const Instance) {
+    let Some(db) = instance(db) else { return; };
+    let mut aof = aof_drain_locked(db, db.aof.lock().unwrap());
+    aof.file = None;
+    aof.rewrite_buf = None;
+    db.aof_durable_signal.notify_all();
+}
+
+// Selects the fsync policy: 0 = no (leave it to the OS), 1 = everysec (default),
+// 2 = always (every write waits for fsync; concurrent writers share one fsync).
+// Returns 1, or InvalidArgument for an unknown policy.
+#[no_mangle]
+pub extern "C" fn cache_aof_set_fsync_policy(policy: i32) -> i32 {
+    cache_instance_aof_set_fsync_policy(default_instance(), policy)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_set_fsync_policy(db: *const Instance, policy: i32) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let policy = match policy {
+        0 => AOF_FSYNC_NO,
+        1 => AOF_FSYNC_EVERYSEC,
+        2 => AOF_FSYNC_ALWAYS,
+        _ => return fail(CacheError::invalid("unknown fsync policy")),
+    };
+    db.aof_fsync_policy.store(policy, Ordering::Relaxed);
+    db.aof_writer_signal.notify_one();
+    1
+}
+
+// Barrier: blocks until every AOF record written before the call is fsynced.
+// Returns 1, InvalidArgument if the AOF is disabled, or Io if a background write/fsync failed.
+#[no_mangle]
+pub extern "C" fn cache_aof_flush() -> i32 {
+    cache_instance_aof_flush(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_flush(db: *const Instance) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let target = {
+        let aof = db.aof.lock().unwrap();
+        if aof.file.is_none() {
+            return fail(CacheError::invalid("AOF is not enabled"));
+        }
+        aof.appended
+    };
+    if aof_wait_synced(db, target) { 1 } else { fail(CacheError::Io("AOF write or fsync failed".to_string())) }
+}
+
+// Compacts the AOF into one record per live key/field/member (with remaining TTLs).
+// Returns 1, InvalidArgument if the AOF is disabled or a rewrite is already running, or Io.
+#[no_mangle]
+pub extern "C" fn cache_aof_rewrite() -> i32 {
+    cache_instance_aof_rewrite(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_rewrite(db: *const Instance) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    status(aof_rewrite(db), |()| 1)
+}
+
+// Enables automatic background rewrites once the AOF is at least `min_size_bytes` and has
+// grown by `percentage` percent since the last rewrite. A percentage of 0 disables it.
+// After a failed rewrite, the next automatic attempt waits AOF_REWRITE_RETRY_DELAY.
+#[no_mangle]
+pub extern "C" fn cache_aof_set_auto_rewrite(percentage: u32, min_size_bytes: u64) {
+    cache_instance_aof_set_auto_rewrite(default_instance(), percentage, min_size_bytes)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_set_auto_rewrite(db: *const Instance, percentage: u32, min_size_bytes: u64) {
+    let Some(db) = instance(db) else { return; };
+    db.aof_auto_rewrite_pct.store(percentage as u64, Ordering::Relaxed);
+    db.aof_auto_rewrite_min_size.store(min_size_bytes, Ordering::Relaxed);
+}
+
+#[no_mangle]
+pub extern "C" fn cache_aof_load(path: *const c_char) -> i32 {
+    cache_instance_aof_load(default_instance(), path)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_aof_load(db: *const Instance, path: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let path_str = unsafe { to_string(path) };
+    if path_str.is_empty() {
+        return fail(CacheError::invalid("empty AOF path"));
+    }
+    let mut file = match std::fs::File::open(&path_str) {
+        Ok(f) => f,
+        Err(e) => return fail(e.into()),
+    };
+
+    let mut state = cache_write_all(db);
+    AOF_REPLAYING.with(|r| r.set(true));
+
+    while let Some(op) = read_exact_u8(&mut file) {
+        if op == AOF_OP_CLEAR {
+            apply_clear_internal(db, &mut state);
+            continue;
+        }
+        let op = aof_record_op(op);
+        let Some(key) = read_exact_u32(&mut file).and_then(|klen| read_exact_vec(&mut file, klen as usize)) else {
+            break;
+        };
+        if aof_replay_record(db, &mut state, op, key.as_slice(), &mut file).is_none() {
+            break;
+        }
+    }
+
+    AOF_REPLAYING.with(|r| r.set(false));
+    // Keys that expired while the process was down are dropped only now.
+    purge_expired(db, &mut state);
+    1
+}
+
+// Applies the rest of one record naming `key`. None if the file ends inside it or the op is
+// unknown, which ends the replay.
+fn aof_replay_record<K: CacheKey + ?Sized>(
+    db: &Instance,
+    all: &mut AllShardsGuard,
+    op: u8,
+    key: &K,
+    file: &mut impl Read,
+) -> Option<()> {
+    let state = all.shard(key);
+    match op {
+        AOF_OP_SET => {
+            let vlen = read_exact_u32(file)? as usize;
+            let val = read_exact_vec(file, vlen)?;
+            apply_set_internal(db, state, key, val);
+        }
+        AOF_OP_REMOVE => {
+            apply_remove_internal(db, state, key);
+        }
+        AOF_OP_EXPIRE => {
+            let ttl_ms = read_exact_u64(file)?;
+            apply_expire_internal(db, state, key, ttl_ms);
+        }
+        AOF_OP_EXPIRE_AT => {
+            let expires_at = read_exact_u64(file)?;
+            // A past expiry is kept, so records that follow still see the key as expiring.
+            apply_expire_at_internal(db, state, key, expires_at);
+        }
+        // Data-structure records against a key of another type are skipped: the live write
+        // failed the same way (see prepare_typed_write).
+        AOF_OP_HSET => {
+            let flen = read_exact_u32(file)? as usize;
+            let field = read_exact_vec(file, flen)?;
+            let vlen = read_exact_u32(file)? as usize;
+            let val = read_exact_vec(file, vlen)?;
+            let _ = apply_hset_internal(db, state, key, field, val);
+        }
+        AOF_OP_HDEL => {
+            let flen = read_exact_u32(file)? as usize;
+            let field = read_exact_vec(file, flen)?;
+            let _ = apply_hdel_internal(db, state, key, &field);
+        }
+        AOF_OP_LPUSH | AOF_OP_RPUSH => {
+            let vlen = read_exact_u32(file)? as usize;
+            let val = read_exact_vec(file, vlen)?;
+            let _ = apply_push_internal(db, state, key, op == AOF_OP_LPUSH, val);
+        }
+        AOF_OP_LPOP | AOF_OP_RPOP => {
+            let count = read_exact_u32(file)? as usize;
+            let _ = apply_pop_internal(db, state, key, op == AOF_OP_LPOP, count);
+        }
+        AOF_OP_LSET | AOF_OP_LINSERT | AOF_OP_LREM => {
+            let n = read_exact_u64(file)? as i64;
+            let vlen = read_exact_u32(file)? as usize;
+            let val = read_exact_vec(file, vlen)?;
+            let _ = match op {
+                AOF_OP_LSET => apply_lset_internal(db, state, key, n, val),
+                AOF_OP_LINSERT => apply_linsert_internal(db, state, key, n as usize, val).map(|_| ()),
+                _ => apply_lrem_internal(db, state, key, n, &val).map(|_| ()),
+            };
+        }
+        AOF_OP_LTRIM => {
+            let start = read_exact_u64(file)? as i64;
+            let end = read_exact_u64(file)? as i64;
+            let _ = apply_ltrim_internal(db, state, key, start, end);
+        }
+        AOF_OP_SADD => {
+            let vlen = read_exact_u32(file)? as usize;
+            let val = read_exact_vec(file, vlen)?;
+            let _ = apply_sadd_internal(db, state, key, val);
+        }
+        AOF_OP_SREM => {
+            let vlen = read_exact_u32(file)? as usize;
+            let val = read_exact_vec(file, vlen)?;
+            let _ = apply_srem_internal(db, state, key, &val);
+        }
+        AOF_OP_ZADD => {
+            let score = read_exact_f64(file)?;
+            let mlen = read_exact_u32(file)? as usize;
+            let member = read_exact_vec(file, mlen)?;
+            if !score.is_nan() {
+                let _ = apply_zadd_internal(db, state, key, score, member);
+            }
+        }
+        AOF_OP_ZREM => {
+            let mlen = read_exact_u32(file)? as usize;
+            let member = read_exact_vec(file, mlen)?;
+            let _ = apply_zrem_internal(db, state, key, &member);
+        }
+        AOF_OP_XADD_PAYLOAD | AOF_OP_XADD_U64 => {
+            let id = read_exact_stream_id(file, op == AOF_OP_XADD_U64)?;
+            let plen = read_exact_u32(file)? as usize;
+            let payload = read_exact_vec(file, plen)?;
+            let fields = vec![(STREAM_PAYLOAD_FIELD.to_vec(), payload)];
+            let _ = apply_xadd_internal(db, state, key, id, fields);
+        }
+        AOF_OP_XADD => {
+            let id = read_exact_stream_id(file, false)?;
+            let fields = read_exact_stream_fields(file)?;
+            let _ = apply_xadd_internal(db, state, key, id, fields);
+        }
+        AOF_OP_XGROUP_CREATE | AOF_OP_XGROUP_CREATE_U64 => {
+            let glen = read_exact_u32(file)? as usize;
+            let group = read_exact_string(file, glen)?;
+            let last_id = read_exact_stream_id(file, op == AOF_OP_XGROUP_CREATE_U64)?;
+            let _ = apply_xgroup_create_internal(db, state, key, &group, last_id, true);
+        }
+        AOF_OP_XGROUP_DESTROY => {
+            let glen = read_exact_u32(file)? as usize;
+            let group = read_exact_string(file, glen)?;
+            let _ = apply_xgroup_destroy_internal(db, state, key, &group);
+        }
+        AOF_OP_XCLAIM | AOF_OP_XCLAIM_U64 => {
+            let u64_ids = op == AOF_OP_XCLAIM_U64;
+            let glen = read_exact_u32(file)? as usize;
+            let group = read_exact_string(file, glen)?;
+            let clen = read_exact_u32(file)? as usize;
+            let consumer = read_exact_string(file, clen)?;
+            let at_ms = read_exact_u64(file)?;
+            let last_id = read_exact_stream_id(file, u64_ids)?;
+            let deliveries = read_exact_u64(file)?;
+            let ids = read_exact_stream_ids(file, u64_ids)?;
+            let d = Delivery { consumer, at_ms, last_id, deliveries, ids };
+            let _ = apply_xclaim_internal(db, state, key, &group, &d);
+        }
+        AOF_OP_XACK | AOF_OP_XACK_U64 => {
+            let glen = read_exact_u32(file)? as usize;
+            let group = read_exact_string(file, glen)?;
+            let ids = read_exact_stream_ids(file, op == AOF_OP_XACK_U64)?;
+            let _ = apply_xack_internal(db, state, key, &group, &ids);
+        }
+        AOF_OP_XDEL => {
+            let ids = read_exact_stream_ids(file, false)?;
+            let _ = apply_xdel_internal(db, state, key, &ids);
+        }
+        AOF_OP_XTRIM | AOF_OP_XSETID => {
+            let id = read_exact_stream_id(file, false)?;
+            let _ = match op {
+                AOF_OP_XTRIM => apply_xtrim_internal(db, state, key, id).map(|_| ()),
+                _ => apply_xsetid_internal(db, state, key, id),
+            };
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn purge_expired(db: &Instance, all: &mut AllShardsGuard) {
+    for state in all.0.iter_mut() {
+        let expired: Vec<Vec<u8>> = state.map.iter().filter(|(_, e)| is_expired(e)).map(|(k, _)| k.clone()).collect();
+        for key in expired {
+            pop_entry(db, state, key.as_slice());
+        }
+    }
+}
+
+// Binary keys (byte-for-byte): the `_b` entry points share the one keyspace, a string key being its UTF-8 bytes.
+
+// Returns 1, or a negative CacheStatus (OutOfMemory if the write was rejected).
+#[no_mangle]
+pub extern "C" fn cache_set_b(key: *const c_uchar, key_len: usize, value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_set_b(default_instance(), key, key_len, value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_set_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let val_vec = unsafe { to_bytes(value, len) };
+    status(cmd_set(db, key_vec.as_slice(), val_vec), |_| 1)
+}
+
+// --- Phase4: JSON Path Support (basic) ---
+
+#[derive(Debug)]
+enum JsonPathToken {
+    Field(String),
+    Index(usize),
+}
+
+fn parse_json_path(path: &str) -> Option<Vec<JsonPathToken>> {
+    let p = path.trim();
+    if p.is_empty() {
+        return None;
+    }
+
+    let mut i = 0usize;
+    let chars: Vec<char> = p.chars().collect();
+
+    if chars.first() == Some(&'$') {
+        i += 1;
+    }
+
+    let mut tokens = Vec::new();
+
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                i += 1;
+                let start = i;
+                while i < chars.len() {
+                    let c = chars[i];
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    i += 1;
+                }
+                if i == start {
+                    return None;
+                }
+                let field: String = chars[start..i].iter().collect();
+                tokens.push(JsonPathToken::Field(field));
+            }
+            '[' => {
+                i += 1;
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                if i == start || i >= chars.len() || chars[i] != ']' {
+                    return None;
+                }
+                let num_str: String = chars[start..i].iter().collect();
+                i += 1; // consume ']'
+                let idx = num_str.parse::<usize>().ok()?;
+                tokens.push(JsonPathToken::Index(idx));
+            }
+            _ => {
+                // allow path starting with field name without leading dot
+                let start = i;
+                while i < chars.len() {
+                    let c = chars[i];
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    i += 1;
+                }
+                if i == start {
+                    return None;
+                }
+                let field: String = chars[start..i].iter().collect();
+                tokens.push(JsonPathToken::Field(field));
+            }
+        }
+    }
+
+    Some(tokens)
+}
+
+fn json_get_at_path<'a>(root: &'a JsonValue, path: &[JsonPathToken]) -> Option<&'a JsonValue> {
+    let mut cur = root;
+    for t in path {
+        match t {
+            JsonPathToken::Field(f) => {
+                cur = cur.get(f)?;
+            }
+            JsonPathToken::Index(idx) => {
+                cur = cur.get(*idx)?;
+            }
+        }
+    }
+    Some(cur)
+}
+
+fn json_set_at_path(root: &mut JsonValue, path: &[JsonPathToken], new_val: JsonValue) -> bool {
+    if path.is_empty() {
+        *root = new_val;
+        return true;
+    }
+
+    let mut cur = root;
+    for (pos, t) in path.iter().enumerate() {
+        let last = pos == path.len() - 1;
+        match t {
+            JsonPathToken::Field(f) => {
+                if last {
+                    if !cur.is_object() {
+                        *cur = JsonValue::Object(Default::default());
+                    }
+                    if let Some(obj) = cur.as_object_mut() {
+                        obj.insert(f.clone(), new_val);
+                        return true;
+                    }
+                    return false;
+                }
+
+                if !cur.is_object() {
+                    *cur = JsonValue::Object(Default::default());
+                }
+                let obj = cur.as_object_mut().unwrap();
+                cur = obj.entry(f.clone()).or_insert(JsonValue::Object(Default::default()));
+            }
+            JsonPathToken::Index(idx) => {
+                if !cur.is_array() {
+                    *cur = JsonValue::Array(Vec::new());
+                }
+                let arr = cur.as_array_mut().unwrap();
+                while arr.len() <= *idx {
+                    arr.push(JsonValue::Null);
+                }
+                if last {
+                    arr[*idx] = new_val;
+                    return true;
+                }
+                cur = &mut arr[*idx];
+            }
+        }
+    }
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn cache_json_get(key: *const c_char, path: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_json_get(default_instance(), key, path, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_json_get(
+    db: *const Instance,
+    key: *const c_char,
+    path: *const c_char,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let path_str = unsafe { to_string(path) };
+    return_found(json_get(db, &key_str, &path_str), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_json_get_b(
+    key: *const c_uchar,
+    key_len: usize,
+    path: *const c_char,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_json_get_b(default_instance(), key, key_len, path, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_json_get_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    path: *const c_char,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let path_str = unsafe { to_string(path) };
+    return_found(json_get(db, &key_vec, &path_str), out_len)
+}
+
+fn json_get<K: CacheKey + ?Sized>(db: &Instance, key: &K, path: &str) -> Result<Option<Vec<u8>>, CacheError> {
+    let tokens = parse_json_path(path).ok_or_else(|| CacheError::invalid("invalid JSON path"))?;
+    let state = shard_read(db, key);
+    let Some(entry) = get_entry(db, &state, key) else { return Ok(None); };
+    if !matches!(entry.value, Value::Bytes(_)) {
+        return Err(CacheError::WrongType);
+    }
+    let json = try_parse_json_from_entry(entry).ok_or_else(|| CacheError::invalid("value is not a JSON document"))?;
+    Ok(json_get_at_path(&json, &tokens).and_then(|v| serde_json::to_vec(v).ok()))
+}
+
+// Returns 1, or a negative CacheStatus (InvalidArgument for an invalid path or document).
+#[no_mangle]
+pub extern "C" fn cache_json_set(key: *const c_char, path: *const c_char, json_value: *const c_uchar, len: usize) -> i32 {
+    cache_instance_json_set(default_instance(), key, path, json_value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_json_set(
+    db: *const Instance,
+    key: *const c_char,
+    path: *const c_char,
+    json_value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let path_str = unsafe { to_string(path) };
+    let new_bytes = unsafe { to_bytes(json_value, len) };
+    status(json_set(db, &key_str, &path_str, &new_bytes), |()| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_json_set_b(
+    key: *const c_uchar,
+    key_len: usize,
+    path: *const c_char,
+    json_value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    cache_instance_json_set_b(default_instance(), key, key_len, path, json_value, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_json_set_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    path: *const c_char,
+    json_value: *const c_uchar,
+    len: usize,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let path_str = unsafe { to_string(path) };
+    let new_bytes = unsafe { to_bytes(json_value, len) };
+    status(json_set(db, &key_vec, &path_str, &new_bytes), |()| 1)
+}
+
+fn json_set<K: CacheKey + ?Sized>(db: &Instance, key: &K, path: &str, json_value: &[u8]) -> Result<(), CacheError> {
+    let tokens = parse_json_path(path).ok_or_else(|| CacheError::invalid("invalid JSON path"))?;
+    let new_val: JsonValue =
+        serde_json::from_slice(json_value).map_err(|_| CacheError::invalid("value is not valid JSON"))?;
+
+    let mut state = shard_write(db, key);
+    prepare_typed_write(db, &mut state, key, Value::Bytes(Arc::new(Vec::new())))?;
+
+    let mut entry =
+        pop_entry(db, &mut state, key).unwrap_or_else(|| Entry::new(Value::Bytes(Arc::new(b"{}".to_vec())), None));
+
+    let mut json = try_parse_json_from_entry(&entry).unwrap_or(JsonValue::Object(Default::default()));
+    let updated_bytes = if json_set_at_path(&mut json, &tokens, new_val) { serde_json::to_vec(&json).ok() } else { None };
+    let Some(updated_bytes) = updated_bytes else {
+        // restore old entry
+        put_entry_with_lru(db, &mut state, key, entry);
+        return Err(CacheError::invalid("path cannot be set in this document"));
+    };
+
+    entry.mem = updated_bytes.len();
+    entry.value = Value::Bytes(Arc::new(updated_bytes.clone()));
+    put_entry_with_lru(db, &mut state, key, entry);
+
+    // AOF logs as SET of the full updated JSON document.
+    aof_write_set(db, key, &updated_bytes);
+    Ok(())
+}
+
+// --- Phase4: Secondary indexing + Find ---
+
+#[no_mangle]
+pub extern "C" fn cache_index_create_numeric(field: *const c_char) -> i32 {
+    cache_instance_index_create_numeric(default_instance(), field)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_index_create_numeric(db: *const Instance, field: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let field_str = unsafe { to_string(field) };
+    if field_str.is_empty() {
+        return fail(CacheError::invalid("empty index field"));
+    }
+
+    rebuild_numeric_index(db, field_str);
+    1
+}
+
+// (Re)builds the numeric index for `field` from the current state. The empty index is
+// published first so concurrent writers maintain it; each shard is then added while its
+// lock is held, so no write can fall between the scan and the index.
+fn rebuild_numeric_index(db: &Instance, field: String) {
+    db.cache.numeric_indexes.write().unwrap().insert(field.clone(), BTreeMap::new());
+    db.cache.has_indexes.store(true, Ordering::Release);
+    for shard in &db.cache.shards {
+        let state = shard.read().unwrap();
+        let mut indexes = db.cache.numeric_indexes.write().unwrap();
+        let Some(idx_map) = indexes.get_mut(&field) else { return; };
+        for (k, v) in state.map.iter() {
+            if let Some(json) = try_parse_json_from_entry(v) {
+                if let Some(num) = extract_numeric_field(&json, &field) {
+                    idx_map.entry(num).or_default().insert(k.clone());
+                }
+            }
+        }
+    }
+}
+
+fn parse_find_query(q: &str) -> Option<(String, String, String)> {
+    // very small grammar: <field> <op> <value>
+    // op: > >= < <= ==
+    let s = q.trim();
+    if s.is_empty() {
+        return None;
+    }
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if parts.len() < 3 {
+        return None;
+    }
+    Some((parts[0].to_string(), parts[1].to_string(), parts[2..].join(" ")))
+}
+
+// Keys whose JSON value has a numeric `field` matching the query, through the field's index
+// if there is one, else by scanning.
+fn find_keys(db: &Instance, query: &str) -> Result<Vec<Vec<u8>>, CacheError> {
+    let Some((field, op, value_str)) = parse_find_query(query) else {
+        return Err(CacheError::invalid("query must be '<field> <op> <value>'"));
+    };
+    let value_num: Option<i64> = value_str.parse::<i64>().ok();
+    if value_num.is_none() || !matches!(op.as_str(), ">" | ">=" | "<" | "<=" | "==") {
+        return Err(CacheError::invalid("query needs an integer value and one of > >= < <= =="));
+    }
+    let mut keys: Vec<Vec<u8>> = Vec::new();
+
+    let indexes = db.cache.numeric_indexes.read().unwrap();
+
+    if let (Some(vnum), Some(idx)) = (value_num, indexes.get(&field)) {
+        match op.as_str() {
+            ">" => {
+                for (_k, set) in idx.range((vnum + 1)..) {
+                    keys.extend(set.iter().cloned());
+                }
+            }
+            ">=" => {
+                for (_k, set) in idx.range(vnum..) {
+                    keys.extend(set.iter().cloned());
+                }
+            }
+            "<" => {
+                for (_k, set) in idx.range(..vnum) {
+                    keys.extend(set.iter().cloned());
+                }
+            }
+            "<=" => {
+                for (_k, set) in idx.range(..=vnum) {
+                    keys.extend(set.iter().cloned());
+                }
+            }
             "==" => {
                 if let Some(set) = idx.get(&vnum) {
                     keys.extend(set.iter().cloned());
                 }
             }
-            _ => {}
+            _ => {}
+        }
+    } else {
+        // fallback scan (only checks top-level numeric field in JSON bytes)
+        drop(indexes);
+        for (k, entry) in cache_read_all(db).iter().flat_map(|state| state.map.iter()) {
+            if is_expired(entry) {
+                continue;
+            }
+            let Some(json) = try_parse_json_from_entry(entry) else { continue; };
+            let Some(num) = extract_numeric_field(&json, &field) else { continue; };
+            let ok = match (op.as_str(), value_num) {
+                (">", Some(v)) => num > v,
+                (">=", Some(v)) => num >= v,
+                ("<", Some(v)) => num < v,
+                ("<=", Some(v)) => num <= v,
+                ("==", Some(v)) => num == v,
+                _ => false,
+            };
+            if ok {
+                keys.push(k.clone());
+            }
+        }
+    }
+    Ok(keys)
+}
+
+// Serializes keys: [Count u32][KeyLen u32][Key bytes]...
+fn pack_found_keys(keys: &[Vec<u8>]) -> Vec<u8> {
+    let mut flat = Vec::new();
+    flat.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+    for k in keys {
+        flat.extend_from_slice(&(k.len() as u32).to_le_bytes());
+        flat.extend_from_slice(k);
+    }
+    flat
+}
+
+#[no_mangle]
+pub extern "C" fn cache_find(query: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_find(default_instance(), query, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_find(db: *const Instance, query: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let query_str = unsafe { to_string(query) };
+    match find_keys(db, &query_str) {
+        Ok(keys) => prepare_return(pack_found_keys(&keys), out_len),
+        Err(e) => {
+            fail(e);
+            prepare_return(Vec::new(), out_len)
+        }
+    }
+}
+
+// Same as cache_find, which already returns the raw key bytes; kept for the binary-key API.
+#[no_mangle]
+pub extern "C" fn cache_find_b(query: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_find(default_instance(), query, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_find_b(db: *const Instance, query: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_find(db, query, out_len)
+}
+
+// --- Phase4: Lightweight scripting (very small command set) ---
+
+#[no_mangle]
+pub extern "C" fn cache_eval(script: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_eval(default_instance(), script, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_eval(db: *const Instance, script: *const c_char, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let s = unsafe { to_string(script) };
+    let s = s.trim();
+    if s.is_empty() {
+        fail(CacheError::invalid("empty script"));
+        return prepare_return(Vec::new(), out_len);
+    }
+
+    // Commands:
+    // GET <key>
+    // SET <key> <value...> (value is treated as UTF-8 bytes)
+    // DEL <key>
+    // JSON.GET <key> <path>
+    // JSON.SET <key> <path> <jsonValue>
+    let mut parts = s.split_whitespace();
+    let cmd = parts.next().unwrap_or("").to_uppercase();
+
+    match cmd.as_str() {
+        "GET" => {
+            let key = parts.next().unwrap_or("");
+            if key.is_empty() {
+                fail(CacheError::invalid(&format!("wrong number of arguments for '{}'", cmd)));
+                return prepare_return(Vec::new(), out_len);
+            }
+            // reuse cache_get via CString
+            let ckey = std::ffi::CString::new(key).ok();
+            if let Some(ck) = ckey {
+                return cache_instance_get(db, ck.as_ptr(), out_len);
+            }
+            unsafe { *out_len = 0 };
+            std::ptr::null_mut()
+        }
+        "SET" => {
+            let key = parts.next().unwrap_or("");
+            if key.is_empty() {
+                fail(CacheError::invalid(&format!("wrong number of arguments for '{}'", cmd)));
+                return prepare_return(Vec::new(), out_len);
+            }
+            // remaining bytes after the key
+            let value_pos = s.find(key).unwrap_or(0) + key.len();
+            let value_str = s[value_pos..].trim();
+            let bytes = value_str.as_bytes().to_vec();
+            let mut state = shard_write(db, key);
+            if let Err(e) = check_oom(db, &mut state, key) {
+                fail(e);
+                return prepare_return(Vec::new(), out_len);
+            }
+            put_entry_with_lru(
+                db,
+                &mut state,
+                key,
+                Entry::new(Value::Bytes(Arc::new(bytes.clone())), None),
+            );
+            aof_write_set(db, key, &bytes);
+            prepare_return(b"OK".to_vec(), out_len)
+        }
+        "DEL" => {
+            let key = parts.next().unwrap_or("");
+            if key.is_empty() {
+                fail(CacheError::invalid(&format!("wrong number of arguments for '{}'", cmd)));
+                return prepare_return(Vec::new(), out_len);
+            }
+            let mut state = shard_write(db, key);
+            let existed = state.map.contains(key.as_bytes());
+            apply_remove_internal(db, &mut state, key);
+            aof_write_remove(db, key);
+            let out = if existed { b"1" } else { b"0" };
+            prepare_return(out.to_vec(), out_len)
+        }
+        "JSON.GET" => {
+            let key = parts.next().unwrap_or("");
+            let path = parts.next().unwrap_or("");
+            if key.is_empty() || path.is_empty() {
+                fail(CacheError::invalid(&format!("wrong number of arguments for '{}'", cmd)));
+                return prepare_return(Vec::new(), out_len);
+            }
+            let ckey = std::ffi::CString::new(key).ok();
+            let cpath = std::ffi::CString::new(path).ok();
+            if let (Some(ck), Some(cp)) = (ckey, cpath) {
+                return cache_instance_json_get(db, ck.as_ptr(), cp.as_ptr(), out_len);
+            }
+            unsafe { *out_len = 0 };
+            std::ptr::null_mut()
+        }
+        "JSON.SET" => {
+            let key = parts.next().unwrap_or("");
+            let path = parts.next().unwrap_or("");
+            if key.is_empty() || path.is_empty() {
+                fail(CacheError::invalid(&format!("wrong number of arguments for '{}'", cmd)));
+                return prepare_return(Vec::new(), out_len);
+            }
+            // remaining after path
+            let path_pos = s.find(path).unwrap_or(0) + path.len();
+            let json_str = s[path_pos..].trim();
+            let ckey = std::ffi::CString::new(key).ok();
+            let cpath = std::ffi::CString::new(path).ok();
+            if let (Some(ck), Some(cp)) = (ckey, cpath) {
+                let ok = cache_instance_json_set(db, ck.as_ptr(), cp.as_ptr(), json_str.as_ptr(), json_str.len());
+                let out = if ok == 1 { b"1" } else { b"0" };
+                return prepare_return(out.to_vec(), out_len);
+            }
+            prepare_return(b"0".to_vec(), out_len)
+        }
+        _ => {
+            fail(CacheError::invalid(&format!("unknown command '{}'", cmd)));
+            prepare_return(Vec::new(), out_len)
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_get_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_get_b(default_instance(), key, key_len, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    return_found(cmd_get(db, key_vec.as_slice()).map(|v| v.map(|v| (*v).clone())), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_get_into_b(key: *const c_uchar, key_len: usize, dst: *mut c_uchar, dst_len: usize) -> i64 {
+    cache_instance_get_into_b(default_instance(), key, key_len, dst, dst_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_into_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    dst: *mut c_uchar,
+    dst_len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return -1; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    if key_vec.is_empty() {
+        fail(CacheError::invalid("empty key"));
+        return -1;
+    }
+
+    let val = match cmd_get(db, key_vec.as_slice()) {
+        Ok(Some(val)) => val,
+        Ok(None) => {
+            fail(CacheError::NotFound);
+            return -1;
+        }
+        Err(e) => {
+            fail(e);
+            return -1;
+        }
+    };
+
+    let value_len = val.len();
+    if value_len == 0 {
+        return 0;
+    }
+    if dst.is_null() || dst_len < value_len {
+        return -(value_len as i64);
+    }
+
+    unsafe {
+        std::ptr::copy_nonoverlapping(val.as_ptr(), dst, value_len);
+    }
+    value_len as i64
+}
+
+// Zero-copy value lease for binary keys.
+//
+// Returns: opaque handle (must be freed via cache_bytes_lease_free), or null if missing/expired.
+// Out params: (*out_ptr, *out_len) are set to the value bytes.
+#[no_mangle]
+pub extern "C" fn cache_get_lease_b(
+    key: *const c_uchar,
+    key_len: usize,
+    out_ptr: *mut *const c_uchar,
+    out_len: *mut usize,
+) -> *const Vec<u8> {
+    cache_instance_get_lease_b(default_instance(), key, key_len, out_ptr, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_get_lease_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    out_ptr: *mut *const c_uchar,
+    out_len: *mut usize,
+) -> *const Vec<u8> {
+    let Some(db) = instance(db) else { return std::ptr::null(); };
+    if out_ptr.is_null() || out_len.is_null() {
+        fail(CacheError::invalid("null output pointer"));
+        return std::ptr::null();
+    }
+    unsafe {
+        *out_ptr = std::ptr::null();
+        *out_len = 0;
+    }
+
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let res = if key_vec.is_empty() { Err(CacheError::invalid("empty key")) } else { cmd_get(db, key_vec.as_slice()) };
+    let val = match res {
+        Ok(Some(val)) => val,
+        Ok(None) => {
+            fail(CacheError::NotFound);
+            return std::ptr::null();
+        }
+        Err(e) => {
+            fail(e);
+            return std::ptr::null();
+        }
+    };
+
+    let handle = Arc::into_raw(val);
+    unsafe {
+        *out_ptr = (*handle).as_ptr();
+        *out_len = (*handle).len();
+    }
+    handle
+}
+
+#[no_mangle]
+pub extern "C" fn cache_bytes_lease_free(handle: *const Vec<u8>) {
+    if handle.is_null() {
+        return;
+    }
+    unsafe {
+        // Drop one Arc refcount.
+        let _ = Arc::from_raw(handle);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_remove_b(key: *const c_uchar, key_len: usize) {
+    cache_instance_remove_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_remove_b(db: *const Instance, key: *const c_uchar, key_len: usize) {
+    let Some(db) = instance(db) else { return; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    cmd_remove(db, key_vec.as_slice());
+}
+
+// --- Phase3: Pub/Sub ---
+
+// Returns the subscription id, or 0 if the channel is empty.
+fn pubsub_subscribe(db: &Instance, channel: &str) -> u64 {
+    if channel.is_empty() {
+        fail(CacheError::invalid("empty channel"));
+        return 0;
+    }
+
+    let mut ps = db.pubsub.lock().unwrap();
+    let id = ps.next_id;
+    ps.next_id = ps.next_id.saturating_add(1);
+    ps.subs.insert(id, channel.to_string());
+    ps.channels.entry(channel.to_string()).or_default().push(id);
+    ps.queues.insert(id, VecDeque::new());
+    id
+}
+
+#[no_mangle]
+pub extern "C" fn cache_pubsub_subscribe(channel: *const c_char) -> u64 {
+    cache_instance_pubsub_subscribe(default_instance(), channel)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_pubsub_subscribe(db: *const Instance, channel: *const c_char) -> u64 {
+    let Some(db) = instance(db) else { return 0; };
+    let channel_str = unsafe { to_string(channel) };
+    pubsub_subscribe(db, &channel_str)
+}
+
+fn pubsub_unsubscribe(db: &Instance, sub_id: u64) {
+    if sub_id == 0 {
+        return;
+    }
+
+    let mut ps = db.pubsub.lock().unwrap();
+    let Some(channel) = ps.subs.remove(&sub_id) else {
+        return;
+    };
+    if let Some(list) = ps.channels.get_mut(&channel) {
+        list.retain(|id| *id != sub_id);
+        if list.is_empty() {
+            ps.channels.remove(&channel);
+        }
+    }
+    ps.queues.remove(&sub_id);
+    // Wake blocked waiters so they notice the subscription is gone.
+    db.pubsub_signal.notify_all();
+}
+
+#[no_mangle]
+pub extern "C" fn cache_pubsub_unsubscribe(sub_id: u64) {
+    cache_instance_pubsub_unsubscribe(default_instance(), sub_id)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_pubsub_unsubscribe(db: *const Instance, sub_id: u64) {
+    let Some(db) = instance(db) else { return; };
+    pubsub_unsubscribe(db, sub_id);
+}
+
+// Returns the number of subscriptions the message was delivered to.
+fn cmd_publish(db: &Instance, channel: &str, payload: &[u8]) -> u64 {
+    if channel.is_empty() {
+        return 0;
+    }
+
+    let mut ps = db.pubsub.lock().unwrap();
+    let Some(subs) = ps.channels.get(channel) else {
+        return 0;
+    };
+    let subs = subs.clone();
+
+    let mut delivered = 0u64;
+    for id in subs.into_iter() {
+        if let Some(q) = ps.queues.get_mut(&id) {
+            q.push_back(PubMessage {
+                channel: channel.to_string(),
+                payload: payload.to_vec(),
+            });
+            delivered += 1;
+        }
+    }
+    if delivered > 0 {
+        db.pubsub_signal.notify_all();
+    }
+    delivered
+}
+
+#[no_mangle]
+pub extern "C" fn cache_pubsub_publish(channel: *const c_char, payload: *const c_uchar, len: usize) -> u64 {
+    cache_instance_pubsub_publish(default_instance(), channel, payload, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_pubsub_publish(
+    db: *const Instance,
+    channel: *const c_char,
+    payload: *const c_uchar,
+    len: usize,
+) -> u64 {
+    let Some(db) = instance(db) else { return 0; };
+    let channel_str = unsafe { to_string(channel) };
+    let payload_vec = unsafe { to_bytes(payload, len) };
+    cmd_publish(db, &channel_str, &payload_vec)
+}
+
+// Blocks until at least one of `sub_ids` has a pending message or `timeout` elapses,
+// then drains every pending message for those subscriptions.
+fn pubsub_wait(db: &Instance, sub_ids: &[u64], timeout: Duration) -> Vec<PubMessage> {
+    let deadline = std::time::Instant::now() + timeout;
+    let mut ps = db.pubsub.lock().unwrap();
+    loop {
+        let mut out = Vec::new();
+        for id in sub_ids {
+            if let Some(q) = ps.queues.get_mut(id) {
+                out.extend(q.drain(..));
+            }
+        }
+        let now = std::time::Instant::now();
+        if !out.is_empty() || now >= deadline {
+            return out;
+        }
+        ps = db.pubsub_signal.wait_timeout(ps, deadline - now).unwrap().0;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_pubsub_poll(sub_id: u64, out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_pubsub_poll(default_instance(), sub_id, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_pubsub_poll(db: *const Instance, sub_id: u64, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let mut ps = db.pubsub.lock().unwrap();
+    let Some(q) = ps.queues.get_mut(&sub_id) else {
+        fail(CacheError::invalid("unknown subscription"));
+        return prepare_return(Vec::new(), out_len);
+    };
+    let Some(msg) = q.pop_front() else {
+        unsafe { *out_len = 0 };
+        return std::ptr::null_mut();
+    };
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(msg.channel.len() as u32).to_le_bytes());
+    buf.extend_from_slice(msg.channel.as_bytes());
+    buf.extend_from_slice(&(msg.payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&msg.payload);
+    prepare_return(buf, out_len)
+}
+
+// --- Phase3: Keyspace notifications polling ---
+
+#[no_mangle]
+pub extern "C" fn cache_notifications_poll(out_len: *mut usize) -> *mut c_uchar {
+    cache_instance_notifications_poll(default_instance(), out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_notifications_poll(db: *const Instance, out_len: *mut usize) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let mut q = db.notify_queue.lock().unwrap();
+    let Some(ev) = q.pop_front() else {
+        unsafe { *out_len = 0 };
+        return std::ptr::null_mut();
+    };
+    let mut buf = Vec::new();
+    buf.push(ev.kind);
+    buf.extend_from_slice(&(ev.key.len() as u32).to_le_bytes());
+    buf.extend_from_slice(ev.key.as_bytes());
+    buf.extend_from_slice(&ev.at_ms.to_le_bytes());
+    prepare_return(buf, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_notifications_clear() {
+    cache_instance_notifications_clear(default_instance())
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_notifications_clear(db: *const Instance) {
+    let Some(db) = instance(db) else { return; };
+    let mut q = db.notify_queue.lock().unwrap();
+    q.clear();
+}
+
+// --- Phase3: Streams ---
+
+// Where the id of a new entry comes from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum XAddId {
+    // `*`: the current time, or just above the last id while the clock is behind it.
+    Auto,
+    // `<ms>-*`: the next sequence number within `ms`.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+// How a stream is capped by XTRIM or XADD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum StreamTrim {
+    // Keep the newest n entries.
+    MaxLen(u64),
+    // Drop the entries below the id.
+    MinId(StreamId),
+}
+
+// Resolves the id of an entry added after `last`; it must be above it.
+fn next_stream_id(last: StreamId, id: XAddId) -> Result<StreamId, CacheError> {
+    let too_small = || CacheError::invalid("The ID specified in XADD is equal or smaller than the target stream top item");
+    match id {
+        XAddId::Explicit(id) if id == StreamId::MIN => {
+            Err(CacheError::invalid("The ID specified in XADD must be greater than 0-0"))
+        }
+        XAddId::Explicit(id) if id <= last => Err(too_small()),
+        XAddId::Explicit(id) => Ok(id),
+        XAddId::AutoSeq(ms) if ms < last.ms => Err(too_small()),
+        XAddId::AutoSeq(ms) if ms == last.ms => last.next().filter(|id| id.ms == ms).ok_or_else(too_small),
+        XAddId::AutoSeq(ms) => Ok(StreamId::new(ms, 0)),
+        XAddId::Auto => match now_ms() {
+            now if now > last.ms => Ok(StreamId::new(now, 0)),
+            _ => last.next().ok_or_else(|| {
+                CacheError::invalid("The stream has exhausted the last possible ID, unable to add more items")
+            }),
+        },
+    }
+}
+
+// The id below which `trim` drops entries, removing at most `limit` of them; None if it drops none.
+fn trim_boundary(stream: &StreamData, trim: StreamTrim, limit: usize) -> Option<StreamId> {
+    let (remove, min_id) = match trim {
+        StreamTrim::MaxLen(n) => (stream.entries.len().saturating_sub(usize::try_from(n).unwrap_or(usize::MAX)), None),
+        StreamTrim::MinId(id) => (stream.entries.range(..id).count(), Some(id)),
+    };
+    if remove == 0 || limit == 0 {
+        return None;
+    }
+    if remove <= limit {
+        if let Some(id) = min_id {
+            return Some(id);
+        }
+    }
+    // The first entry kept, or just above the newest one when all of them go.
+    let mut ids = stream.entries.keys();
+    ids.nth(remove.min(limit)).copied().or_else(|| stream.entries.keys().next_back().map(|id| id.next().unwrap_or(StreamId::MAX)))
+}
+
+// Trims the stream at `key` under a held shard lock. Returns how many entries went.
+fn xtrim_locked<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    trim: StreamTrim,
+    limit: usize,
+) -> Result<usize, CacheError> {
+    maybe_remove_if_expired(db, state, key);
+    let below = match state.map.peek(key.as_bytes()).map(|e| &e.value) {
+        Some(Value::Stream(stream)) => trim_boundary(stream, trim, limit),
+        Some(_) => return Err(CacheError::WrongType),
+        None => None,
+    };
+    let Some(below) = below else { return Ok(0); };
+    aof_write_key_id(db, AOF_OP_XTRIM, key, below);
+    apply_xtrim_internal(db, state, key, below)
+}
+
+// Adds an entry with the single field `payload`. Returns the new entry id.
+fn cmd_xadd<K: CacheKey + ?Sized>(db: &Instance, key: &K, payload: Vec<u8>) -> Result<StreamId, CacheError> {
+    let fields = vec![(STREAM_PAYLOAD_FIELD.to_vec(), payload)];
+    let id = cmd_xadd_with(db, key, XAddId::Auto, fields, None, false)?;
+    Ok(id.expect("XADD creates missing streams"))
+}
+
+// Adds an entry and then applies `trim` (removing at most its limit of entries). Returns the new
+// id, or None if the stream is missing and `nomkstream` is set.
+fn cmd_xadd_with<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    id: XAddId,
+    fields: StreamFields,
+    trim: Option<(StreamTrim, usize)>,
+    nomkstream: bool,
+) -> Result<Option<StreamId>, CacheError> {
+    if key.as_bytes().is_empty() {
+        return Err(CacheError::invalid("empty key"));
+    }
+    if fields.is_empty() {
+        return Err(CacheError::invalid("stream entries need at least one field"));
+    }
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    if nomkstream && !state.map.contains(key.as_bytes()) {
+        return Ok(None);
+    }
+    prepare_typed_write(db, &mut state, key, Value::Stream(StreamData::default()))?;
+    let last = match state.map.peek(key.as_bytes()).map(|e| &e.value) {
+        Some(Value::Stream(stream)) => stream.last_id,
+        _ => StreamId::MIN,
+    };
+    let id = next_stream_id(last, id)?;
+    aof_write_xadd(db, key, id, &fields);
+    apply_xadd_internal(db, &mut state, key, id, fields)?;
+    if let Some((trim, limit)) = trim {
+        xtrim_locked(db, &mut state, key, trim, limit)?;
+    }
+    drop(state);
+    blocking::wake(db, key);
+    Ok(Some(id))
+}
+
+// Returns how many entries were removed, at most `limit`.
+fn cmd_xtrim<K: CacheKey + ?Sized>(db: &Instance, key: &K, trim: StreamTrim, limit: usize) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    xtrim_locked(db, &mut state, key, trim, limit)
+}
+
+// Returns how many of `ids` were entries. The last id is kept, so new ids stay above them.
+fn cmd_xdel<K: CacheKey + ?Sized>(db: &Instance, key: &K, ids: &[StreamId]) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    let deleted = apply_xdel_internal(db, &mut state, key, ids)?;
+    if deleted > 0 {
+        aof_write_xdel(db, key, ids);
+    }
+    Ok(deleted)
+}
+
+fn cmd_xlen<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<usize, CacheError> {
+    let state = shard_read(db, key);
+    match get_entry(db, &state, key).map(|e| &e.value) {
+        Some(Value::Stream(stream)) => Ok(stream.entries.len()),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(0),
+    }
+}
+
+// Adds an entry with the single field `payload`. Returns its id, or 0-0 with the reason
+// recorded (empty key, rejected write).
+#[no_mangle]
+pub extern "C" fn cache_xadd(key: *const c_char, payload: *const c_uchar, len: usize) -> StreamId {
+    cache_instance_xadd(default_instance(), key, payload, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xadd(
+    db: *const Instance,
+    key: *const c_char,
+    payload: *const c_uchar,
+    len: usize,
+) -> StreamId {
+    let Some(db) = instance(db) else { return StreamId::MIN; };
+    let key_str = unsafe { to_string(key) };
+    let payload_vec = unsafe { to_bytes(payload, len) };
+    cmd_xadd(db, &key_str, payload_vec).unwrap_or_else(|e| {
+        fail(e);
+        StreamId::MIN
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xadd_b(key: *const c_uchar, key_len: usize, payload: *const c_uchar, len: usize) -> StreamId {
+    cache_instance_xadd_b(default_instance(), key, key_len, payload, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xadd_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    payload: *const c_uchar,
+    len: usize,
+) -> StreamId {
+    let Some(db) = instance(db) else { return StreamId::MIN; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let payload_vec = unsafe { to_bytes(payload, len) };
+    cmd_xadd(db, &key_vec, payload_vec).unwrap_or_else(|e| {
+        fail(e);
+        StreamId::MIN
+    })
+}
+
+// XADD of the fields packed as items alternating name and value ([Count u32] { [Len u32]
+// [Bytes] }), with an explicit `id` (null = generated) and a cap: keep the newest `max_len`
+// entries (negative = no cap) or drop those below `min_id` (null = none), not both.
+// Writes the new id to `out_id`.
+#[no_mangle]
+pub extern "C" fn cache_xadd_ex(
+    key: *const c_char,
+    fields: *const c_uchar,
+    fields_len: usize,
+    id: *const StreamId,
+    max_len: i64,
+    min_id: *const StreamId,
+    out_id: *mut StreamId,
+) -> i32 {
+    cache_instance_xadd_ex(default_instance(), key, fields, fields_len, id, max_len, min_id, out_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xadd_ex(
+    db: *const Instance,
+    key: *const c_char,
+    fields: *const c_uchar,
+    fields_len: usize,
+    id: *const StreamId,
+    max_len: i64,
+    min_id: *const StreamId,
+    out_id: *mut StreamId,
+) -> i32 {
+    let key_str = unsafe { to_string(key) };
+    xadd_ex_export(db, key_str.as_str(), fields, fields_len, id, max_len, min_id, out_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xadd_ex_b(
+    key: *const c_uchar,
+    key_len: usize,
+    fields: *const c_uchar,
+    fields_len: usize,
+    id: *const StreamId,
+    max_len: i64,
+    min_id: *const StreamId,
+    out_id: *mut StreamId,
+) -> i32 {
+    cache_instance_xadd_ex_b(default_instance(), key, key_len, fields, fields_len, id, max_len, min_id, out_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xadd_ex_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    fields: *const c_uchar,
+    fields_len: usize,
+    id: *const StreamId,
+    max_len: i64,
+    min_id: *const StreamId,
+    out_id: *mut StreamId,
+) -> i32 {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    xadd_ex_export(db, key_vec.as_slice(), fields, fields_len, id, max_len, min_id, out_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn xadd_ex_export<K: CacheKey + ?Sized>(
+    db: *const Instance,
+    key: &K,
+    fields: *const c_uchar,
+    fields_len: usize,
+    id: *const StreamId,
+    max_len: i64,
+    min_id: *const StreamId,
+    out_id: *mut StreamId,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let packed = unsafe { to_bytes(fields, fields_len) };
+    let id = if id.is_null() { XAddId::Auto } else { XAddId::Explicit(unsafe { *id }) };
+    let res = unpack_stream_fields(&packed).and_then(|fields| {
+        let trim = stream_trim_arg(max_len, min_id)?.map(|trim| (trim, usize::MAX));
+        cmd_xadd_with(db, key, id, fields, trim, false)
+    });
+    match res {
+        Ok(id) => {
+            if !out_id.is_null() {
+                unsafe { *out_id = id.unwrap_or_default() };
+            }
+            CacheStatus::Ok as i32
+        }
+        Err(e) => fail(e),
+    }
+}
+
+// Field/value pairs from packed items alternating name and value.
+fn unpack_stream_fields(packed: &[u8]) -> Result<StreamFields, CacheError> {
+    let items = unpack_items(packed)?;
+    if items.len() % 2 != 0 {
+        return Err(CacheError::invalid("stream fields must alternate name and value"));
+    }
+    let mut items = items.into_iter();
+    Ok(std::iter::from_fn(|| Some((items.next()?, items.next()?))).collect())
+}
+
+// The cap given as `max_len` (negative = none) or `min_id` (null = none).
+fn stream_trim_arg(max_len: i64, min_id: *const StreamId) -> Result<Option<StreamTrim>, CacheError> {
+    match (max_len, min_id.is_null()) {
+        (n, true) if n < 0 => Ok(None),
+        (n, true) => Ok(Some(StreamTrim::MaxLen(n as u64))),
+        (n, false) if n < 0 => Ok(Some(StreamTrim::MinId(unsafe { *min_id }))),
+        _ => Err(CacheError::invalid("MAXLEN and MINID are mutually exclusive")),
+    }
+}
+
+// Keeps the newest `max_len` entries (negative = not this way) or drops those below `min_id`
+// (null = not this way); exactly one must be given. Returns how many entries were removed.
+#[no_mangle]
+pub extern "C" fn cache_xtrim(key: *const c_char, max_len: i64, min_id: *const StreamId) -> i64 {
+    cache_instance_xtrim(default_instance(), key, max_len, min_id)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xtrim(
+    db: *const Instance,
+    key: *const c_char,
+    max_len: i64,
+    min_id: *const StreamId,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let res = stream_trim_arg(max_len, min_id).and_then(|trim| {
+        let trim = trim.ok_or_else(|| CacheError::invalid("MAXLEN or MINID required"))?;
+        cmd_xtrim(db, &key_str, trim, usize::MAX)
+    });
+    match res {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xtrim_b(key: *const c_uchar, key_len: usize, max_len: i64, min_id: *const StreamId) -> i64 {
+    cache_instance_xtrim_b(default_instance(), key, key_len, max_len, min_id)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xtrim_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    max_len: i64,
+    min_id: *const StreamId,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let res = stream_trim_arg(max_len, min_id).and_then(|trim| {
+        let trim = trim.ok_or_else(|| CacheError::invalid("MAXLEN or MINID required"))?;
+        cmd_xtrim(db, &key_vec, trim, usize::MAX)
+    });
+    match res {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Returns how many of the `len` ids were entries.
+#[no_mangle]
+pub extern "C" fn cache_xdel(key: *const c_char, ids: *const StreamId, len: usize) -> i64 {
+    cache_instance_xdel(default_instance(), key, ids, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xdel(db: *const Instance, key: *const c_char, ids: *const StreamId, len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let ids = unsafe { ids_slice(ids, len) };
+    match cmd_xdel(db, &key_str, ids) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xdel_b(key: *const c_uchar, key_len: usize, ids: *const StreamId, len: usize) -> i64 {
+    cache_instance_xdel_b(default_instance(), key, key_len, ids, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xdel_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    ids: *const StreamId,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let ids = unsafe { ids_slice(ids, len) };
+    match cmd_xdel(db, &key_vec, ids) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Number of entries, 0 for a missing key.
+#[no_mangle]
+pub extern "C" fn cache_xlen(key: *const c_char) -> i64 {
+    cache_instance_xlen(default_instance(), key)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xlen(db: *const Instance, key: *const c_char) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    match cmd_xlen(db, &key_str) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xlen_b(key: *const c_uchar, key_len: usize) -> i64 {
+    cache_instance_xlen_b(default_instance(), key, key_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xlen_b(db: *const Instance, key: *const c_uchar, key_len: usize) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    match cmd_xlen(db, &key_vec) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// (id, fields) pairs of a stream range.
+type StreamItems = Vec<(StreamId, StreamFields)>;
+
+// Entries from `start` to `end` inclusive, oldest first (newest first with `rev`), at most `count`.
+fn cmd_xrange<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    start: StreamId,
+    end: StreamId,
+    count: Option<usize>,
+    rev: bool,
+) -> Result<Option<StreamItems>, CacheError> {
+    let state = shard_read(db, key);
+    let Some(entry) = get_entry(db, &state, key) else { return Ok(None); };
+    let Value::Stream(stream) = &entry.value else {
+        return Err(CacheError::WrongType);
+    };
+    if start > end {
+        return Ok(Some(Vec::new()));
+    }
+
+    let range = stream.entries.range(start..=end).map(|(id, e)| (*id, e.fields()));
+    let count = count.unwrap_or(usize::MAX);
+    let items = if rev { range.rev().take(count).collect() } else { range.take(count).collect() };
+    Ok(Some(items))
+}
+
+// Entries from `start_id` to `end_id` inclusive, oldest first, at most `count` (0 = all).
+#[no_mangle]
+pub extern "C" fn cache_xrange(
+    key: *const c_char,
+    start_id: StreamId,
+    end_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xrange(default_instance(), key, start_id, end_id, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xrange(
+    db: *const Instance,
+    key: *const c_char,
+    start_id: StreamId,
+    end_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_str = unsafe { to_string(key) };
+    xrange_export(db, key_str.as_str(), start_id, end_id, count, false, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    start_id: StreamId,
+    end_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xrange_b(default_instance(), key, key_len, start_id, end_id, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    start_id: StreamId,
+    end_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    xrange_export(db, key_vec.as_slice(), start_id, end_id, count, false, out_len)
+}
+
+// Entries from `end_id` down to `start_id` inclusive, newest first, at most `count` (0 = all).
+#[no_mangle]
+pub extern "C" fn cache_xrevrange(
+    key: *const c_char,
+    end_id: StreamId,
+    start_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xrevrange(default_instance(), key, end_id, start_id, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xrevrange(
+    db: *const Instance,
+    key: *const c_char,
+    end_id: StreamId,
+    start_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_str = unsafe { to_string(key) };
+    xrange_export(db, key_str.as_str(), start_id, end_id, count, true, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xrevrange_b(
+    key: *const c_uchar,
+    key_len: usize,
+    end_id: StreamId,
+    start_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xrevrange_b(default_instance(), key, key_len, end_id, start_id, count, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xrevrange_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    end_id: StreamId,
+    start_id: StreamId,
+    count: usize,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    xrange_export(db, key_vec.as_slice(), start_id, end_id, count, true, out_len)
+}
+
+fn xrange_export<K: CacheKey + ?Sized>(
+    db: *const Instance,
+    key: &K,
+    start_id: StreamId,
+    end_id: StreamId,
+    count: usize,
+    rev: bool,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let count = (count > 0).then_some(count);
+    let res = cmd_xrange(db, key, start_id, end_id, count, rev).map(|items| {
+        items.map(|items| {
+            let mut flat = Vec::new();
+            pack_stream_items(&mut flat, &items, Some);
+            flat
+        })
+    });
+    return_found(res, out_len)
+}
+
+// Appends [Count u32] { [Ms u64] [Seq u64] [FieldCount u32] { [NameLen u32] [Name]
+// [ValueLen u32] [Value] } }; a FieldCount of u32::MAX (and no fields) marks an entry deleted
+// since it was delivered.
+fn pack_stream_items<'a, P: 'a>(flat: &mut Vec<u8>, items: &'a [(StreamId, P)], fields: impl Fn(&'a P) -> Option<&'a StreamFields>) {
+    flat.extend_from_slice(&(items.len() as u32).to_le_bytes());
+    for (id, p) in items {
+        flat.extend_from_slice(&id.ms.to_le_bytes());
+        flat.extend_from_slice(&id.seq.to_le_bytes());
+        let Some(fields) = fields(p) else {
+            flat.extend_from_slice(&u32::MAX.to_le_bytes());
+            continue;
+        };
+        flat.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+        for (name, value) in fields {
+            flat.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            flat.extend_from_slice(name);
+            flat.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            flat.extend_from_slice(value);
+        }
+    }
+}
+
+// Id of the newest entry ever added, 0-0 for a missing stream (what XREAD's `$` reads after).
+fn stream_last_id<K: CacheKey + ?Sized>(db: &Instance, key: &K) -> Result<StreamId, CacheError> {
+    let state = shard_read(db, key);
+    match get_entry(db, &state, key).map(|e| &e.value) {
+        Some(Value::Stream(stream)) => Ok(stream.last_id),
+        Some(_) => Err(CacheError::WrongType),
+        None => Ok(StreamId::MIN),
+    }
+}
+
+// Entries after each (key, id), at most `count` per stream; streams without any are left out.
+fn cmd_xread<K: CacheKey + Clone>(
+    db: &Instance,
+    streams: &[(K, StreamId)],
+    count: Option<usize>,
+) -> Result<Vec<(K, StreamItems)>, CacheError> {
+    let mut found = Vec::new();
+    for (key, after) in streams {
+        let Some(start) = after.next() else { continue; };
+        let items = cmd_xrange(db, key, start, StreamId::MAX, count, false)?.unwrap_or_default();
+        if !items.is_empty() {
+            found.push((key.clone(), items));
+        }
+    }
+    Ok(found)
+}
+
+// Reads entries newer than `after_ids[i]` from each of the packed `keys` (StreamId::MAX = only
+// entries added from now on, like `$`), at most `count` per stream (0 = all), waiting up to
+// `timeout_ms` (0 = until one arrives) if there are none yet.
+// Returns [StreamCount u32] { [KeyLen u32] [Key] <cache_xrange layout> } for the streams that
+// have entries, or null on timeout (NotFound).
+#[no_mangle]
+pub extern "C" fn cache_xread_block(
+    keys: *const c_uchar,
+    keys_len: usize,
+    after_ids: *const StreamId,
+    count: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xread_block(default_instance(), keys, keys_len, after_ids, count, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xread_block(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    after_ids: *const StreamId,
+    count: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    xread_block_export(db, unpack_keys, keys, keys_len, after_ids, count, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xread_block_b(
+    keys: *const c_uchar,
+    keys_len: usize,
+    after_ids: *const StreamId,
+    count: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xread_block_b(default_instance(), keys, keys_len, after_ids, count, timeout_ms, out_len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xread_block_b(
+    db: *const Instance,
+    keys: *const c_uchar,
+    keys_len: usize,
+    after_ids: *const StreamId,
+    count: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    xread_block_export(db, unpack_keys_b, keys, keys_len, after_ids, count, timeout_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn xread_block_export<K: CacheKey + Clone>(
+    db: *const Instance,
+    unpack: fn(&[u8]) -> Result<Vec<K>, CacheError>,
+    keys: *const c_uchar,
+    keys_len: usize,
+    after_ids: *const StreamId,
+    count: usize,
+    timeout_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    let res = unpack(&packed).and_then(|keys| {
+        if after_ids.is_null() {
+            return Err(CacheError::invalid("missing stream ids"));
+        }
+        let ids = unsafe { std::slice::from_raw_parts(after_ids, keys.len()) };
+        let mut streams = Vec::with_capacity(keys.len());
+        for (key, &id) in keys.into_iter().zip(ids) {
+            let after = if id == StreamId::MAX { stream_last_id(db, &key)? } else { id };
+            streams.push((key, after));
+        }
+        let count = (count > 0).then_some(count);
+        let found = blocking::blocking_xread(db, &streams, count, block_timeout(timeout_ms), &|| false)?;
+        Ok(found.map(|found| pack_stream_reads(&found, Some)))
+    });
+    return_found(res, out_len)
+}
+
+// Packs [StreamCount u32] { [KeyLen u32] [Key] <cache_xrange layout> }.
+fn pack_stream_reads<'a, K: CacheKey, P: 'a>(
+    found: &'a [(K, Vec<(StreamId, P)>)],
+    fields: impl Fn(&'a P) -> Option<&'a StreamFields> + Copy,
+) -> Vec<u8> {
+    let mut flat = Vec::new();
+    flat.extend_from_slice(&(found.len() as u32).to_le_bytes());
+    for (key, items) in found {
+        flat.extend_from_slice(&(key.as_bytes().len() as u32).to_le_bytes());
+        flat.extend_from_slice(key.as_bytes());
+        pack_stream_items(&mut flat, items, fields);
+    }
+    flat
+}
+
+// --- Stream consumer groups ---
+
+// Error for XGROUP on a missing key, with Redis' wording.
+fn xgroup_missing_key() -> CacheError {
+    CacheError::invalid(
+        "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.",
+    )
+}
+
+// The stream at a looked-up entry and its group `group`; NoGroup if either is missing.
+fn stream_group<'a>(entry: Option<&'a Entry>, group: &str) -> Result<(&'a StreamData, &'a ConsumerGroup), CacheError> {
+    let Some(entry) = entry else { return Err(CacheError::NoGroup); };
+    let Value::Stream(stream) = &entry.value else { return Err(CacheError::WrongType); };
+    let g = stream.groups.get(group).ok_or(CacheError::NoGroup)?;
+    Ok((stream, g))
+}
+
+// Creates `group` delivering entries after `id` (None = `$`, the newest entry).
+fn cmd_xgroup_create<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    group: &str,
+    id: Option<StreamId>,
+    mkstream: bool,
+) -> Result<(), CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let last_id = match state.map.peek(key.as_bytes()).map(|e| &e.value) {
+        Some(Value::Stream(stream)) if stream.groups.contains_key(group) => return Err(CacheError::BusyGroup),
+        Some(Value::Stream(stream)) => id.unwrap_or(stream.last_id),
+        Some(_) => return Err(CacheError::WrongType),
+        None if !mkstream => return Err(xgroup_missing_key()),
+        None => {
+            check_oom(db, &mut state, key)?;
+            id.unwrap_or(StreamId::MIN)
+        }
+    };
+    let mut buf = Vec::new();
+    aof_encode_xgroup_create(&mut buf, key, group, last_id);
+    aof_write(db, &buf);
+    apply_xgroup_create_internal(db, &mut state, key, group, last_id, mkstream)?;
+    Ok(())
+}
+
+// Returns true if the group existed.
+fn cmd_xgroup_destroy<K: CacheKey + ?Sized>(db: &Instance, key: &K, group: &str) -> Result<bool, CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    if !state.map.contains(key.as_bytes()) {
+        return Err(xgroup_missing_key());
+    }
+    let destroyed = apply_xgroup_destroy_internal(db, &mut state, key, group)?;
+    if destroyed {
+        aof_write_key_item(db, AOF_OP_XGROUP_DESTROY, key, group.as_bytes());
+    }
+    Ok(destroyed)
+}
+
+// (id, fields) pairs read through a group; history reads give None for entries deleted since
+// they were delivered.
+type GroupItems = Vec<(StreamId, Option<StreamFields>)>;
+
+// Hands entries of `key` to `consumer`: with `after` None (`>`) the entries the group has not
+// delivered yet, which join the consumer's pending list unless `noack`; otherwise the
+// consumer's own pending entries after that id (a history read, which counts as a redelivery).
+fn xreadgroup_one<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    group: &str,
+    consumer: &str,
+    after: Option<StreamId>,
+    count: Option<usize>,
+    noack: bool,
+) -> Result<GroupItems, CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let (stream, g) = stream_group(state.map.peek(key.as_bytes()), group)?;
+    let count = count.unwrap_or(usize::MAX);
+    let (items, d) = match after {
+        None => {
+            let Some(start) = g.last_delivered.next() else { return Ok(Vec::new()); };
+            let items: GroupItems =
+                stream.entries.range(start..).take(count).map(|(id, e)| (*id, Some(e.fields()))).collect();
+            let Some(&(last_id, _)) = items.last() else { return Ok(items); };
+            let ids = if noack { Vec::new() } else { items.iter().map(|(id, _)| *id).collect() };
+            (items, Delivery { consumer: consumer.to_string(), at_ms: now_ms(), last_id, deliveries: 1, ids })
+        }
+        Some(after) => {
+            let Some(start) = after.next() else { return Ok(Vec::new()); };
+            let items: GroupItems = g
+                .pending
+                .range(start..)
+                .filter(|(_, p)| p.consumer == consumer)
+                .map(|(id, _)| (*id, stream.entries.get(id).map(StreamEntry::fields)))
+                .take(count)
+                .collect();
+            if items.is_empty() {
+                return Ok(items);
+            }
+            let ids = items.iter().map(|(id, _)| *id).collect();
+            let last_id = StreamId::MIN;
+            (items, Delivery { consumer: consumer.to_string(), at_ms: now_ms(), last_id, deliveries: 1, ids })
+        }
+    };
+    aof_write_xclaim(db, key, group, &d);
+    apply_xclaim_internal(db, &mut state, key, group, &d)?;
+    Ok(items)
+}
+
+// XREADGROUP over several streams; `None` ids read new entries (`>`), which leave out streams
+// without any, while history reads always report their stream.
+fn cmd_xreadgroup<K: CacheKey + Clone>(
+    db: &Instance,
+    group: &str,
+    consumer: &str,
+    streams: &[(K, Option<StreamId>)],
+    count: Option<usize>,
+    noack: bool,
+) -> Result<Vec<(K, GroupItems)>, CacheError> {
+    let mut found = Vec::new();
+    for (key, after) in streams {
+        let items = xreadgroup_one(db, key, group, consumer, *after, count, noack)?;
+        if after.is_some() || !items.is_empty() {
+            found.push((key.clone(), items));
+        }
+    }
+    Ok(found)
+}
+
+// Returns how many of `ids` were pending; a missing key or group acknowledges nothing.
+fn cmd_xack<K: CacheKey + ?Sized>(db: &Instance, key: &K, group: &str, ids: &[StreamId]) -> Result<usize, CacheError> {
+    let mut state = shard_write(db, key);
+    let acked = apply_xack_internal(db, &mut state, key, group, ids)?;
+    if acked > 0 {
+        aof_write_xack(db, key, group, ids);
+    }
+    Ok(acked)
+}
+
+// XPENDING without a range: how many entries are pending, the lowest and highest of their
+// ids (0-0 when none) and the pending count per consumer.
+#[derive(Debug, Default, PartialEq)]
+struct PendingSummary {
+    count: usize,
+    first: StreamId,
+    last: StreamId,
+    consumers: Vec<(String, usize)>,
+}
+
+fn cmd_xpending_summary<K: CacheKey + ?Sized>(db: &Instance, key: &K, group: &str) -> Result<PendingSummary, CacheError> {
+    let state = shard_read(db, key);
+    let (_, g) = stream_group(get_entry(db, &state, key), group)?;
+    let mut consumers: BTreeMap<&str, usize> = BTreeMap::new();
+    for p in g.pending.values() {
+        *consumers.entry(&p.consumer).or_default() += 1;
+    }
+    Ok(PendingSummary {
+        count: g.pending.len(),
+        first: g.pending.keys().next().copied().unwrap_or_default(),
+        last: g.pending.keys().next_back().copied().unwrap_or_default(),
+        consumers: consumers.into_iter().map(|(c, n)| (c.to_string(), n)).collect(),
+    })
+}
+
+// One pending entry as XPENDING reports it.
+#[derive(Debug, PartialEq)]
+struct PendingItem {
+    id: StreamId,
+    consumer: String,
+    idle_ms: u64,
+    deliveries: u64,
+}
+
+// Pending entries with ids in [start, end], at most `count`, optionally only those of
+// `consumer` and idle for at least `min_idle_ms`.
+#[allow(clippy::too_many_arguments)]
+fn cmd_xpending<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    group: &str,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: Option<&str>,
+    min_idle_ms: u64,
+) -> Result<Vec<PendingItem>, CacheError> {
+    let state = shard_read(db, key);
+    let (_, g) = stream_group(get_entry(db, &state, key), group)?;
+    if start > end {
+        return Ok(Vec::new());
+    }
+    let now = now_ms();
+    Ok(g.pending
+        .range(start..=end)
+        .filter(|(_, p)| consumer.is_none_or(|c| p.consumer == c))
+        .map(|(id, p)| PendingItem {
+            id: *id,
+            consumer: p.consumer.clone(),
+            idle_ms: now.saturating_sub(p.delivered_at_ms),
+            deliveries: p.deliveries,
+        })
+        .filter(|item| item.idle_ms >= min_idle_ms)
+        .take(count)
+        .collect())
+}
+
+// Moves the pending entries among `ids` idle for at least `min_idle_ms` to `consumer`.
+// Returns the claimed entries, with `justid` their fields are left out and the delivery
+// count is not raised, and the ids whose entries were deleted, which leave the pending list.
+fn claim_ids<K: CacheKey + ?Sized>(
+    db: &Instance,
+    state: &mut Shard,
+    key: &K,
+    group: &str,
+    consumer: &str,
+    ids: Vec<StreamId>,
+    justid: bool,
+) -> Result<(StreamItems, Vec<StreamId>), CacheError> {
+    let (stream, _) = stream_group(state.map.peek(key.as_bytes()), group)?;
+    let (ids, deleted): (Vec<StreamId>, Vec<StreamId>) = ids.into_iter().partition(|id| stream.entries.contains_key(id));
+    let items: StreamItems = ids
+        .iter()
+        .map(|id| (*id, if justid { Vec::new() } else { stream.entries[id].fields() }))
+        .collect();
+    if !deleted.is_empty() {
+        aof_write_xack(db, key, group, &deleted);
+        apply_xack_internal(db, state, key, group, &deleted)?;
+    }
+    if !ids.is_empty() {
+        let last_id = StreamId::MIN;
+        let d = Delivery { consumer: consumer.to_string(), at_ms: now_ms(), last_id, deliveries: u64::from(!justid), ids };
+        aof_write_xclaim(db, key, group, &d);
+        apply_xclaim_internal(db, state, key, group, &d)?;
+    }
+    Ok((items, deleted))
+}
+
+// Whether `id` is pending in `g` and has been idle for at least `min_idle_ms`.
+fn idle_pending(g: &ConsumerGroup, min_idle_ms: u64, id: StreamId) -> bool {
+    g.pending.get(&id).is_some_and(|p| now_ms().saturating_sub(p.delivered_at_ms) >= min_idle_ms)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_xclaim<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    group: &str,
+    consumer: &str,
+    min_idle_ms: u64,
+    ids: &[StreamId],
+    justid: bool,
+) -> Result<StreamItems, CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let (_, g) = stream_group(state.map.peek(key.as_bytes()), group)?;
+    let mut claimable = Vec::new();
+    for &id in ids {
+        if idle_pending(g, min_idle_ms, id) && !claimable.contains(&id) {
+            claimable.push(id);
+        }
+    }
+    Ok(claim_ids(db, &mut state, key, group, consumer, claimable, justid)?.0)
+}
+
+// XAUTOCLAIM: claims up to `count` entries idle for `min_idle_ms`, scanning the PEL from
+// `start` (at most ten entries per claim, as in Redis). Returns the id to continue from (0-0
+// once the scan reached the end), the claimed entries and the ids of deleted entries it dropped.
+#[allow(clippy::too_many_arguments)]
+fn cmd_xautoclaim<K: CacheKey + ?Sized>(
+    db: &Instance,
+    key: &K,
+    group: &str,
+    consumer: &str,
+    min_idle_ms: u64,
+    start: StreamId,
+    count: usize,
+    justid: bool,
+) -> Result<(StreamId, StreamItems, Vec<StreamId>), CacheError> {
+    let mut state = shard_write(db, key);
+    maybe_remove_if_expired(db, &mut state, key);
+    let (_, g) = stream_group(state.map.peek(key.as_bytes()), group)?;
+    let mut scan = g.pending.range(start..).map(|(id, _)| *id);
+    let mut claimable = Vec::new();
+    let mut attempts = count.saturating_mul(10);
+    let mut next = StreamId::MIN;
+    while claimable.len() < count && attempts > 0 {
+        let Some(id) = scan.next() else { break; };
+        attempts -= 1;
+        if idle_pending(g, min_idle_ms, id) {
+            claimable.push(id);
+        }
+    }
+    if let Some(id) = scan.next() {
+        next = id;
+    }
+    let (items, deleted) = claim_ids(db, &mut state, key, group, consumer, claimable, justid)?;
+    Ok((next, items, deleted))
+}
+
+// Creates consumer group `group` on stream `key`, delivering entries after `last_id`
+// (StreamId::MAX = `$`, only entries added from now on). With `mkstream` != 0 a missing key gets
+// an empty stream. Returns 1, or InvalidArgument (BUSYGROUP, missing key) / WrongType.
+#[no_mangle]
+pub extern "C" fn cache_xgroup_create(
+    key: *const c_char,
+    group: *const c_char,
+    last_id: StreamId,
+    mkstream: i32,
+) -> i32 {
+    cache_instance_xgroup_create(default_instance(), key, group, last_id, mkstream)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xgroup_create(
+    db: *const Instance,
+    key: *const c_char,
+    group: *const c_char,
+    last_id: StreamId,
+    mkstream: i32,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let group_str = unsafe { to_string(group) };
+    let id = (last_id != StreamId::MAX).then_some(last_id);
+    status(cmd_xgroup_create(db, &key_str, &group_str, id, mkstream != 0), |_| 1)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xgroup_create_b(
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    last_id: StreamId,
+    mkstream: i32,
+) -> i32 {
+    cache_instance_xgroup_create_b(default_instance(), key, key_len, group, last_id, mkstream)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xgroup_create_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    last_id: StreamId,
+    mkstream: i32,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let group_str = unsafe { to_string(group) };
+    let id = (last_id != StreamId::MAX).then_some(last_id);
+    status(cmd_xgroup_create(db, &key_vec, &group_str, id, mkstream != 0), |_| 1)
+}
+
+// Returns 1 if the group was destroyed, 0 if there was none, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_xgroup_destroy(key: *const c_char, group: *const c_char) -> i32 {
+    cache_instance_xgroup_destroy(default_instance(), key, group)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xgroup_destroy(db: *const Instance, key: *const c_char, group: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_str = unsafe { to_string(key) };
+    let group_str = unsafe { to_string(group) };
+    status(cmd_xgroup_destroy(db, &key_str, &group_str), |destroyed| destroyed as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xgroup_destroy_b(key: *const c_uchar, key_len: usize, group: *const c_char) -> i32 {
+    cache_instance_xgroup_destroy_b(default_instance(), key, key_len, group)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xgroup_destroy_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let group_str = unsafe { to_string(group) };
+    status(cmd_xgroup_destroy(db, &key_vec, &group_str), |destroyed| destroyed as i32)
+}
+
+// Reads the packed `keys` as `consumer` of `group`. `ids[i]` StreamId::MAX reads entries the group
+// has not delivered yet (`>`); any other id re-reads the consumer's pending entries after it.
+// At most `count` entries per stream (0 = all); `noack` != 0 skips the pending list. If every
+// id is `>` and nothing is new, waits up to `block_ms` (0 = until an entry arrives, negative =
+// do not block). Returns the cache_xread_block layout, where history reads mark entries deleted
+// since delivery with a PayloadLen of u32::MAX, or null (NotFound) when nothing was read.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xreadgroup(
+    group: *const c_char,
+    consumer: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+    ids: *const StreamId,
+    count: usize,
+    noack: i32,
+    block_ms: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xreadgroup(default_instance(), group, consumer, keys, keys_len, ids, count, noack, block_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xreadgroup(
+    db: *const Instance,
+    group: *const c_char,
+    consumer: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+    ids: *const StreamId,
+    count: usize,
+    noack: i32,
+    block_ms: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    xreadgroup_export(db, group, consumer, unpack_keys, keys, keys_len, ids, count, noack, block_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xreadgroup_b(
+    group: *const c_char,
+    consumer: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+    ids: *const StreamId,
+    count: usize,
+    noack: i32,
+    block_ms: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xreadgroup_b(
+        default_instance(),
+        group,
+        consumer,
+        keys,
+        keys_len,
+        ids,
+        count,
+        noack,
+        block_ms,
+        out_len,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xreadgroup_b(
+    db: *const Instance,
+    group: *const c_char,
+    consumer: *const c_char,
+    keys: *const c_uchar,
+    keys_len: usize,
+    ids: *const StreamId,
+    count: usize,
+    noack: i32,
+    block_ms: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    xreadgroup_export(db, group, consumer, unpack_keys_b, keys, keys_len, ids, count, noack, block_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn xreadgroup_export<K: CacheKey + Clone>(
+    db: *const Instance,
+    group: *const c_char,
+    consumer: *const c_char,
+    unpack: fn(&[u8]) -> Result<Vec<K>, CacheError>,
+    keys: *const c_uchar,
+    keys_len: usize,
+    ids: *const StreamId,
+    count: usize,
+    noack: i32,
+    block_ms: i64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let group_str = unsafe { to_string(group) };
+    let consumer_str = unsafe { to_string(consumer) };
+    let packed = unsafe { to_bytes(keys, keys_len) };
+    let res = unpack(&packed).and_then(|keys| {
+        if ids.is_null() {
+            return Err(CacheError::invalid("missing stream ids"));
         }
-    } else {
-        // fallback scan (only checks top-level numeric field in JSON bytes)
-        for (k, entry) in state.map.iter() {
-            if is_expired(entry) {
-                continue;
-            }
-            let Some(json) = try_parse_json_from_entry(entry) else { continue; };
-            let Some(num) = extract_numeric_field(&json, &field) else { continue; };
-            let ok = match (op.as_str(), value_num) {
-                (">", Some(v)) => num > v,
-                (">=", Some(v)) => num >= v,
-                ("<", Some(v)) => num < v,
-                ("<=", Some(v)) => num <= v,
-                ("==", Some(v)) => num == v,
-                _ => false,
-            };
-            if ok {
-                keys.push(k.clone());
-            }
+        let ids = unsafe { std::slice::from_raw_parts(ids, keys.len()) };
+        let streams: Vec<(K, Option<StreamId>)> =
+            keys.into_iter().zip(ids).map(|(key, &id)| (key, (id != StreamId::MAX).then_some(id))).collect();
+        let count = (count > 0).then_some(count);
+        let read = || cmd_xreadgroup(db, &group_str, &consumer_str, &streams, count, noack != 0);
+        let found = if block_ms < 0 || streams.iter().any(|(_, id)| id.is_some()) {
+            Some(read()?).filter(|found| !found.is_empty())
+        } else {
+            let keys: Vec<K> = streams.iter().map(|(key, _)| key.clone()).collect();
+            blocking::blocking_stream_read(db, &keys, block_timeout(block_ms as u64), &|| false, &read)?
+        };
+        Ok(found.map(|found| pack_stream_reads(&found, |p| p.as_ref())))
+    });
+    return_found(res, out_len)
+}
+
+// Acknowledges `ids` (`len` of them). Returns how many were pending, or a negative CacheStatus.
+#[no_mangle]
+pub extern "C" fn cache_xack(key: *const c_char, group: *const c_char, ids: *const StreamId, len: usize) -> i64 {
+    cache_instance_xack(default_instance(), key, group, ids, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xack(
+    db: *const Instance,
+    key: *const c_char,
+    group: *const c_char,
+    ids: *const StreamId,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_str = unsafe { to_string(key) };
+    let group_str = unsafe { to_string(group) };
+    let ids = unsafe { ids_slice(ids, len) };
+    match cmd_xack(db, &key_str, &group_str, ids) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cache_xack_b(
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    ids: *const StreamId,
+    len: usize,
+) -> i64 {
+    cache_instance_xack_b(default_instance(), key, key_len, group, ids, len)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_xack_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    ids: *const StreamId,
+    len: usize,
+) -> i64 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i64; };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let group_str = unsafe { to_string(group) };
+    let ids = unsafe { ids_slice(ids, len) };
+    match cmd_xack(db, &key_vec, &group_str, ids) {
+        Ok(n) => n as i64,
+        Err(e) => fail(e) as i64,
+    }
+}
+
+// Borrows `len` ids from the caller (none if null).
+unsafe fn ids_slice<'a>(ids: *const StreamId, len: usize) -> &'a [StreamId] {
+    if ids.is_null() || len == 0 { &[] } else { std::slice::from_raw_parts(ids, len) }
+}
+
+// Lists pending entries of `group` with ids in [start, end], at most `count`, only those of
+// `consumer` unless it is null, idle for at least `min_idle_ms`. Returns
+// [Count u32] { [Ms u64] [Seq u64] [ConsumerLen u32] [Consumer] [IdleMs u64] [Deliveries u64] },
+// or null with the reason recorded (NOGROUP, WrongType).
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xpending(
+    key: *const c_char,
+    group: *const c_char,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xpending(default_instance(), key, group, start, end, count, consumer, min_idle_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xpending(
+    db: *const Instance,
+    key: *const c_char,
+    group: *const c_char,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_str = unsafe { to_string(key) };
+    xpending_export(db, key_str.as_str(), group, start, end, count, consumer, min_idle_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xpending_b(
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xpending_b(
+        default_instance(),
+        key,
+        key_len,
+        group,
+        start,
+        end,
+        count,
+        consumer,
+        min_idle_ms,
+        out_len,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xpending_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    xpending_export(db, key_vec.as_slice(), group, start, end, count, consumer, min_idle_ms, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn xpending_export<K: CacheKey + ?Sized>(
+    db: *const Instance,
+    key: &K,
+    group: *const c_char,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let group_str = unsafe { to_string(group) };
+    let consumer_str = (!consumer.is_null()).then(|| unsafe { to_string(consumer) });
+    let res = cmd_xpending(db, key, &group_str, start, end, count, consumer_str.as_deref(), min_idle_ms).map(|items| {
+        let mut flat = Vec::new();
+        flat.extend_from_slice(&(items.len() as u32).to_le_bytes());
+        for item in &items {
+            flat.extend_from_slice(&item.id.ms.to_le_bytes());
+            flat.extend_from_slice(&item.id.seq.to_le_bytes());
+            flat.extend_from_slice(&(item.consumer.len() as u32).to_le_bytes());
+            flat.extend_from_slice(item.consumer.as_bytes());
+            flat.extend_from_slice(&item.idle_ms.to_le_bytes());
+            flat.extend_from_slice(&item.deliveries.to_le_bytes());
+        }
+        Some(flat)
+    });
+    return_found(res, out_len)
+}
+
+// Gives `consumer` the pending entries among `ids` idle for at least `min_idle_ms`. Returns
+// the claimed entries in the cache_xrange layout (no fields with `justid` != 0), or null
+// with the reason recorded.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xclaim(
+    key: *const c_char,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    ids: *const StreamId,
+    len: usize,
+    justid: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xclaim(default_instance(), key, group, consumer, min_idle_ms, ids, len, justid, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xclaim(
+    db: *const Instance,
+    key: *const c_char,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    ids: *const StreamId,
+    len: usize,
+    justid: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_str = unsafe { to_string(key) };
+    let group_str = unsafe { to_string(group) };
+    let consumer_str = unsafe { to_string(consumer) };
+    let ids = unsafe { ids_slice(ids, len) };
+    let res = cmd_xclaim(db, &key_str, &group_str, &consumer_str, min_idle_ms, ids, justid != 0).map(|items| {
+        let mut flat = Vec::new();
+        pack_stream_items(&mut flat, &items, Some);
+        Some(flat)
+    });
+    return_found(res, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xclaim_b(
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    ids: *const StreamId,
+    len: usize,
+    justid: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xclaim_b(default_instance(), key, key_len, group, consumer, min_idle_ms, ids, len, justid, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xclaim_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    ids: *const StreamId,
+    len: usize,
+    justid: i32,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    let group_str = unsafe { to_string(group) };
+    let consumer_str = unsafe { to_string(consumer) };
+    let ids = unsafe { ids_slice(ids, len) };
+    let res = cmd_xclaim(db, &key_vec, &group_str, &consumer_str, min_idle_ms, ids, justid != 0).map(|items| {
+        let mut flat = Vec::new();
+        pack_stream_items(&mut flat, &items, Some);
+        Some(flat)
+    });
+    return_found(res, out_len)
+}
+
+// Claims up to `count` (0 = 100, Redis' default) pending entries idle for at least
+// `min_idle_ms`, scanning from id `start`. Stores the id to pass as the next `start` (0-0 when
+// the scan is complete) in `out_next` and returns the claimed entries like cache_xclaim,
+// followed by [DeletedCount u32] { [Ms u64] [Seq u64] }: pending ids whose entries were
+// deleted, which the scan dropped from the pending list.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xautoclaim(
+    key: *const c_char,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    start: StreamId,
+    count: usize,
+    justid: i32,
+    out_next: *mut StreamId,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xautoclaim(default_instance(), key, group, consumer, min_idle_ms, start, count, justid, out_next, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xautoclaim(
+    db: *const Instance,
+    key: *const c_char,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    start: StreamId,
+    count: usize,
+    justid: i32,
+    out_next: *mut StreamId,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_str = unsafe { to_string(key) };
+    xautoclaim_export(db, key_str.as_str(), group, consumer, min_idle_ms, start, count, justid, out_next, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_xautoclaim_b(
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    start: StreamId,
+    count: usize,
+    justid: i32,
+    out_next: *mut StreamId,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    cache_instance_xautoclaim_b(
+        default_instance(),
+        key,
+        key_len,
+        group,
+        consumer,
+        min_idle_ms,
+        start,
+        count,
+        justid,
+        out_next,
+        out_len,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn cache_instance_xautoclaim_b(
+    db: *const Instance,
+    key: *const c_uchar,
+    key_len: usize,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    start: StreamId,
+    count: usize,
+    justid: i32,
+    out_next: *mut StreamId,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let key_vec = unsafe { to_bytes(key, key_len) };
+    xautoclaim_export(db, key_vec.as_slice(), group, consumer, min_idle_ms, start, count, justid, out_next, out_len)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn xautoclaim_export<K: CacheKey + ?Sized>(
+    db: *const Instance,
+    key: &K,
+    group: *const c_char,
+    consumer: *const c_char,
+    min_idle_ms: u64,
+    start: StreamId,
+    count: usize,
+    justid: i32,
+    out_next: *mut StreamId,
+    out_len: *mut usize,
+) -> *mut c_uchar {
+    let Some(db) = instance(db) else { return prepare_return(Vec::new(), out_len); };
+    let group_str = unsafe { to_string(group) };
+    let consumer_str = unsafe { to_string(consumer) };
+    let count = if count == 0 { 100 } else { count };
+    let res = cmd_xautoclaim(db, key, &group_str, &consumer_str, min_idle_ms, start, count, justid != 0).map(|(next, items, deleted)| {
+        if !out_next.is_null() {
+            unsafe { *out_next = next };
+        }
+        let mut flat = Vec::new();
+        pack_stream_items(&mut flat, &items, Some);
+        flat.extend_from_slice(&(deleted.len() as u32).to_le_bytes());
+        for id in &deleted {
+            flat.extend_from_slice(&id.ms.to_le_bytes());
+            flat.extend_from_slice(&id.seq.to_le_bytes());
         }
+        Some(flat)
+    });
+    return_found(res, out_len)
+}
+
+// --- Snapshots ---
+
+// Saves a point-in-time binary snapshot of the whole cache to `path`.
+// Returns 1, or Io/InvalidArgument on failure.
+#[no_mangle]
+pub extern "C" fn cache_snapshot_save(path: *const c_char) -> i32 {
+    cache_instance_snapshot_save(default_instance(), path)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_snapshot_save(db: *const Instance, path: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let path_str = unsafe { to_string(path) };
+    if path_str.is_empty() {
+        return fail(CacheError::invalid("empty snapshot path"));
     }
+    status(snapshot::save(db, &path_str).map_err(CacheError::from), |()| 1)
+}
 
-    // Serialize keys: [Count u32][KeyLen u32][Key bytes]...
-    let mut flat = Vec::new();
-    flat.extend_from_slice(&(keys.len() as u32).to_le_bytes());
-    for k in keys {
-        let b = k.as_bytes();
-        flat.extend_from_slice(&(b.len() as u32).to_le_bytes());
-        flat.extend_from_slice(b);
+// Replaces the cache contents with the snapshot at `path`.
+// Returns 1, Io if the file cannot be read, or InvalidArgument if it is corrupt or of an
+// unknown version
+// (the current state is left untouched in that case).
+#[no_mangle]
+pub extern "C" fn cache_snapshot_load(path: *const c_char) -> i32 {
+    cache_instance_snapshot_load(default_instance(), path)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_snapshot_load(db: *const Instance, path: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let path_str = unsafe { to_string(path) };
+    if path_str.is_empty() {
+        return fail(CacheError::invalid("empty snapshot path"));
     }
-    prepare_return(flat, out_len)
+    status(snapshot::load(db, &path_str), |()| 1)
 }
 
-// --- Phase4: Lightweight scripting (very small command set) ---
+// --- RESP server ---
 
+// Starts a RESP2/RESP3 listener on `addr` (e.g. "127.0.0.1:6379") in the background.
+// Returns 1 once bound, or InvalidArgument/Io if the address is empty or cannot be bound.
 #[no_mangle]
-pub extern "C" fn cache_eval(script: *const c_char, out_len: *mut usize) -> *mut c_uchar {
-    let s = unsafe { to_string(script) };
-    let s = s.trim();
-    if s.is_empty() {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+pub extern "C" fn cache_server_start(addr: *const c_char) -> i32 {
+    cache_instance_server_start(default_instance(), addr)
+}
+
+#[no_mangle]
+pub extern "C" fn cache_instance_server_start(db: *const Instance, addr: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let addr_str = unsafe { to_string(addr) };
+    if addr_str.is_empty() {
+        return fail(CacheError::invalid("empty listen address"));
     }
+    status(server::spawn_tcp_for(db.arc(), &addr_str).map_err(CacheError::from), |_| 1)
+}
 
-    // Commands:
-    // GET <key>
-    // SET <key> <value...> (value is treated as UTF-8 bytes)
-    // DEL <key>
-    // JSON.GET <key> <path>
-    // JSON.SET <key> <path> <jsonValue>
-    let mut parts = s.split_whitespace();
-    let cmd = parts.next().unwrap_or("").to_uppercase();
+// Starts a RESP2/RESP3 listener on a Unix socket path in the background.
+// Returns 1 once bound, or a negative CacheStatus (always InvalidArgument on non-Unix platforms).
+#[no_mangle]
+pub extern "C" fn cache_server_start_unix(path: *const c_char) -> i32 {
+    cache_instance_server_start_unix(default_instance(), path)
+}
 
-    match cmd.as_str() {
-        "GET" => {
-            let key = parts.next().unwrap_or("");
-            if key.is_empty() {
-                unsafe { *out_len = 0 };
-                return std::ptr::null_mut();
-            }
-            // reuse cache_get via CString
-            let ckey = std::ffi::CString::new(key).ok();
-            if let Some(ck) = ckey {
-                return cache_get(ck.as_ptr(), out_len);
+#[no_mangle]
+pub extern "C" fn cache_instance_server_start_unix(db: *const Instance, path: *const c_char) -> i32 {
+    let Some(db) = instance(db) else { return CacheStatus::InvalidHandle as i32; };
+    let path_str = unsafe { to_string(path) };
+    if path_str.is_empty() {
+        return fail(CacheError::invalid("empty socket path"));
+    }
+    #[cfg(unix)]
+    {
+        status(server::spawn_unix_for(db.arc(), &path_str).map_err(CacheError::from), |()| 1)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = db;
+        fail(CacheError::invalid("unix sockets are not supported on this platform"))
+    }
+}
+
+// The default instance is process-wide, so tests take this lock and start from the default settings.
+#[cfg(test)]
+pub(crate) fn test_reset() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    cache_aof_disable();
+    cache_aof_set_auto_rewrite(0, 64 * 1024 * 1024);
+    cache_set_eviction_policy(eviction::POLICY_ALLKEYS_LRU as i32);
+    cache_set_max_memory_bytes(0);
+    cache_set_max_items(DEFAULT_MAX_ITEMS);
+    cache_set_type_overwrite(0);
+    cache_aof_set_fsync_policy(AOF_FSYNC_EVERYSEC as i32);
+    cmd_clear_all(db());
+    guard
+}
+
+#[cfg(test)]
+pub(crate) fn db() -> &'static Instance {
+    &DEFAULT_INSTANCE
+}
+
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("rust_cache_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn c_path(path: &std::path::Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    fn wait_for<T>(mut f: impl FnMut() -> Option<T>) -> T {
+        for _ in 0..200 {
+            if let Some(v) = f() {
+                return v;
             }
-            unsafe { *out_len = 0 };
-            std::ptr::null_mut()
+            std::thread::sleep(Duration::from_millis(10));
         }
-        "SET" => {
-            let key = parts.next().unwrap_or("");
-            if key.is_empty() {
-                unsafe { *out_len = 0 };
-                return std::ptr::null_mut();
-            }
-            // remaining bytes after the key
-            let value_pos = s.find(key).unwrap_or(0) + key.len();
-            let value_str = s[value_pos..].trim();
-            let bytes = value_str.as_bytes().to_vec();
-            let mut state = CACHE.write().unwrap();
-            put_entry_with_lru(
-                &mut state,
-                key.to_string(),
-                Entry { value: Value::Bytes(Arc::new(bytes.clone())), expires_at_ms: None },
-            );
-            aof_write_set(key, &bytes);
-            prepare_return(b"OK".to_vec(), out_len)
+        panic!("condition not reached");
+    }
+
+    #[test]
+    fn multi_element_writes_are_all_or_nothing() {
+        let _guard = test_reset();
+        cmd_set(db(), "filler", vec![0; 256]).unwrap();
+        cache_set_eviction_policy(eviction::POLICY_NOEVICTION as i32);
+        cache_set_max_memory_bytes(cache_used_memory() + 1);
+
+        let fields = vec![(b"a".to_vec(), vec![1; 64]), (b"b".to_vec(), vec![2; 64])];
+        assert_eq!(cmd_hset(db(), "h", fields.clone()), Ok(2));
+        assert_eq!(cmd_hgetall(db(), "h").unwrap().map(|f| f.len()), Some(2));
+
+        // Memory is now over the limit: the whole command is rejected, not just its tail.
+        assert_eq!(cmd_hset(db(), "h2", fields), Err(CacheError::OutOfMemory));
+        assert_eq!(cmd_lpush(db(), "l", vec![b"x".to_vec(), b"y".to_vec()]), Err(CacheError::OutOfMemory));
+        assert_eq!(cmd_sadd(db(), "s", vec![b"x".to_vec(), b"y".to_vec()]), Err(CacheError::OutOfMemory));
+        assert_eq!(cmd_zadd(db(), "z", vec![(1.0, b"x".to_vec()), (2.0, b"y".to_vec())]), Err(CacheError::OutOfMemory));
+        for key in ["h2", "l", "s", "z"] {
+            assert_eq!(cmd_ttl(db(), key), -2, "{}", key);
         }
-        "DEL" => {
-            let key = parts.next().unwrap_or("");
-            if key.is_empty() {
-                unsafe { *out_len = 0 };
-                return std::ptr::null_mut();
-            }
-            let mut state = CACHE.write().unwrap();
-            let existed = state.map.contains(&key.to_string());
-            apply_remove_internal(&mut state, &key.to_string());
-            aof_write_remove(key);
-            let out = if existed { b"1" } else { b"0" };
-            prepare_return(out.to_vec(), out_len)
+    }
+
+    #[test]
+    fn multi_element_writes_report_counts() {
+        let _guard = test_reset();
+        assert_eq!(cmd_lpush(db(), "l", vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]), Ok(3));
+        assert_eq!(cmd_lrange(db(), "l", 0, -1).unwrap(), Some(vec![b"c".to_vec(), b"b".to_vec(), b"a".to_vec()]));
+        assert_eq!(cmd_sadd(db(), "s", vec![b"a".to_vec(), b"a".to_vec(), b"b".to_vec()]), Ok(2));
+        assert_eq!(cmd_zadd(db(), "z", vec![(2.0, b"b".to_vec()), (1.0, b"a".to_vec()), (3.0, b"a".to_vec())]), Ok(2));
+        assert_eq!(cmd_zrange(db(), "z", 0, -1, false).unwrap(), Some(vec![(b"b".to_vec(), 2.0), (b"a".to_vec(), 3.0)]));
+    }
+
+    #[test]
+    fn failed_auto_rewrite_is_not_retried_on_every_write() {
+        let _guard = test_reset();
+        let path = temp_path("backoff.aof");
+        // A directory in place of the rewrite's temp file makes every rewrite fail.
+        let blocker = format!("{}.rewrite", path.display());
+        std::fs::create_dir_all(&blocker).unwrap();
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cache_aof_set_auto_rewrite(1, 0);
+
+        cmd_set(db(), "k", b"v".to_vec()).unwrap();
+        let failed_at = wait_for(|| {
+            let failed_at = db().aof.lock().unwrap().rewrite_failed_at;
+            failed_at.filter(|_| !db().aof_rewrite_in_progress.load(Ordering::Acquire))
+        });
+        for _ in 0..100 {
+            cmd_set(db(), "k", b"v".to_vec()).unwrap();
         }
-        "JSON.GET" => {
-            let key = parts.next().unwrap_or("");
-            let path = parts.next().unwrap_or("");
-            if key.is_empty() || path.is_empty() {
-                unsafe { *out_len = 0 };
-                return std::ptr::null_mut();
-            }
-            let ckey = std::ffi::CString::new(key).ok();
-            let cpath = std::ffi::CString::new(path).ok();
-            if let (Some(ck), Some(cp)) = (ckey, cpath) {
-                return cache_json_get(ck.as_ptr(), cp.as_ptr(), out_len);
-            }
-            unsafe { *out_len = 0 };
-            std::ptr::null_mut()
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!db().aof_rewrite_in_progress.load(Ordering::Acquire));
+        assert_eq!(db().aof.lock().unwrap().rewrite_failed_at, Some(failed_at));
+
+        cache_aof_disable();
+        let _ = std::fs::remove_dir(&blocker);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn flushed_records_are_in_the_file() {
+        let _guard = test_reset();
+        let path = temp_path("flush.aof");
+        cache_aof_set_fsync_policy(AOF_FSYNC_NO as i32);
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_set(db(), "k", b"v".to_vec()).unwrap();
+        cmd_hset(db(), "h", vec![(b"f".to_vec(), b"1".to_vec())]).unwrap();
+        assert_eq!(cache_aof_flush(), 1);
+        let mut expected = Vec::new();
+        aof_encode_set(&mut expected, "k", b"v");
+        aof_encode_hset(&mut expected, "h", b"f", b"1");
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        {
+            let aof = db().aof.lock().unwrap();
+            assert_eq!((aof.written, aof.synced), (aof.appended, aof.appended));
         }
-        "JSON.SET" => {
-            let key = parts.next().unwrap_or("");
-            let path = parts.next().unwrap_or("");
-            if key.is_empty() || path.is_empty() {
-                unsafe { *out_len = 0 };
-                return std::ptr::null_mut();
-            }
-            // remaining after path
-            let path_pos = s.find(path).unwrap_or(0) + path.len();
-            let json_str = s[path_pos..].trim();
-            let ckey = std::ffi::CString::new(key).ok();
-            let cpath = std::ffi::CString::new(path).ok();
-            if let (Some(ck), Some(cp)) = (ckey, cpath) {
-                let ok = cache_json_set(ck.as_ptr(), cp.as_ptr(), json_str.as_ptr(), json_str.len());
-                let out = if ok != 0 { b"1" } else { b"0" };
-                return prepare_return(out.to_vec(), out_len);
-            }
-            prepare_return(b"0".to_vec(), out_len)
+        cache_aof_disable();
+        assert_eq!(cache_aof_flush(), CacheStatus::InvalidArgument as i32);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn always_policy_waits_for_fsync_and_policies_switch_at_runtime() {
+        let _guard = test_reset();
+        let path = temp_path("always.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        let caught_up = || {
+            let aof = db().aof.lock().unwrap();
+            aof.synced >= aof.appended
+        };
+        assert_eq!(cache_aof_set_fsync_policy(AOF_FSYNC_ALWAYS as i32), 1);
+        for i in 0..20 {
+            cmd_set(db(), format!("k{i}").as_str(), b"v".to_vec()).unwrap();
+            assert!(caught_up());
         }
-        _ => {
-            unsafe { *out_len = 0 };
-            std::ptr::null_mut()
+        // Concurrent writers share fsyncs (group commit) and none of them is left waiting.
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    for i in 0..20 {
+                        cmd_set(db(), format!("t{t}:{i}").as_str(), b"v".to_vec()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        assert!(caught_up());
+
+        // Back to everysec: writes no longer wait, and a flush still syncs them.
+        assert_eq!(cache_aof_set_fsync_policy(AOF_FSYNC_EVERYSEC as i32), 1);
+        cmd_set(db(), "later", b"v".to_vec()).unwrap();
+        assert_eq!(cache_aof_flush(), 1);
+        assert!(caught_up());
+        assert_eq!(cache_aof_set_fsync_policy(3), CacheStatus::InvalidArgument as i32);
+        assert_eq!(db().aof_fsync_policy.load(Ordering::Relaxed), AOF_FSYNC_EVERYSEC);
+
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(cache_len(), 101);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn aof_failures_report_io() {
+        let _guard = test_reset();
+        let missing = std::env::temp_dir().join(format!("rust_cache_{}_missing_dir", std::process::id())).join("x.aof");
+        assert_eq!(cache_aof_enable(c_path(&missing).as_ptr()), CacheStatus::Io as i32);
+        assert!(!aof_enabled(db()));
+
+        // Every write to /dev/full fails with ENOSPC once it reaches the device.
+        if cfg!(target_os = "linux") {
+            assert_eq!(cache_aof_enable(c_path(std::path::Path::new("/dev/full")).as_ptr()), 1);
+            cmd_set(db(), "k", b"v".to_vec()).unwrap();
+            assert_eq!(cache_aof_flush(), CacheStatus::Io as i32);
+            assert_eq!(cache_last_status(), CacheStatus::Io as i32);
+            // Under "always" the writer is released too, with the key still set in memory.
+            assert_eq!(cache_aof_set_fsync_policy(AOF_FSYNC_ALWAYS as i32), 1);
+            cmd_set(db(), "k2", b"v".to_vec()).unwrap();
+            assert_eq!(cmd_get(db(), "k2"), Ok(Some(Arc::new(b"v".to_vec()))));
+            assert_eq!(cache_aof_flush(), CacheStatus::Io as i32);
+            cache_aof_disable();
+        }
+    }
+
+    #[test]
+    fn rewrite_swaps_in_the_new_file_while_writes_continue() {
+        let _guard = test_reset();
+        let path = temp_path("swap.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        for i in 0..200 {
+            cmd_set(db(), "k", format!("{i}").into_bytes()).unwrap();
         }
+
+        let writer = std::thread::spawn(|| {
+            for i in 0..500 {
+                cmd_set(db(), format!("w{i}").as_str(), b"v".to_vec()).unwrap();
+            }
+        });
+        assert_eq!(cache_aof_rewrite(), 1);
+        writer.join().unwrap();
+        // Writes after the swap go to the new file.
+        cmd_set(db(), "after", b"1".to_vec()).unwrap();
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+        assert!(!std::path::Path::new(&format!("{}.rewrite", path.display())).exists());
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(cmd_get(db(), "k"), Ok(Some(Arc::new(b"199".to_vec()))));
+        assert!((0..500).all(|i| cmd_get(db(), format!("w{i}").as_str()).unwrap().is_some()));
+        assert_eq!(cmd_get(db(), "after"), Ok(Some(Arc::new(b"1".to_vec()))));
+        let _ = std::fs::remove_file(&path);
     }
-}
 
-#[no_mangle]
-pub extern "C" fn cache_get_b(key: *const c_uchar, key_len: usize, out_len: *mut usize) -> *mut c_uchar {
-    let key_vec = unsafe { to_bytes(key, key_len) };
-    let mut state = CACHE.write().unwrap();
+    fn load_records(name: &str, records: &[u8]) {
+        let path = temp_path(name);
+        std::fs::write(&path, records).unwrap();
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        let _ = std::fs::remove_file(&path);
+    }
 
-    if maybe_remove_if_expired_b(&mut state, &key_vec) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+    #[test]
+    fn replay_keeps_past_expiry_for_later_records() {
+        let _guard = test_reset();
+        // The key expired while the process was down; the last HSET happened before that.
+        let mut log = Vec::new();
+        aof_encode_hset(&mut log, "k", b"f1", b"1");
+        aof_encode_hset(&mut log, "k", b"f2", b"2");
+        aof_encode_expire_at(&mut log, "k", now_ms() - 1000);
+        aof_encode_hset(&mut log, "k", b"f3", b"3");
+        aof_encode_set(&mut log, "live", b"v");
+        load_records("replay_expired.aof", &log);
+
+        assert_eq!(cmd_ttl(db(), "k"), -2);
+        assert_eq!(cmd_hget(db(), "k", b"f3").unwrap(), None);
+        assert_eq!(cmd_get(db(), "live").unwrap().as_deref(), Some(&b"v".to_vec()));
+        assert_eq!(cache_len(), 1);
+    }
+
+    #[test]
+    fn expired_key_recreated_while_running_survives_replay() {
+        let _guard = test_reset();
+        let path = temp_path("recreated.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_hset(db(), "k", vec![(b"f1".to_vec(), b"1".to_vec())]).unwrap();
+        assert!(cmd_expire(db(), "k", 1));
+        std::thread::sleep(Duration::from_millis(5));
+        // Writes to the expired key start a new one; the expiry is logged as a removal first.
+        cmd_hset(db(), "k", vec![(b"f3".to_vec(), b"3".to_vec())]).unwrap();
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(cmd_ttl(db(), "k"), -1);
+        assert_eq!(cmd_hgetall(db(), "k").unwrap(), Some(vec![(b"f3".to_vec(), b"3".to_vec())]));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn list(key: &str) -> Vec<String> {
+        let items = cmd_lrange(db(), key, 0, -1).unwrap().unwrap_or_default();
+        items.into_iter().map(|i| String::from_utf8(i).unwrap()).collect()
+    }
+
+    fn items(vals: &[&str]) -> Vec<Vec<u8>> {
+        vals.iter().map(|v| v.as_bytes().to_vec()).collect()
+    }
+
+    fn string(key: &str) -> Option<Vec<u8>> {
+        cmd_get(db(), key).unwrap().map(|v| (*v).clone())
     }
 
-    if let Some(entry) = state.map_b.get(&key_vec) {
-        if let Value::Bytes(val) = &entry.value {
-            return prepare_return((**val).clone(), out_len);
+    #[test]
+    fn string_commands_follow_redis_semantics() {
+        let _guard = test_reset();
+        let not_int = Err(CacheError::invalid("value is not an integer or out of range"));
+        assert_eq!(cmd_incrby(db(), "n", 1), Ok(1));
+        assert_eq!(cmd_incrby(db(), "n", -11), Ok(-10));
+        for bad in ["", " 1", "+1", "01", "-0", "1.5", "x"] {
+            cmd_set(db(), "bad", bad.as_bytes().to_vec()).unwrap();
+            assert_eq!(cmd_incrby(db(), "bad", 1), not_int, "{:?}", bad);
         }
+        cmd_set(db(), "max", i64::MAX.to_string().into_bytes()).unwrap();
+        assert_eq!(cmd_incrby(db(), "max", 1), Err(CacheError::invalid("increment or decrement would overflow")));
+        assert_eq!(string("max"), Some(i64::MAX.to_string().into_bytes()));
+
+        assert_eq!(cmd_incrbyfloat(db(), "f", 10.5), Ok(10.5));
+        assert_eq!(cmd_incrbyfloat(db(), "f", -0.5), Ok(10.0));
+        assert_eq!(string("f"), Some(b"10".to_vec()));
+        assert_eq!(cmd_incrbyfloat(db(), "f", f64::INFINITY), Err(CacheError::invalid("increment would produce NaN or Infinity")));
+        assert_eq!(cmd_incrbyfloat(db(), "bad", 1.0), Err(CacheError::invalid("value is not a valid float")));
+
+        assert_eq!(cmd_append(db(), "s", b"Hello"), Ok(5));
+        assert_eq!(cmd_append(db(), "s", b" World"), Ok(11));
+        assert_eq!(cmd_strlen(db(), "s"), Ok(11));
+        assert_eq!(cmd_strlen(db(), "missing"), Ok(0));
+        assert_eq!(cmd_getrange(db(), "s", 0, 4), Ok(b"Hello".to_vec()));
+        assert_eq!(cmd_getrange(db(), "s", -5, -1), Ok(b"World".to_vec()));
+        assert_eq!(cmd_getrange(db(), "s", 5, 2), Ok(Vec::new()));
+        assert_eq!(cmd_getrange(db(), "missing", 0, -1), Ok(Vec::new()));
+        assert_eq!(cmd_setrange(db(), "s", 6, b"Redis"), Ok(11));
+        assert_eq!(string("s"), Some(b"Hello Redis".to_vec()));
+        assert_eq!(cmd_setrange(db(), "pad", 3, b"x"), Ok(4));
+        assert_eq!(string("pad"), Some(b"\0\0\0x".to_vec()));
+        assert_eq!(cmd_setrange(db(), "none", 5, b""), Ok(0));
+        assert_eq!(cmd_ttl(db(), "none"), -2);
+        let too_big = Err(CacheError::invalid("string exceeds maximum allowed size (proto-max-bulk-len)"));
+        assert_eq!(cmd_setrange(db(), "s", MAX_STRING_LEN, b"x"), too_big);
+
+        assert_eq!(cmd_getset(db(), "s", b"new".to_vec()), Ok(Some(b"Hello Redis".to_vec())));
+        assert_eq!(cmd_getset(db(), "fresh", b"v".to_vec()), Ok(None));
+        assert_eq!(cmd_getdel(db(), "s"), Ok(Some(b"new".to_vec())));
+        assert_eq!(cmd_getdel(db(), "s"), Ok(None));
+        assert_eq!(cmd_ttl(db(), "s"), -2);
+
+        // In-place edits keep the TTL; GETSET replaces the value and drops it.
+        cmd_set_with_ttl(db(), "t", b"1".to_vec(), 60_000).unwrap();
+        cmd_incrby(db(), "t", 1).unwrap();
+        cmd_append(db(), "t", b"0").unwrap();
+        cmd_setrange(db(), "t", 0, b"3").unwrap();
+        assert_eq!(string("t"), Some(b"30".to_vec()));
+        assert!(cmd_ttl(db(), "t") > 0);
+        cmd_getset(db(), "t", b"x".to_vec()).unwrap();
+        assert_eq!(cmd_ttl(db(), "t"), -1);
+
+        cmd_rpush(db(), "l", items(&["x"])).unwrap();
+        assert_eq!(cmd_incrby(db(), "l", 1), Err(CacheError::WrongType));
+        assert_eq!(cmd_append(db(), "l", b"x"), Err(CacheError::WrongType));
+        assert_eq!(cmd_getset(db(), "l", b"x".to_vec()), Err(CacheError::WrongType));
+        assert_eq!(cmd_getdel(db(), "l"), Err(CacheError::WrongType));
+        assert_eq!(cmd_strlen(db(), "l"), Err(CacheError::WrongType));
+        assert_eq!(cmd_llen(db(), "l"), Ok(1));
     }
 
-    unsafe { *out_len = 0 };
-    std::ptr::null_mut()
-}
+    #[test]
+    fn conditional_sets_apply_atomically() {
+        let _guard = test_reset();
+        let set = |key, val: &str, opts| cmd_set_with(db(), key, val.as_bytes().to_vec(), opts);
+        let nx = SetOptions { nx: true, ..SetOptions::default() };
+        let xx = SetOptions { xx: true, ..SetOptions::default() };
+        let get = SetOptions { get: true, ..SetOptions::default() };
+        assert_eq!(set("lock", "a", nx), Ok((true, None)));
+        assert_eq!(set("lock", "b", nx), Ok((false, None)));
+        assert_eq!(set("lock", "b", SetOptions { get: true, ..nx }), Ok((false, Some(b"a".to_vec()))));
+        assert_eq!(string("lock"), Some(b"a".to_vec()));
+        assert_eq!(set("none", "x", xx), Ok((false, None)));
+        assert_eq!(cmd_ttl(db(), "none"), -2);
+        assert_eq!(set("lock", "c", SetOptions { get: true, ..xx }), Ok((true, Some(b"a".to_vec()))));
+        assert_eq!(set("fresh", "v", get), Ok((true, None)));
+
+        // KEEPTTL keeps the current expiry; a plain write drops it; EXAT sets an absolute one.
+        let at = now_ms() + 60_000;
+        set("t", "1", SetOptions { expires_at_ms: Some(at), ..SetOptions::default() }).unwrap();
+        assert_eq!(set("t", "2", SetOptions { keep_ttl: true, ..xx }), Ok((true, None)));
+        assert_eq!(db().cache.shards[shard_index("t")].read().unwrap().map.peek(b"t".as_slice()).unwrap().expires_at_ms, Some(at));
+        set("t", "3", SetOptions::default()).unwrap();
+        assert_eq!(cmd_ttl(db(), "t"), -1);
+        set("past", "v", SetOptions { expires_at_ms: Some(1), ..SetOptions::default() }).unwrap();
+        assert_eq!(cmd_ttl(db(), "past"), -2);
+
+        // GET refuses another type without writing; a plain SET replaces it.
+        cmd_rpush(db(), "l", items(&["x"])).unwrap();
+        assert_eq!(set("l", "v", get), Err(CacheError::WrongType));
+        assert_eq!(cmd_llen(db(), "l"), Ok(1));
+        assert_eq!(set("l", "v", nx), Ok((false, None)));
+        assert_eq!(set("l", "v", SetOptions::default()), Ok((true, None)));
+        assert_eq!(string("l"), Some(b"v".to_vec()));
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_get_into_b(key: *const c_uchar, key_len: usize, dst: *mut c_uchar, dst_len: usize) -> i64 {
-    let key_vec = unsafe { to_bytes(key, key_len) };
-    if key_vec.is_empty() {
-        return -1;
+    #[test]
+    fn set_ex_export_reports_writes_and_old_values() {
+        let _guard = test_reset();
+        let key = CString::new("k").unwrap();
+        let set_ex = |val: &[u8], flags, ttl| {
+            let (mut old, mut old_len) = (std::ptr::null_mut(), 0usize);
+            let res = cache_set_ex(key.as_ptr(), val.as_ptr(), val.len(), flags, ttl, &mut old, &mut old_len);
+            (res, old, old_len)
+        };
+        assert_eq!(set_ex(b"a", SET_NX | SET_GET, 60_000), (1, std::ptr::null_mut(), usize::MAX));
+        assert_eq!(set_ex(b"b", SET_NX, 0).0, 0);
+        let (res, old, old_len) = set_ex(b"", SET_XX | SET_GET | SET_KEEPTTL, 0);
+        assert_eq!((res, unsafe { std::slice::from_raw_parts(old, old_len) }), (1, &b"a"[..]));
+        cache_free(old, old_len);
+        assert!(cache_ttl(key.as_ptr()) > 0);
+        assert_eq!(set_ex(b"c", SET_GET, 0), (1, std::ptr::null_mut(), 0));
+        assert_eq!(cache_ttl(key.as_ptr()), -1);
+
+        let at = now_ms() + 60_000;
+        assert_eq!(set_ex(b"d", SET_EXAT, at).0, 1);
+        assert_eq!(set_ex(b"e", SET_NX | SET_XX, 0).0, CacheStatus::InvalidArgument as i32);
+        assert_eq!(set_ex(b"e", SET_KEEPTTL, 1_000).0, CacheStatus::InvalidArgument as i32);
+        assert_eq!(set_ex(b"e", SET_EXAT, 0).0, CacheStatus::InvalidArgument as i32);
+        assert_eq!(last_error().as_deref(), Some("ERR invalid expire time in 'set' command"));
+        assert!(cache_ttl(key.as_ptr()) > 50_000);
     }
 
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired_b(&mut state, &key_vec) {
-        return -1;
+    #[test]
+    fn string_edits_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("strings.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_incrby(db(), "n", 5).unwrap();
+        cmd_incrby(db(), "n", -2).unwrap();
+        cmd_incrbyfloat(db(), "f", 0.1).unwrap();
+        cmd_incrbyfloat(db(), "f", 0.2).unwrap();
+        cmd_append(db(), "s", b"ab").unwrap();
+        cmd_setrange(db(), "s", 4, b"z").unwrap();
+        cmd_set_with_ttl(db(), "t", b"7".to_vec(), 60_000).unwrap();
+        cmd_incrby(db(), "t", 1).unwrap();
+        cmd_getset(db(), "g", b"v".to_vec()).unwrap();
+        cmd_set(db(), "d", b"v".to_vec()).unwrap();
+        cmd_getdel(db(), "d").unwrap();
+        let f = string("f");
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(string("n"), Some(b"3".to_vec()));
+        assert_eq!(string("f"), f);
+        assert_eq!(string("s"), Some(b"ab\0\0z".to_vec()));
+        assert_eq!(string("t"), Some(b"8".to_vec()));
+        assert!(cmd_ttl(db(), "t") > 0);
+        assert_eq!(string("g"), Some(b"v".to_vec()));
+        assert_eq!(cmd_ttl(db(), "d"), -2);
+        let _ = std::fs::remove_file(&path);
     }
 
-    let Some(entry) = state.map_b.get(&key_vec) else {
-        return -1;
-    };
-    let Value::Bytes(val) = &entry.value else {
-        return -1;
-    };
+    #[test]
+    fn string_exports_report_results_and_errors() {
+        let _guard = test_reset();
+        let key = CString::new("k").unwrap();
+        let mut value = 0i64;
+        assert_eq!(cache_incrby(key.as_ptr(), 41, &mut value), 1);
+        assert_eq!(cache_incrby(key.as_ptr(), 1, &mut value), 1);
+        assert_eq!(value, 42);
+        let mut float = 0.0;
+        assert_eq!(cache_incrbyfloat(key.as_ptr(), 0.5, &mut float), 1);
+        assert_eq!(float, 42.5);
+        assert_eq!(cache_incrby(key.as_ptr(), 1, &mut value), CacheStatus::InvalidArgument as i32);
+        assert_eq!(last_error().as_deref(), Some("ERR value is not an integer or out of range"));
+        assert_eq!(cache_append(key.as_ptr(), b"!".as_ptr(), 1), 5);
+        assert_eq!(cache_setrange(key.as_ptr(), 0, b"7".as_ptr(), 1), 5);
+        assert_eq!(cache_strlen(key.as_ptr()), 5);
+
+        let mut len = 0;
+        let ptr = cache_getrange(key.as_ptr(), 0, 1, &mut len);
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"72");
+        cache_free(ptr, len);
+        let ptr = cache_getset(key.as_ptr(), b"v".as_ptr(), 1, &mut len);
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"72.5!");
+        cache_free(ptr, len);
+        let ptr = cache_getdel(key.as_ptr(), &mut len);
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"v");
+        cache_free(ptr, len);
+        assert!(cache_getdel(key.as_ptr(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::NotFound as i32);
+        assert!(cache_getrange(key.as_ptr(), 0, -1, &mut len).is_null());
+        assert_eq!((len, cache_last_status()), (0, CacheStatus::Ok as i32));
+    }
 
-    let value_len = val.len();
-    if value_len == 0 {
-        return 0;
+    fn pairs(kvs: &[(&str, &str)]) -> Vec<(String, Vec<u8>)> {
+        kvs.iter().map(|(k, v)| (k.to_string(), v.as_bytes().to_vec())).collect()
     }
-    if dst.is_null() || dst_len < value_len {
-        return -(value_len as i64);
+
+    #[test]
+    fn multi_key_commands_span_shards_atomically() {
+        let _guard = test_reset();
+        let keys: Vec<String> = (0..64).map(|i| format!("k{}", i)).collect();
+        let all: Vec<(String, Vec<u8>)> = keys.iter().map(|k| (k.clone(), k.as_bytes().to_vec())).collect();
+        assert_eq!(cmd_mset(db(), all), Ok(()));
+        assert_eq!(shard_indexes(&keys).len(), SHARD_COUNT);
+        assert!(cmd_mget(db(), &keys).iter().zip(&keys).all(|(v, k)| v.as_deref() == Some(k.as_bytes())));
+
+        cmd_rpush(db(), "list", items(&["x"])).unwrap();
+        cmd_set_with_ttl(db(), "gone", b"v".to_vec(), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        let names = ["k1", "list", "gone", "none"].map(String::from);
+        assert_eq!(cmd_mget(db(), &names), [Some(b"k1".to_vec()), None, None, None]);
+
+        // MSETNX writes nothing if any key exists, whatever its type; expired keys do not count.
+        assert_eq!(cmd_msetnx(db(), pairs(&[("new", "1"), ("list", "2")])), Ok(false));
+        assert_eq!(cmd_ttl(db(), "new"), -2);
+        assert_eq!(cmd_msetnx(db(), pairs(&[("new", "1"), ("gone", "2"), ("new", "3")])), Ok(true));
+        assert_eq!(string("new"), Some(b"3".to_vec()));
+        assert_eq!(string("gone"), Some(b"2".to_vec()));
+
+        // MSET replaces values of any type and drops their TTLs.
+        cmd_expire(db(), "k2", 60_000);
+        assert_eq!(cmd_mset(db(), pairs(&[("k2", "a"), ("list", "b")])), Ok(()));
+        assert_eq!((cmd_ttl(db(), "k2"), string("list")), (-1, Some(b"b".to_vec())));
+
+        let names = ["k1", "k2", "k1", "none"].map(String::from);
+        assert_eq!(cmd_del(db(), &names), 2);
+        assert_eq!(cmd_del(db(), &keys), 62);
+        assert_eq!(cache_len(), 3);
+
+        // Binary keys live in the same keyspace; a UTF-8 one is the same key as its string.
+        let bkeys = vec![b"\0a".to_vec(), b"\xffb".to_vec()];
+        assert_eq!(cmd_mset(db(), vec![(bkeys[0].clone(), b"1".to_vec())]), Ok(()));
+        assert_eq!(cmd_msetnx(db(), vec![(bkeys[0].clone(), b"x".to_vec())]), Ok(false));
+        assert_eq!(cmd_msetnx(db(), vec![(bkeys[1].clone(), b"2".to_vec())]), Ok(true));
+        assert_eq!(cmd_mget(db(), &bkeys), [Some(b"1".to_vec()), Some(b"2".to_vec())]);
+        assert_eq!(cmd_mget(db(), &["\0a".to_string()]), [Some(b"1".to_vec())]);
+        assert_eq!(cmd_del(db(), &[bkeys[0].clone(), bkeys[0].clone()]), 1);
+        assert_eq!(cmd_mget(db(), &bkeys), [None, Some(b"2".to_vec())]);
+
+        cache_set_eviction_policy(eviction::POLICY_NOEVICTION as i32);
+        cache_set_max_memory_bytes(cache_used_memory() - 1);
+        assert_eq!(cmd_mset(db(), pairs(&[("new", "4"), ("other", "5")])), Err(CacheError::OutOfMemory));
+        assert_eq!((string("new"), cmd_ttl(db(), "other")), (Some(b"3".to_vec()), -2));
     }
 
-    unsafe {
-        std::ptr::copy_nonoverlapping(val.as_ptr(), dst, value_len);
+    #[test]
+    fn multi_key_writes_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("multi.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_mset(db(), pairs(&[("a", "1"), ("b", "2"), ("c", "3")])).unwrap();
+        cmd_msetnx(db(), pairs(&[("d", "4")])).unwrap();
+        cmd_del(db(), &["a".to_string(), "c".to_string()]);
+        cmd_mset(db(), vec![(b"x".to_vec(), b"1".to_vec()), (b"y".to_vec(), b"2".to_vec())]).unwrap();
+        cmd_del(db(), &[b"x".to_vec()]);
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        let names = ["a", "b", "c", "d"].map(String::from);
+        assert_eq!(cmd_mget(db(), &names), [None, Some(b"2".to_vec()), None, Some(b"4".to_vec())]);
+        assert_eq!(cmd_mget(db(), &[b"x".to_vec(), b"y".to_vec()]), [None, Some(b"2".to_vec())]);
+        let _ = std::fs::remove_file(&path);
     }
-    value_len as i64
-}
 
-// Zero-copy value lease for binary keys.
-//
-// Returns: opaque handle (must be freed via cache_bytes_lease_free), or null if missing/expired.
-// Out params: (*out_ptr, *out_len) are set to the value bytes.
-#[no_mangle]
-pub extern "C" fn cache_get_lease_b(
-    key: *const c_uchar,
-    key_len: usize,
-    out_ptr: *mut *const c_uchar,
-    out_len: *mut usize,
-) -> *const Vec<u8> {
-    if out_ptr.is_null() || out_len.is_null() {
-        return std::ptr::null();
+    #[test]
+    fn multi_key_exports_take_packed_buffers() {
+        let _guard = test_reset();
+        let kv = pack_items([&b"a"[..], b"1", b"b", b""].into_iter());
+        assert_eq!(cache_mset(kv.as_ptr(), kv.len()), 1);
+        assert_eq!(cache_msetnx(kv.as_ptr(), kv.len()), 0);
+        let odd = pack_items([&b"a"[..]].into_iter());
+        assert_eq!(cache_mset(odd.as_ptr(), odd.len()), CacheStatus::InvalidArgument as i32);
+        assert_eq!(cache_mset_b(odd.as_ptr(), odd.len()), CacheStatus::InvalidArgument as i32);
+
+        let keys = pack_items([&b"a"[..], b"none", b"b"].into_iter());
+        let mut len = 0;
+        let ptr = cache_mget(keys.as_ptr(), keys.len(), &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_optional_items(&[Some(b"1".to_vec()), None, Some(Vec::new())]));
+        let none = pack_items(std::iter::empty());
+        assert!(cache_mget(none.as_ptr(), none.len(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::InvalidArgument as i32);
+
+        assert_eq!(cache_mset_b(kv.as_ptr(), kv.len()), 1);
+        assert_eq!(cache_msetnx_b(kv.as_ptr(), kv.len()), 0);
+        let ptr = cache_mget_b(keys.as_ptr(), keys.len(), &mut len);
+        let blob_b = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob_b, blob);
+        // Both exports name the same keys.
+        assert_eq!(cache_del_b(keys.as_ptr(), keys.len()), 2);
+        assert_eq!(cache_del(keys.as_ptr(), keys.len()), 0);
+        assert_eq!(cache_del(none.as_ptr(), none.len()), CacheStatus::InvalidArgument as i64);
     }
 
-    let key_vec = unsafe { to_bytes(key, key_len) };
-    if key_vec.is_empty() {
-        unsafe {
-            *out_ptr = std::ptr::null();
-            *out_len = 0;
-        }
-        return std::ptr::null();
+    #[test]
+    fn binary_keys_support_every_command() {
+        let _guard = test_reset();
+        // Neither is UTF-8, and both would decode lossily to the same string.
+        let (k1, k2): (&[u8], &[u8]) = (b"\xff\x00k", b"\xfe\x00k");
+        cmd_set_with_ttl(db(), k1, b"1".to_vec(), 60_000).unwrap();
+        assert!(cmd_ttl(db(), k1) > 0);
+        assert_eq!(cmd_ttl(db(), k2), -2);
+        assert_eq!(cmd_incrby(db(), k1, 4), Ok(5));
+        assert_eq!(cmd_append(db(), k1, b"0"), Ok(2));
+        assert!(cmd_ttl(db(), k1) > 0);
+        assert!(cmd_expire(db(), k1, 1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(cmd_get(db(), k1), Ok(None));
+
+        cmd_hset(db(), k1, vec![(b"f".to_vec(), b"v".to_vec())]).unwrap();
+        assert_eq!(cmd_hget(db(), k1, b"f"), Ok(Some(b"v".to_vec())));
+        assert_eq!(cmd_hget(db(), k2, b"f"), Ok(None));
+        cmd_rpush(db(), k2, vec![b"a".to_vec(), b"b".to_vec()]).unwrap();
+        assert_eq!(cmd_lmove(db(), k2, b"\xfd".as_slice(), true, false), Ok(Some(b"a".to_vec())));
+        assert_eq!(cmd_lrange(db(), b"\xfd".as_slice(), 0, -1), Ok(Some(vec![b"a".to_vec()])));
+        assert_eq!(cmd_sadd(db(), k2, members(&["x"])), Err(CacheError::WrongType));
+
+        let (s1, s2, dst) = (b"\xf0a".to_vec(), b"\xf0b".to_vec(), b"\xf0dst".to_vec());
+        cmd_sadd(db(), &s1, members(&["a", "b"])).unwrap();
+        cmd_sadd(db(), &s2, members(&["b", "c"])).unwrap();
+        assert_eq!(cmd_set_op_store(db(), SetOp::Inter, &dst, &[s1, s2]), Ok(1));
+        assert_eq!(cmd_smembers(db(), &dst), Ok(Some(members(&["b"]))));
+
+        let z: &[u8] = b"\xf1z";
+        cmd_zadd(db(), z, vec![(2.0, b"m".to_vec())]).unwrap();
+        assert_eq!(cmd_zscore(db(), z, b"m"), Ok(Some(2.0)));
+
+        let stream = b"\xf2s".to_vec();
+        let id = cmd_xadd(db(), &stream, b"p".to_vec()).unwrap();
+        assert_eq!(cmd_xlen(db(), &stream), Ok(1));
+        cmd_xgroup_create(db(), &stream, "g", Some(StreamId::MIN), false).unwrap();
+        let read = cmd_xreadgroup(db(), "g", "c", &[(stream.clone(), None)], None, false).unwrap();
+        assert_eq!(read, vec![(stream, vec![(id, Some(vec![(b"payload".to_vec(), b"p".to_vec())]))])]);
+
+        let queue = b"\xf3q".to_vec();
+        let waiter = {
+            let keys = [queue.clone()];
+            std::thread::spawn(move || blocking::blocking_pop(db(), &keys, true, Some(Duration::from_secs(10)), &|| false))
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        cmd_rpush(db(), &queue, vec![b"item".to_vec()]).unwrap();
+        assert_eq!(waiter.join().unwrap(), Ok(Some((queue, b"item".to_vec()))));
     }
 
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired_b(&mut state, &key_vec) {
-        unsafe {
-            *out_ptr = std::ptr::null();
-            *out_len = 0;
-        }
-        return std::ptr::null();
+    #[test]
+    fn binary_key_edits_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("binary.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        let [t, h, l, s, z, x]: [&[u8]; 6] = [b"\xfft", b"\xffh", b"\xffl", b"\xffs", b"\xffz", b"\xffx"];
+        cmd_set_with_ttl(db(), t, b"v".to_vec(), 60_000).unwrap();
+        cmd_setrange(db(), t, 1, b"w").unwrap();
+        cmd_hset(db(), h, vec![(b"a".to_vec(), b"1".to_vec())]).unwrap();
+        cmd_hincrby(db(), h, b"n".to_vec(), 3).unwrap();
+        cmd_rpush(db(), l, members(&["a", "b", "c"])).unwrap();
+        cmd_lpop(db(), l).unwrap();
+        cmd_sadd(db(), s, members(&["a", "b"])).unwrap();
+        cmd_srem(db(), s, &members(&["a"])).unwrap();
+        cmd_zadd(db(), z, vec![(1.0, b"m".to_vec())]).unwrap();
+        cmd_zincrby(db(), z, 2.0, b"m".to_vec()).unwrap();
+        cmd_xadd(db(), x, b"p".to_vec()).unwrap();
+        cmd_xgroup_create(db(), x, "g", None, false).unwrap();
+        let snapshot = || {
+            let mut fields = cmd_hgetall(db(), h).unwrap().unwrap();
+            fields.sort();
+            (
+                (cmd_get(db(), t).unwrap(), cmd_ttl(db(), t) > 0),
+                fields,
+                cmd_lrange(db(), l, 0, -1).unwrap(),
+                cmd_smembers(db(), s).unwrap(),
+                cmd_zscore(db(), z, b"m").unwrap(),
+                cmd_xlen(db(), x).unwrap(),
+                cmd_xpending_summary(db(), x, "g").is_ok(),
+            )
+        };
+        let expected = snapshot();
+        assert_eq!(expected.0, (Some(Arc::new(b"vw".to_vec())), true));
+        assert_eq!(expected.4, Some(3.0));
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(snapshot(), expected);
+
+        // A rewrite keeps every binary key too.
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        assert_eq!(cache_aof_rewrite(), 1);
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(snapshot(), expected);
+        let _ = std::fs::remove_file(&path);
     }
 
-    let Some(entry) = state.map_b.get(&key_vec) else {
-        unsafe {
-            *out_ptr = std::ptr::null();
-            *out_len = 0;
-        }
-        return std::ptr::null();
-    };
+    #[test]
+    fn find_covers_binary_keys() {
+        let _guard = test_reset();
+        cmd_set(db(), "s", br#"{"bin_age":30}"#.to_vec()).unwrap();
+        cmd_set(db(), b"\xffb".as_slice(), br#"{"bin_age":40}"#.to_vec()).unwrap();
+        let mut found = find_keys(db(), "bin_age > 10").unwrap();
+        found.sort();
+        assert_eq!(found, vec![b"s".to_vec(), b"\xffb".to_vec()]);
+
+        let field = CString::new("bin_age").unwrap();
+        assert_eq!(cache_index_create_numeric(field.as_ptr()), 1);
+        cmd_set(db(), b"\xffc".as_slice(), br#"{"bin_age":50}"#.to_vec()).unwrap();
+        let mut found = find_keys(db(), "bin_age >= 40").unwrap();
+        found.sort();
+        assert_eq!(found, vec![b"\xffb".to_vec(), b"\xffc".to_vec()]);
+
+        let query = CString::new("bin_age == 50").unwrap();
+        let mut len = 0;
+        let ptr = cache_find_b(query.as_ptr(), &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_items([&b"\xffc"[..]].into_iter()));
+    }
 
-    let Value::Bytes(val) = &entry.value else {
-        unsafe {
-            *out_ptr = std::ptr::null();
-            *out_len = 0;
+    #[test]
+    fn string_and_binary_keys_share_one_keyspace() {
+        let _guard = test_reset();
+        let key = CString::new("abc").unwrap();
+        assert_eq!(cache_set(key.as_ptr(), b"1".as_ptr(), 1), 1);
+        let mut len = 0;
+        let ptr = cache_get_b(b"abc".as_ptr(), 3, &mut len);
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"1");
+        cache_free(ptr, len);
+        assert_eq!(cache_set_b(b"abc".as_ptr(), 3, b"2".as_ptr(), 1), 1);
+        assert_eq!(cmd_get(db(), "abc"), Ok(Some(Arc::new(b"2".to_vec()))));
+        assert_eq!(cache_len(), 1);
+        cache_remove_b(b"abc".as_ptr(), 3);
+        assert_eq!(cmd_get(db(), "abc"), Ok(None));
+        assert_eq!(cache_len(), 0);
+
+        // One capacity and one eviction order for both kinds of keys.
+        cache_set_max_items(4);
+        for i in 0..4u8 {
+            cmd_set(db(), format!("s{i}").as_str(), vec![i]).unwrap();
+            cmd_set(db(), [0xff, i].as_slice(), vec![i]).unwrap();
         }
-        return std::ptr::null();
-    };
+        assert_eq!(cache_len(), 4);
+        let kept = (0..4u8).filter(|&i| cmd_get(db(), format!("s{i}").as_str()).unwrap().is_some()).count()
+            + (0..4u8).filter(|&i| cmd_get(db(), [0xff, i].as_slice()).unwrap().is_some()).count();
+        assert_eq!(kept, 4);
+        cache_set_max_items(0);
+    }
 
-    let handle = Arc::into_raw(val.clone());
-    unsafe {
-        *out_ptr = (*handle).as_ptr();
-        *out_len = (*handle).len();
+    #[test]
+    fn aof_merges_legacy_binary_key_records() {
+        let _guard = test_reset();
+        let path = temp_path("legacy_binary.aof");
+        // A file from when binary keys had a keyspace of their own: "abc" written both ways,
+        // then a marked HSET and a marked REMOVE.
+        let mut buf = Vec::new();
+        aof_encode_set(&mut buf, "abc", b"1");
+        buf.push(AOF_OP_SET_B);
+        aof_put_bytes(&mut buf, b"abc");
+        aof_put_bytes(&mut buf, b"2");
+        aof_encode_hset(&mut buf, b"\xffh".as_slice(), b"f", b"v");
+        let start = buf.len() - (1 + 4 + 2 + 4 + 1 + 4 + 1);
+        buf[start] |= AOF_OP_BINARY_KEY;
+        aof_encode_set(&mut buf, "gone", b"x");
+        buf.push(AOF_OP_REMOVE_B);
+        aof_put_bytes(&mut buf, b"gone");
+        std::fs::write(&path, &buf).unwrap();
+
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        let loaded = || {
+            (cache_len(), cmd_get(db(), b"abc".as_slice()), cmd_hget(db(), b"\xffh".as_slice(), b"f"), cmd_get(db(), "gone"))
+        };
+        let expected = loaded();
+        assert_eq!(expected, (2, Ok(Some(Arc::new(b"2".to_vec()))), Ok(Some(b"v".to_vec())), Ok(None)));
+
+        // A rewrite leaves only plain records, which load the same.
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        assert_eq!(cache_aof_rewrite(), 1);
+        cache_aof_disable();
+        let mut rewritten = Vec::new();
+        aof_encode_set(&mut rewritten, "abc", b"2");
+        assert!(std::fs::read(&path).unwrap().windows(rewritten.len()).any(|w| w == rewritten));
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(loaded(), expected);
+        let _ = std::fs::remove_file(&path);
     }
-    handle
-}
 
-#[no_mangle]
-pub extern "C" fn cache_bytes_lease_free(handle: *const Vec<u8>) {
-    if handle.is_null() {
-        return;
+    #[test]
+    fn binary_key_exports() {
+        let _guard = test_reset();
+        let key = b"\xff\xfekey";
+        assert_eq!(cache_set_with_ttl_b(key.as_ptr(), key.len(), b"v".as_ptr(), 1, 60_000), 1);
+        assert!(cache_ttl_b(key.as_ptr(), key.len()) > 0);
+        assert_eq!(cache_expire_b(key.as_ptr(), key.len(), 0), 1);
+        assert_eq!(cache_ttl_b(key.as_ptr(), key.len()), -2);
+        assert_eq!(cache_expire_b(key.as_ptr(), key.len(), 1000), CacheStatus::NotFound as i32);
+
+        let field = CString::new("f").unwrap();
+        assert_eq!(cache_hset_b(key.as_ptr(), key.len(), field.as_ptr(), b"1".as_ptr(), 1), 1);
+        let mut value = 0;
+        assert_eq!(cache_hincrby_b(key.as_ptr(), key.len(), field.as_ptr(), 2, &mut value), 1);
+        assert_eq!(value, 3);
+        assert_eq!(cache_hlen_b(key.as_ptr(), key.len()), 1);
+        // Its lossy UTF-8 decoding names another key.
+        let lossy = CString::new(String::from_utf8_lossy(key).as_bytes()).unwrap();
+        assert_eq!(cache_hlen(lossy.as_ptr()), 0);
+        assert_eq!(cache_llen_b(key.as_ptr(), key.len()), CacheStatus::WrongType as i64);
+
+        let keys = pack_items([&b"\xf0a"[..], b"\xf0b"].into_iter());
+        assert_eq!(cache_rpush_b(b"\xf0b".as_ptr(), 2, b"x".as_ptr(), 1), 1);
+        let mut len = 0;
+        let ptr = cache_blpop_b(keys.as_ptr(), keys.len(), 10, &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_items([&b"\xf0b"[..], b"x"].into_iter()));
     }
-    unsafe {
-        // Drop one Arc refcount.
-        let _ = Arc::from_raw(handle);
+
+    #[test]
+    fn binary_fields_and_members() {
+        let _guard = test_reset();
+        let path = temp_path("binary_fields.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        let key = b"k";
+        let z = b"z";
+        let field = b"f\0\xff";
+        assert_eq!(cache_hset_bf(key.as_ptr(), 1, field.as_ptr(), field.len(), b"1".as_ptr(), 1), 1);
+        assert_eq!(cache_hsetnx_bf(key.as_ptr(), 1, field.as_ptr(), field.len(), b"2".as_ptr(), 1), 0);
+        let mut value = 0;
+        assert_eq!(cache_hincrby_bf(key.as_ptr(), 1, field.as_ptr(), field.len(), 4, &mut value), 1);
+        assert_eq!(value, 5);
+        // The prefix before the NUL is another field.
+        assert_eq!(cache_hexists_bf(key.as_ptr(), 1, field.as_ptr(), 1), 0);
+        let mut len = 0;
+        let ptr = cache_hget_bf(key.as_ptr(), 1, field.as_ptr(), field.len(), &mut len);
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, b"5");
+        cache_free(ptr, len);
+        // C string fields are taken as bytes too, without UTF-8 decoding.
+        let c_field = CString::new(b"\xfe".to_vec()).unwrap();
+        assert_eq!(cache_hset_b(key.as_ptr(), 1, c_field.as_ptr(), b"x".as_ptr(), 1), 1);
+        let c_key = CString::new("k").unwrap();
+        let ptr = cache_hkeys(c_key.as_ptr(), &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert!(blob == pack_items([&field[..], b"\xfe"].into_iter()) || blob == pack_items([&b"\xfe"[..], field].into_iter()));
+
+        let member = b"\xffm\0";
+        assert_eq!(cache_zadd_bm(z.as_ptr(), 1, 1.0, member.as_ptr(), member.len()), 1);
+        assert_eq!(cache_zadd_flags_bm(z.as_ptr(), 1, 0.5, member.as_ptr(), member.len(), ZADD_GT), 0);
+        assert_eq!(cache_zadd_bm(z.as_ptr(), 1, 1.0, b"a".as_ptr(), 1), 1);
+        let mut score = 0.0;
+        assert_eq!(cache_zincrby_bm(z.as_ptr(), 1, 2.0, member.as_ptr(), member.len(), &mut score), 1);
+        assert_eq!(cache_zscore_bm(z.as_ptr(), 1, member.as_ptr(), member.len(), &mut score), 1);
+        assert_eq!(score, 3.0);
+        assert_eq!(cache_zrank_bm(z.as_ptr(), 1, member.as_ptr(), member.len(), 1), 0);
+        let (min, max) = (b"[\xff", b"+");
+        let ptr = cache_zrangebylex_bm(z.as_ptr(), 1, min.as_ptr(), min.len(), max.as_ptr(), max.len(), 0, 0, -1, &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_items([&member[..]].into_iter()));
+        assert_eq!(cache_zrem_bm(z.as_ptr(), 1, b"a".as_ptr(), 1), 1);
+
+        let snapshot = || {
+            let mut fields = cmd_hgetall(db(), "k").unwrap().unwrap();
+            fields.sort();
+            (fields, cmd_zrange(db(), "z", 0, -1, false).unwrap())
+        };
+        let expected = snapshot();
+        assert_eq!(expected.0, vec![(field.to_vec(), b"5".to_vec()), (b"\xfe".to_vec(), b"x".to_vec())]);
+        assert_eq!(expected.1, Some(vec![(member.to_vec(), 3.0)]));
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(snapshot(), expected);
+
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        assert_eq!(cache_aof_rewrite(), 1);
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(snapshot(), expected);
+        let _ = std::fs::remove_file(&path);
     }
-}
 
-#[no_mangle]
-pub extern "C" fn cache_remove_b(key: *const c_uchar, key_len: usize) {
-    let key_vec = unsafe { to_bytes(key, key_len) };
-    let mut state = CACHE.write().unwrap();
-    apply_remove_internal_b(&mut state, &key_vec);
-    aof_write_remove_b(&key_vec);
-}
+    #[test]
+    fn list_commands_follow_redis_semantics() {
+        let _guard = test_reset();
+        let empty = cache_used_memory();
+        assert_eq!(cmd_rpush(db(), "l", items(&["a", "b", "c"])), Ok(3));
+        assert_eq!(cmd_lpush(db(), "l", items(&["z"])), Ok(4));
+        assert_eq!(list("l"), ["z", "a", "b", "c"]);
+        assert_eq!(cmd_llen(db(), "l"), Ok(4));
+        assert_eq!(cmd_lindex(db(), "l", -1), Ok(Some(b"c".to_vec())));
+        assert_eq!(cmd_lindex(db(), "l", 4), Ok(None));
+
+        assert_eq!(cmd_lset(db(), "l", 1, b"A".to_vec()), Ok(()));
+        assert_eq!(cmd_lset(db(), "l", 9, b"x".to_vec()), Err(CacheError::invalid("index out of range")));
+        assert_eq!(cmd_lset(db(), "missing", 0, b"x".to_vec()), Err(CacheError::NotFound));
+        assert_eq!(cmd_linsert(db(), "l", true, b"b", b"b0".to_vec()), Ok(5));
+        assert_eq!(cmd_linsert(db(), "l", false, b"c", b"d".to_vec()), Ok(6));
+        assert_eq!(cmd_linsert(db(), "l", false, b"nope", b"x".to_vec()), Ok(-1));
+        assert_eq!(cmd_linsert(db(), "missing", false, b"a", b"x".to_vec()), Ok(0));
+        assert_eq!(list("l"), ["z", "A", "b0", "b", "c", "d"]);
+
+        cmd_rpush(db(), "l", items(&["b", "x", "b"])).unwrap();
+        assert_eq!(cmd_lrem(db(), "l", -2, b"b"), Ok(2));
+        assert_eq!(list("l"), ["z", "A", "b0", "b", "c", "d", "x"]);
+        assert_eq!(cmd_ltrim(db(), "l", 1, -2), Ok(()));
+        assert_eq!(list("l"), ["A", "b0", "b", "c", "d"]);
+
+        assert_eq!(cmd_pop(db(), "l", true, 2), Ok(Some(items(&["A", "b0"]))));
+        assert_eq!(cmd_rpop(db(), "l"), Ok(Some(b"d".to_vec())));
+        assert_eq!(cmd_lmove(db(), "l", "dst", true, false), Ok(Some(b"b".to_vec())));
+        assert_eq!(cmd_lmove(db(), "l", "l", false, true), Ok(Some(b"c".to_vec())));
+        assert_eq!(cmd_lmove(db(), "none", "dst", true, true), Ok(None));
+        assert_eq!(list("dst"), ["b"]);
+
+        // Emptied lists are removed, and their memory is given back.
+        assert_eq!(cmd_lpop(db(), "l"), Ok(Some(b"c".to_vec())));
+        assert_eq!(cmd_ttl(db(), "l"), -2);
+        assert_eq!(cmd_ltrim(db(), "dst", 5, 10), Ok(()));
+        assert_eq!(cmd_ttl(db(), "dst"), -2);
+        assert_eq!(cache_used_memory(), empty);
+
+        cmd_set(db(), "str", b"v".to_vec()).unwrap();
+        assert_eq!(cmd_lmove(db(), "dst", "str", true, true), Ok(None));
+        cmd_rpush(db(), "src", items(&["a"])).unwrap();
+        assert_eq!(cmd_lmove(db(), "src", "str", true, true), Err(CacheError::WrongType));
+        assert_eq!(list("src"), ["a"]);
+        assert_eq!(cmd_llen(db(), "str"), Err(CacheError::WrongType));
+    }
 
-// --- Phase3: Pub/Sub ---
+    #[test]
+    fn list_edits_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("lists.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_rpush(db(), "q", items(&["a", "b", "c", "b", "d", "e"])).unwrap();
+        cmd_lpush(db(), "q", items(&["z"])).unwrap();
+        cmd_pop(db(), "q", true, 1).unwrap();
+        cmd_rpop(db(), "q").unwrap();
+        cmd_lset(db(), "q", 0, b"A".to_vec()).unwrap();
+        cmd_linsert(db(), "q", false, b"b", b"b1".to_vec()).unwrap();
+        cmd_lrem(db(), "q", 1, b"b").unwrap();
+        cmd_ltrim(db(), "q", 0, -2).unwrap();
+        cmd_lmove(db(), "q", "done", false, true).unwrap();
+        cmd_rpush(db(), "gone", items(&["x"])).unwrap();
+        cmd_rpop(db(), "gone").unwrap();
+        let expected = list("q");
+        assert_eq!(expected, ["A", "b1", "c"]);
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(list("q"), expected);
+        assert_eq!(list("done"), ["b"]);
+        assert_eq!(cmd_ttl(db(), "gone"), -2);
+
+        // A rewrite keeps the order.
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        assert_eq!(aof_rewrite(db()), Ok(()));
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(list("q"), expected);
+        let _ = std::fs::remove_file(&path);
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_pubsub_subscribe(channel: *const c_char) -> u64 {
-    let channel_str = unsafe { to_string(channel) };
-    if channel_str.is_empty() {
-        return 0;
+    #[test]
+    fn hash_commands_follow_redis_semantics() {
+        let _guard = test_reset();
+        let fields = |pairs: &[(&str, &str)]| pairs.iter().map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec())).collect();
+        assert_eq!(cmd_hset(db(), "h", fields(&[("a", "1"), ("b", "x"), ("c", "2.5")])), Ok(3));
+        assert_eq!(cmd_hsetnx(db(), "h", b"a".to_vec(), b"9".to_vec()), Ok(false));
+        assert_eq!(cmd_hsetnx(db(), "h", b"d".to_vec(), b"4".to_vec()), Ok(true));
+        assert_eq!(cmd_hexists(db(), "h", b"d"), Ok(true));
+        assert_eq!(cmd_hexists(db(), "missing", b"d"), Ok(false));
+        assert_eq!(cmd_hlen(db(), "h"), Ok(4));
+        let mut keys = cmd_hkeys(db(), "h").unwrap().unwrap();
+        keys.sort();
+        assert_eq!(keys, [b"a", b"b", b"c", b"d"]);
+        assert_eq!(cmd_hvals(db(), "h").unwrap().map(|v| v.len()), Some(4));
+        let wanted = [b"a".to_vec(), b"nope".to_vec(), b"d".to_vec()];
+        assert_eq!(cmd_hmget(db(), "h", &wanted), Ok(vec![Some(b"1".to_vec()), None, Some(b"4".to_vec())]));
+        assert_eq!(cmd_hmget(db(), "missing", &wanted), Ok(vec![None, None, None]));
+
+        assert_eq!(cmd_hincrby(db(), "h", b"a".to_vec(), 41), Ok(42));
+        assert_eq!(cmd_hincrby(db(), "h", b"new".to_vec(), -3), Ok(-3));
+        assert_eq!(cmd_hincrby(db(), "h", b"b".to_vec(), 1), Err(CacheError::invalid("hash value is not an integer")));
+        cmd_hset(db(), "h", fields(&[("max", &i64::MAX.to_string())])).unwrap();
+        assert_eq!(cmd_hincrby(db(), "h", b"max".to_vec(), 1), Err(CacheError::invalid("increment or decrement would overflow")));
+        assert_eq!(cmd_hincrbyfloat(db(), "h", b"c".to_vec(), 0.5), Ok(3.0));
+        assert_eq!(cmd_hget(db(), "h", b"c"), Ok(Some(b"3".to_vec())));
+        assert_eq!(cmd_hincrbyfloat(db(), "h", b"b".to_vec(), 1.0), Err(CacheError::invalid("hash value is not a float")));
+        assert_eq!(cmd_hincrbyfloat(db(), "h", b"c".to_vec(), f64::INFINITY), Err(CacheError::invalid("increment would produce NaN or Infinity")));
+
+        let all = cmd_hkeys(db(), "h").unwrap().unwrap();
+        assert_eq!(cmd_hdel(db(), "h", &[b"nope".to_vec()]), Ok(0));
+        assert_eq!(cmd_hdel(db(), "h", &all), Ok(6));
+        assert_eq!(cmd_ttl(db(), "h"), -2);
+        assert_eq!(db().cache.used_memory.load(Ordering::Relaxed), 0);
+
+        cmd_rpush(db(), "l", items(&["x"])).unwrap();
+        assert_eq!(cmd_hdel(db(), "l", &[b"x".to_vec()]), Err(CacheError::WrongType));
+        assert_eq!(cmd_hincrby(db(), "l", b"x".to_vec(), 1), Err(CacheError::WrongType));
     }
 
-    let mut ps = PUBSUB.lock().unwrap();
-    let id = ps.next_id;
-    ps.next_id = ps.next_id.saturating_add(1);
-    ps.subs.insert(id, channel_str.clone());
-    ps.channels.entry(channel_str).or_default().push(id);
-    ps.queues.insert(id, VecDeque::new());
-    id
-}
+    #[test]
+    fn hash_edits_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("hashes.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_hset(db(), "h", vec![(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())]).unwrap();
+        cmd_hincrby(db(), "h", b"a".to_vec(), 10).unwrap();
+        cmd_hincrbyfloat(db(), "h", b"f".to_vec(), 1.5).unwrap();
+        cmd_hsetnx(db(), "h", b"b".to_vec(), b"ignored".to_vec()).unwrap();
+        cmd_hdel(db(), "h", &[b"b".to_vec()]).unwrap();
+        cmd_hset(db(), "gone", vec![(b"x".to_vec(), b"1".to_vec())]).unwrap();
+        cmd_hdel(db(), "gone", &[b"x".to_vec()]).unwrap();
+        let sorted = || {
+            let mut fields = cmd_hgetall(db(), "h").unwrap().unwrap();
+            fields.sort();
+            fields
+        };
+        let expected = sorted();
+        assert_eq!(expected, vec![(b"a".to_vec(), b"11".to_vec()), (b"f".to_vec(), b"1.5".to_vec())]);
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(sorted(), expected);
+        assert_eq!(cmd_ttl(db(), "gone"), -2);
+        let _ = std::fs::remove_file(&path);
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_pubsub_unsubscribe(sub_id: u64) {
-    if sub_id == 0 {
-        return;
+    #[test]
+    fn multi_field_hash_exports_take_packed_items() {
+        let _guard = test_reset();
+        let key = CString::new("h").unwrap();
+        let packed = pack_items([&b"a"[..], b"1", b"b", b"2"].into_iter());
+        assert_eq!(cache_hmset(key.as_ptr(), packed.as_ptr(), packed.len()), 2);
+        let odd = pack_items([&b"a"[..]].into_iter());
+        assert_eq!(cache_hmset(key.as_ptr(), odd.as_ptr(), odd.len()), CacheStatus::InvalidArgument as i64);
+
+        let fields = pack_items([&b"b"[..], b"nope", b"a"].into_iter());
+        let mut len = 0;
+        let ptr = cache_hmget(key.as_ptr(), fields.as_ptr(), fields.len(), &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_optional_items(&[Some(b"2".to_vec()), None, Some(b"1".to_vec())]));
+
+        let mut value = 0;
+        assert_eq!(cache_hincrby(key.as_ptr(), CString::new("a").unwrap().as_ptr(), 5, &mut value), 1);
+        assert_eq!(value, 6);
+        assert_eq!(cache_hlen(key.as_ptr()), 2);
+        assert_eq!(cache_hdel(key.as_ptr(), CString::new("a").unwrap().as_ptr()), 1);
+        assert_eq!(cache_hexists(key.as_ptr(), CString::new("a").unwrap().as_ptr()), 0);
     }
 
-    let mut ps = PUBSUB.lock().unwrap();
-    let Some(channel) = ps.subs.remove(&sub_id) else {
-        return;
-    };
-    if let Some(list) = ps.channels.get_mut(&channel) {
-        list.retain(|id| *id != sub_id);
-        if list.is_empty() {
-            ps.channels.remove(&channel);
+    fn members(items: &[&str]) -> Vec<Vec<u8>> {
+        items.iter().map(|m| m.as_bytes().to_vec()).collect()
+    }
+
+    fn sorted_members(found: Option<Vec<Vec<u8>>>) -> Vec<Vec<u8>> {
+        let mut found = found.unwrap_or_default();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn set_commands_follow_redis_semantics() {
+        let _guard = test_reset();
+        cmd_sadd(db(), "s", members(&["a", "b", "c", "d"])).unwrap();
+        assert_eq!(cmd_scard(db(), "s"), Ok(4));
+        assert_eq!(cmd_srem(db(), "s", &members(&["a", "x"])), Ok(1));
+        assert_eq!(sorted_members(cmd_smembers(db(), "s").unwrap()), members(&["b", "c", "d"]));
+        assert_eq!(cmd_smismember(db(), "s", &members(&["b", "a"])), Ok(vec![true, false]));
+        assert_eq!(cmd_smembers(db(), "missing"), Ok(None));
+        assert_eq!(cmd_scard(db(), "missing"), Ok(0));
+
+        // Positive counts give distinct members, negative ones may repeat.
+        let picked = cmd_srandmember(db(), "s", 2).unwrap().unwrap();
+        assert_eq!(picked.len(), 2);
+        assert_ne!(picked[0], picked[1]);
+        assert_eq!(cmd_srandmember(db(), "s", 10).unwrap().unwrap().len(), 3);
+        let repeated = cmd_srandmember(db(), "s", -7).unwrap().unwrap();
+        assert_eq!(repeated.len(), 7);
+        assert!(repeated.iter().all(|m| cmd_sismember(db(), "s", m).unwrap()));
+        assert_eq!(cmd_scard(db(), "s"), Ok(3));
+
+        let popped = cmd_spop(db(), "s", 2).unwrap().unwrap();
+        assert_eq!(popped.len(), 2);
+        assert!(popped.iter().all(|m| !cmd_sismember(db(), "s", m).unwrap()));
+        assert_eq!(cmd_spop(db(), "s", 5).unwrap().unwrap().len(), 1);
+        assert_eq!(cmd_ttl(db(), "s"), -2);
+        assert_eq!(cmd_spop(db(), "s", 1), Ok(None));
+
+        cmd_set(db(), "str", b"v".to_vec()).unwrap();
+        assert_eq!(cmd_srem(db(), "str", &members(&["v"])), Err(CacheError::WrongType));
+        assert_eq!(cmd_spop(db(), "str", 1), Err(CacheError::WrongType));
+    }
+
+    #[test]
+    fn set_algebra_and_store_variants() {
+        let _guard = test_reset();
+        cmd_sadd(db(), "a", members(&["1", "2", "3"])).unwrap();
+        cmd_sadd(db(), "b", members(&["2", "3", "4"])).unwrap();
+        let keys = |names: &[&str]| names.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        let op = |op, names: &[&str]| sorted_members(Some(cmd_set_op(db(), op, &keys(names)).unwrap()));
+
+        assert_eq!(op(SetOp::Union, &["a", "b", "missing"]), members(&["1", "2", "3", "4"]));
+        assert_eq!(op(SetOp::Inter, &["a", "b"]), members(&["2", "3"]));
+        assert_eq!(op(SetOp::Inter, &["a", "missing"]), Vec::<Vec<u8>>::new());
+        assert_eq!(op(SetOp::Diff, &["a", "b"]), members(&["1"]));
+        assert_eq!(op(SetOp::Diff, &["missing", "a"]), Vec::<Vec<u8>>::new());
+
+        // The destination is replaced whatever it held, TTL included.
+        cmd_set(db(), "dst", b"old".to_vec()).unwrap();
+        assert!(cmd_expire(db(), "dst", 60_000));
+        assert_eq!(cmd_set_op_store(db(), SetOp::Union, &"dst".to_string(), &keys(&["a", "b"])), Ok(4));
+        assert_eq!(cmd_ttl(db(), "dst"), -1);
+        // A destination that is also a source is read before it is overwritten.
+        assert_eq!(cmd_set_op_store(db(), SetOp::Inter, &"dst".to_string(), &keys(&["dst", "a"])), Ok(3));
+        assert_eq!(sorted_members(cmd_smembers(db(), "dst").unwrap()), members(&["1", "2", "3"]));
+        assert_eq!(cmd_set_op_store(db(), SetOp::Diff, &"dst".to_string(), &keys(&["a", "a"])), Ok(0));
+        assert_eq!(cmd_ttl(db(), "dst"), -2);
+
+        cmd_set(db(), "str", b"v".to_vec()).unwrap();
+        assert_eq!(cmd_set_op(db(), SetOp::Union, &keys(&["a", "str"])), Err(CacheError::WrongType));
+        assert_eq!(cmd_set_op_store(db(), SetOp::Union, &"a".to_string(), &keys(&["str"])), Err(CacheError::WrongType));
+        assert_eq!(cmd_scard(db(), "a"), Ok(3));
+    }
+
+    #[test]
+    fn set_edits_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("sets.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_sadd(db(), "s", members(&["a", "b", "c", "d"])).unwrap();
+        cmd_srem(db(), "s", &members(&["a"])).unwrap();
+        cmd_spop(db(), "s", 1).unwrap();
+        cmd_sadd(db(), "t", members(&["c", "z"])).unwrap();
+        cmd_set_op_store(db(), SetOp::Union, &"u".to_string(), &["s".to_string(), "t".to_string()]).unwrap();
+        let expected = (sorted_members(cmd_smembers(db(), "s").unwrap()), sorted_members(cmd_smembers(db(), "u").unwrap()));
+        assert_eq!(expected.0.len(), 2);
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!((sorted_members(cmd_smembers(db(), "s").unwrap()), sorted_members(cmd_smembers(db(), "u").unwrap())), expected);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn set_exports_take_packed_keys_and_members() {
+        let _guard = test_reset();
+        let key = CString::new("s").unwrap();
+        cmd_sadd(db(), "s", members(&["a", "b"])).unwrap();
+        cmd_sadd(db(), "t", members(&["b"])).unwrap();
+        let read = |ptr: *mut c_uchar, len: usize| {
+            let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+            cache_free(ptr, len);
+            blob
+        };
+
+        let probe = pack_items([&b"b"[..], b"x"].into_iter());
+        let mut len = 0;
+        let ptr = cache_smismember(key.as_ptr(), probe.as_ptr(), probe.len(), &mut len);
+        assert_eq!(read(ptr, len), vec![1, 0]);
+        let none = pack_items(std::iter::empty());
+        assert!(cache_smismember(key.as_ptr(), none.as_ptr(), none.len(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::InvalidArgument as i32);
+
+        let keys = pack_items([&b"s"[..], b"t"].into_iter());
+        let ptr = cache_sdiff(keys.as_ptr(), keys.len(), &mut len);
+        assert_eq!(read(ptr, len), pack_items([&b"a"[..]].into_iter()));
+        let dst = CString::new("d").unwrap();
+        assert_eq!(cache_sinterstore(dst.as_ptr(), keys.as_ptr(), keys.len()), 1);
+        assert_eq!(cache_scard(dst.as_ptr()), 1);
+        assert_eq!(cache_srem(dst.as_ptr(), b"b".as_ptr(), 1), 1);
+        assert!(cache_smembers(dst.as_ptr(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::NotFound as i32);
+    }
+
+    fn stream_ids(key: &str) -> Vec<StreamId> {
+        let items = cmd_xrange(db(), key, StreamId::MIN, StreamId::MAX, None, false).unwrap().unwrap_or_default();
+        items.into_iter().map(|(id, _)| id).collect()
+    }
+
+    fn xadd_id(key: &str, id: XAddId) -> Result<Option<StreamId>, CacheError> {
+        cmd_xadd_with(db(), key, id, payload(b""), None, false)
+    }
+
+    fn payload(p: &[u8]) -> StreamFields {
+        vec![(STREAM_PAYLOAD_FIELD.to_vec(), p.to_vec())]
+    }
+
+    #[test]
+    fn stream_ids_grow_per_stream() {
+        let _guard = test_reset();
+        let id = |ms, seq| StreamId::new(ms, seq);
+        assert_eq!(xadd_id("s", XAddId::Explicit(id(5, 1))), Ok(Some(id(5, 1))));
+        assert_eq!(xadd_id("s", XAddId::AutoSeq(5)), Ok(Some(id(5, 2))));
+        assert_eq!(xadd_id("s", XAddId::AutoSeq(6)), Ok(Some(id(6, 0))));
+        assert!(xadd_id("s", XAddId::Explicit(id(6, 0))).is_err());
+        assert!(xadd_id("s", XAddId::AutoSeq(4)).is_err());
+        assert!(xadd_id("t", XAddId::Explicit(StreamId::MIN)).is_err());
+        // Every stream counts on its own, and `*` stays above explicit ids from the future.
+        assert_eq!(xadd_id("t", XAddId::Explicit(id(1, 0))), Ok(Some(id(1, 0))));
+        assert_eq!(xadd_id("u", XAddId::Explicit(id(u64::MAX, 7))), Ok(Some(id(u64::MAX, 7))));
+        assert_eq!(xadd_id("u", XAddId::Auto), Ok(Some(id(u64::MAX, 8))));
+        assert!(cmd_xadd(db(), "t", Vec::new()).unwrap() > id(1, 0));
+
+        // Deleting the newest entry keeps its id used.
+        assert_eq!(cmd_xdel(db(), "s", &[id(6, 0), id(9, 9)]), Ok(1));
+        assert_eq!(cmd_xdel(db(), "s", &[id(6, 0)]), Ok(0));
+        assert!(xadd_id("s", XAddId::Explicit(id(6, 0))).is_err());
+        assert_eq!(cmd_xlen(db(), "s"), Ok(2));
+        assert_eq!(cmd_xlen(db(), "missing"), Ok(0));
+        assert_eq!(xadd_id("missing", XAddId::Auto).map(|id| id.is_some()), Ok(true));
+        let nomkstream = cmd_xadd_with(db(), "none", XAddId::Auto, payload(b""), None, true);
+        assert_eq!(nomkstream, Ok(None));
+        assert_eq!(cmd_xrange(db(), "none", StreamId::MIN, StreamId::MAX, None, false), Ok(None));
+    }
+
+    #[test]
+    fn streams_trim_and_read_in_both_directions() {
+        let _guard = test_reset();
+        let ids: Vec<StreamId> = (1..=6).map(|ms| xadd_id("s", XAddId::Explicit(StreamId::new(ms, 0))).unwrap().unwrap()).collect();
+        let range = |start, end, count, rev| {
+            let items = cmd_xrange(db(), "s", start, end, count, rev).unwrap().unwrap();
+            items.into_iter().map(|(id, _)| id).collect::<Vec<_>>()
+        };
+        assert_eq!(range(ids[1], ids[3], None, false), ids[1..4].to_vec());
+        assert_eq!(range(StreamId::MIN, StreamId::MAX, Some(2), true), vec![ids[5], ids[4]]);
+        assert_eq!(range(ids[3], ids[1], None, false), Vec::new());
+
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MaxLen(5), usize::MAX), Ok(1));
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MinId(ids[3]), 1), Ok(1));
+        assert_eq!(stream_ids("s"), ids[2..].to_vec());
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MinId(ids[3]), usize::MAX), Ok(1));
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MinId(ids[3]), usize::MAX), Ok(0));
+        assert_eq!(cmd_xtrim(db(), "missing", StreamTrim::MaxLen(0), usize::MAX), Ok(0));
+
+        let capped = cmd_xadd_with(db(), "s", XAddId::Auto, payload(b""), Some((StreamTrim::MaxLen(2), usize::MAX)), false);
+        let newest = capped.unwrap().unwrap();
+        assert_eq!(stream_ids("s"), vec![ids[5], newest]);
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MaxLen(0), usize::MAX), Ok(2));
+        assert_eq!(cmd_xlen(db(), "s"), Ok(0));
+        assert!(xadd_id("s", XAddId::Explicit(newest)).is_err());
+
+        cmd_set(db(), "str", b"v".to_vec()).unwrap();
+        assert_eq!(cmd_xtrim(db(), "str", StreamTrim::MaxLen(0), usize::MAX), Err(CacheError::WrongType));
+        assert_eq!(cmd_xlen(db(), "str"), Err(CacheError::WrongType));
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> StreamFields {
+        pairs.iter().map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec())).collect()
+    }
+
+    #[test]
+    fn stream_entries_keep_their_fields_and_share_names() {
+        let _guard = test_reset();
+        let add = |ms, f: &[(&str, &str)]| cmd_xadd_with(db(), "s", XAddId::Explicit(StreamId::new(ms, 0)), fields(f), None, false);
+        // The entry's memory stays what it would take when rebuilt from scratch.
+        let mem_matches = || {
+            let state = shard_read(db(), "s");
+            let entry = state.map.peek(b"s".as_slice()).unwrap();
+            let Value::Stream(stream) = &entry.value else { unreachable!() };
+            assert!(Arc::ptr_eq(&stream.entries[&StreamId::new(2, 0)].names, &stream.entries[&StreamId::new(3, 0)].names));
+            entry.mem == value_mem(&entry.value)
+        };
+        add(1, &[("a", "1")]).unwrap();
+        add(2, &[("t", "20"), ("h", "5")]).unwrap();
+        add(3, &[("t", "21"), ("h", "6")]).unwrap();
+        add(4, &[("t", "22"), ("h", "7")]).unwrap();
+        add(5, &[("h", "8"), ("t", "23")]).unwrap();
+        assert!(mem_matches());
+        assert_eq!(add(6, &[]), Err(CacheError::invalid("stream entries need at least one field")));
+        let items = cmd_xrange(db(), "s", StreamId::new(3, 0), StreamId::new(5, 0), None, false).unwrap().unwrap();
+        let expected = vec![
+            (StreamId::new(3, 0), fields(&[("t", "21"), ("h", "6")])),
+            (StreamId::new(4, 0), fields(&[("t", "22"), ("h", "7")])),
+            (StreamId::new(5, 0), fields(&[("h", "8"), ("t", "23")])),
+        ];
+        assert_eq!(items, expected);
+
+        assert_eq!(cmd_xdel(db(), "s", &[StreamId::new(4, 0)]), Ok(1));
+        assert!(mem_matches());
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MinId(StreamId::new(2, 0)), usize::MAX), Ok(1));
+        assert!(mem_matches());
+        assert_eq!(cmd_xtrim(db(), "s", StreamTrim::MaxLen(0), usize::MAX), Ok(3));
+        let empty = ENTRY_OVERHEAD + 1;
+        assert_eq!(cache_used_memory() as usize, empty);
+    }
+
+    #[test]
+    fn stream_trims_and_deletes_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("streams.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        for ms in 1..=5 {
+            xadd_id("s", XAddId::Explicit(StreamId::new(ms, 0))).unwrap();
         }
+        cmd_xtrim(db(), "s", StreamTrim::MaxLen(3), usize::MAX).unwrap();
+        cmd_xdel(db(), "s", &[StreamId::new(5, 0)]).unwrap();
+        let f = vec![(StreamId::new(1, 0), fields(&[("a", "1"), ("b", "")]))];
+        cmd_xadd_with(db(), "f", XAddId::Explicit(f[0].0), f[0].1.clone(), None, false).unwrap();
+        cmd_xadd_with(db(), "e", XAddId::Explicit(StreamId::new(9, 0)), payload(b""), Some((StreamTrim::MaxLen(0), usize::MAX)), false).unwrap();
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        for rewrite in [false, true] {
+            cmd_clear_all(db());
+            assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+            assert_eq!(stream_ids("s"), vec![StreamId::new(3, 0), StreamId::new(4, 0)]);
+            assert_eq!(stream_ids("e"), Vec::new());
+            assert_eq!(cmd_xrange(db(), "f", StreamId::MIN, StreamId::MAX, None, false), Ok(Some(f.clone())));
+            // The last ids survive, even of emptied streams.
+            assert_eq!(xadd_id("s", XAddId::AutoSeq(5)), Ok(Some(StreamId::new(5, 1))));
+            assert_eq!(xadd_id("e", XAddId::AutoSeq(9)), Ok(Some(StreamId::new(9, 1))));
+            if !rewrite {
+                cmd_clear_all(db());
+                assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+                assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+                assert_eq!(aof_rewrite(db()), Ok(()));
+                cache_aof_disable();
+            }
+        }
+        let _ = std::fs::remove_file(&path);
     }
-    ps.queues.remove(&sub_id);
-}
 
-#[no_mangle]
-pub extern "C" fn cache_pubsub_publish(channel: *const c_char, payload: *const c_uchar, len: usize) -> u64 {
-    let channel_str = unsafe { to_string(channel) };
-    if channel_str.is_empty() {
-        return 0;
+    fn read_group(consumer: &str, after: Option<StreamId>, count: Option<usize>) -> Result<Vec<StreamId>, CacheError> {
+        let streams = [("s".to_string(), after)];
+        let found = cmd_xreadgroup(db(), "g", consumer, &streams, count, false)?;
+        Ok(found.into_iter().flat_map(|(_, items)| items).map(|(id, _)| id).collect())
     }
-    let payload_vec = unsafe { to_bytes(payload, len) };
 
-    let mut ps = PUBSUB.lock().unwrap();
-    let Some(subs) = ps.channels.get(&channel_str) else {
-        return 0;
-    };
-    let subs = subs.clone();
+    // (id, consumer, deliveries) of every pending entry of group `g` on `s`.
+    fn pending(consumer: Option<&str>) -> Vec<(StreamId, String, u64)> {
+        let items = cmd_xpending(db(), "s", "g", StreamId::MIN, StreamId::MAX, usize::MAX, consumer, 0).unwrap();
+        items.into_iter().map(|p| (p.id, p.consumer, p.deliveries)).collect()
+    }
 
-    let mut delivered = 0u64;
-    for id in subs.into_iter() {
-        if let Some(q) = ps.queues.get_mut(&id) {
-            q.push_back(PubMessage {
-                channel: channel_str.clone(),
-                payload: payload_vec.clone(),
-            });
-            delivered += 1;
+    #[test]
+    fn consumer_groups_deliver_each_entry_once() {
+        let _guard = test_reset();
+        let ids: Vec<StreamId> = ["a", "b", "c"].iter().map(|p| cmd_xadd(db(), "s", p.as_bytes().to_vec()).unwrap()).collect();
+        assert_eq!(cmd_xgroup_create(db(), "s", "g", Some(StreamId::MIN), false), Ok(()));
+        assert_eq!(cmd_xgroup_create(db(), "s", "g", None, false), Err(CacheError::BusyGroup));
+        assert!(cmd_xgroup_create(db(), "none", "g", None, false).is_err());
+        assert_eq!(cmd_xgroup_create(db(), "empty", "g", None, true), Ok(()));
+        assert_eq!(cmd_xrange(db(), "empty", StreamId::MIN, StreamId::MAX, None, false), Ok(Some(Vec::new())));
+        assert_eq!(read_group("c1", None, Some(2)), Ok(ids[..2].to_vec()));
+        assert_eq!(read_group("c2", None, None), Ok(ids[2..].to_vec()));
+        assert_eq!(read_group("c1", None, None), Ok(Vec::new()));
+        assert_eq!(
+            cmd_xpending_summary(db(), "s", "g"),
+            Ok(PendingSummary { count: 3, first: ids[0], last: ids[2], consumers: vec![("c1".to_string(), 2), ("c2".to_string(), 1)] })
+        );
+
+        // History reads return only the reader's own pending entries and count as redeliveries.
+        assert_eq!(read_group("c1", Some(StreamId::MIN), None), Ok(ids[..2].to_vec()));
+        assert_eq!(read_group("c1", Some(ids[0]), None), Ok(ids[1..2].to_vec()));
+        assert_eq!(pending(Some("c1")), vec![(ids[0], "c1".to_string(), 2), (ids[1], "c1".to_string(), 3)]);
+        assert_eq!(cmd_xack(db(), "s", "g", &[ids[0], StreamId::new(999, 0)]), Ok(1));
+        assert_eq!(cmd_xack(db(), "s", "g", &[ids[0]]), Ok(0));
+        assert_eq!(cmd_xack(db(), "s", "missing", &[ids[1]]), Ok(0));
+
+        assert_eq!(cmd_xclaim(db(), "s", "g", "c3", 60_000, &[ids[1]], false), Ok(Vec::new()));
+        assert_eq!(cmd_xclaim(db(), "s", "g", "c3", 0, &[ids[1], ids[0]], false), Ok(vec![(ids[1], payload(b"b"))]));
+        assert_eq!(cmd_xclaim(db(), "s", "g", "c2", 0, &[ids[1]], true), Ok(vec![(ids[1], Vec::new())]));
+        assert_eq!(pending(None), vec![(ids[1], "c2".to_string(), 4), (ids[2], "c2".to_string(), 1)]);
+        let claimed = cmd_xautoclaim(db(), "s", "g", "c4", 0, StreamId::MIN, 1, false);
+        assert_eq!(claimed, Ok((ids[2], vec![(ids[1], payload(b"b"))], Vec::new())));
+        let claimed = cmd_xautoclaim(db(), "s", "g", "c4", 0, ids[2], 1, true);
+        assert_eq!(claimed, Ok((StreamId::MIN, vec![(ids[2], Vec::new())], Vec::new())));
+        assert_eq!(pending(Some("c4")).len(), 2);
+
+        // NOACK reads move the group forward without tracking the entries.
+        let d = cmd_xadd(db(), "s", b"d".to_vec()).unwrap();
+        let found = cmd_xreadgroup(db(), "g", "c1", &[("s".to_string(), None)], None, true).unwrap();
+        assert_eq!(found, vec![("s".to_string(), vec![(d, Some(payload(b"d")))])]);
+        assert_eq!(cmd_xpending_summary(db(), "s", "g").unwrap().count, 2);
+
+        assert_eq!(cmd_xgroup_destroy(db(), "s", "g"), Ok(true));
+        assert_eq!(cmd_xgroup_destroy(db(), "s", "g"), Ok(false));
+        assert_eq!(read_group("c1", None, None), Err(CacheError::NoGroup));
+        assert_eq!(cmd_xpending_summary(db(), "none", "g"), Err(CacheError::NoGroup));
+        cmd_set(db(), "str", b"v".to_vec()).unwrap();
+        assert_eq!(cmd_xgroup_create(db(), "str", "g", None, true), Err(CacheError::WrongType));
+    }
+
+    #[test]
+    fn consumer_groups_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("groups.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        let ids: Vec<StreamId> = (0..4).map(|i| cmd_xadd(db(), "s", vec![i]).unwrap()).collect();
+        cmd_xgroup_create(db(), "s", "g", Some(StreamId::MIN), false).unwrap();
+        cmd_xgroup_create(db(), "s", "gone", None, false).unwrap();
+        read_group("c1", None, Some(3)).unwrap();
+        read_group("c1", Some(StreamId::MIN), Some(1)).unwrap();
+        cmd_xack(db(), "s", "g", &[ids[1]]).unwrap();
+        cmd_xclaim(db(), "s", "g", "c2", 0, &[ids[2]], false).unwrap();
+        cmd_xgroup_destroy(db(), "s", "gone").unwrap();
+        let expected = pending(None);
+        assert_eq!(expected, vec![(ids[0], "c1".to_string(), 2), (ids[2], "c2".to_string(), 2)]);
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        for rewrite in [false, true] {
+            cmd_clear_all(db());
+            assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+            assert_eq!(pending(None), expected);
+            assert_eq!(read_group("c3", None, None), Ok(vec![ids[3]]));
+            assert_eq!(cmd_xpending_summary(db(), "s", "gone"), Err(CacheError::NoGroup));
+            if !rewrite {
+                // Rewrite from the state before the read above.
+                cmd_clear_all(db());
+                assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+                assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+                assert_eq!(aof_rewrite(db()), Ok(()));
+                cache_aof_disable();
+            }
         }
+        let _ = std::fs::remove_file(&path);
     }
-    delivered
-}
 
-#[no_mangle]
-pub extern "C" fn cache_pubsub_poll(sub_id: u64, out_len: *mut usize) -> *mut c_uchar {
-    if sub_id == 0 {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+    #[test]
+    fn consumer_group_exports() {
+        let _guard = test_reset();
+        let (key, group, consumer) = (CString::new("s").unwrap(), CString::new("g").unwrap(), CString::new("c").unwrap());
+        let read = |ptr: *mut c_uchar, len: usize| {
+            let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+            cache_free(ptr, len);
+            blob
+        };
+        let (key_ptr, group_ptr) = (key.as_ptr(), group.as_ptr());
+        assert_eq!(cache_xgroup_create(key_ptr, group_ptr, StreamId::MAX, 0), CacheStatus::InvalidArgument as i32);
+        assert_eq!(cache_xgroup_create(key_ptr, group_ptr, StreamId::MAX, 1), 1);
+        assert_eq!(cache_xgroup_create(key_ptr, group_ptr, StreamId::MIN, 0), CacheStatus::InvalidArgument as i32);
+        assert_eq!(last_error().as_deref(), Some("BUSYGROUP Consumer Group name already exists"));
+        let id = cmd_xadd(db(), "s", b"p".to_vec()).unwrap();
+
+        let keys = pack_items([&b"s"[..]].into_iter());
+        let new_only = [StreamId::MAX];
+        let mut len = 0;
+        let ptr = cache_xreadgroup(group.as_ptr(), consumer.as_ptr(), keys.as_ptr(), keys.len(), new_only.as_ptr(), 0, 0, -1, &mut len);
+        let expected = pack_stream_reads(&[("s".to_string(), vec![(id, payload(b"p"))])], Some);
+        assert_eq!(read(ptr, len), expected);
+        let ptr = cache_xreadgroup(group.as_ptr(), consumer.as_ptr(), keys.as_ptr(), keys.len(), new_only.as_ptr(), 0, 0, 10, &mut len);
+        assert!(ptr.is_null());
+        assert_eq!(cache_last_status(), CacheStatus::NotFound as i32);
+
+        let ptr = cache_xpending(key_ptr, group_ptr, StreamId::MIN, StreamId::MAX, 10, std::ptr::null(), 0, &mut len);
+        let blob = read(ptr, len);
+        let head = [&1u32.to_le_bytes()[..], &id.ms.to_le_bytes(), &id.seq.to_le_bytes(), &1u32.to_le_bytes(), b"c"].concat();
+        assert_eq!(&blob[..25], &head[..]);
+        assert_eq!(&blob[33..], &1u64.to_le_bytes());
+
+        let mut next = StreamId::new(7, 0);
+        let ptr = cache_xautoclaim(key_ptr, group_ptr, consumer.as_ptr(), 0, StreamId::MIN, 0, 1, &mut next, &mut len);
+        let mut expected = Vec::new();
+        pack_stream_items(&mut expected, &[(id, Vec::new())], Some);
+        expected.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!((read(ptr, len), next), (expected, StreamId::MIN));
+        assert_eq!(cache_xack(key_ptr, group_ptr, [id, StreamId::new(id.ms + 1, 0)].as_ptr(), 2), 1);
+        assert_eq!(cache_xgroup_destroy(key_ptr, group_ptr), 1);
+        let ptr = cache_xpending(key_ptr, group_ptr, StreamId::MIN, StreamId::MAX, 10, std::ptr::null(), 0, &mut len);
+        assert!(ptr.is_null());
+        assert_eq!(cache_last_status(), CacheStatus::InvalidArgument as i32);
     }
 
-    let mut ps = PUBSUB.lock().unwrap();
-    let Some(q) = ps.queues.get_mut(&sub_id) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
-    let Some(msg) = q.pop_front() else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+    fn scored(pairs: &[(&str, f64)]) -> ScoredMembers {
+        pairs.iter().map(|(m, s)| (m.as_bytes().to_vec(), *s)).collect()
+    }
 
-    let mut buf = Vec::new();
-    buf.extend_from_slice(&(msg.channel.as_bytes().len() as u32).to_le_bytes());
-    buf.extend_from_slice(msg.channel.as_bytes());
-    buf.extend_from_slice(&(msg.payload.len() as u32).to_le_bytes());
-    buf.extend_from_slice(&msg.payload);
-    prepare_return(buf, out_len)
-}
+    #[test]
+    fn sorted_set_commands_follow_redis_semantics() {
+        let _guard = test_reset();
+        let zadd = |members: &[(f64, &str)], flags| {
+            let members = members.iter().map(|(s, m)| (*s, m.as_bytes().to_vec())).collect();
+            cmd_zadd_flags(db(), "z", members, ZAddFlags::from_bits(flags))
+        };
+        let outcome = |added, updated| Ok(ZAddOutcome { added, updated });
+        assert_eq!(zadd(&[(1.0, "a"), (2.0, "b"), (3.0, "c")], 0), outcome(3, 0));
+        assert_eq!(zadd(&[(5.0, "a"), (9.0, "d")], ZADD_XX), outcome(0, 1));
+        assert_eq!(zadd(&[(0.0, "a"), (4.0, "d")], ZADD_NX), outcome(1, 0));
+        assert_eq!(zadd(&[(4.0, "a"), (6.0, "b")], ZADD_GT), outcome(0, 1));
+        assert_eq!(zadd(&[(1.0, "c"), (7.0, "d"), (0.5, "e")], ZADD_LT), outcome(1, 1));
+        assert_eq!(zadd(&[(1.0, "a")], ZADD_NX | ZADD_XX), Err(CacheError::invalid("XX and NX options at the same time are not compatible")));
+        assert!(zadd(&[(1.0, "a")], ZADD_GT | ZADD_LT).is_err());
+        assert!(zadd(&[(f64::NAN, "a")], 0).is_err());
+        // XX never creates the key.
+        assert_eq!(cmd_zadd_flags(db(), "none", vec![(1.0, b"a".to_vec())], ZAddFlags::from_bits(ZADD_XX)), outcome(0, 0));
+        assert_eq!(cmd_ttl(db(), "none"), -2);
+
+        let all = scored(&[("e", 0.5), ("c", 1.0), ("d", 4.0), ("a", 5.0), ("b", 6.0)]);
+        assert_eq!(cmd_zrange(db(), "z", 0, -1, false), Ok(Some(all.clone())));
+        assert_eq!(cmd_zrange(db(), "z", 0, 1, true), Ok(Some(scored(&[("b", 6.0), ("a", 5.0)]))));
+        assert_eq!(cmd_zscore(db(), "z", b"d"), Ok(Some(4.0)));
+        assert_eq!(cmd_zscore(db(), "z", b"x"), Ok(None));
+        assert_eq!(cmd_zrank(db(), "z", b"d", false), Ok(Some(2)));
+        assert_eq!(cmd_zrank(db(), "z", b"d", true), Ok(Some(2)));
+        assert_eq!(cmd_zrank(db(), "z", b"e", true), Ok(Some(4)));
+        assert_eq!(cmd_zincrby(db(), "z", 10.0, b"e".to_vec()), Ok(10.5));
+        assert_eq!(cmd_zincrby(db(), "z", 2.0, b"new".to_vec()), Ok(2.0));
+        assert_eq!(cmd_zcard(db(), "z"), Ok(6));
+
+        let inc = |value| ScoreBound { value, exclusive: false };
+        let exc = |value| ScoreBound { value, exclusive: true };
+        assert_eq!(cmd_zrangebyscore(db(), "z", inc(1.0), exc(5.0), false, 0, None), Ok(scored(&[("c", 1.0), ("new", 2.0), ("d", 4.0)])));
+        assert_eq!(cmd_zrangebyscore(db(), "z", inc(f64::NEG_INFINITY), inc(f64::INFINITY), true, 1, Some(1)), Ok(scored(&[("b", 6.0)])));
+        assert_eq!(cmd_zrangebyscore(db(), "missing", inc(0.0), inc(1.0), false, 0, None), Ok(Vec::new()));
+
+        assert_eq!(cmd_zrem(db(), "z", &[b"a".to_vec(), b"x".to_vec(), b"b".to_vec()]), Ok(2));
+        assert_eq!(cmd_zrem(db(), "z", &[b"c".to_vec(), b"d".to_vec(), b"e".to_vec(), b"new".to_vec()]), Ok(4));
+        assert_eq!(cmd_ttl(db(), "z"), -2);
+        assert_eq!(db().cache.used_memory.load(Ordering::Relaxed), 0);
+
+        cmd_zadd(db(), "lex", ["b", "a", "c"].iter().map(|m| (0.0, m.as_bytes().to_vec())).collect()).unwrap();
+        let lex = |s: &str| LexBound::parse(s.as_bytes()).unwrap();
+        assert_eq!(cmd_zrangebylex(db(), "lex", &lex("(a"), &lex("+"), false, 0, None), Ok(scored(&[("b", 0.0), ("c", 0.0)])));
+        assert_eq!(cmd_zrangebylex(db(), "lex", &lex("-"), &lex("[b"), true, 0, None), Ok(scored(&[("b", 0.0), ("a", 0.0)])));
+
+        cmd_set(db(), "s", b"v".to_vec()).unwrap();
+        assert_eq!(cmd_zrank(db(), "s", b"a", false), Err(CacheError::WrongType));
+        assert_eq!(cmd_zincrby(db(), "s", 1.0, b"a".to_vec()), Err(CacheError::WrongType));
+    }
 
-// --- Phase3: Keyspace notifications polling ---
+    #[test]
+    fn sorted_set_edits_replay_from_the_aof() {
+        let _guard = test_reset();
+        let path = temp_path("zsets.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cmd_zadd(db(), "z", vec![(1.0, b"a".to_vec()), (2.0, b"b".to_vec()), (3.0, b"c".to_vec())]).unwrap();
+        cmd_zadd_flags(db(), "z", vec![(0.0, b"b".to_vec()), (9.0, b"c".to_vec())], ZAddFlags::from_bits(ZADD_GT)).unwrap();
+        cmd_zincrby(db(), "z", 2.5, b"a".to_vec()).unwrap();
+        cmd_zrem(db(), "z", &[b"b".to_vec()]).unwrap();
+        cmd_zadd(db(), "gone", vec![(1.0, b"x".to_vec())]).unwrap();
+        cmd_zrem(db(), "gone", &[b"x".to_vec()]).unwrap();
+        let expected = scored(&[("a", 3.5), ("c", 9.0)]);
+        assert_eq!(cmd_zrange(db(), "z", 0, -1, false), Ok(Some(expected.clone())));
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(cmd_zrange(db(), "z", 0, -1, false), Ok(Some(expected.clone())));
+        assert_eq!(cmd_ttl(db(), "gone"), -2);
+
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        assert_eq!(aof_rewrite(db()), Ok(()));
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(cmd_zrange(db(), "z", 0, -1, false), Ok(Some(expected)));
+        let _ = std::fs::remove_file(&path);
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_notifications_poll(out_len: *mut usize) -> *mut c_uchar {
-    let mut q = NOTIFY_QUEUE.lock().unwrap();
-    let Some(ev) = q.pop_front() else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
-    let mut buf = Vec::new();
-    buf.push(ev.kind);
-    buf.extend_from_slice(&(ev.key.as_bytes().len() as u32).to_le_bytes());
-    buf.extend_from_slice(ev.key.as_bytes());
-    buf.extend_from_slice(&ev.at_ms.to_le_bytes());
-    prepare_return(buf, out_len)
-}
+    #[test]
+    fn sorted_set_exports_pack_scores() {
+        let _guard = test_reset();
+        let key = CString::new("z").unwrap();
+        let member = |m: &str| CString::new(m).unwrap();
+        assert_eq!(cache_zadd(key.as_ptr(), 1.0, member("a").as_ptr()), 1);
+        assert_eq!(cache_zadd_flags(key.as_ptr(), 0.5, member("a").as_ptr(), ZADD_GT), 0);
+        assert_eq!(cache_zadd_flags(key.as_ptr(), 2.0, member("b").as_ptr(), ZADD_NX), 1);
+        assert_eq!(cache_zadd_flags(key.as_ptr(), 2.0, member("b").as_ptr(), ZADD_NX | ZADD_XX), CacheStatus::InvalidArgument as i32);
+
+        let mut score = 0.0;
+        assert_eq!(cache_zincrby(key.as_ptr(), 2.0, member("a").as_ptr(), &mut score), 1);
+        assert_eq!(score, 3.0);
+        assert_eq!(cache_zscore(key.as_ptr(), member("b").as_ptr(), &mut score), 1);
+        assert_eq!(score, 2.0);
+        assert_eq!(cache_zscore(key.as_ptr(), member("x").as_ptr(), &mut score), 0);
+        assert_eq!(cache_zrank(key.as_ptr(), member("a").as_ptr(), 0), 1);
+        assert_eq!(cache_zrank(key.as_ptr(), member("x").as_ptr(), 0), CacheStatus::NotFound as i64);
+        assert_eq!(cache_zcard(key.as_ptr()), 2);
+
+        let mut len = 0;
+        let ptr = cache_zrangebyscore(key.as_ptr(), 2.0, f64::INFINITY, ZRANGE_MIN_EXCLUSIVE | ZRANGE_REV, 0, -1, &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_scored(&scored(&[("a", 3.0)])));
+
+        let ptr = cache_zrange_withscores(key.as_ptr(), 0, -1, 1, &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_scored(&scored(&[("a", 3.0), ("b", 2.0)])));
+
+        let lex = CString::new("lex").unwrap();
+        cmd_zadd(db(), "lex", vec![(0.0, b"b".to_vec()), (0.0, b"a".to_vec())]).unwrap();
+        let ptr = cache_zrangebylex(lex.as_ptr(), member("[a").as_ptr(), member("(b").as_ptr(), 0, 0, -1, &mut len);
+        let blob = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        assert_eq!(blob, pack_items([&b"a"[..]].into_iter()));
+        assert!(cache_zrangebylex(lex.as_ptr(), member("a").as_ptr(), member("+").as_ptr(), 0, 0, -1, &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::InvalidArgument as i32);
+
+        assert_eq!(cache_zrem(key.as_ptr(), member("a").as_ptr()), 1);
+        assert_eq!(cache_zrem(key.as_ptr(), member("a").as_ptr()), 0);
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_notifications_clear() {
-    let mut q = NOTIFY_QUEUE.lock().unwrap();
-    q.clear();
-}
+    #[test]
+    fn multi_value_pushes_take_packed_items() {
+        let _guard = test_reset();
+        let key = CString::new("l").unwrap();
+        let packed = pack_items([&b"a"[..], b"b"].into_iter());
+        assert_eq!(cache_rpush_multi(key.as_ptr(), packed.as_ptr(), packed.len()), 2);
+        assert_eq!(cache_lpush_multi(key.as_ptr(), packed.as_ptr(), packed.len()), 4);
+        assert_eq!(list("l"), ["b", "a", "a", "b"]);
+        assert_eq!(cache_llen(key.as_ptr()), 4);
+        assert_eq!(unpack_items(&packed), Ok(items(&["a", "b"])));
+
+        let truncated = &packed[..packed.len() - 1];
+        assert_eq!(cache_rpush_multi(key.as_ptr(), truncated.as_ptr(), truncated.len()), CacheStatus::InvalidArgument as i64);
+        let pivot = b"nope";
+        assert_eq!(cache_linsert(key.as_ptr(), 1, pivot.as_ptr(), pivot.len(), pivot.as_ptr(), pivot.len()), -3);
+        assert_eq!(cache_llen(key.as_ptr()), 4);
+    }
 
-// --- Phase3: Streams ---
+    #[test]
+    fn writes_against_another_type_fail_with_wrongtype() {
+        let _guard = test_reset();
+        cmd_hset(db(), "h", vec![(b"f".to_vec(), b"v".to_vec())]).unwrap();
+        cmd_set(db(), "s", b"v".to_vec()).unwrap();
+
+        assert_eq!(cmd_lpush(db(), "h", vec![b"x".to_vec()]), Err(CacheError::WrongType));
+        assert_eq!(cmd_sadd(db(), "h", vec![b"x".to_vec()]), Err(CacheError::WrongType));
+        assert_eq!(cmd_zadd(db(), "h", vec![(1.0, b"x".to_vec())]), Err(CacheError::WrongType));
+        assert_eq!(cmd_xadd(db(), "h", b"x".to_vec()), Err(CacheError::WrongType));
+        assert_eq!(cmd_hset(db(), "s", vec![(b"f".to_vec(), b"x".to_vec())]), Err(CacheError::WrongType));
+        assert_eq!(json_set(db(), "h", "$.a", b"1"), Err(CacheError::WrongType));
+        // The values are untouched.
+        assert_eq!(cmd_hgetall(db(), "h").unwrap(), Some(vec![(b"f".to_vec(), b"v".to_vec())]));
+        assert_eq!(cmd_get(db(), "s").unwrap().as_deref(), Some(&b"v".to_vec()));
+
+        // Opting in to overwrites replaces the old value, and the AOF replays the same way.
+        let path = temp_path("overwrite.aof");
+        assert_eq!(cache_aof_enable(c_path(&path).as_ptr()), 1);
+        cache_set_type_overwrite(1);
+        assert_eq!(cmd_lpush(db(), "h", vec![b"x".to_vec()]), Ok(1));
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(c_path(&path).as_ptr()), 1);
+        assert_eq!(cmd_lrange(db(), "h", 0, -1).unwrap(), Some(vec![b"x".to_vec()]));
+        let _ = std::fs::remove_file(&path);
+    }
 
-#[no_mangle]
-pub extern "C" fn cache_xadd(key: *const c_char, payload: *const c_uchar, len: usize) -> u64 {
-    let key_str = unsafe { to_string(key) };
-    if key_str.is_empty() {
-        return 0;
+    #[test]
+    fn replay_skips_records_against_another_type() {
+        let _guard = test_reset();
+        // Written by a version that overwrote mismatched keys.
+        let mut log = Vec::new();
+        aof_encode_hset(&mut log, "k", b"f", b"1");
+        aof_encode_push(&mut log, "k", true, b"x");
+        aof_encode_zadd(&mut log, "k", 1.0, b"m");
+        aof_encode_hset(&mut log, "k", b"g", b"2");
+        load_records("replay_wrongtype.aof", &log);
+
+        let fields = cmd_hgetall(db(), "k").unwrap().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(cmd_lrange(db(), "k", 0, -1), Err(CacheError::WrongType));
     }
-    let payload_vec = unsafe { to_bytes(payload, len) };
 
-    let id = STREAM_ID.fetch_add(1, Ordering::Relaxed);
+    fn eval(script: &str) -> Vec<u8> {
+        let script = CString::new(script).unwrap();
+        let mut len = 0;
+        let ptr = cache_eval(script.as_ptr(), &mut len);
+        if ptr.is_null() {
+            return Vec::new();
+        }
+        let out = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        out
+    }
 
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        // create fresh
+    #[test]
+    fn volatile_policies_reject_new_keys_without_a_ttl_victim() {
+        for policy in [eviction::POLICY_VOLATILE_LRU, eviction::POLICY_VOLATILE_TTL] {
+            let _guard = test_reset();
+            cache_set_max_items(2);
+            cache_set_eviction_policy(policy as i32);
+            cmd_set(db(), "a", b"1".to_vec()).unwrap();
+            cmd_set(db(), "b", b"2".to_vec()).unwrap();
+
+            assert_eq!(cmd_set(db(), "c", b"3".to_vec()), Err(CacheError::OutOfMemory));
+            assert_eq!(cmd_hset(db(), "h", vec![(b"f".to_vec(), b"v".to_vec())]), Err(CacheError::OutOfMemory));
+            // Overwrites need no room.
+            assert_eq!(cmd_set(db(), "a", b"new".to_vec()), Ok(()));
+
+            assert!(cmd_expire(db(), "b", 60_000));
+            assert_eq!(cmd_set(db(), "c", b"3".to_vec()), Ok(()));
+            assert!(cmd_get(db(), "b").unwrap().is_none());
+            assert_eq!(cache_len(), 2);
+        }
     }
 
-    let mut entry = state
-        .map
-        .pop(&key_str)
-        .unwrap_or(Entry { value: Value::Stream(StreamData { entries: Vec::new() }), expires_at_ms: None });
+    #[test]
+    fn volatile_policies_reach_ttl_keys_behind_keys_without_one() {
+        for policy in [eviction::POLICY_VOLATILE_LRU, eviction::POLICY_VOLATILE_TTL] {
+            let _guard = test_reset();
+            cache_set_max_items(2000);
+            cache_set_eviction_policy(policy as i32);
+            // Every shard's LRU end is far more than a sample of keys without a TTL.
+            for i in 0..1900 {
+                cmd_set(db(), format!("plain{}", i).as_str(), b"v".to_vec()).unwrap();
+            }
+            for i in 0..100 {
+                cmd_set_with_ttl(db(), format!("ttl{}", i).as_str(), b"v".to_vec(), 60_000).unwrap();
+            }
 
-    match &mut entry.value {
-        Value::Stream(stream) => {
-            stream.entries.push(StreamEntry { id, payload: payload_vec.clone() });
+            for i in 0..100 {
+                assert_eq!(cmd_set(db(), format!("new{}", i).as_str(), b"v".to_vec()), Ok(()));
+            }
+            assert_eq!(cache_len(), 2000);
+            assert!((0..1900).all(|i| cmd_get(db(), format!("plain{}", i).as_str()).unwrap().is_some()));
+            assert!((0..100).all(|i| cmd_get(db(), format!("ttl{}", i).as_str()).unwrap().is_none()));
+            assert_eq!(cmd_set(db(), "one_more", b"v".to_vec()), Err(CacheError::OutOfMemory));
         }
-        _ => {
-            entry.value = Value::Stream(StreamData { entries: vec![StreamEntry { id, payload: payload_vec.clone() }] });
+    }
+
+    fn notifications() -> Vec<(u8, String)> {
+        db().notify_queue.lock().unwrap().drain(..).map(|ev| (ev.kind, ev.key)).collect()
+    }
+
+    #[test]
+    fn used_memory_follows_writes_and_removals() {
+        let _guard = test_reset();
+        cache_notifications_clear();
+        assert_eq!(cache_used_memory(), 0);
+        cmd_set(db(), "a", vec![b'x'; 100]).unwrap();
+        let small = cache_used_memory();
+        assert!(small >= 100);
+        cmd_set(db(), "a", vec![b'x'; 1000]).unwrap();
+        assert_eq!(cache_used_memory(), small + 900);
+        cmd_set(db(), "a", vec![b'x'; 100]).unwrap();
+        assert_eq!(cache_used_memory(), small);
+        assert!(cmd_remove(db(), "a"));
+        assert_eq!(cache_used_memory(), 0);
+
+        cmd_set_with_ttl(db(), "t", vec![b'x'; 100], 1).unwrap();
+        assert_eq!(cache_used_memory(), small);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(cmd_get(db(), "t"), Ok(None));
+        // Reads leave the expired key in place; the sweep frees it.
+        assert_eq!(cache_used_memory(), small);
+        for shard in &db().cache.shards {
+            sweep_expired(db(), shard);
         }
+        assert_eq!(cache_used_memory(), 0);
+        assert_eq!(notifications(), vec![(NOTIFY_KIND_EXPIRED, "t".to_string())]);
     }
 
-    put_entry_with_lru(&mut state, key_str.clone(), entry);
-    aof_write_xadd(&key_str, id, &payload_vec);
-    id
-}
+    #[test]
+    fn memory_limit_evicts_or_rejects_by_policy() {
+        for policy in [eviction::POLICY_ALLKEYS_LRU, eviction::POLICY_ALLKEYS_LFU] {
+            let _guard = test_reset();
+            cache_set_eviction_policy(policy as i32);
+            cache_notifications_clear();
+            for i in 0..10 {
+                cmd_set(db(), format!("k{i}").as_str(), vec![b'x'; 100]).unwrap();
+            }
+            let per_key = cache_used_memory() / 10;
+            let limit = per_key * 5 + per_key / 2;
+            // Lowering the limit evicts right away.
+            cache_set_max_memory_bytes(limit);
+            assert!(cache_used_memory() <= limit);
+            assert_eq!(cache_len(), 5);
+            let mut evicted: Vec<String> = notifications()
+                .into_iter()
+                .map(|(kind, key)| {
+                    assert_eq!(kind, NOTIFY_KIND_EVICTED);
+                    key
+                })
+                .collect();
+            evicted.sort();
+            let mut missing: Vec<String> =
+                (0..10).map(|i| format!("k{i}")).filter(|k| cmd_get(db(), k.as_str()).unwrap().is_none()).collect();
+            missing.sort();
+            assert_eq!(evicted, missing);
+
+            // So does every write that goes over it.
+            for i in 10..20 {
+                cmd_set(db(), format!("k{i}").as_str(), vec![b'x'; 100]).unwrap();
+                assert!(cache_used_memory() <= limit);
+            }
+            assert_eq!(notifications().len(), 10);
+            cache_set_max_memory_bytes(0);
+        }
 
-#[no_mangle]
-pub extern "C" fn cache_xrange(key: *const c_char, start_id: u64, end_id: u64, out_len: *mut usize) -> *mut c_uchar {
-    let key_str = unsafe { to_string(key) };
-    let mut state = CACHE.write().unwrap();
-    if maybe_remove_if_expired(&mut state, &key_str) {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
+        let _guard = test_reset();
+        cache_set_eviction_policy(eviction::POLICY_NOEVICTION as i32);
+        cache_notifications_clear();
+        cmd_set(db(), "a", vec![b'x'; 100]).unwrap();
+        cmd_set(db(), "b", vec![b'x'; 100]).unwrap();
+        let used = cache_used_memory();
+        // Nothing is evicted to meet a lower limit; writes are refused while usage is over it.
+        cache_set_max_memory_bytes(used - 1);
+        assert_eq!(cache_len(), 2);
+        assert_eq!(cmd_set(db(), "c", vec![b'x'; 100]), Err(CacheError::OutOfMemory));
+        let key = CString::new("c").unwrap();
+        assert_eq!(cache_set(key.as_ptr(), [b'x'; 100].as_ptr(), 100), CacheStatus::OutOfMemory as i32);
+        assert_eq!(cmd_set(db(), "a", vec![b'x'; 200]), Err(CacheError::OutOfMemory));
+        assert_eq!(cache_used_memory(), used);
+        assert!(notifications().is_empty());
+        // Freeing memory lets writes through again.
+        assert!(cmd_remove(db(), "b"));
+        assert_eq!(cmd_set(db(), "c", vec![b'x'; 100]), Ok(()));
+        assert_eq!(cache_len(), 2);
+        cache_set_max_memory_bytes(0);
     }
 
-    let Some(entry) = state.map.get(&key_str) else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+    #[test]
+    fn eval_json_set_reports_rejected_writes() {
+        let _guard = test_reset();
+        assert_eq!(eval(r#"JSON.SET doc $ {"a":1}"#), b"1");
+        cache_set_max_items(1);
+        cache_set_eviction_policy(eviction::POLICY_NOEVICTION as i32);
+        assert_eq!(eval(r#"JSON.SET other $ {"a":1}"#), b"0");
+        assert!(cmd_get(db(), "other").unwrap().is_none());
+    }
 
-    let Value::Stream(stream) = &entry.value else {
-        unsafe { *out_len = 0 };
-        return std::ptr::null_mut();
-    };
+    #[test]
+    fn concurrent_writers_stay_near_the_item_limit() {
+        let _guard = test_reset();
+        const THREADS: usize = 8;
+        cache_set_max_items(64);
+        let writers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    for i in 0..2_000 {
+                        cmd_set(db(), &format!("w{}:{}", t, i), b"v".to_vec()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for w in writers {
+            w.join().unwrap();
+        }
+        // A writer may still add its key between another writer's check and insert, but
+        // overshoot no longer accumulates.
+        assert!(cache_len() <= 64 + THREADS, "len {}", cache_len());
+    }
 
-    let mut items: Vec<&StreamEntry> = stream
-        .entries
-        .iter()
-        .filter(|e| e.id >= start_id && e.id <= end_id)
-        .collect();
-    items.sort_by_key(|e| e.id);
+    fn instance_get(db: *const Instance, key: &str) -> Option<Vec<u8>> {
+        let key = CString::new(key).unwrap();
+        let mut len = 0usize;
+        let ptr = cache_instance_get(db, key.as_ptr(), &mut len);
+        if ptr.is_null() {
+            return None;
+        }
+        let out = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+        cache_free(ptr, len);
+        Some(out)
+    }
 
-    let mut flat = Vec::new();
-    flat.extend_from_slice(&(items.len() as u32).to_le_bytes());
-    for e in items {
-        flat.extend_from_slice(&e.id.to_le_bytes());
-        flat.extend_from_slice(&(e.payload.len() as u32).to_le_bytes());
-        flat.extend_from_slice(&e.payload);
+    fn instance_set(db: *const Instance, key: &str, val: &[u8]) -> i32 {
+        let key = CString::new(key).unwrap();
+        cache_instance_set(db, key.as_ptr(), val.as_ptr(), val.len())
+    }
+
+    #[test]
+    fn instances_are_isolated() {
+        let _guard = test_reset();
+        let config = |max_items, eviction_policy| CacheConfig {
+            max_items,
+            max_memory_bytes: 0,
+            eviction_policy,
+            aof_path: std::ptr::null(),
+        };
+        let small = cache_instance_create(&config(2, eviction::POLICY_NOEVICTION as i32));
+        let large = cache_instance_create(&config(10, 0));
+        assert!(!small.is_null() && !large.is_null());
+        assert!(cache_instance_create(&config(10, 99)).is_null());
+
+        for db in [small, large] {
+            assert_eq!(instance_set(db, "a", b"1"), 1);
+            assert_eq!(instance_set(db, "b", b"2"), 1);
+        }
+        // Each instance applies its own limit and policy.
+        assert_eq!(instance_set(small, "c", b"3"), CacheStatus::OutOfMemory as i32);
+        assert_eq!(instance_set(large, "c", b"3"), 1);
+        assert_eq!(cache_instance_len(small), 2);
+        assert_eq!(cache_instance_len(large), 3);
+        assert_eq!(cache_instance_get_max_items(small), 2);
+        assert_eq!(cache_instance_get_eviction_policy(large), 0);
+
+        assert_eq!(instance_set(large, "a", b"other"), 1);
+        assert_eq!(instance_get(small, "a").as_deref(), Some(&b"1"[..]));
+        assert_eq!(instance_get(large, "a").as_deref(), Some(&b"other"[..]));
+        assert!(cmd_get(db(), "a").unwrap().is_none());
+        assert_eq!(cache_len(), 0);
+
+        // Pub/sub channels are per instance too.
+        let channel = CString::new("news").unwrap();
+        let sub = cache_instance_pubsub_subscribe(small, channel.as_ptr());
+        assert_eq!(cache_instance_pubsub_publish(large, channel.as_ptr(), b"x".as_ptr(), 1), 0);
+        assert_eq!(cache_instance_pubsub_publish(small, channel.as_ptr(), b"x".as_ptr(), 1), 1);
+        cache_instance_pubsub_unsubscribe(small, sub);
+
+        assert_eq!(cache_instance_destroy(small), 1);
+        assert_eq!(cache_instance_destroy(large), 1);
+        assert_eq!(cache_instance_destroy(default_instance()), CacheStatus::InvalidArgument as i32);
+        assert_eq!(cache_instance_destroy(std::ptr::null()), CacheStatus::InvalidHandle as i32);
+        assert_eq!(instance_set(std::ptr::null(), "a", b"1"), CacheStatus::InvalidHandle as i32);
+        assert_eq!(instance_get(std::ptr::null(), "a"), None);
+        assert_eq!(cache_last_status(), CacheStatus::InvalidHandle as i32);
+    }
+
+    fn last_error() -> Option<String> {
+        let mut len = 0usize;
+        let ptr = cache_last_error(&mut len);
+        if ptr.is_null() {
+            return None;
+        }
+        let msg = String::from_utf8(unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()).unwrap();
+        cache_free(ptr, len);
+        Some(msg)
+    }
+
+    #[test]
+    fn failures_report_a_status_and_message() {
+        let _guard = test_reset();
+        cmd_hset(db(), "h", vec![(b"f".to_vec(), b"v".to_vec())]).unwrap();
+        let h = CString::new("h").unwrap();
+        let missing = CString::new("missing").unwrap();
+        let mut len = 0usize;
+
+        // Reading a hash as a string is a type error, not a miss.
+        assert!(cache_get(h.as_ptr(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::WrongType as i32);
+        assert!(last_error().unwrap().starts_with("WRONGTYPE "));
+        // Reading the message does not clear it.
+        assert_eq!(cache_last_status(), CacheStatus::WrongType as i32);
+        assert_eq!(cache_sismember(h.as_ptr(), b"v".as_ptr(), 1), CacheStatus::WrongType as i32);
+
+        assert!(cache_get(missing.as_ptr(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::NotFound as i32);
+        assert_eq!(cache_expire(missing.as_ptr(), 1_000), CacheStatus::NotFound as i32);
+
+        // The next successful call clears the error.
+        assert_eq!(cache_set(missing.as_ptr(), b"v".as_ptr(), 1), 1);
+        assert_eq!(cache_last_status(), CacheStatus::Ok as i32);
+        assert_eq!(last_error(), None);
+
+        let dir = CString::new(std::env::temp_dir().join("no-such-dir").join("x.aof").to_str().unwrap()).unwrap();
+        assert_eq!(cache_aof_load(dir.as_ptr()), CacheStatus::Io as i32);
+        assert!(last_error().unwrap().starts_with("ERR I/O error"));
+        assert_eq!(cache_set_eviction_policy(42), CacheStatus::InvalidArgument as i32);
+        let path = CString::new("$.[").unwrap();
+        assert!(cache_json_get(h.as_ptr(), path.as_ptr(), &mut len).is_null());
+        assert_eq!(cache_last_status(), CacheStatus::InvalidArgument as i32);
+    }
+
+    #[test]
+    fn instances_keep_their_own_aof() {
+        let _guard = test_reset();
+        let path = temp_path("instance.aof");
+        let cpath = c_path(&path);
+        let config = CacheConfig { max_items: 0, max_memory_bytes: 0, eviction_policy: 0, aof_path: cpath.as_ptr() };
+
+        let db = cache_instance_create(&config);
+        assert_eq!(instance_set(db, "k", b"v"), 1);
+        assert_eq!(cache_instance_expire(db, CString::new("k").unwrap().as_ptr(), 60_000), 1);
+        cmd_set(crate::db(), "default-only", b"x".to_vec()).unwrap();
+        // Destroying flushes the log; a new instance on the same file replays it.
+        assert_eq!(cache_instance_destroy(db), 1);
+
+        let db = cache_instance_create(&config);
+        assert!(!db.is_null());
+        assert_eq!(instance_get(db, "k").as_deref(), Some(&b"v"[..]));
+        assert!(cache_instance_ttl(db, CString::new("k").unwrap().as_ptr()) > 0);
+        assert_eq!(instance_get(db, "default-only"), None);
+        assert_eq!(cache_instance_destroy(db), 1);
+        let _ = std::fs::remove_file(&path);
     }
-    prepare_return(flat, out_len)
 }
@@ -0,0 +1,2272 @@
+// RESP2/RESP3 server: exposes a cache instance (the default one unless started through
+// cache_instance_server_start) over TCP and Unix sockets, so redis-cli and regular Redis
+// clients can share the keyspace with the FFI.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::blocking::{blocking_pop, blocking_stream_read, blocking_xread};
+use crate::zset::{LexBound, ScoreBound, ScoredMembers};
+use crate::{
+    aof_rewrite, cmd_append, cmd_clear_all, cmd_del, cmd_expire, cmd_get, cmd_getdel, cmd_getrange, cmd_getset,
+    cmd_hdel, cmd_hexists, cmd_hget, cmd_hgetall, cmd_hincrby, cmd_hincrbyfloat, cmd_hkeys, cmd_hlen, cmd_hmget,
+    cmd_hset, cmd_hsetnx, cmd_hvals, cmd_incrby, cmd_incrbyfloat, cmd_lindex, cmd_linsert, cmd_llen, cmd_lmove,
+    cmd_lpush, cmd_lrange, cmd_lrem, cmd_lset, cmd_ltrim, cmd_mget, cmd_mset, cmd_msetnx, cmd_pop, cmd_publish,
+    cmd_remove, cmd_rpush, cmd_sadd, cmd_scard, cmd_set_op, cmd_set_op_store, cmd_set_with, cmd_setrange, cmd_sismember,
+    cmd_smembers, cmd_smismember, cmd_spop, cmd_srandmember, cmd_srem, cmd_strlen, cmd_ttl, cmd_xack, cmd_xadd_with,
+    cmd_xautoclaim, cmd_xclaim, cmd_xdel, cmd_xgroup_create, cmd_xgroup_destroy, cmd_xlen, cmd_xpending,
+    cmd_xpending_summary, cmd_xrange, cmd_xread, cmd_xreadgroup, cmd_xtrim, cmd_zadd_flags, cmd_zcard, cmd_zincrby,
+    cmd_zrange, cmd_zrangebylex, cmd_zrangebyscore, cmd_zrank, cmd_zrem, cmd_zscore, eviction, now_ms, pubsub_subscribe,
+    pubsub_unsubscribe, pubsub_wait, stream_last_id, CacheError, GroupItems, Instance, SetOp, SetOptions, StreamFields,
+    StreamId, StreamItems, StreamTrim, XAddId, ZAddFlags, DEFAULT_INSTANCE, STREAM_PAYLOAD_FIELD,
+};
+
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+const MAX_ARGS: usize = 1024 * 1024;
+// Total size of one command's bulk strings (Redis' client-query-buffer-limit).
+const MAX_COMMAND_LEN: usize = 1024 * 1024 * 1024;
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+// --- Transport ---
+
+trait Stream: Read + Write + Send + 'static {
+    fn try_clone_boxed(&self) -> io::Result<Box<dyn Stream>>;
+    // True once the peer has closed the connection. Only asked while a command blocks, when
+    // nothing else reads from the connection.
+    fn peer_closed(&self) -> bool;
+}
+
+impl Stream for TcpStream {
+    fn try_clone_boxed(&self) -> io::Result<Box<dyn Stream>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    #[cfg(unix)]
+    fn peer_closed(&self) -> bool {
+        fd_peer_closed(std::os::unix::io::AsRawFd::as_raw_fd(self))
+    }
+
+    #[cfg(not(unix))]
+    fn peer_closed(&self) -> bool {
+        if self.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let closed = matches!(self.peek(&mut [0u8; 1]), Ok(0));
+        let _ = self.set_nonblocking(false);
+        closed
+    }
+}
+
+#[cfg(unix)]
+impl Stream for std::os::unix::net::UnixStream {
+    fn try_clone_boxed(&self) -> io::Result<Box<dyn Stream>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn peer_closed(&self) -> bool {
+        fd_peer_closed(std::os::unix::io::AsRawFd::as_raw_fd(self))
+    }
+}
+
+// A non-blocking peek: 0 bytes means the peer sent EOF; pending data or "would block" do not.
+#[cfg(unix)]
+fn fd_peer_closed(fd: std::os::unix::io::RawFd) -> bool {
+    let mut byte = 0u8;
+    let n = unsafe { libc::recv(fd, (&mut byte as *mut u8).cast(), 1, libc::MSG_PEEK | libc::MSG_DONTWAIT) };
+    n == 0
+}
+
+/// Binds `addr` and serves the default instance on the calling thread until the listener fails.
+pub fn serve_tcp(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    accept_tcp(listener, DEFAULT_INSTANCE.clone());
+    Ok(())
+}
+
+/// Binds `addr` and serves the default instance from a background thread; returns the bound address.
+pub fn spawn_tcp(addr: &str) -> io::Result<SocketAddr> {
+    spawn_tcp_for(DEFAULT_INSTANCE.clone(), addr)
+}
+
+pub(crate) fn spawn_tcp_for(db: Arc<Instance>, addr: &str) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    std::thread::spawn(move || accept_tcp(listener, db));
+    Ok(local)
+}
+
+fn accept_tcp(listener: TcpListener, db: Arc<Instance>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue; };
+        let _ = stream.set_nodelay(true);
+        let db = db.clone();
+        std::thread::spawn(move || handle_client(Box::new(stream), db));
+    }
+}
+
+/// Binds a Unix socket at `path` (replacing a stale socket file) and serves the default
+/// instance on the calling thread until the listener fails.
+#[cfg(unix)]
+pub fn serve_unix(path: &str) -> io::Result<()> {
+    let listener = bind_unix(path)?;
+    accept_unix(listener, DEFAULT_INSTANCE.clone());
+    Ok(())
+}
+
+/// Binds a Unix socket at `path` and serves the default instance from a background thread.
+#[cfg(unix)]
+pub fn spawn_unix(path: &str) -> io::Result<()> {
+    spawn_unix_for(DEFAULT_INSTANCE.clone(), path)
+}
+
+#[cfg(unix)]
+pub(crate) fn spawn_unix_for(db: Arc<Instance>, path: &str) -> io::Result<()> {
+    let listener = bind_unix(path)?;
+    std::thread::spawn(move || accept_unix(listener, db));
+    Ok(())
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str) -> io::Result<std::os::unix::net::UnixListener> {
+    let _ = std::fs::remove_file(path);
+    std::os::unix::net::UnixListener::bind(path)
+}
+
+#[cfg(unix)]
+fn accept_unix(listener: std::os::unix::net::UnixListener, db: Arc<Instance>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue; };
+        let db = db.clone();
+        std::thread::spawn(move || handle_client(Box::new(stream), db));
+    }
+}
+
+// --- Protocol ---
+
+enum Reply {
+    Simple(&'static str),
+    Error(String),
+    Int(i64),
+    Bulk(Vec<u8>),
+    Nil,
+    // RESP2 encodes a missing array (blocking timeouts) as *-1.
+    NilArray,
+    Double(f64),
+    Array(Vec<Reply>),
+    Map(Vec<(Reply, Reply)>),
+    Push(Vec<Reply>),
+}
+
+impl Reply {
+    fn ok() -> Reply {
+        Reply::Simple("OK")
+    }
+
+    fn err(msg: impl Into<String>) -> Reply {
+        Reply::Error(msg.into())
+    }
+
+    fn bulk_str(s: &str) -> Reply {
+        Reply::Bulk(s.as_bytes().to_vec())
+    }
+
+    fn encode(&self, proto: u8, out: &mut Vec<u8>) {
+        match self {
+            Reply::Simple(s) => {
+                out.push(b'+');
+                out.extend_from_slice(s.as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Error(msg) => {
+                out.push(b'-');
+                out.extend_from_slice(msg.replace(['\r', '\n'], " ").as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Int(n) => {
+                out.extend_from_slice(format!(":{}\r\n", n).as_bytes());
+            }
+            Reply::Bulk(b) => {
+                out.extend_from_slice(format!("${}\r\n", b.len()).as_bytes());
+                out.extend_from_slice(b);
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Nil => {
+                out.extend_from_slice(if proto >= 3 { b"_\r\n" } else { b"$-1\r\n" });
+            }
+            Reply::NilArray => {
+                out.extend_from_slice(if proto >= 3 { b"_\r\n" } else { b"*-1\r\n" });
+            }
+            Reply::Double(d) => {
+                let s = format_double(*d);
+                if proto >= 3 {
+                    out.extend_from_slice(format!(",{}\r\n", s).as_bytes());
+                } else {
+                    Reply::Bulk(s.into_bytes()).encode(proto, out);
+                }
+            }
+            Reply::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.encode(proto, out);
+                }
+            }
+            Reply::Map(pairs) => {
+                if proto >= 3 {
+                    out.extend_from_slice(format!("%{}\r\n", pairs.len()).as_bytes());
+                } else {
+                    out.extend_from_slice(format!("*{}\r\n", pairs.len() * 2).as_bytes());
+                }
+                for (k, v) in pairs {
+                    k.encode(proto, out);
+                    v.encode(proto, out);
+                }
+            }
+            Reply::Push(items) => {
+                let marker = if proto >= 3 { '>' } else { '*' };
+                out.extend_from_slice(format!("{}{}\r\n", marker, items.len()).as_bytes());
+                for item in items {
+                    item.encode(proto, out);
+                }
+            }
+        }
+    }
+}
+
+fn format_double(d: f64) -> String {
+    if d.is_infinite() {
+        return if d > 0.0 { "inf".to_string() } else { "-inf".to_string() };
+    }
+    format!("{}", d)
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Protocol error: {}", msg))
+}
+
+fn read_line(r: &mut impl BufRead, limit: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    let n = r.by_ref().take(limit as u64 + 2).read_until(b'\n', &mut line)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(protocol_error("line too long or truncated"));
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn parse_len(line: &[u8]) -> io::Result<i64> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| protocol_error("invalid length"))
+}
+
+// Reads one command as an argument vector. Returns None on a clean EOF.
+fn read_command(r: &mut impl BufRead) -> io::Result<Option<Vec<Vec<u8>>>> {
+    read_command_within(r, MAX_COMMAND_LEN)
+}
+
+// Buffers grow with the bytes actually received, so declared lengths alone cannot make the
+// server allocate; a command whose bulk strings add up to more than `max_len` is refused.
+fn read_command_within(r: &mut impl BufRead, max_len: usize) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let Some(line) = read_line(r, MAX_INLINE_LEN)? else {
+        return Ok(None);
+    };
+
+    if line.first() != Some(&b'*') {
+        // Inline command (telnet style).
+        let args = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|a| !a.is_empty())
+            .map(|a| a.to_vec())
+            .collect();
+        return Ok(Some(args));
+    }
+
+    let count = parse_len(&line[1..])?;
+    if count <= 0 {
+        return Ok(Some(Vec::new()));
+    }
+    if count as usize > MAX_ARGS {
+        return Err(protocol_error("invalid multibulk length"));
+    }
+
+    let mut args = Vec::with_capacity((count as usize).min(1024));
+    let mut total = 0usize;
+    for _ in 0..count {
+        let Some(header) = read_line(r, MAX_INLINE_LEN)? else {
+            return Ok(None);
+        };
+        if header.first() != Some(&b'$') {
+            return Err(protocol_error("expected '$'"));
+        }
+        let len = parse_len(&header[1..])?;
+        if len < 0 || len as usize > MAX_BULK_LEN {
+            return Err(protocol_error("invalid bulk length"));
+        }
+        total += len as usize;
+        if total > max_len {
+            return Err(protocol_error("command too large"));
+        }
+        let mut data = Vec::new();
+        r.by_ref().take(len as u64 + 2).read_to_end(&mut data)?;
+        if data.len() < len as usize + 2 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if &data[len as usize..] != b"\r\n" {
+            return Err(protocol_error("bulk string not terminated by CRLF"));
+        }
+        data.truncate(len as usize);
+        args.push(data);
+    }
+    Ok(Some(args))
+}
+
+// --- Argument helpers ---
+
+// Keys, values, hash fields and sorted-set members are taken as raw bytes. Only stream group and
+// consumer names and pub/sub channels are strings; bytes that are not valid UTF-8 are rejected
+// there rather than replaced (distinct names must not collapse into one).
+fn arg_str(arg: &[u8]) -> Result<String, Reply> {
+    String::from_utf8(arg.to_vec()).map_err(|_| Reply::err("ERR invalid argument: names must be valid UTF-8"))
+}
+
+fn arg_i64(arg: &[u8]) -> Result<i64, Reply> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| Reply::err("ERR value is not an integer or out of range"))
+}
+
+fn arg_f64(arg: &[u8]) -> Result<f64, Reply> {
+    let s = std::str::from_utf8(arg).map_err(|_| Reply::err("ERR value is not a valid float"))?;
+    let v = match s.to_ascii_lowercase().as_str() {
+        "inf" | "+inf" => f64::INFINITY,
+        "-inf" => f64::NEG_INFINITY,
+        _ => s.parse::<f64>().map_err(|_| Reply::err("ERR value is not a valid float"))?,
+    };
+    if v.is_nan() {
+        return Err(Reply::err("ERR value is not a valid float"));
+    }
+    Ok(v)
+}
+
+// How ZRANGE interprets its start and stop arguments.
+#[derive(Clone, Copy, PartialEq)]
+enum ZRangeBy {
+    Rank,
+    Score,
+    Lex,
+}
+
+// Score range end: `1.5`, `(1.5` (exclusive), `-inf` or `+inf`.
+fn arg_score_bound(arg: &[u8]) -> Result<ScoreBound, Reply> {
+    let (value, exclusive) = match arg.strip_prefix(b"(") {
+        Some(rest) => (rest, true),
+        None => (arg, false),
+    };
+    let value = arg_f64(value).map_err(|_| Reply::err("ERR min or max is not a float"))?;
+    Ok(ScoreBound { value, exclusive })
+}
+
+fn arg_lex_bound(arg: &[u8]) -> Result<LexBound, Reply> {
+    LexBound::parse(arg).ok_or_else(|| Reply::err("ERR min or max not valid string range item"))
+}
+
+// Blocking timeout in seconds (fractions allowed); 0 blocks indefinitely.
+fn arg_block_timeout(arg: &[u8]) -> Result<Option<Duration>, Reply> {
+    let secs = arg_f64(arg).map_err(|_| Reply::err("ERR timeout is not a float or out of range"))?;
+    if secs < 0.0 {
+        return Err(Reply::err("ERR timeout is negative"));
+    }
+    if secs == 0.0 {
+        return Ok(None);
+    }
+    Duration::try_from_secs_f64(secs).map(Some).map_err(|_| Reply::err("ERR timeout is out of range"))
+}
+
+// SUNION / SINTER / SDIFF by name (STORE variants pass it without the suffix).
+fn set_op(cmd: &str) -> SetOp {
+    match cmd {
+        "SUNION" => SetOp::Union,
+        "SINTER" => SetOp::Inter,
+        _ => SetOp::Diff,
+    }
+}
+
+// LEFT / RIGHT of LMOVE; true for the head.
+fn arg_side(arg: &[u8]) -> Result<bool, Reply> {
+    match arg.to_ascii_uppercase().as_slice() {
+        b"LEFT" => Ok(true),
+        b"RIGHT" => Ok(false),
+        _ => Err(Reply::err("ERR syntax error")),
+    }
+}
+
+fn arg_ttl(arg: &[u8], unit_ms: u64) -> Result<u64, Reply> {
+    let n = arg_i64(arg)?;
+    if n <= 0 {
+        return Err(Reply::err("ERR invalid expire time in 'set' command"));
+    }
+    (n as u64)
+        .checked_mul(unit_ms)
+        .ok_or_else(|| Reply::err("ERR invalid expire time in 'set' command"))
+}
+
+// Stream ids render in Redis' `<ms>-<seq>` shape.
+fn format_stream_id(id: StreamId) -> Vec<u8> {
+    id.to_string().into_bytes()
+}
+
+fn invalid_stream_id() -> Reply {
+    Reply::err("ERR Invalid stream ID specified as stream command argument")
+}
+
+// An exact id, `<ms>-<seq>`, or `<ms>` with sequence `missing_seq`.
+fn parse_stream_id(arg: &[u8], missing_seq: u64) -> Result<StreamId, Reply> {
+    let s = std::str::from_utf8(arg).map_err(|_| invalid_stream_id())?;
+    let (ms, seq) = match s.split_once('-') {
+        Some((ms, seq)) => (ms, seq.parse::<u64>().map_err(|_| invalid_stream_id())?),
+        None => (s, missing_seq),
+    };
+    Ok(StreamId::new(ms.parse::<u64>().map_err(|_| invalid_stream_id())?, seq))
+}
+
+// A range end: `-`, `+`, an id or `(id`, which leaves the id out. A start without a sequence
+// number begins at `<ms>-0`, an end without one stops after every entry of `<ms>`.
+fn parse_stream_bound(arg: &[u8], is_start: bool) -> Result<StreamId, Reply> {
+    match arg {
+        b"-" => Ok(StreamId::MIN),
+        b"+" => Ok(StreamId::MAX),
+        _ => {
+            let (exclusive, arg) = match arg.strip_prefix(b"(") {
+                Some(rest) => (true, rest),
+                None => (false, arg),
+            };
+            let id = parse_stream_id(arg, if is_start { 0 } else { u64::MAX })?;
+            match (exclusive, is_start) {
+                (false, _) => Ok(id),
+                (true, true) => id.next().ok_or_else(|| Reply::err("ERR invalid start ID for the interval")),
+                (true, false) => id.prev().ok_or_else(|| Reply::err("ERR invalid end ID for the interval")),
+            }
+        }
+    }
+}
+
+// The id argument of XADD: `*`, `<ms>-*` or an explicit id.
+fn parse_xadd_id(arg: &[u8]) -> Result<XAddId, Reply> {
+    if arg == b"*" {
+        return Ok(XAddId::Auto);
+    }
+    if let Some(ms) = arg.strip_suffix(b"-*") {
+        let ms = std::str::from_utf8(ms).ok().and_then(|ms| ms.parse::<u64>().ok()).ok_or_else(invalid_stream_id)?;
+        return Ok(XAddId::AutoSeq(ms));
+    }
+    Ok(XAddId::Explicit(parse_stream_id(arg, 0)?))
+}
+
+// Parses `MAXLEN | MINID [= | ~] threshold [LIMIT count]` at `argv[*i]`, moving `i` past it;
+// None if no trim option starts there. `~` is accepted but trims exactly; its LIMIT caps how
+// many entries one call removes.
+fn parse_stream_trim(argv: &[Vec<u8>], i: &mut usize) -> Result<Option<(StreamTrim, usize)>, Reply> {
+    let Some(kind) = argv.get(*i).map(|a| a.to_ascii_uppercase()) else { return Ok(None); };
+    if kind != b"MAXLEN" && kind != b"MINID" {
+        return Ok(None);
+    }
+    *i += 1;
+    let mut approx = false;
+    match argv.get(*i).map(|a| a.as_slice()) {
+        Some(b"~") => {
+            approx = true;
+            *i += 1;
+        }
+        Some(b"=") => *i += 1,
+        _ => {}
+    }
+    let Some(threshold) = argv.get(*i) else { return Err(Reply::err("ERR syntax error")); };
+    let trim = if kind == b"MAXLEN" {
+        match arg_i64(threshold)? {
+            n if n < 0 => return Err(Reply::err("ERR The MAXLEN argument must be >= 0.")),
+            n => StreamTrim::MaxLen(n as u64),
+        }
+    } else {
+        StreamTrim::MinId(parse_stream_id(threshold, 0)?)
+    };
+    *i += 1;
+    let mut limit = usize::MAX;
+    if argv.get(*i).is_some_and(|a| a.eq_ignore_ascii_case(b"LIMIT")) {
+        if !approx {
+            return Err(Reply::err("ERR syntax error, LIMIT cannot be used without the special ~ option"));
+        }
+        let Some(count) = argv.get(*i + 1) else { return Err(Reply::err("ERR syntax error")); };
+        limit = match arg_i64(count)? {
+            c if c < 0 => return Err(Reply::err("ERR The LIMIT argument must be >= 0.")),
+            0 => usize::MAX,
+            c => c as usize,
+        };
+        *i += 2;
+    }
+    Ok(Some((trim, limit)))
+}
+
+// One entry: its id and flattened field/value pairs, nil for an entry deleted since it was
+// delivered.
+fn stream_entry(id: StreamId, fields: Option<StreamFields>) -> Reply {
+    let fields = match fields {
+        Some(fields) => Reply::Array(fields.into_iter().flat_map(|(n, v)| [Reply::Bulk(n), Reply::Bulk(v)]).collect()),
+        None => Reply::NilArray,
+    };
+    Reply::Array(vec![Reply::Bulk(format_stream_id(id)), fields])
+}
+
+fn stream_entries(items: StreamItems) -> Reply {
+    Reply::Array(items.into_iter().map(|(id, fields)| stream_entry(id, Some(fields))).collect())
+}
+
+fn group_entries(items: GroupItems) -> Reply {
+    Reply::Array(items.into_iter().map(|(id, fields)| stream_entry(id, fields)).collect())
+}
+
+fn stream_ids(ids: Vec<StreamId>) -> Reply {
+    Reply::Array(ids.into_iter().map(|id| Reply::Bulk(format_stream_id(id))).collect())
+}
+
+// XCLAIM / XAUTOCLAIM reply: the claimed entries, or just their ids with JUSTID.
+fn claimed_entries(items: StreamItems, justid: bool) -> Reply {
+    if justid {
+        return stream_ids(items.into_iter().map(|(id, _)| id).collect());
+    }
+    stream_entries(items)
+}
+
+// Minimum idle time in milliseconds (XCLAIM, XAUTOCLAIM, XPENDING IDLE).
+fn arg_idle(arg: &[u8]) -> Result<u64, Reply> {
+    match arg_i64(arg)? {
+        ms if ms < 0 => Err(Reply::err("ERR Invalid min-idle-time argument")),
+        ms => Ok(ms as u64),
+    }
+}
+
+impl From<CacheError> for Reply {
+    fn from(e: CacheError) -> Reply {
+        Reply::err(e.to_string())
+    }
+}
+
+fn wrong_args(cmd: &str) -> Reply {
+    Reply::err(format!("ERR wrong number of arguments for '{}' command", cmd.to_ascii_lowercase()))
+}
+
+// --- Connection ---
+
+type SharedWriter = Arc<Mutex<BufWriter<Box<dyn Stream>>>>;
+
+struct Client {
+    id: u64,
+    db: Arc<Instance>,
+    // Shared with the delivery thread, which encodes pushes for the current protocol.
+    proto: Arc<AtomicU8>,
+    writer: SharedWriter,
+    // channel -> pubsub subscription id
+    subs: Arc<Mutex<HashMap<String, u64>>>,
+    alive: Arc<AtomicBool>,
+    delivery_started: bool,
+    quit: bool,
+}
+
+fn handle_client(stream: Box<dyn Stream>, db: Arc<Instance>) {
+    let Ok(write_half) = stream.try_clone_boxed() else { return; };
+    let mut reader = BufReader::new(stream);
+    let mut client = Client {
+        id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+        db,
+        proto: Arc::new(AtomicU8::new(2)),
+        writer: Arc::new(Mutex::new(BufWriter::new(write_half))),
+        subs: Arc::new(Mutex::new(HashMap::new())),
+        alive: Arc::new(AtomicBool::new(true)),
+        delivery_started: false,
+        quit: false,
+    };
+
+    loop {
+        let args = match read_command(&mut reader) {
+            Ok(Some(args)) => args,
+            Ok(None) => break,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::InvalidData {
+                    let _ = client.send(&[Reply::err(format!("ERR {}", e))]);
+                }
+                break;
+            }
+        };
+        if args.is_empty() {
+            continue;
+        }
+
+        let replies = client.dispatch(&args);
+        if !replies.is_empty() && client.send(&replies).is_err() {
+            break;
+        }
+        if client.quit {
+            break;
+        }
+    }
+
+    client.alive.store(false, Ordering::Relaxed);
+    let subs = std::mem::take(&mut *client.subs.lock().unwrap());
+    for (_, sub_id) in subs {
+        pubsub_unsubscribe(&client.db, sub_id);
+    }
+}
+
+impl Client {
+    fn proto(&self) -> u8 {
+        self.proto.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, replies: &[Reply]) -> io::Result<()> {
+        let mut buf = Vec::new();
+        for r in replies {
+            r.encode(self.proto(), &mut buf);
+        }
+        let mut w = self.writer.lock().unwrap();
+        w.write_all(&buf)?;
+        w.flush()
+    }
+
+    fn in_subscribe_mode(&self) -> bool {
+        !self.subs.lock().unwrap().is_empty()
+    }
+
+    fn dispatch(&mut self, args: &[Vec<u8>]) -> Vec<Reply> {
+        let cmd = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+        let argv = &args[1..];
+
+        // RESP2 connections are restricted to pub/sub commands while subscribed.
+        if self.proto() < 3
+            && self.in_subscribe_mode()
+            && !matches!(cmd.as_str(), "SUBSCRIBE" | "UNSUBSCRIBE" | "PING" | "QUIT" | "RESET")
+        {
+            return vec![Reply::err(format!(
+                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                cmd.to_ascii_lowercase()
+            ))];
+        }
+
+        match cmd.as_str() {
+            "SUBSCRIBE" => return self.subscribe(argv),
+            "UNSUBSCRIBE" => return self.unsubscribe(argv),
+            _ => {}
+        }
+
+        let reply = match self.execute(&cmd, argv) {
+            Ok(r) => r,
+            Err(r) => r,
+        };
+        vec![reply]
+    }
+
+    fn execute(&mut self, cmd: &str, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        let n = argv.len();
+        match cmd {
+            // --- Connection ---
+            "PING" => match n {
+                0 if self.proto() < 3 && self.in_subscribe_mode() => {
+                    Ok(Reply::Array(vec![Reply::bulk_str("pong"), Reply::bulk_str("")]))
+                }
+                0 => Ok(Reply::Simple("PONG")),
+                1 => Ok(Reply::Bulk(argv[0].clone())),
+                _ => Err(wrong_args(cmd)),
+            },
+            "ECHO" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Bulk(argv[0].clone()))
+            }
+            "HELLO" => self.hello(argv),
+            "QUIT" => {
+                self.quit = true;
+                Ok(Reply::ok())
+            }
+            "RESET" => {
+                let subs = std::mem::take(&mut *self.subs.lock().unwrap());
+                for (_, sub_id) in subs {
+                    pubsub_unsubscribe(&self.db, sub_id);
+                }
+                self.proto.store(2, Ordering::Relaxed);
+                Ok(Reply::Simple("RESET"))
+            }
+            "SELECT" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                match arg_i64(&argv[0])? {
+                    0 => Ok(Reply::ok()),
+                    _ => Err(Reply::err("ERR DB index is out of range")),
+                }
+            }
+            "CLIENT" => match argv.first().map(|a| a.to_ascii_uppercase()).as_deref() {
+                Some(b"ID") => Ok(Reply::Int(self.id as i64)),
+                Some(b"GETNAME") => Ok(Reply::Nil),
+                Some(_) => Ok(Reply::ok()),
+                None => Err(wrong_args(cmd)),
+            },
+            // Clients probe COMMAND / COMMAND DOCS on connect; an empty table is acceptable.
+            "COMMAND" => Ok(Reply::Array(Vec::new())),
+            "INFO" => Ok(Reply::Bulk(self.info().into_bytes())),
+            "DBSIZE" => Ok(Reply::Int(self.db.len() as i64)),
+            "BGREWRITEAOF" => {
+                let db = self.db.clone();
+                std::thread::spawn(move || aof_rewrite(&db));
+                Ok(Reply::Simple("Background append only file rewriting started"))
+            }
+            "FLUSHALL" | "FLUSHDB" => {
+                cmd_clear_all(&self.db);
+                Ok(Reply::ok())
+            }
+
+            // --- Keys / strings ---
+            "GET" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(match cmd_get(&self.db, argv[0].as_slice())? {
+                    Some(v) => Reply::Bulk((*v).clone()),
+                    None => Reply::Nil,
+                })
+            }
+            "SET" => self.set(argv),
+            "INCR" | "DECR" | "INCRBY" | "DECRBY" => {
+                let by = matches!(cmd, "INCRBY" | "DECRBY");
+                if n != 1 + by as usize {
+                    return Err(wrong_args(cmd));
+                }
+                let amount = if by { arg_i64(&argv[1])? } else { 1 };
+                let incr = if cmd.starts_with("DECR") {
+                    amount.checked_neg().ok_or_else(|| Reply::err("ERR decrement would overflow"))?
+                } else {
+                    amount
+                };
+                Ok(Reply::Int(cmd_incrby(&self.db, argv[0].as_slice(), incr)?))
+            }
+            "INCRBYFLOAT" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let incr = arg_f64(&argv[1])?;
+                let value = cmd_incrbyfloat(&self.db, argv[0].as_slice(), incr)?;
+                Ok(Reply::Bulk(value.to_string().into_bytes()))
+            }
+            "APPEND" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_append(&self.db, argv[0].as_slice(), &argv[1])? as i64))
+            }
+            "SETRANGE" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let offset = match arg_i64(&argv[1])? {
+                    offset if offset < 0 => return Err(Reply::err("ERR offset is out of range")),
+                    offset => offset as usize,
+                };
+                Ok(Reply::Int(cmd_setrange(&self.db, argv[0].as_slice(), offset, &argv[2])? as i64))
+            }
+            "GETRANGE" | "SUBSTR" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let (start, end) = (arg_i64(&argv[1])?, arg_i64(&argv[2])?);
+                Ok(Reply::Bulk(cmd_getrange(&self.db, argv[0].as_slice(), start, end)?))
+            }
+            "STRLEN" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_strlen(&self.db, argv[0].as_slice())? as i64))
+            }
+            "GETSET" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(cmd_getset(&self.db, argv[0].as_slice(), argv[1].clone())?.map_or(Reply::Nil, Reply::Bulk))
+            }
+            "GETDEL" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(cmd_getdel(&self.db, argv[0].as_slice())?.map_or(Reply::Nil, Reply::Bulk))
+            }
+            "MGET" => {
+                if n == 0 {
+                    return Err(wrong_args(cmd));
+                }
+                let vals = cmd_mget(&self.db, argv);
+                Ok(Reply::Array(vals.into_iter().map(|v| v.map_or(Reply::Nil, Reply::Bulk)).collect()))
+            }
+            "MSET" | "MSETNX" => {
+                if n == 0 || !n.is_multiple_of(2) {
+                    return Err(wrong_args(cmd));
+                }
+                let pairs: Vec<_> = argv.chunks(2).map(|kv| (kv[0].clone(), kv[1].clone())).collect();
+                if cmd == "MSET" {
+                    cmd_mset(&self.db, pairs)?;
+                    return Ok(Reply::ok());
+                }
+                Ok(Reply::Int(cmd_msetnx(&self.db, pairs)? as i64))
+            }
+            "DEL" | "UNLINK" => {
+                if n == 0 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_del(&self.db, argv) as i64))
+            }
+            "EXISTS" => {
+                if n == 0 {
+                    return Err(wrong_args(cmd));
+                }
+                let found = argv.iter().filter(|k| cmd_ttl(&self.db, k.as_slice()) != -2).count();
+                Ok(Reply::Int(found as i64))
+            }
+            "EXPIRE" | "PEXPIRE" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let amount = arg_i64(&argv[1])?;
+                let unit = if cmd == "EXPIRE" { 1000 } else { 1 };
+                let key = argv[0].as_slice();
+                if amount <= 0 {
+                    // Non-positive TTLs delete the key, as in Redis.
+                    return Ok(Reply::Int(cmd_remove(&self.db, key) as i64));
+                }
+                let ttl_ms = (amount as u64).saturating_mul(unit);
+                Ok(Reply::Int(cmd_expire(&self.db, key, ttl_ms) as i64))
+            }
+            "TTL" | "PTTL" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                let ttl = cmd_ttl(&self.db, argv[0].as_slice());
+                if ttl < 0 || cmd == "PTTL" {
+                    return Ok(Reply::Int(ttl));
+                }
+                Ok(Reply::Int((ttl + 500) / 1000))
+            }
+
+            // --- Hashes ---
+            "HSET" | "HMSET" => {
+                if n < 3 || n.is_multiple_of(2) {
+                    return Err(wrong_args(cmd));
+                }
+                let key = argv[0].as_slice();
+                let mut fields = Vec::with_capacity(n / 2);
+                for fv in argv[1..].chunks(2) {
+                    fields.push((fv[0].clone(), fv[1].clone()));
+                }
+                let created = cmd_hset(&self.db, key, fields)?;
+                Ok(if cmd == "HMSET" { Reply::ok() } else { Reply::Int(created as i64) })
+            }
+            "HSETNX" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let set = cmd_hsetnx(&self.db, argv[0].as_slice(), argv[1].clone(), argv[2].clone())?;
+                Ok(Reply::Int(set as i64))
+            }
+            "HMGET" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let vals = cmd_hmget(&self.db, argv[0].as_slice(), &argv[1..])?;
+                Ok(Reply::Array(vals.into_iter().map(|v| v.map_or(Reply::Nil, Reply::Bulk)).collect()))
+            }
+            "HDEL" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_hdel(&self.db, argv[0].as_slice(), &argv[1..])? as i64))
+            }
+            "HEXISTS" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_hexists(&self.db, argv[0].as_slice(), &argv[1])? as i64))
+            }
+            "HLEN" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_hlen(&self.db, argv[0].as_slice())? as i64))
+            }
+            "HKEYS" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                let keys = cmd_hkeys(&self.db, argv[0].as_slice())?.unwrap_or_default();
+                Ok(Reply::Array(keys.into_iter().map(Reply::Bulk).collect()))
+            }
+            "HVALS" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                let vals = cmd_hvals(&self.db, argv[0].as_slice())?.unwrap_or_default();
+                Ok(Reply::Array(vals.into_iter().map(Reply::Bulk).collect()))
+            }
+            "HINCRBY" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let incr = arg_i64(&argv[2])?;
+                Ok(Reply::Int(cmd_hincrby(&self.db, argv[0].as_slice(), argv[1].clone(), incr)?))
+            }
+            "HINCRBYFLOAT" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let incr = arg_f64(&argv[2])?;
+                let value = cmd_hincrbyfloat(&self.db, argv[0].as_slice(), argv[1].clone(), incr)?;
+                Ok(Reply::Bulk(value.to_string().into_bytes()))
+            }
+            "HGET" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(match cmd_hget(&self.db, argv[0].as_slice(), &argv[1])? {
+                    Some(v) => Reply::Bulk(v),
+                    None => Reply::Nil,
+                })
+            }
+            "HGETALL" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                let fields = cmd_hgetall(&self.db, argv[0].as_slice())?.unwrap_or_default();
+                Ok(Reply::Map(
+                    fields
+                        .into_iter()
+                        .map(|(f, v)| (Reply::Bulk(f), Reply::Bulk(v)))
+                        .collect(),
+                ))
+            }
+
+            // --- Lists ---
+            "LPUSH" | "RPUSH" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let key = argv[0].as_slice();
+                let len = if cmd == "LPUSH" {
+                    cmd_lpush(&self.db, key, argv[1..].to_vec())?
+                } else {
+                    cmd_rpush(&self.db, key, argv[1..].to_vec())?
+                };
+                Ok(Reply::Int(len as i64))
+            }
+            "LPOP" | "RPOP" => {
+                if n != 1 && n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let count = match argv.get(1) {
+                    Some(c) => match arg_i64(c)? {
+                        c if c < 0 => return Err(Reply::err("ERR value is out of range, must be positive")),
+                        c => Some(c as usize),
+                    },
+                    None => None,
+                };
+                let popped = cmd_pop(&self.db, argv[0].as_slice(), cmd == "LPOP", count.unwrap_or(1))?;
+                Ok(match (popped, count) {
+                    (None, _) => Reply::Nil,
+                    (Some(items), Some(_)) => Reply::Array(items.into_iter().map(Reply::Bulk).collect()),
+                    (Some(items), None) => items.into_iter().next().map_or(Reply::Nil, Reply::Bulk),
+                })
+            }
+            "LLEN" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_llen(&self.db, argv[0].as_slice())? as i64))
+            }
+            "LINDEX" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let index = arg_i64(&argv[1])?;
+                Ok(match cmd_lindex(&self.db, argv[0].as_slice(), index)? {
+                    Some(v) => Reply::Bulk(v),
+                    None => Reply::Nil,
+                })
+            }
+            "LSET" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let index = arg_i64(&argv[1])?;
+                cmd_lset(&self.db, argv[0].as_slice(), index, argv[2].clone())?;
+                Ok(Reply::ok())
+            }
+            "LINSERT" => {
+                if n != 4 {
+                    return Err(wrong_args(cmd));
+                }
+                let before = match argv[1].to_ascii_uppercase().as_slice() {
+                    b"BEFORE" => true,
+                    b"AFTER" => false,
+                    _ => return Err(Reply::err("ERR syntax error")),
+                };
+                let len = cmd_linsert(&self.db, argv[0].as_slice(), before, &argv[2], argv[3].clone())?;
+                Ok(Reply::Int(len))
+            }
+            "LREM" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let count = arg_i64(&argv[1])?;
+                Ok(Reply::Int(cmd_lrem(&self.db, argv[0].as_slice(), count, &argv[2])? as i64))
+            }
+            "LTRIM" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let start = arg_i64(&argv[1])?;
+                let end = arg_i64(&argv[2])?;
+                cmd_ltrim(&self.db, argv[0].as_slice(), start, end)?;
+                Ok(Reply::ok())
+            }
+            "LMOVE" | "RPOPLPUSH" => {
+                let (from_left, to_left) = match (cmd, n) {
+                    ("LMOVE", 4) => (arg_side(&argv[2])?, arg_side(&argv[3])?),
+                    ("RPOPLPUSH", 2) => (false, true),
+                    _ => return Err(wrong_args(cmd)),
+                };
+                let moved = cmd_lmove(&self.db, argv[0].as_slice(), argv[1].as_slice(), from_left, to_left)?;
+                Ok(moved.map_or(Reply::Nil, Reply::Bulk))
+            }
+            "BLPOP" | "BRPOP" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let timeout = arg_block_timeout(&argv[n - 1])?;
+                let keys = &argv[..n - 1];
+                let writer = self.writer.clone();
+                let cancelled = || writer.lock().unwrap().get_ref().peer_closed();
+                Ok(match blocking_pop(&self.db, keys, cmd == "BLPOP", timeout, &cancelled)? {
+                    Some((key, val)) => Reply::Array(vec![Reply::Bulk(key), Reply::Bulk(val)]),
+                    None => Reply::NilArray,
+                })
+            }
+            "LRANGE" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let start = arg_i64(&argv[1])?;
+                let end = arg_i64(&argv[2])?;
+                let items = cmd_lrange(&self.db, argv[0].as_slice(), start, end)?.unwrap_or_default();
+                Ok(Reply::Array(items.into_iter().map(Reply::Bulk).collect()))
+            }
+
+            // --- Sets ---
+            "SADD" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let key = argv[0].as_slice();
+                Ok(Reply::Int(cmd_sadd(&self.db, key, argv[1..].to_vec())? as i64))
+            }
+            "SISMEMBER" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_sismember(&self.db, argv[0].as_slice(), &argv[1])? as i64))
+            }
+            "SMISMEMBER" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let found = cmd_smismember(&self.db, argv[0].as_slice(), &argv[1..])?;
+                Ok(Reply::Array(found.into_iter().map(|f| Reply::Int(f as i64)).collect()))
+            }
+            "SREM" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_srem(&self.db, argv[0].as_slice(), &argv[1..])? as i64))
+            }
+            "SMEMBERS" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                let members = cmd_smembers(&self.db, argv[0].as_slice())?.unwrap_or_default();
+                Ok(Reply::Array(members.into_iter().map(Reply::Bulk).collect()))
+            }
+            "SCARD" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_scard(&self.db, argv[0].as_slice())? as i64))
+            }
+            "SPOP" | "SRANDMEMBER" => {
+                // Without a count both reply with a single member (or nil); with one, an array.
+                if n != 1 && n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let count = argv.get(1).map(|c| arg_i64(c)).transpose()?;
+                let key = argv[0].as_slice();
+                let picked = match (cmd, count) {
+                    ("SPOP", Some(c)) if c < 0 => return Err(Reply::err("ERR value is out of range, must be positive")),
+                    ("SPOP", c) => cmd_spop(&self.db, key, c.unwrap_or(1) as usize)?,
+                    (_, c) => cmd_srandmember(&self.db, key, c.unwrap_or(1))?,
+                };
+                Ok(match count {
+                    Some(_) => Reply::Array(picked.unwrap_or_default().into_iter().map(Reply::Bulk).collect()),
+                    None => picked.and_then(|m| m.into_iter().next()).map_or(Reply::Nil, Reply::Bulk),
+                })
+            }
+            "SUNION" | "SINTER" | "SDIFF" => {
+                if n < 1 {
+                    return Err(wrong_args(cmd));
+                }
+                let members = cmd_set_op(&self.db, set_op(cmd), argv)?;
+                Ok(Reply::Array(members.into_iter().map(Reply::Bulk).collect()))
+            }
+            "SUNIONSTORE" | "SINTERSTORE" | "SDIFFSTORE" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let op = set_op(cmd.trim_end_matches("STORE"));
+                Ok(Reply::Int(cmd_set_op_store(&self.db, op, &argv[0], &argv[1..])? as i64))
+            }
+
+            // --- Sorted sets ---
+            "ZADD" => {
+                // ZADD key [NX|XX] [GT|LT] [CH] score member [score member ...]
+                if n < 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let key = argv[0].as_slice();
+                let mut flags = ZAddFlags::default();
+                let mut changed = false;
+                let mut i = 1;
+                while i < n {
+                    match argv[i].to_ascii_uppercase().as_slice() {
+                        b"NX" => flags.nx = true,
+                        b"XX" => flags.xx = true,
+                        b"GT" => flags.gt = true,
+                        b"LT" => flags.lt = true,
+                        b"CH" => changed = true,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                let pairs = &argv[i..];
+                if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+                    return Err(Reply::err("ERR syntax error"));
+                }
+                let mut members = Vec::with_capacity(pairs.len() / 2);
+                for sm in pairs.chunks(2) {
+                    members.push((arg_f64(&sm[0])?, sm[1].clone()));
+                }
+                let outcome = cmd_zadd_flags(&self.db, key, members, flags)?;
+                let reply = outcome.added + if changed { outcome.updated } else { 0 };
+                Ok(Reply::Int(reply as i64))
+            }
+            "ZREM" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let key = argv[0].as_slice();
+                Ok(Reply::Int(cmd_zrem(&self.db, key, &argv[1..])? as i64))
+            }
+            "ZINCRBY" => {
+                if n != 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let incr = arg_f64(&argv[1])?;
+                Ok(Reply::Double(cmd_zincrby(&self.db, argv[0].as_slice(), incr, argv[2].clone())?))
+            }
+            "ZSCORE" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(cmd_zscore(&self.db, argv[0].as_slice(), &argv[1])?.map_or(Reply::Nil, Reply::Double))
+            }
+            "ZRANK" | "ZREVRANK" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let rank = cmd_zrank(&self.db, argv[0].as_slice(), &argv[1], cmd == "ZREVRANK")?;
+                Ok(rank.map_or(Reply::Nil, |r| Reply::Int(r as i64)))
+            }
+            "ZCARD" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_zcard(&self.db, argv[0].as_slice())? as i64))
+            }
+            "ZRANGE" => self.zrange(cmd, argv, ZRangeBy::Rank, false),
+            "ZREVRANGE" => self.zrange(cmd, argv, ZRangeBy::Rank, true),
+            "ZRANGEBYSCORE" => self.zrange(cmd, argv, ZRangeBy::Score, false),
+            "ZREVRANGEBYSCORE" => self.zrange(cmd, argv, ZRangeBy::Score, true),
+            "ZRANGEBYLEX" => self.zrange(cmd, argv, ZRangeBy::Lex, false),
+            "ZREVRANGEBYLEX" => self.zrange(cmd, argv, ZRangeBy::Lex, true),
+
+            // --- Streams ---
+            "XADD" => {
+                // XADD key [NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]] * | id
+                //      field value [field value ...] | <payload>
+                if n < 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let mut nomkstream = false;
+                let mut trim = None;
+                let mut i = 1;
+                loop {
+                    match argv.get(i) {
+                        Some(opt) if opt.eq_ignore_ascii_case(b"NOMKSTREAM") => {
+                            nomkstream = true;
+                            i += 1;
+                        }
+                        Some(_) if trim.is_none() => match parse_stream_trim(argv, &mut i)? {
+                            Some(t) => trim = Some(t),
+                            None => break,
+                        },
+                        _ => break,
+                    }
+                }
+                let Some(id) = argv.get(i) else { return Err(wrong_args(cmd)); };
+                let id = parse_xadd_id(id)?;
+                // A lone value is the single-payload form, stored as field `payload`.
+                let fields: StreamFields = match &argv[i + 1..] {
+                    [payload] => vec![(STREAM_PAYLOAD_FIELD.to_vec(), payload.clone())],
+                    args if !args.is_empty() && args.len().is_multiple_of(2) => {
+                        args.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect()
+                    }
+                    _ => return Err(wrong_args(cmd)),
+                };
+                match cmd_xadd_with(&self.db, argv[0].as_slice(), id, fields, trim, nomkstream)? {
+                    Some(id) => Ok(Reply::Bulk(format_stream_id(id))),
+                    None => Ok(Reply::Nil),
+                }
+            }
+            "XTRIM" => {
+                // XTRIM key MAXLEN | MINID [= | ~] threshold [LIMIT count]
+                let mut i = 1;
+                let trim = parse_stream_trim(argv, &mut i)?;
+                let (Some((trim, limit)), true) = (trim, i == n) else { return Err(Reply::err("ERR syntax error")); };
+                Ok(Reply::Int(cmd_xtrim(&self.db, argv[0].as_slice(), trim, limit)? as i64))
+            }
+            "XDEL" => {
+                if n < 2 {
+                    return Err(wrong_args(cmd));
+                }
+                let ids = argv[1..].iter().map(|id| parse_stream_id(id, 0)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Reply::Int(cmd_xdel(&self.db, argv[0].as_slice(), &ids)? as i64))
+            }
+            "XLEN" => {
+                if n != 1 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_xlen(&self.db, argv[0].as_slice())? as i64))
+            }
+            "XRANGE" | "XREVRANGE" => {
+                // XRANGE key start end [COUNT n] | XREVRANGE key end start [COUNT n]
+                if n != 3 && n != 5 {
+                    return Err(wrong_args(cmd));
+                }
+                let rev = cmd == "XREVRANGE";
+                let (start, end) = if rev { (&argv[2], &argv[1]) } else { (&argv[1], &argv[2]) };
+                let start = parse_stream_bound(start, true)?;
+                let end = parse_stream_bound(end, false)?;
+                let count = match argv.get(3) {
+                    Some(opt) if opt.eq_ignore_ascii_case(b"COUNT") => Some(arg_i64(&argv[4])?.max(0) as usize),
+                    Some(_) => return Err(Reply::err("ERR syntax error")),
+                    None => None,
+                };
+                let items = cmd_xrange(&self.db, argv[0].as_slice(), start, end, count, rev)?.unwrap_or_default();
+                Ok(stream_entries(items))
+            }
+            "XREAD" => self.xread(argv),
+            "XGROUP" => self.xgroup(argv),
+            "XREADGROUP" => self.xreadgroup(argv),
+            "XACK" => {
+                if n < 3 {
+                    return Err(wrong_args(cmd));
+                }
+                let ids = argv[2..].iter().map(|id| parse_stream_id(id, 0)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Reply::Int(cmd_xack(&self.db, argv[0].as_slice(), &arg_str(&argv[1])?, &ids)? as i64))
+            }
+            "XPENDING" => self.xpending(argv),
+            "XCLAIM" => {
+                // XCLAIM key group consumer min-idle-time id [id ...] [JUSTID]
+                if n < 5 {
+                    return Err(wrong_args(cmd));
+                }
+                let justid = argv[n - 1].eq_ignore_ascii_case(b"JUSTID");
+                let ids = argv[4..if justid { n - 1 } else { n }]
+                    .iter()
+                    .map(|id| parse_stream_id(id, 0))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (key, group, consumer) = (argv[0].as_slice(), arg_str(&argv[1])?, arg_str(&argv[2])?);
+                let items = cmd_xclaim(&self.db, key, &group, &consumer, arg_idle(&argv[3])?, &ids, justid)?;
+                Ok(claimed_entries(items, justid))
+            }
+            "XAUTOCLAIM" => {
+                // XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+                if n < 5 {
+                    return Err(wrong_args(cmd));
+                }
+                let mut count = 100;
+                let mut justid = false;
+                let mut i = 5;
+                while i < n {
+                    match argv[i].to_ascii_uppercase().as_slice() {
+                        b"COUNT" if i + 1 < n => {
+                            count = match arg_i64(&argv[i + 1])? {
+                                c if c < 1 => return Err(Reply::err("ERR COUNT must be > 0")),
+                                c => c as usize,
+                            };
+                            i += 1;
+                        }
+                        b"JUSTID" => justid = true,
+                        _ => return Err(Reply::err("ERR syntax error")),
+                    }
+                    i += 1;
+                }
+                let (key, group, consumer) = (argv[0].as_slice(), arg_str(&argv[1])?, arg_str(&argv[2])?);
+                let start = parse_stream_bound(&argv[4], true)?;
+                let idle = arg_idle(&argv[3])?;
+                let (next, items, deleted) = cmd_xautoclaim(&self.db, key, &group, &consumer, idle, start, count, justid)?;
+                Ok(Reply::Array(vec![Reply::Bulk(format_stream_id(next)), claimed_entries(items, justid), stream_ids(deleted)]))
+            }
+
+            // --- Pub/Sub ---
+            "PUBLISH" => {
+                if n != 2 {
+                    return Err(wrong_args(cmd));
+                }
+                Ok(Reply::Int(cmd_publish(&self.db, &arg_str(&argv[0])?, &argv[1]) as i64))
+            }
+
+            _ => {
+                let preview: Vec<String> =
+                    argv.iter().take(3).map(|a| format!("'{}'", String::from_utf8_lossy(a))).collect();
+                Err(Reply::err(format!(
+                    "ERR unknown command '{}', with args beginning with: {}",
+                    cmd.to_ascii_lowercase(),
+                    preview.join(" ")
+                )))
+            }
+        }
+    }
+
+    fn hello(&mut self, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        if let Some(ver) = argv.first() {
+            match arg_i64(ver) {
+                Ok(v @ (2 | 3)) => self.proto.store(v as u8, Ordering::Relaxed),
+                _ => return Err(Reply::err("NOPROTO unsupported protocol version")),
+            }
+            // AUTH / SETNAME are accepted and ignored: the server has no users.
+            let mut i = 1;
+            while i < argv.len() {
+                let opt = argv[i].to_ascii_uppercase();
+                i += match opt.as_slice() {
+                    b"AUTH" if i + 2 < argv.len() => 3,
+                    b"AUTH" => return Err(Reply::err("ERR syntax error")),
+                    b"SETNAME" if i + 1 < argv.len() => 2,
+                    _ => return Err(Reply::err("ERR syntax error")),
+                };
+            }
+        }
+
+        Ok(Reply::Map(vec![
+            (Reply::bulk_str("server"), Reply::bulk_str("rust_cache")),
+            (Reply::bulk_str("version"), Reply::bulk_str(env!("CARGO_PKG_VERSION"))),
+            (Reply::bulk_str("proto"), Reply::Int(self.proto() as i64)),
+            (Reply::bulk_str("id"), Reply::Int(self.id as i64)),
+            (Reply::bulk_str("mode"), Reply::bulk_str("standalone")),
+            (Reply::bulk_str("role"), Reply::bulk_str("master")),
+            (Reply::bulk_str("modules"), Reply::Array(Vec::new())),
+        ]))
+    }
+
+    // SET key value [NX | XX] [GET] [EX s | PX ms | EXAT s | PXAT ms | KEEPTTL]
+    fn set(&mut self, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        if argv.len() < 2 {
+            return Err(wrong_args("SET"));
+        }
+        let key = argv[0].as_slice();
+        let val = argv[1].clone();
+
+        let mut opts = SetOptions::default();
+        let mut i = 2;
+        while i < argv.len() {
+            let opt = argv[i].to_ascii_uppercase();
+            let has_ttl = opts.keep_ttl || opts.expires_at_ms.is_some();
+            match opt.as_slice() {
+                b"NX" if !opts.xx => opts.nx = true,
+                b"XX" if !opts.nx => opts.xx = true,
+                b"GET" => opts.get = true,
+                b"KEEPTTL" if !has_ttl => opts.keep_ttl = true,
+                b"EX" | b"PX" | b"EXAT" | b"PXAT" if !has_ttl && i + 1 < argv.len() => {
+                    let unit = if opt.starts_with(b"EX") { 1000 } else { 1 };
+                    let amount = arg_ttl(&argv[i + 1], unit)?;
+                    let relative = !opt.ends_with(b"AT");
+                    opts.expires_at_ms = Some(if relative { now_ms().saturating_add(amount) } else { amount });
+                    i += 1;
+                }
+                _ => return Err(Reply::err("ERR syntax error")),
+            }
+            i += 1;
+        }
+
+        let (written, old) = cmd_set_with(&self.db, key, val, opts)?;
+        Ok(match (opts.get, written) {
+            (true, _) => old.map_or(Reply::Nil, Reply::Bulk),
+            (false, true) => Reply::ok(),
+            (false, false) => Reply::Nil,
+        })
+    }
+
+    // XREAD [COUNT n] [BLOCK ms] STREAMS key [key ...] id [id ...]
+    fn xread(&mut self, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        let mut count = None;
+        let mut block = None;
+        let mut i = 0;
+        loop {
+            let Some(opt) = argv.get(i) else { return Err(wrong_args("XREAD")); };
+            match opt.to_ascii_uppercase().as_slice() {
+                b"COUNT" if i + 1 < argv.len() => count = Some(arg_i64(&argv[i + 1])?.max(0) as usize),
+                b"BLOCK" if i + 1 < argv.len() => match arg_i64(&argv[i + 1])? {
+                    ms if ms < 0 => return Err(Reply::err("ERR timeout is negative")),
+                    0 => block = Some(None),
+                    ms => block = Some(Some(Duration::from_millis(ms as u64))),
+                },
+                b"STREAMS" => break,
+                _ => return Err(Reply::err("ERR syntax error")),
+            }
+            i += 2;
+        }
+        let rest = &argv[i + 1..];
+        if rest.is_empty() || !rest.len().is_multiple_of(2) {
+            return Err(Reply::err(
+                "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.",
+            ));
+        }
+        let (keys, ids) = rest.split_at(rest.len() / 2);
+        let mut streams = Vec::with_capacity(keys.len());
+        for (key, id) in keys.iter().zip(ids) {
+            let after = match id.as_slice() {
+                b"$" => stream_last_id(&self.db, key)?,
+                _ => parse_stream_id(id, 0)?,
+            };
+            streams.push((key.clone(), after));
+        }
+
+        let found = match block {
+            Some(timeout) => {
+                let writer = self.writer.clone();
+                let cancelled = || writer.lock().unwrap().get_ref().peer_closed();
+                blocking_xread(&self.db, &streams, count, timeout, &cancelled)?
+            }
+            None => Some(cmd_xread(&self.db, &streams, count)?).filter(|f| !f.is_empty()),
+        };
+        let Some(found) = found else { return Ok(Reply::NilArray); };
+        let found = found.into_iter().map(|(key, items)| (Reply::Bulk(key), stream_entries(items)));
+        if self.proto() >= 3 {
+            return Ok(Reply::Map(found.collect()));
+        }
+        Ok(Reply::Array(found.map(|(k, v)| Reply::Array(vec![k, v])).collect()))
+    }
+
+    // XGROUP CREATE key group id|$ [MKSTREAM] | XGROUP DESTROY key group
+    fn xgroup(&mut self, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        let Some(sub) = argv.first() else { return Err(wrong_args("XGROUP")); };
+        match sub.to_ascii_uppercase().as_slice() {
+            b"CREATE" if argv.len() == 4 || argv.len() == 5 => {
+                let mkstream = match argv.get(4) {
+                    Some(opt) if opt.eq_ignore_ascii_case(b"MKSTREAM") => true,
+                    Some(_) => return Err(Reply::err("ERR syntax error")),
+                    None => false,
+                };
+                let id = match argv[3].as_slice() {
+                    b"$" => None,
+                    id => Some(parse_stream_id(id, 0)?),
+                };
+                cmd_xgroup_create(&self.db, argv[1].as_slice(), &arg_str(&argv[2])?, id, mkstream)?;
+                Ok(Reply::ok())
+            }
+            b"DESTROY" if argv.len() == 3 => {
+                let destroyed = cmd_xgroup_destroy(&self.db, argv[1].as_slice(), &arg_str(&argv[2])?)?;
+                Ok(Reply::Int(destroyed as i64))
+            }
+            b"CREATE" | b"DESTROY" => Err(wrong_args(&format!("XGROUP|{}", String::from_utf8_lossy(sub)))),
+            _ => Err(Reply::err(format!("ERR unknown subcommand '{}'. Try XGROUP HELP.", String::from_utf8_lossy(sub)))),
+        }
+    }
+
+    // XREADGROUP GROUP group consumer [COUNT n] [BLOCK ms] [NOACK] STREAMS key [key ...] id [id ...]
+    // An id of `>` reads entries never delivered to the group; only such reads block.
+    fn xreadgroup(&mut self, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        if argv.len() < 6 || !argv[0].eq_ignore_ascii_case(b"GROUP") {
+            return Err(Reply::err("ERR syntax error"));
+        }
+        let group = arg_str(&argv[1])?;
+        let consumer = arg_str(&argv[2])?;
+        let mut count = None;
+        let mut block = None;
+        let mut noack = false;
+        let mut i = 3;
+        loop {
+            let Some(opt) = argv.get(i) else { return Err(wrong_args("XREADGROUP")); };
+            match opt.to_ascii_uppercase().as_slice() {
+                b"COUNT" if i + 1 < argv.len() => count = Some(arg_i64(&argv[i + 1])?.max(0) as usize),
+                b"BLOCK" if i + 1 < argv.len() => match arg_i64(&argv[i + 1])? {
+                    ms if ms < 0 => return Err(Reply::err("ERR timeout is negative")),
+                    0 => block = Some(None),
+                    ms => block = Some(Some(Duration::from_millis(ms as u64))),
+                },
+                b"NOACK" => {
+                    noack = true;
+                    i += 1;
+                    continue;
+                }
+                b"STREAMS" => break,
+                _ => return Err(Reply::err("ERR syntax error")),
+            }
+            i += 2;
+        }
+        let rest = &argv[i + 1..];
+        if rest.is_empty() || !rest.len().is_multiple_of(2) {
+            return Err(Reply::err(
+                "ERR Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified.",
+            ));
+        }
+        let (keys, ids) = rest.split_at(rest.len() / 2);
+        let mut streams = Vec::with_capacity(keys.len());
+        for (key, id) in keys.iter().zip(ids) {
+            let after = match id.as_slice() {
+                b">" => None,
+                _ => Some(parse_stream_id(id, 0)?),
+            };
+            streams.push((key.clone(), after));
+        }
+
+        let read = || cmd_xreadgroup(&self.db, &group, &consumer, &streams, count, noack);
+        let found = match block {
+            Some(timeout) if streams.iter().all(|(_, id)| id.is_none()) => {
+                let writer = self.writer.clone();
+                let cancelled = || writer.lock().unwrap().get_ref().peer_closed();
+                let keys: Vec<Vec<u8>> = streams.iter().map(|(key, _)| key.clone()).collect();
+                blocking_stream_read(&self.db, &keys, timeout, &cancelled, &read)?
+            }
+            _ => Some(read()?).filter(|f| !f.is_empty()),
+        };
+        let Some(found) = found else { return Ok(Reply::NilArray); };
+        let found = found.into_iter().map(|(key, items)| (Reply::Bulk(key), group_entries(items)));
+        if self.proto() >= 3 {
+            return Ok(Reply::Map(found.collect()));
+        }
+        Ok(Reply::Array(found.map(|(k, v)| Reply::Array(vec![k, v])).collect()))
+    }
+
+    // XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+    fn xpending(&self, argv: &[Vec<u8>]) -> Result<Reply, Reply> {
+        if argv.len() < 2 {
+            return Err(wrong_args("XPENDING"));
+        }
+        let key = argv[0].as_slice();
+        let group = arg_str(&argv[1])?;
+        if argv.len() == 2 {
+            let summary = cmd_xpending_summary(&self.db, key, &group)?;
+            if summary.count == 0 {
+                return Ok(Reply::Array(vec![Reply::Int(0), Reply::Nil, Reply::Nil, Reply::NilArray]));
+            }
+            let consumers = summary
+                .consumers
+                .into_iter()
+                .map(|(name, n)| Reply::Array(vec![Reply::Bulk(name.into_bytes()), Reply::Bulk(n.to_string().into_bytes())]))
+                .collect();
+            return Ok(Reply::Array(vec![
+                Reply::Int(summary.count as i64),
+                Reply::Bulk(format_stream_id(summary.first)),
+                Reply::Bulk(format_stream_id(summary.last)),
+                Reply::Array(consumers),
+            ]));
+        }
+
+        let (min_idle, range) = if argv[2].eq_ignore_ascii_case(b"IDLE") && argv.len() > 3 {
+            (arg_idle(&argv[3])?, &argv[4..])
+        } else {
+            (0, &argv[2..])
+        };
+        if range.len() != 3 && range.len() != 4 {
+            return Err(Reply::err("ERR syntax error"));
+        }
+        let start = parse_stream_bound(&range[0], true)?;
+        let end = parse_stream_bound(&range[1], false)?;
+        let count = arg_i64(&range[2])?.max(0) as usize;
+        let consumer = range.get(3).map(|c| arg_str(c)).transpose()?;
+        let items = cmd_xpending(&self.db, key, &group, start, end, count, consumer.as_deref(), min_idle)?;
+        Ok(Reply::Array(
+            items
+                .into_iter()
+                .map(|item| {
+                    Reply::Array(vec![
+                        Reply::Bulk(format_stream_id(item.id)),
+                        Reply::Bulk(item.consumer.into_bytes()),
+                        Reply::Int(item.idle_ms as i64),
+                        Reply::Int(item.deliveries as i64),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    // ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES] and the
+    // older ZREVRANGE / Z[REV]RANGEBYSCORE / Z[REV]RANGEBYLEX forms, which fix `by` and `rev`.
+    // Reversed score and lex ranges name the upper end first, as in Redis.
+    fn zrange(&self, cmd: &str, argv: &[Vec<u8>], mut by: ZRangeBy, mut rev: bool) -> Result<Reply, Reply> {
+        if argv.len() < 3 {
+            return Err(wrong_args(cmd));
+        }
+        let options = cmd == "ZRANGE";
+        let mut with_scores = false;
+        let mut limit = None;
+        let mut i = 3;
+        while i < argv.len() {
+            match argv[i].to_ascii_uppercase().as_slice() {
+                b"WITHSCORES" if cmd != "ZRANGEBYLEX" && cmd != "ZREVRANGEBYLEX" => with_scores = true,
+                b"LIMIT" if i + 2 < argv.len() && cmd != "ZREVRANGE" => {
+                    limit = Some((arg_i64(&argv[i + 1])?, arg_i64(&argv[i + 2])?));
+                    i += 2;
+                }
+                b"BYSCORE" if options => by = ZRangeBy::Score,
+                b"BYLEX" if options => by = ZRangeBy::Lex,
+                b"REV" if options => rev = true,
+                _ => return Err(Reply::err("ERR syntax error")),
+            }
+            i += 1;
+        }
+        if limit.is_some() && by == ZRangeBy::Rank {
+            return Err(Reply::err("ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"));
+        }
+        if with_scores && by == ZRangeBy::Lex {
+            return Err(Reply::err("ERR syntax error, WITHSCORES not supported in combination with BYLEX"));
+        }
+
+        let key = argv[0].as_slice();
+        let (lo, hi) = if rev && by != ZRangeBy::Rank { (&argv[2], &argv[1]) } else { (&argv[1], &argv[2]) };
+        let (offset, count) = match limit {
+            Some((offset, _)) if offset < 0 => return Ok(Reply::Array(Vec::new())),
+            Some((offset, count)) => (offset as usize, usize::try_from(count).ok()),
+            None => (0, None),
+        };
+        let members = match by {
+            ZRangeBy::Rank => cmd_zrange(&self.db, key, arg_i64(lo)?, arg_i64(hi)?, rev)?.unwrap_or_default(),
+            ZRangeBy::Score => {
+                cmd_zrangebyscore(&self.db, key, arg_score_bound(lo)?, arg_score_bound(hi)?, rev, offset, count)?
+            }
+            ZRangeBy::Lex => cmd_zrangebylex(&self.db, key, &arg_lex_bound(lo)?, &arg_lex_bound(hi)?, rev, offset, count)?,
+        };
+        Ok(Reply::Array(self.scored_members(members, with_scores)))
+    }
+
+    fn scored_members(&self, members: ScoredMembers, with_scores: bool) -> Vec<Reply> {
+        if !with_scores {
+            return members.into_iter().map(|(m, _)| Reply::Bulk(m)).collect();
+        }
+        if self.proto() >= 3 {
+            return members
+                .into_iter()
+                .map(|(m, s)| Reply::Array(vec![Reply::Bulk(m), Reply::Double(s)]))
+                .collect();
+        }
+        members
+            .into_iter()
+            .flat_map(|(m, s)| [Reply::Bulk(m), Reply::Double(s)])
+            .collect()
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "# Server\r\nredis_version:7.0.0\r\nrust_cache_version:{}\r\nredis_mode:standalone\r\n\r\n# Memory\r\nused_memory:{}\r\nmaxmemory:{}\r\nmaxmemory_policy:{}\r\n\r\n# Keyspace\r\ndb0:keys={},expires=0,avg_ttl=0\r\n",
+            env!("CARGO_PKG_VERSION"),
+            self.db.cache.used_memory.load(Ordering::Relaxed),
+            self.db.max_memory.load(Ordering::Relaxed),
+            eviction::policy_name(self.db.policy()),
+            self.db.len()
+        )
+    }
+
+    // --- Pub/Sub ---
+
+    fn subscribe(&mut self, argv: &[Vec<u8>]) -> Vec<Reply> {
+        if argv.is_empty() {
+            return vec![wrong_args("SUBSCRIBE")];
+        }
+        let channels = match argv.iter().map(|c| arg_str(c)).collect::<Result<Vec<_>, _>>() {
+            Ok(channels) => channels,
+            Err(e) => return vec![e],
+        };
+        self.start_delivery();
+
+        // Hold the writer while subscribing so the confirmation precedes any message.
+        let mut buf = Vec::new();
+        let mut w = self.writer.lock().unwrap();
+        {
+            let mut subs = self.subs.lock().unwrap();
+            for channel in channels {
+                if !subs.contains_key(&channel) {
+                    let sub_id = pubsub_subscribe(&self.db, &channel);
+                    if sub_id != 0 {
+                        subs.insert(channel.clone(), sub_id);
+                    }
+                }
+                Reply::Push(vec![
+                    Reply::bulk_str("subscribe"),
+                    Reply::Bulk(channel.into_bytes()),
+                    Reply::Int(subs.len() as i64),
+                ])
+                .encode(self.proto(), &mut buf);
+            }
+        }
+        let _ = w.write_all(&buf).and_then(|_| w.flush());
+        Vec::new()
+    }
+
+    fn unsubscribe(&mut self, argv: &[Vec<u8>]) -> Vec<Reply> {
+        let mut subs = self.subs.lock().unwrap();
+        let channels: Vec<String> = if argv.is_empty() {
+            subs.keys().cloned().collect()
+        } else {
+            match argv.iter().map(|c| arg_str(c)).collect() {
+                Ok(channels) => channels,
+                Err(e) => return vec![e],
+            }
+        };
+
+        if channels.is_empty() {
+            return vec![Reply::Push(vec![Reply::bulk_str("unsubscribe"), Reply::Nil, Reply::Int(0)])];
+        }
+
+        channels
+            .into_iter()
+            .map(|channel| {
+                if let Some(sub_id) = subs.remove(&channel) {
+                    pubsub_unsubscribe(&self.db, sub_id);
+                }
+                Reply::Push(vec![
+                    Reply::bulk_str("unsubscribe"),
+                    Reply::Bulk(channel.into_bytes()),
+                    Reply::Int(subs.len() as i64),
+                ])
+            })
+            .collect()
+    }
+
+    // Spawns the per-connection thread that forwards published messages as pushes.
+    fn start_delivery(&mut self) {
+        if self.delivery_started {
+            return;
+        }
+        self.delivery_started = true;
+
+        let writer = self.writer.clone();
+        let subs = self.subs.clone();
+        let alive = self.alive.clone();
+        let proto = self.proto.clone();
+        let db = self.db.clone();
+        std::thread::spawn(move || {
+            while alive.load(Ordering::Relaxed) {
+                let ids: Vec<u64> = subs.lock().unwrap().values().copied().collect();
+                let msgs = pubsub_wait(&db, &ids, Duration::from_millis(100));
+                if msgs.is_empty() {
+                    continue;
+                }
+                let mut buf = Vec::new();
+                for msg in msgs {
+                    Reply::Push(vec![
+                        Reply::bulk_str("message"),
+                        Reply::Bulk(msg.channel.into_bytes()),
+                        Reply::Bulk(msg.payload),
+                    ])
+                    .encode(proto.load(Ordering::Relaxed), &mut buf);
+                }
+                let mut w = writer.lock().unwrap();
+                if w.write_all(&buf).and_then(|_| w.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &[u8]) -> io::Result<Option<Vec<Vec<u8>>>> {
+        read_command(&mut BufReader::new(input))
+    }
+
+    fn encoded(reply: Reply, proto: u8) -> Vec<u8> {
+        let mut out = Vec::new();
+        reply.encode(proto, &mut out);
+        out
+    }
+
+    #[test]
+    fn reads_multibulk_command() {
+        let args = parse(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\nv\r\nx\r\n").unwrap().unwrap();
+        assert_eq!(args, vec![b"SET".to_vec(), b"k".to_vec(), b"v\r\nx".to_vec()]);
+    }
+
+    #[test]
+    fn reads_inline_command() {
+        let args = parse(b"PING  hello\r\n").unwrap().unwrap();
+        assert_eq!(args, vec![b"PING".to_vec(), b"hello".to_vec()]);
+        assert_eq!(parse(b"*0\r\n").unwrap().unwrap(), Vec::<Vec<u8>>::new());
+    }
+
+    #[test]
+    fn clean_eof_is_none() {
+        assert!(parse(b"").unwrap().is_none());
+        assert!(parse(b"*2\r\n$1\r\na\r\n").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_commands() {
+        for input in [
+            &b"*x\r\n"[..],
+            b"*1\r\n+OK\r\n",
+            b"*1\r\n$-1\r\n",
+            b"*1\r\n$3\r\nabcd\r\n",
+            b"*1\r\n$3\r\nab",
+        ] {
+            assert!(parse(input).is_err(), "{:?}", String::from_utf8_lossy(input));
+        }
+        let too_many = format!("*{}\r\n", MAX_ARGS + 1);
+        assert_eq!(parse(too_many.as_bytes()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn declared_lengths_are_not_trusted() {
+        // A huge declared bulk with little data behind it fails without allocating its length.
+        let short = format!("*1\r\n${}\r\nabc", MAX_BULK_LEN);
+        assert_eq!(parse(short.as_bytes()).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let within = |input: &[u8], max_len| read_command_within(&mut BufReader::new(input), max_len);
+        let cmd = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\nvalu\r\n";
+        assert_eq!(within(cmd, 8).unwrap().unwrap().len(), 3);
+        assert_eq!(within(cmd, 7).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn encodes_resp2_and_resp3() {
+        assert_eq!(encoded(Reply::ok(), 2), b"+OK\r\n");
+        assert_eq!(encoded(Reply::err("ERR bad\r\nline"), 2), b"-ERR bad  line\r\n");
+        assert_eq!(encoded(Reply::Int(-7), 2), b":-7\r\n");
+        assert_eq!(encoded(Reply::Nil, 2), b"$-1\r\n");
+        assert_eq!(encoded(Reply::Nil, 3), b"_\r\n");
+        assert_eq!(encoded(Reply::Double(1.5), 2), b"$3\r\n1.5\r\n");
+        assert_eq!(encoded(Reply::Double(f64::NEG_INFINITY), 3), b",-inf\r\n");
+
+        let map = || Reply::Map(vec![(Reply::bulk_str("a"), Reply::Int(1))]);
+        assert_eq!(encoded(map(), 2), b"*2\r\n$1\r\na\r\n:1\r\n");
+        assert_eq!(encoded(map(), 3), b"%1\r\n$1\r\na\r\n:1\r\n");
+
+        let push = || Reply::Push(vec![Reply::bulk_str("message")]);
+        assert_eq!(encoded(push(), 2), b"*1\r\n$7\r\nmessage\r\n");
+        assert_eq!(encoded(push(), 3), b">1\r\n$7\r\nmessage\r\n");
+    }
+
+    #[test]
+    fn encoded_arrays_round_trip_through_the_parser() {
+        let args = vec![b"HSET".to_vec(), b"key".to_vec(), Vec::new(), vec![0, 255, b'\r', b'\n']];
+        let out = encoded(Reply::Array(args.iter().cloned().map(Reply::Bulk).collect()), 2);
+        assert_eq!(parse(&out).unwrap().unwrap(), args);
+    }
+
+    #[test]
+    fn non_utf8_keys_are_reachable() {
+        let mut conn = connect();
+        let key: &[u8] = b"\xffk\0";
+        let expect = |conn: &mut _, args: &[&[u8]], reply: &[u8]| assert_eq!(call_raw(conn, args, reply), reply);
+        expect(&mut conn, &[b"SET", key, b"v"], b"+OK\r\n");
+        expect(&mut conn, &[b"GET", key], b"$1\r\nv\r\n");
+        // Its lossy UTF-8 decoding is another key.
+        expect(&mut conn, &[b"EXISTS", "\u{fffd}k\0".as_bytes()], b":0\r\n");
+        expect(&mut conn, &[b"RPUSH", b"\xffl", b"x"], b":1\r\n");
+        expect(&mut conn, &[b"BLPOP", b"l", b"\xffl", b"0"], b"*2\r\n$2\r\n\xffl\r\n$1\r\nx\r\n");
+        expect(&mut conn, &[b"DEL", key], b":1\r\n");
+        expect(&mut conn, &[b"GET", key], b"$-1\r\n");
+        // Group, consumer and channel names are still text.
+        assert!(arg_str(&[b'g', 0xff]).is_err());
+    }
+
+    // A RESP2 connection to a server on a fresh instance.
+    fn connect() -> (TcpStream, BufReader<TcpStream>) {
+        let addr = spawn_tcp_for(Instance::new(), "127.0.0.1:0").unwrap();
+        let conn = TcpStream::connect(addr).unwrap();
+        let reader = BufReader::new(conn.try_clone().unwrap());
+        (conn, reader)
+    }
+
+    // Sends one command and returns its reply as raw RESP (bulk strings and arrays are read whole).
+    fn call(conn: &mut (TcpStream, BufReader<TcpStream>), args: &[&str]) -> String {
+        let mut req = format!("*{}\r\n", args.len());
+        for a in args {
+            req.push_str(&format!("${}\r\n{}\r\n", a.len(), a));
+        }
+        conn.0.write_all(req.as_bytes()).unwrap();
+        let mut out = String::new();
+        let mut pending = 1;
+        while pending > 0 {
+            let mut line = String::new();
+            conn.1.read_line(&mut line).unwrap();
+            pending -= 1;
+            match line.as_bytes()[0] {
+                b'$' if !line.starts_with("$-1") => pending += 1,
+                b'*' => pending += line[1..].trim().parse::<i64>().unwrap().max(0),
+                _ => {}
+            }
+            out.push_str(&line);
+        }
+        out
+    }
+
+    #[test]
+    fn commands_on_the_wrong_type_reply_wrongtype() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["HSET", "h", "f", "v"]), ":1\r\n");
+        for cmd in [
+            &["GET", "h"][..],
+            &["LRANGE", "h", "0", "-1"],
+            &["SISMEMBER", "h", "m"],
+            &["XRANGE", "h", "-", "+"],
+            &["LPUSH", "h", "x"],
+            &["SADD", "h", "x"],
+            &["ZADD", "h", "1", "x"],
+            &["XADD", "h", "*", "payload", "x"],
+        ] {
+            assert!(call(&mut conn, cmd).starts_with("-WRONGTYPE "), "{:?}", cmd);
+        }
+        assert_eq!(call(&mut conn, &["GET", "missing"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["HGET", "h", "f"]), "$1\r\nv\r\n");
+    }
+
+    #[test]
+    fn blocking_reads_wait_for_writes() {
+        let addr = spawn_tcp_for(Instance::new(), "127.0.0.1:0").unwrap();
+        let open = || {
+            let conn = TcpStream::connect(addr).unwrap();
+            let reader = BufReader::new(conn.try_clone().unwrap());
+            (conn, reader)
+        };
+        let mut conn = open();
+        assert_eq!(call(&mut conn, &["BLPOP", "q", "0.05"]), "*-1\r\n");
+        assert_eq!(call(&mut conn, &["BLPOP", "q", "-1"]), "-ERR timeout is negative\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "s", "1-0", "payload", "a"]), "$3\r\n1-0\r\n");
+        assert_eq!(call(&mut conn, &["XREAD", "BLOCK", "50", "STREAMS", "s", "$"]), "*-1\r\n");
+        assert!(call(&mut conn, &["XREAD", "STREAMS", "s", "0"]).starts_with("*1\r\n*2\r\n$1\r\ns\r\n"));
+
+        let mut blocked = open();
+        let pop = std::thread::spawn(move || call(&mut blocked, &["BRPOP", "a", "q", "5"]));
+        let mut reader = open();
+        let read = std::thread::spawn(move || call(&mut reader, &["XREAD", "COUNT", "1", "BLOCK", "5000", "STREAMS", "s", "$"]));
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(call(&mut conn, &["RPUSH", "q", "x"]), ":1\r\n");
+        assert_eq!(pop.join().unwrap(), "*2\r\n$1\r\nq\r\n$1\r\nx\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "s", "2-*", "payload", "b"]), "$3\r\n2-0\r\n");
+        assert_eq!(
+            read.join().unwrap(),
+            "*1\r\n*2\r\n$1\r\ns\r\n*1\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$7\r\npayload\r\n$1\r\nb\r\n"
+        );
+    }
+
+    #[test]
+    fn list_commands() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["RPUSH", "q", "a", "b", "c"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["LPUSH", "q", "z"]), ":4\r\n");
+        assert_eq!(call(&mut conn, &["LLEN", "q"]), ":4\r\n");
+        assert_eq!(call(&mut conn, &["LINDEX", "q", "-1"]), "$1\r\nc\r\n");
+        assert_eq!(call(&mut conn, &["LSET", "q", "0", "y"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["LSET", "q", "9", "y"]), "-ERR index out of range\r\n");
+        assert_eq!(call(&mut conn, &["LINSERT", "q", "BEFORE", "b", "a2"]), ":5\r\n");
+        assert_eq!(call(&mut conn, &["LINSERT", "q", "AFTER", "nope", "x"]), ":-1\r\n");
+        assert_eq!(call(&mut conn, &["LREM", "q", "0", "a2"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["LTRIM", "q", "0", "2"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["LRANGE", "q", "0", "-1"]), "*3\r\n$1\r\ny\r\n$1\r\na\r\n$1\r\nb\r\n");
+        assert_eq!(call(&mut conn, &["LMOVE", "q", "done", "LEFT", "RIGHT"]), "$1\r\ny\r\n");
+        assert_eq!(call(&mut conn, &["RPOPLPUSH", "q", "done"]), "$1\r\nb\r\n");
+        assert_eq!(call(&mut conn, &["LPOP", "done", "5"]), "*2\r\n$1\r\nb\r\n$1\r\ny\r\n");
+        assert_eq!(call(&mut conn, &["RPOP", "q"]), "$1\r\na\r\n");
+        assert_eq!(call(&mut conn, &["LPOP", "q"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["EXISTS", "q", "done"]), ":0\r\n");
+        assert_eq!(call(&mut conn, &["LSET", "q", "0", "y"]), "-ERR no such key\r\n");
+    }
+
+    #[test]
+    fn hash_commands() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["HMSET", "h", "a", "1", "b", "2"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["HSETNX", "h", "a", "9"]), ":0\r\n");
+        assert_eq!(call(&mut conn, &["HSETNX", "h", "c", "x"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["HMGET", "h", "a", "nope", "c"]), "*3\r\n$1\r\n1\r\n$-1\r\n$1\r\nx\r\n");
+        assert_eq!(call(&mut conn, &["HINCRBY", "h", "a", "41"]), ":42\r\n");
+        assert_eq!(call(&mut conn, &["HINCRBY", "h", "c", "1"]), "-ERR hash value is not an integer\r\n");
+        assert_eq!(call(&mut conn, &["HINCRBYFLOAT", "h", "b", "0.5"]), "$3\r\n2.5\r\n");
+        assert_eq!(call(&mut conn, &["HEXISTS", "h", "b"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["HLEN", "h"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["HDEL", "h", "b", "c", "nope"]), ":2\r\n");
+        assert_eq!(call(&mut conn, &["HKEYS", "h"]), "*1\r\n$1\r\na\r\n");
+        assert_eq!(call(&mut conn, &["HVALS", "h"]), "*1\r\n$2\r\n42\r\n");
+        assert_eq!(call(&mut conn, &["HDEL", "h", "a"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["EXISTS", "h"]), ":0\r\n");
+        assert_eq!(call(&mut conn, &["HKEYS", "h"]), "*0\r\n");
+    }
+
+    // Like `call` with raw byte arguments; reads exactly `expected.len()` bytes of reply.
+    fn call_raw(conn: &mut (TcpStream, BufReader<TcpStream>), args: &[&[u8]], expected: &[u8]) -> Vec<u8> {
+        let mut req = format!("*{}\r\n", args.len()).into_bytes();
+        for a in args {
+            req.extend_from_slice(format!("${}\r\n", a.len()).as_bytes());
+            req.extend_from_slice(a);
+            req.extend_from_slice(b"\r\n");
+        }
+        conn.0.write_all(&req).unwrap();
+        let mut out = vec![0; expected.len()];
+        conn.1.read_exact(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn binary_fields_and_members() {
+        let mut conn = connect();
+        let field: &[u8] = b"\xff\x00f";
+        let expect = |conn: &mut _, args: &[&[u8]], reply: &[u8]| assert_eq!(call_raw(conn, args, reply), reply);
+        expect(&mut conn, &[b"HSET", b"h", field, b"v"], b":1\r\n");
+        expect(&mut conn, &[b"HGET", b"h", field], b"$1\r\nv\r\n");
+        expect(&mut conn, &[b"HKEYS", b"h"], b"*1\r\n$3\r\n\xff\x00f\r\n");
+        expect(&mut conn, &[b"ZADD", b"z", b"1", field], b":1\r\n");
+        expect(&mut conn, &[b"ZRANGEBYLEX", b"z", b"[\xff", b"+"], b"*1\r\n$3\r\n\xff\x00f\r\n");
+        expect(&mut conn, &[b"ZSCORE", b"z", field], b"$1\r\n1\r\n");
+        // The field's lossy UTF-8 decoding is another field.
+        expect(&mut conn, &[b"HEXISTS", b"h", "\u{fffd}\0f".as_bytes()], b":0\r\n");
+        expect(&mut conn, &[b"ZREM", b"z", field], b":1\r\n");
+    }
+
+    #[test]
+    fn set_commands() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["SADD", "a", "1", "2", "3"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["SADD", "b", "2", "3", "4"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["SMISMEMBER", "a", "1", "4"]), "*2\r\n:1\r\n:0\r\n");
+        assert_eq!(call(&mut conn, &["SREM", "a", "3", "nope"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["SCARD", "a"]), ":2\r\n");
+        assert_eq!(call(&mut conn, &["SDIFF", "a", "b"]), "*1\r\n$1\r\n1\r\n");
+        assert_eq!(call(&mut conn, &["SINTER", "a", "b"]), "*1\r\n$1\r\n2\r\n");
+        assert_eq!(call(&mut conn, &["SUNIONSTORE", "u", "a", "b"]), ":4\r\n");
+        assert_eq!(call(&mut conn, &["SINTERSTORE", "i", "a", "missing"]), ":0\r\n");
+        assert_eq!(call(&mut conn, &["EXISTS", "i"]), ":0\r\n");
+        assert_eq!(call(&mut conn, &["SDIFFSTORE", "d", "b", "a"]), ":2\r\n");
+        assert_eq!(call(&mut conn, &["SREM", "d", "3"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["SMEMBERS", "d"]), "*1\r\n$1\r\n4\r\n");
+        assert_eq!(call(&mut conn, &["SRANDMEMBER", "d"]), "$1\r\n4\r\n");
+        assert_eq!(call(&mut conn, &["SRANDMEMBER", "d", "-2"]), "*2\r\n$1\r\n4\r\n$1\r\n4\r\n");
+        assert_eq!(call(&mut conn, &["SPOP", "d", "-1"]), "-ERR value is out of range, must be positive\r\n");
+        assert_eq!(call(&mut conn, &["SPOP", "d"]), "$1\r\n4\r\n");
+        assert_eq!(call(&mut conn, &["SPOP", "d"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["SPOP", "d", "3"]), "*0\r\n");
+        assert_eq!(call(&mut conn, &["SMEMBERS", "d"]), "*0\r\n");
+        assert_eq!(call(&mut conn, &["SET", "s", "v"]), "+OK\r\n");
+        assert!(call(&mut conn, &["SUNION", "a", "s"]).starts_with("-WRONGTYPE"));
+    }
+
+    #[test]
+    fn consumer_group_commands() {
+        let mut conn = connect();
+        assert!(call(&mut conn, &["XGROUP", "CREATE", "s", "g", "$"]).starts_with("-ERR The XGROUP subcommand requires the key to exist."));
+        assert_eq!(call(&mut conn, &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["XGROUP", "CREATE", "s", "g", "0"]), "-BUSYGROUP Consumer Group name already exists\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "s", "1-0", "payload", "a"]), "$3\r\n1-0\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "s", "2-*", "payload", "b"]), "$3\r\n2-0\r\n");
+        let entry = |id: &str, payload: &str| format!("*2\r\n$3\r\n{}\r\n*2\r\n$7\r\npayload\r\n$1\r\n{}\r\n", id, payload);
+        assert_eq!(
+            call(&mut conn, &["XREADGROUP", "GROUP", "g", "alice", "COUNT", "1", "STREAMS", "s", ">"]),
+            format!("*1\r\n*2\r\n$1\r\ns\r\n*1\r\n{}", entry("1-0", "a"))
+        );
+        assert_eq!(
+            call(&mut conn, &["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", ">"]),
+            format!("*1\r\n*2\r\n$1\r\ns\r\n*1\r\n{}", entry("2-0", "b"))
+        );
+        assert_eq!(call(&mut conn, &["XREADGROUP", "GROUP", "g", "bob", "BLOCK", "20", "STREAMS", "s", ">"]), "*-1\r\n");
+        assert_eq!(call(&mut conn, &["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", "0"]), format!("*1\r\n*2\r\n$1\r\ns\r\n*1\r\n{}", entry("2-0", "b")));
+        assert_eq!(call(&mut conn, &["XREADGROUP", "GROUP", "nope", "bob", "STREAMS", "s", ">"]), "-NOGROUP No such key or consumer group\r\n");
+        assert_eq!(
+            call(&mut conn, &["XPENDING", "s", "g"]),
+            "*4\r\n:2\r\n$3\r\n1-0\r\n$3\r\n2-0\r\n*2\r\n*2\r\n$5\r\nalice\r\n$1\r\n1\r\n*2\r\n$3\r\nbob\r\n$1\r\n1\r\n"
+        );
+        assert!(call(&mut conn, &["XPENDING", "s", "g", "-", "+", "10", "bob"]).starts_with("*1\r\n*4\r\n$3\r\n2-0\r\n$3\r\nbob\r\n:"));
+        assert_eq!(call(&mut conn, &["XPENDING", "s", "g", "IDLE", "60000", "-", "+", "10"]), "*0\r\n");
+        assert_eq!(call(&mut conn, &["XCLAIM", "s", "g", "carol", "0", "1-0", "JUSTID"]), "*1\r\n$3\r\n1-0\r\n");
+        assert_eq!(
+            call(&mut conn, &["XAUTOCLAIM", "s", "g", "carol", "0", "0", "COUNT", "1"]),
+            format!("*3\r\n$3\r\n2-0\r\n*1\r\n{}*0\r\n", entry("1-0", "a"))
+        );
+        assert_eq!(call(&mut conn, &["XACK", "s", "g", "1-0", "2-0", "9-0"]), ":2\r\n");
+        assert_eq!(call(&mut conn, &["XPENDING", "s", "g"]), "*4\r\n:0\r\n$-1\r\n$-1\r\n*-1\r\n");
+        assert_eq!(call(&mut conn, &["XGROUP", "DESTROY", "s", "g"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["XGROUP", "DESTROY", "s", "g"]), ":0\r\n");
+    }
+
+    #[test]
+    fn stream_trim_and_delete_commands() {
+        let mut conn = connect();
+        let id = |id: &str| format!("${}\r\n{}\r\n", id.len(), id);
+        assert_eq!(call(&mut conn, &["XADD", "s", "5-1", "a"]), id("5-1"));
+        assert_eq!(call(&mut conn, &["XADD", "s", "5-*", "payload", "b"]), id("5-2"));
+        assert_eq!(call(&mut conn, &["XADD", "s", "MAXLEN", "=", "3", "6", "c"]), id("6-0"));
+        assert!(call(&mut conn, &["XADD", "s", "6-0", "d"]).starts_with("-ERR The ID specified in XADD is equal or smaller"));
+        assert!(call(&mut conn, &["XADD", "s", "0-0", "d"]).starts_with("-ERR The ID specified in XADD must be greater than 0-0"));
+        assert_eq!(call(&mut conn, &["XADD", "s", "7-x", "d"]), "-ERR Invalid stream ID specified as stream command argument\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "none", "NOMKSTREAM", "*", "d"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "s", "MAXLEN", "3", "LIMIT", "1", "*", "d"]), "-ERR syntax error, LIMIT cannot be used without the special ~ option\r\n");
+        assert_eq!(call(&mut conn, &["XLEN", "s"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["XLEN", "none"]), ":0\r\n");
+
+        let entry = |i: &str, payload: &str| format!("*2\r\n{}*2\r\n$7\r\npayload\r\n$1\r\n{}\r\n", id(i), payload);
+        assert_eq!(call(&mut conn, &["XRANGE", "s", "(5-1", "5"]), format!("*1\r\n{}", entry("5-2", "b")));
+        assert_eq!(call(&mut conn, &["XREVRANGE", "s", "+", "-", "COUNT", "2"]), format!("*2\r\n{}{}", entry("6-0", "c"), entry("5-2", "b")));
+        assert_eq!(call(&mut conn, &["XDEL", "s", "5-2", "9-9"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["XTRIM", "s", "MINID", "~", "6", "LIMIT", "10"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["XTRIM", "s", "MAXLEN", "0"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["XTRIM", "s", "MAXLEN", "-1"]), "-ERR The MAXLEN argument must be >= 0.\r\n");
+        assert_eq!(call(&mut conn, &["XTRIM", "s"]), "-ERR syntax error\r\n");
+        assert_eq!(call(&mut conn, &["XRANGE", "s", "-", "+"]), "*0\r\n");
+        // An emptied stream still issues ids above its last one.
+        assert!(call(&mut conn, &["XADD", "s", "6-0", "e"]).starts_with("-ERR The ID specified in XADD is equal or smaller"));
+    }
+
+    #[test]
+    fn multi_key_commands() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["MSET", "m1", "a", "m2", "b"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["MSET", "m1"]), "-ERR wrong number of arguments for 'mset' command\r\n");
+        assert_eq!(call(&mut conn, &["RPUSH", "ml", "x"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["MGET", "m1", "ml", "none", "m2"]), "*4\r\n$1\r\na\r\n$-1\r\n$-1\r\n$1\r\nb\r\n");
+        assert_eq!(call(&mut conn, &["MSETNX", "m3", "c", "ml", "d"]), ":0\r\n");
+        assert_eq!(call(&mut conn, &["MSETNX", "m3", "c", "m4", "d"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["DEL", "m1", "m1", "ml", "none"]), ":2\r\n");
+        assert_eq!(call(&mut conn, &["UNLINK", "m2", "m3", "m4"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["MGET", "m1", "m2"]), "*2\r\n$-1\r\n$-1\r\n");
+    }
+
+    #[test]
+    fn set_options() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["SET", "lock", "a", "NX", "PX", "60000"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["SET", "lock", "b", "NX"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["SET", "lock", "b", "XX", "GET", "KEEPTTL"]), "$1\r\na\r\n");
+        let ttl: i64 = call(&mut conn, &["PTTL", "lock"]).trim()[1..].parse().unwrap();
+        assert!(ttl > 50_000 && ttl <= 60_000);
+        assert_eq!(call(&mut conn, &["SET", "none", "v", "XX"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["SET", "none", "v", "GET"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["SET", "at", "v", "PXAT", "4102444800000"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["SET", "past", "v", "EXAT", "1"]), "+OK\r\n");
+        assert_eq!(call(&mut conn, &["GET", "past"]), "$-1\r\n");
+        for bad in [&["NX", "XX"][..], &["EX", "1", "KEEPTTL"], &["PX", "1", "EX", "1"], &["EX"]] {
+            let args = [&["SET", "k", "v"][..], bad].concat();
+            assert_eq!(call(&mut conn, &args), "-ERR syntax error\r\n");
+        }
+        assert_eq!(call(&mut conn, &["SET", "k", "v", "EXAT", "0"]), "-ERR invalid expire time in 'set' command\r\n");
+        assert_eq!(call(&mut conn, &["RPUSH", "l", "x"]), ":1\r\n");
+        assert!(call(&mut conn, &["SET", "l", "v", "GET"]).starts_with("-WRONGTYPE"));
+    }
+
+    #[test]
+    fn string_counter_and_range_commands() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["INCR", "n"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["INCRBY", "n", "9"]), ":10\r\n");
+        assert_eq!(call(&mut conn, &["DECR", "n"]), ":9\r\n");
+        assert_eq!(call(&mut conn, &["DECRBY", "n", "10"]), ":-1\r\n");
+        assert_eq!(call(&mut conn, &["DECRBY", "n", "-9223372036854775808"]), "-ERR decrement would overflow\r\n");
+        assert_eq!(call(&mut conn, &["INCRBY", "n"]), "-ERR wrong number of arguments for 'incrby' command\r\n");
+        assert_eq!(call(&mut conn, &["INCRBYFLOAT", "n", "1.5"]), "$3\r\n0.5\r\n");
+        assert_eq!(call(&mut conn, &["INCR", "n"]), "-ERR value is not an integer or out of range\r\n");
+
+        assert_eq!(call(&mut conn, &["APPEND", "s", "Hello"]), ":5\r\n");
+        assert_eq!(call(&mut conn, &["SETRANGE", "s", "5", " World"]), ":11\r\n");
+        assert_eq!(call(&mut conn, &["SETRANGE", "s", "-1", "x"]), "-ERR offset is out of range\r\n");
+        assert_eq!(call(&mut conn, &["GETRANGE", "s", "-5", "-1"]), "$5\r\nWorld\r\n");
+        assert_eq!(call(&mut conn, &["STRLEN", "s"]), ":11\r\n");
+        assert_eq!(call(&mut conn, &["GETSET", "s", "v"]), "$11\r\nHello World\r\n");
+        assert_eq!(call(&mut conn, &["GETDEL", "s"]), "$1\r\nv\r\n");
+        assert_eq!(call(&mut conn, &["GETDEL", "s"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["GETRANGE", "s", "0", "-1"]), "$0\r\n\r\n");
+    }
+
+    #[test]
+    fn stream_entries_with_several_fields() {
+        let mut conn = connect();
+        assert_eq!(call(&mut conn, &["XADD", "s", "1-0", "temp", "21", "hum", "60"]), "$3\r\n1-0\r\n");
+        assert_eq!(call(&mut conn, &["XADD", "s", "2-0", "temp", "22", "hum"]), "-ERR wrong number of arguments for 'xadd' command\r\n");
+        let expected = "*1\r\n*2\r\n$3\r\n1-0\r\n*4\r\n$4\r\ntemp\r\n$2\r\n21\r\n$3\r\nhum\r\n$2\r\n60\r\n";
+        assert_eq!(call(&mut conn, &["XRANGE", "s", "-", "+"]), expected);
+    }
+
+    #[test]
+    fn sorted_set_commands() {
+        let mut conn = connect();
+        let bulks = |items: &[&str]| {
+            let mut out = format!("*{}\r\n", items.len());
+            for i in items {
+                out.push_str(&format!("${}\r\n{}\r\n", i.len(), i));
+            }
+            out
+        };
+        assert_eq!(call(&mut conn, &["ZADD", "z", "1", "a", "2", "b", "3", "c"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["ZADD", "z", "XX", "CH", "5", "a", "9", "x"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["ZADD", "z", "GT", "CH", "1", "b", "4", "c"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["ZADD", "z", "NX", "XX", "1", "a"]), "-ERR XX and NX options at the same time are not compatible\r\n");
+        assert_eq!(call(&mut conn, &["ZADD", "z", "NX", "1"]), "-ERR syntax error\r\n");
+        assert_eq!(call(&mut conn, &["ZINCRBY", "z", "2.5", "b"]), "$3\r\n4.5\r\n");
+        assert_eq!(call(&mut conn, &["ZSCORE", "z", "c"]), "$1\r\n4\r\n");
+        assert_eq!(call(&mut conn, &["ZSCORE", "z", "x"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["ZRANK", "z", "b"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["ZREVRANK", "z", "b"]), ":1\r\n");
+        assert_eq!(call(&mut conn, &["ZRANK", "z", "x"]), "$-1\r\n");
+        assert_eq!(call(&mut conn, &["ZCARD", "z"]), ":3\r\n");
+
+        assert_eq!(call(&mut conn, &["ZRANGE", "z", "0", "-1", "WITHSCORES"]), bulks(&["c", "4", "b", "4.5", "a", "5"]));
+        assert_eq!(call(&mut conn, &["ZREVRANGE", "z", "0", "0"]), bulks(&["a"]));
+        assert_eq!(call(&mut conn, &["ZRANGEBYSCORE", "z", "(4", "+inf"]), bulks(&["b", "a"]));
+        assert_eq!(call(&mut conn, &["ZREVRANGEBYSCORE", "z", "+inf", "-inf", "LIMIT", "1", "1", "WITHSCORES"]), bulks(&["b", "4.5"]));
+        assert_eq!(call(&mut conn, &["ZRANGE", "z", "5", "(4", "BYSCORE", "REV"]), bulks(&["a", "b"]));
+        assert_eq!(call(&mut conn, &["ZRANGEBYSCORE", "z", "x", "1"]), "-ERR min or max is not a float\r\n");
+        assert_eq!(call(&mut conn, &["ZRANGE", "z", "0", "1", "LIMIT", "0", "1"]), "-ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX\r\n");
+
+        assert_eq!(call(&mut conn, &["ZADD", "lex", "0", "a", "0", "b", "0", "c", "0", "d"]), ":4\r\n");
+        assert_eq!(call(&mut conn, &["ZRANGEBYLEX", "lex", "[b", "(d"]), bulks(&["b", "c"]));
+        assert_eq!(call(&mut conn, &["ZREVRANGEBYLEX", "lex", "+", "-", "LIMIT", "0", "2"]), bulks(&["d", "c"]));
+        assert_eq!(call(&mut conn, &["ZRANGE", "lex", "-", "[b", "BYLEX"]), bulks(&["a", "b"]));
+        assert_eq!(call(&mut conn, &["ZRANGEBYLEX", "lex", "b", "+"]), "-ERR min or max not valid string range item\r\n");
+
+        assert_eq!(call(&mut conn, &["ZREM", "z", "a", "nope", "b", "c"]), ":3\r\n");
+        assert_eq!(call(&mut conn, &["EXISTS", "z"]), ":0\r\n");
+    }
+}
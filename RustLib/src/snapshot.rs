@@ -0,0 +1,641 @@
+// Point-in-time binary snapshot (RDB-style) of the whole cache state.
+//
+// Layout (little-endian):
+//   [Magic "RCSNAPSH"] [Version u32] [CreatedAtMs u64]
+//   [IndexCount u32] { [FieldLen u32] [Field] }
+//   [EntryCount u64] { [KeyLen u32] [Key] [ExpiresAtMs u64 (0 = none)] [Value] }
+//   [Crc32 u32]  // over every preceding byte
+//
+// Value: [Tag u8] followed by
+//   Bytes:     [Len u32] [Bytes]
+//   Hash:      [Count u32] { [FieldLen u32] [Field] [ValLen u32] [Val] }
+//   List:      [Count u32] { [Len u32] [Item] }
+//   Set:       [Count u32] { [Len u32] [Member] }
+//   SortedSet: [Count u32] { [MemberLen u32] [Member] [Score f64] }
+//   Stream:    [LastId] [Count u32] { [Id] [FieldCount u32] { [NameLen u32] [Name] [ValLen u32] [Val] } }
+//              [GroupCount u32] { [NameLen u32] [Name] [LastDeliveredId]
+//                [PendingCount u32] { [Id] [ConsumerLen u32] [Consumer] [DeliveredAtMs u64] [Deliveries u64] } }
+//   Stream ids are [Ms u64] [Seq u64].
+//
+// Older files are still read. Before version 5 string and binary keys were two entry lists,
+// string keys first; both are loaded into the one keyspace, so a binary key replaces a string
+// key with the same bytes. Before version 4 a stream entry was a single [PayloadLen u32]
+// [Payload], loaded as field `payload`. Before version 3 the header carried a [NextStreamId u64] after
+// CreatedAtMs and stream ids were a single u64 (loaded as `<id>-0`, with the newest entry as
+// the last id); version 1 has no consumer groups after the stream entries.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::zset::SortedSet;
+use crate::{
+    aof_enabled, aof_encode_entry, aof_rewrite, aof_write, apply_clear_internal, cache_read_all, cache_write_all,
+    is_expired, put_entry_with_lru, rebuild_numeric_index, CacheError, ConsumerGroup, Entry, Instance, PendingEntry,
+    StreamData, StreamId, Value, AOF_OP_CLEAR, STREAM_PAYLOAD_FIELD,
+};
+
+const MAGIC: &[u8; 8] = b"RCSNAPSH";
+const VERSION: u32 = 5;
+// Before the single keyspace.
+const VERSION_SPLIT_KEYS: u32 = 4;
+// Before field/value stream entries.
+const VERSION_PAYLOADS: u32 = 3;
+// Before `<ms>-<seq>` stream ids.
+const VERSION_U64_IDS: u32 = 2;
+// Before stream consumer groups.
+const VERSION_NO_GROUPS: u32 = 1;
+
+const TAG_BYTES: u8 = 1;
+const TAG_HASH: u8 = 2;
+const TAG_LIST: u8 = 3;
+const TAG_SET: u8 = 4;
+const TAG_SORTED_SET: u8 = 5;
+const TAG_STREAM: u8 = 6;
+
+struct Snapshot {
+    index_fields: Vec<String>,
+    entries: Vec<(Vec<u8>, Entry)>,
+}
+
+// --- CRC32 (IEEE) ---
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut c = 0xFFFF_FFFFu32;
+    for b in data {
+        c = CRC32_TABLE[((c ^ *b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    c ^ 0xFFFF_FFFF
+}
+
+// --- Save ---
+
+fn put_bytes(out: &mut Vec<u8>, b: &[u8]) {
+    out.extend_from_slice(&(b.len() as u32).to_le_bytes());
+    out.extend_from_slice(b);
+}
+
+fn put_id(out: &mut Vec<u8>, id: StreamId) {
+    out.extend_from_slice(&id.ms.to_le_bytes());
+    out.extend_from_slice(&id.seq.to_le_bytes());
+}
+
+fn encode_entry(out: &mut Vec<u8>, key: &[u8], entry: &Entry) {
+    put_bytes(out, key);
+    out.extend_from_slice(&entry.expires_at_ms.unwrap_or(0).to_le_bytes());
+    match &entry.value {
+        Value::Bytes(val) => {
+            out.push(TAG_BYTES);
+            put_bytes(out, val);
+        }
+        Value::Hash(hmap) => {
+            out.push(TAG_HASH);
+            out.extend_from_slice(&(hmap.len() as u32).to_le_bytes());
+            for (field, val) in hmap {
+                put_bytes(out, field);
+                put_bytes(out, val);
+            }
+        }
+        Value::List(list) => {
+            out.push(TAG_LIST);
+            out.extend_from_slice(&(list.len() as u32).to_le_bytes());
+            for item in list {
+                put_bytes(out, item);
+            }
+        }
+        Value::Set(set) => {
+            out.push(TAG_SET);
+            out.extend_from_slice(&(set.len() as u32).to_le_bytes());
+            for member in set {
+                put_bytes(out, member);
+            }
+        }
+        Value::SortedSet(ss) => {
+            out.push(TAG_SORTED_SET);
+            out.extend_from_slice(&(ss.len() as u32).to_le_bytes());
+            for (member, score) in ss.iter() {
+                put_bytes(out, member);
+                out.extend_from_slice(&score.to_le_bytes());
+            }
+        }
+        Value::Stream(stream) => {
+            out.push(TAG_STREAM);
+            put_id(out, stream.last_id);
+            out.extend_from_slice(&(stream.entries.len() as u32).to_le_bytes());
+            for (id, entry) in &stream.entries {
+                put_id(out, *id);
+                out.extend_from_slice(&(entry.values.len() as u32).to_le_bytes());
+                for (name, value) in entry.names.iter().zip(&entry.values) {
+                    put_bytes(out, name);
+                    put_bytes(out, value);
+                }
+            }
+            out.extend_from_slice(&(stream.groups.len() as u32).to_le_bytes());
+            for (name, group) in &stream.groups {
+                put_bytes(out, name.as_bytes());
+                put_id(out, group.last_delivered);
+                out.extend_from_slice(&(group.pending.len() as u32).to_le_bytes());
+                for (id, p) in &group.pending {
+                    put_id(out, *id);
+                    put_bytes(out, p.consumer.as_bytes());
+                    out.extend_from_slice(&p.delivered_at_ms.to_le_bytes());
+                    out.extend_from_slice(&p.deliveries.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+fn encode(snap: &Snapshot) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&crate::now_ms().to_le_bytes());
+
+    out.extend_from_slice(&(snap.index_fields.len() as u32).to_le_bytes());
+    for field in &snap.index_fields {
+        put_bytes(&mut out, field.as_bytes());
+    }
+
+    out.extend_from_slice(&(snap.entries.len() as u64).to_le_bytes());
+    for (key, entry) in &snap.entries {
+        encode_entry(&mut out, key, entry);
+    }
+
+    let crc = crc32(&out);
+    out.extend_from_slice(&crc.to_le_bytes());
+    out
+}
+
+/// Writes a snapshot of the current state to `path` (via a temp file + rename).
+/// The shard locks are only held (shared) while entries are copied, not during encoding or I/O.
+pub(crate) fn save(db: &Instance, path: &str) -> std::io::Result<()> {
+    let snap = {
+        let shards = cache_read_all(db);
+        // Oldest first, so loading restores each shard's LRU order.
+        Snapshot {
+            index_fields: db.cache.numeric_indexes.read().unwrap().keys().cloned().collect(),
+            entries: shards
+                .iter()
+                .flat_map(|s| s.map.iter().rev())
+                .filter(|(_, v)| !is_expired(v))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    };
+
+    let bytes = encode(&snap);
+    let tmp_path = format!("{}.tmp", path);
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+// --- Load ---
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let out = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(out)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    // A stream id; files before version 3 store it as one u64, the millisecond part.
+    fn stream_id(&mut self, version: u32) -> Option<StreamId> {
+        let ms = self.u64()?;
+        let seq = if version <= VERSION_U64_IDS { 0 } else { self.u64()? };
+        Some(StreamId { ms, seq })
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Some(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?).ok()
+    }
+
+    // Element counts are bounded by the remaining input so corrupt files cannot force huge allocations.
+    fn count(&mut self, min_elem_size: usize) -> Option<usize> {
+        let n = self.u32()? as usize;
+        if n.checked_mul(min_elem_size)? > self.buf.len() - self.pos {
+            return None;
+        }
+        Some(n)
+    }
+}
+
+fn decode_groups(c: &mut Cursor, version: u32) -> Option<BTreeMap<String, ConsumerGroup>> {
+    let n = c.count(16)?;
+    let mut groups = BTreeMap::new();
+    for _ in 0..n {
+        let name = c.string()?;
+        let last_delivered = c.stream_id(version)?;
+        let pending_count = c.count(28)?;
+        let mut pending = BTreeMap::new();
+        for _ in 0..pending_count {
+            let id = c.stream_id(version)?;
+            let consumer = c.string()?;
+            pending.insert(id, PendingEntry { consumer, delivered_at_ms: c.u64()?, deliveries: c.u64()? });
+        }
+        groups.insert(name, ConsumerGroup { last_delivered, pending });
+    }
+    Some(groups)
+}
+
+fn decode_value(c: &mut Cursor, version: u32) -> Option<Value> {
+    let value = match c.u8()? {
+        TAG_BYTES => Value::Bytes(Arc::new(c.bytes()?)),
+        TAG_HASH => {
+            let n = c.count(8)?;
+            let mut hmap = HashMap::with_capacity(n);
+            for _ in 0..n {
+                let field = c.bytes()?;
+                hmap.insert(field, c.bytes()?);
+            }
+            Value::Hash(hmap)
+        }
+        TAG_LIST => {
+            let n = c.count(4)?;
+            let mut list = VecDeque::with_capacity(n);
+            for _ in 0..n {
+                list.push_back(c.bytes()?);
+            }
+            Value::List(list)
+        }
+        TAG_SET => {
+            let n = c.count(4)?;
+            let mut set = HashSet::with_capacity(n);
+            for _ in 0..n {
+                set.insert(c.bytes()?);
+            }
+            Value::Set(set)
+        }
+        TAG_SORTED_SET => {
+            let n = c.count(12)?;
+            let mut ss = SortedSet::default();
+            for _ in 0..n {
+                let member = c.bytes()?;
+                let score = c.f64()?;
+                if score.is_nan() {
+                    return None;
+                }
+                ss.insert(member, score);
+            }
+            Value::SortedSet(ss)
+        }
+        TAG_STREAM => {
+            let stored_last_id = if version <= VERSION_U64_IDS { None } else { Some(c.stream_id(version)?) };
+            let n = c.count(12)?;
+            let mut stream = StreamData::default();
+            for _ in 0..n {
+                let id = c.stream_id(version)?;
+                let fields = if version <= VERSION_PAYLOADS {
+                    vec![(STREAM_PAYLOAD_FIELD.to_vec(), c.bytes()?)]
+                } else {
+                    let count = c.count(8)?;
+                    let mut fields = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        fields.push((c.bytes()?, c.bytes()?));
+                    }
+                    fields
+                };
+                if fields.is_empty() {
+                    return None;
+                }
+                stream.insert(id, fields);
+            }
+            if let Some(last_id) = stored_last_id {
+                stream.last_id = last_id;
+            }
+            if version != VERSION_NO_GROUPS {
+                stream.groups = decode_groups(c, version)?;
+            }
+            Value::Stream(stream)
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn decode_entry(c: &mut Cursor, version: u32) -> Option<(Vec<u8>, Entry)> {
+    let key = c.bytes()?;
+    let expires_at_ms = match c.u64()? {
+        0 => None,
+        t => Some(t),
+    };
+    let value = decode_value(c, version)?;
+    Some((key, Entry::new(value, expires_at_ms)))
+}
+
+fn decode(buf: &[u8]) -> Option<Snapshot> {
+    if buf.len() < MAGIC.len() + 4 + 4 || &buf[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    let (body, crc_bytes) = buf.split_at(buf.len() - 4);
+    if crc32(body) != u32::from_le_bytes(crc_bytes.try_into().ok()?) {
+        return None;
+    }
+
+    let mut c = Cursor { buf: body, pos: MAGIC.len() };
+    let version = c.u32()?;
+    if !(VERSION_NO_GROUPS..=VERSION).contains(&version) {
+        return None;
+    }
+    let _created_at_ms = c.u64()?;
+    if version <= VERSION_U64_IDS {
+        let _next_stream_id = c.u64()?;
+    }
+
+    let n = c.count(4)?;
+    let mut index_fields = Vec::with_capacity(n);
+    for _ in 0..n {
+        index_fields.push(c.string()?);
+    }
+
+    let lists = if version <= VERSION_SPLIT_KEYS { 2 } else { 1 };
+    let mut entries = Vec::new();
+    for _ in 0..lists {
+        let n = c.u64()?;
+        for _ in 0..n {
+            entries.push(decode_entry(&mut c, version)?);
+        }
+    }
+
+    if c.pos != body.len() {
+        return None;
+    }
+    Some(Snapshot { index_fields, entries })
+}
+
+/// Replaces the current state with the snapshot at `path`. The file is fully validated
+/// (magic, version, checksum, structure) before anything is applied. With the AOF enabled,
+/// the loaded state is logged under the same locks and the AOF is then compacted.
+pub(crate) fn load(db: &Instance, path: &str) -> Result<(), CacheError> {
+    let buf = std::fs::read(path)?;
+    let snap = decode(&buf).ok_or_else(|| CacheError::InvalidArgument("corrupt or unsupported snapshot".to_string()))?;
+
+    let logged;
+    {
+        let mut state = cache_write_all(db);
+        logged = aof_enabled(db);
+        apply_clear_internal(db, &mut state);
+        let mut log = if logged { vec![AOF_OP_CLEAR] } else { Vec::new() };
+        for (key, entry) in snap.entries {
+            if !is_expired(&entry) {
+                if logged {
+                    aof_encode_entry(&mut log, key.as_slice(), &entry);
+                }
+                put_entry_with_lru(db, state.shard(&key), key.as_slice(), entry);
+            }
+        }
+        if logged {
+            aof_write(db, &log);
+        }
+    }
+    if logged {
+        // Everything before the CLEAR record is dead weight now.
+        let _ = aof_rewrite(db);
+    }
+    // Indexes are rebuilt shard by shard, so this needs the shard locks released.
+    for field in snap.index_fields {
+        rebuild_numeric_index(db, field);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache_aof_disable, cache_aof_enable, cache_aof_flush, cache_aof_load, cache_len, cmd_clear_all, cmd_get,
+        cmd_hgetall, cmd_lrange, cmd_set, cmd_set_with_ttl, cmd_sismember, cmd_ttl, cmd_xadd, cmd_xrange, cmd_zadd,
+        cmd_zrange, db, temp_path, test_reset,
+    };
+    use std::ffi::CString;
+
+    fn fill() {
+        cmd_set(db(), "s", b"value".to_vec()).unwrap();
+        cmd_set_with_ttl(db(), "ttl", b"v".to_vec(), 60_000).unwrap();
+        crate::cmd_hset(db(), "h", vec![(b"f".to_vec(), b"1".to_vec()), (b"\0\xff".to_vec(), b"2".to_vec())]).unwrap();
+        crate::cmd_lpush(db(), "l", vec![b"a".to_vec(), b"b".to_vec()]).unwrap();
+        crate::cmd_sadd(db(), "set", vec![b"m".to_vec()]).unwrap();
+        cmd_zadd(db(), "z", vec![(2.5, b"m".to_vec()), (3.0, b"\xffm\0".to_vec())]).unwrap();
+        cmd_xadd(db(), "x", b"payload".to_vec()).unwrap();
+        crate::cmd_xgroup_create(db(), "x", "g", Some(StreamId::MIN), false).unwrap();
+        crate::cmd_xreadgroup(db(), "g", "c", &[("x".to_string(), None)], None, false).unwrap();
+        crate::cmd_xadd_with(db(), "x", crate::XAddId::Auto, stream_fields(), None, false).unwrap();
+    }
+
+    fn stream_fields() -> Vec<(Vec<u8>, Vec<u8>)> {
+        vec![(b"t".to_vec(), b"21".to_vec()), (b"h".to_vec(), Vec::new())]
+    }
+
+    fn check() {
+        assert_eq!(cmd_get(db(), "s").unwrap().as_deref(), Some(&b"value".to_vec()));
+        assert!(cmd_ttl(db(), "ttl") > 0);
+        let mut fields = cmd_hgetall(db(), "h").unwrap().unwrap();
+        fields.sort();
+        assert_eq!(fields, vec![(b"\0\xff".to_vec(), b"2".to_vec()), (b"f".to_vec(), b"1".to_vec())]);
+        assert_eq!(cmd_lrange(db(), "l", 0, -1), Ok(Some(vec![b"b".to_vec(), b"a".to_vec()])));
+        assert_eq!(cmd_sismember(db(), "set", b"m"), Ok(true));
+        assert_eq!(cmd_zrange(db(), "z", 0, -1, false), Ok(Some(vec![(b"m".to_vec(), 2.5), (b"\xffm\0".to_vec(), 3.0)])));
+        let entries = cmd_xrange(db(), "x", StreamId::MIN, StreamId::MAX, None, false).unwrap().unwrap();
+        let fields: Vec<_> = entries.into_iter().map(|(_, fields)| fields).collect();
+        assert_eq!(fields, vec![vec![(b"payload".to_vec(), b"payload".to_vec())], stream_fields()]);
+        let pending = crate::cmd_xpending(db(), "x", "g", StreamId::MIN, StreamId::MAX, 10, Some("c"), 0).unwrap();
+        assert_eq!(pending.iter().map(|p| p.deliveries).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(cache_len(), 7);
+    }
+
+    fn save_to(name: &str) -> (std::path::PathBuf, Vec<u8>) {
+        let path = temp_path(name);
+        save(db(), path.to_str().unwrap()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        (path, bytes)
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let _guard = test_reset();
+        fill();
+        let (path, _) = save_to("roundtrip.snap");
+        cmd_clear_all(db());
+        assert_eq!(load(db(), path.to_str().unwrap()), Ok(()));
+        check();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn corrupt_and_truncated_files_are_rejected() {
+        let _guard = test_reset();
+        fill();
+        let (path, bytes) = save_to("corrupt.snap");
+        cmd_set(db(), "after", b"1".to_vec()).unwrap();
+
+        let mut flipped = bytes.clone();
+        flipped[bytes.len() / 2] ^= 0x40;
+        let mut bad_version = bytes.clone();
+        bad_version[MAGIC.len()] = 99;
+        for bad in [flipped, bad_version, bytes[..bytes.len() - 1].to_vec(), bytes[..MAGIC.len()].to_vec()] {
+            std::fs::write(&path, &bad).unwrap();
+            assert!(load(db(), path.to_str().unwrap()).is_err());
+            // The current state is left untouched.
+            assert_eq!(cmd_get(db(), "after").unwrap().as_deref(), Some(&b"1".to_vec()));
+            assert_eq!(cache_len(), 8);
+        }
+        assert!(decode(&bytes).is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // A file in the layout before `<ms>-<seq>` ids holding stream "x" with one entry, 7 -> "p",
+    // and (from version 2) group "g" that delivered it to "c".
+    fn u64_id_file(version: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&8u64.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&1u64.to_le_bytes());
+        put_bytes(&mut out, b"x");
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.push(TAG_STREAM);
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&7u64.to_le_bytes());
+        put_bytes(&mut out, b"p");
+        if version != VERSION_NO_GROUPS {
+            out.extend_from_slice(&1u32.to_le_bytes());
+            put_bytes(&mut out, b"g");
+            out.extend_from_slice(&7u64.to_le_bytes());
+            out.extend_from_slice(&1u32.to_le_bytes());
+            out.extend_from_slice(&7u64.to_le_bytes());
+            put_bytes(&mut out, b"c");
+            out.extend_from_slice(&0u64.to_le_bytes());
+            out.extend_from_slice(&1u64.to_le_bytes());
+        }
+        out.extend_from_slice(&0u64.to_le_bytes());
+        let crc = crc32(&out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn older_files_load_with_u64_stream_ids() {
+        let _guard = test_reset();
+        let path = temp_path("u64ids.snap");
+        let id = StreamId::new(7, 0);
+        for version in [VERSION_NO_GROUPS, VERSION_U64_IDS] {
+            std::fs::write(&path, u64_id_file(version)).unwrap();
+            assert_eq!(load(db(), path.to_str().unwrap()), Ok(()));
+            assert_eq!(cmd_xrange(db(), "x", StreamId::MIN, StreamId::MAX, None, false), Ok(Some(vec![(id, vec![(b"payload".to_vec(), b"p".to_vec())])])));
+            let pending = crate::cmd_xpending(db(), "x", "g", StreamId::MIN, StreamId::MAX, 10, None, 0);
+            let expected = if version == VERSION_NO_GROUPS { 0 } else { 1 };
+            assert_eq!(pending.map(|p| p.len()).unwrap_or(0), expected);
+            // The newest loaded entry is the stream's last id.
+            let next = crate::cmd_xadd_with(db(), "x", crate::XAddId::AutoSeq(7), vec![(b"f".to_vec(), Vec::new())], None, false);
+            assert_eq!(next, Ok(Some(StreamId::new(7, 1))));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn split_key_files_load_into_one_keyspace() {
+        let _guard = test_reset();
+        let path = temp_path("split.snap");
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION_SPLIT_KEYS.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        // "abc" as a string key and as a binary key, plus a binary-only key.
+        for list in [&[(&b"abc"[..], &b"1"[..])][..], &[(b"abc", b"2"), (b"\xff", b"3")]] {
+            out.extend_from_slice(&(list.len() as u64).to_le_bytes());
+            for (key, val) in list {
+                put_bytes(&mut out, key);
+                out.extend_from_slice(&0u64.to_le_bytes());
+                out.push(TAG_BYTES);
+                put_bytes(&mut out, val);
+            }
+        }
+        let crc = crc32(&out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        std::fs::write(&path, out).unwrap();
+
+        assert_eq!(load(db(), path.to_str().unwrap()), Ok(()));
+        assert_eq!(cmd_get(db(), "abc").unwrap().as_deref(), Some(&b"2".to_vec()));
+        assert_eq!(cmd_get(db(), b"\xff".as_slice()).unwrap().as_deref(), Some(&b"3".to_vec()));
+        assert_eq!(cache_len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_is_persisted_to_an_enabled_aof() {
+        let _guard = test_reset();
+        fill();
+        let (snap_path, _) = save_to("aof.snap");
+        cmd_clear_all(db());
+        cmd_set(db(), "stale", b"1".to_vec()).unwrap();
+
+        let aof_path = CString::new(temp_path("snapshot.aof").to_str().unwrap()).unwrap();
+        assert_eq!(cache_aof_enable(aof_path.as_ptr()), 1);
+        assert_eq!(load(db(), snap_path.to_str().unwrap()), Ok(()));
+        assert_eq!(cache_aof_flush(), 1);
+        cache_aof_disable();
+
+        cmd_clear_all(db());
+        assert_eq!(cache_aof_load(aof_path.as_ptr()), 1);
+        check();
+        assert!(cmd_get(db(), "stale").unwrap().is_none());
+        let _ = std::fs::remove_file(&snap_path);
+        let _ = std::fs::remove_file(aof_path.to_str().unwrap());
+    }
+}
@@ -0,0 +1,503 @@
+// Sorted sets: a member -> score map plus a skiplist ordered by (score, member), as in Redis.
+//
+// Every forward link records how many nodes it skips (its span), so besides inserts and
+// removals, a member's rank and the node at a given rank are found in O(log n). Range queries
+// seek to their first node the same way and then walk level 0 (or the back links, reversed).
+// Nodes live in an arena indexed by position; removed slots are reused.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// Members with their scores, in range order.
+pub(crate) type ScoredMembers = Vec<(Vec<u8>, f64)>;
+
+const MAX_LEVEL: usize = 32;
+const NIL: usize = usize::MAX;
+const HEAD: usize = 0;
+
+#[derive(Clone)]
+struct Link {
+    next: usize,
+    span: usize,
+}
+
+#[derive(Clone)]
+struct Node {
+    member: Vec<u8>,
+    score: f64,
+    back: usize,
+    links: Vec<Link>,
+}
+
+impl Node {
+    fn cmp_to(&self, score: f64, member: &[u8]) -> Ordering {
+        // NaN scores are rejected before they get here.
+        self.score.partial_cmp(&score).unwrap_or(Ordering::Equal).then_with(|| self.member.as_slice().cmp(member))
+    }
+}
+
+#[derive(Clone)]
+struct SkipList {
+    // nodes[HEAD] is the header; it carries MAX_LEVEL links and no member.
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+    rng: u64,
+}
+
+impl Default for SkipList {
+    fn default() -> SkipList {
+        let head = Node {
+            member: Vec::new(),
+            score: 0.0,
+            back: NIL,
+            links: vec![Link { next: NIL, span: 0 }; MAX_LEVEL],
+        };
+        SkipList { nodes: vec![head], free: Vec::new(), level: 1, len: 0, rng: 0x2545_f491_4f6c_dd1d }
+    }
+}
+
+impl SkipList {
+    // Level for a new node: each extra level with probability 1/4.
+    fn random_level(&mut self) -> usize {
+        let mut level = 1;
+        loop {
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            if level >= MAX_LEVEL || self.rng & 3 != 0 {
+                return level;
+            }
+            level += 1;
+        }
+    }
+
+    fn next(&self, x: usize, level: usize) -> usize {
+        self.nodes[x].links[level].next
+    }
+
+    // Last node on each level that sorts before (score, member).
+    fn predecessors(&self, score: f64, member: &[u8]) -> [usize; MAX_LEVEL] {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.next(x, i);
+                if next == NIL || self.nodes[next].cmp_to(score, member) != Ordering::Less {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+        update
+    }
+
+    // The caller guarantees that (score, member) is not in the list yet.
+    fn insert(&mut self, score: f64, member: Vec<u8>) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0usize; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i + 1 == self.level { 0 } else { rank[i + 1] };
+            loop {
+                let next = self.next(x, i);
+                if next == NIL || self.nodes[next].cmp_to(score, &member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += self.nodes[x].links[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = self.random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].links[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node { member, score, back: NIL, links: Vec::with_capacity(level) };
+        let x = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for i in 0..level {
+            let prev = update[i];
+            let Link { next, span } = self.nodes[prev].links[i];
+            let skipped = rank[0] - rank[i];
+            self.nodes[x].links.push(Link { next, span: span - skipped });
+            self.nodes[prev].links[i] = Link { next: x, span: skipped + 1 };
+        }
+        for (i, &prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[prev].links[i].span += 1;
+        }
+
+        self.nodes[x].back = if update[0] == HEAD { NIL } else { update[0] };
+        let next = self.next(x, 0);
+        if next != NIL {
+            self.nodes[next].back = x;
+        }
+        self.len += 1;
+    }
+
+    fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let update = self.predecessors(score, member);
+        let x = self.next(update[0], 0);
+        if x == NIL || self.nodes[x].cmp_to(score, member) != Ordering::Equal {
+            return false;
+        }
+
+        for (i, &prev) in update.iter().enumerate().take(self.level) {
+            if self.next(prev, i) == x {
+                let Link { next, span } = self.nodes[x].links[i];
+                self.nodes[prev].links[i] = Link { next, span: self.nodes[prev].links[i].span + span - 1 };
+            } else {
+                self.nodes[prev].links[i].span -= 1;
+            }
+        }
+        let next = self.next(x, 0);
+        if next != NIL {
+            self.nodes[next].back = self.nodes[x].back;
+        }
+        while self.level > 1 && self.next(HEAD, self.level - 1) == NIL {
+            self.level -= 1;
+        }
+        self.len -= 1;
+
+        let node = &mut self.nodes[x];
+        node.member = Vec::new();
+        node.links = Vec::new();
+        self.free.push(x);
+        true
+    }
+
+    // 0-based position of (score, member), if present.
+    fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.next(x, i);
+                if next == NIL || self.nodes[next].cmp_to(score, member) == Ordering::Greater {
+                    break;
+                }
+                rank += self.nodes[x].links[i].span;
+                x = next;
+            }
+            if x != HEAD && self.nodes[x].cmp_to(score, member) == Ordering::Equal {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    // Node at 0-based position `rank`, or NIL.
+    fn by_rank(&self, rank: usize) -> usize {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.next(x, i);
+                if next == NIL || traversed + self.nodes[x].links[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].links[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return x;
+            }
+        }
+        NIL
+    }
+
+    // First node for which `below` is false, assuming `below` holds for a prefix of the list.
+    fn first_not(&self, below: impl Fn(&Node) -> bool) -> usize {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.next(x, i);
+                if next == NIL || !below(&self.nodes[next]) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.next(x, 0)
+    }
+
+    // Last node for which `within` holds, assuming it holds for a prefix of the list.
+    fn last_within(&self, within: impl Fn(&Node) -> bool) -> usize {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.next(x, i);
+                if next == NIL || !within(&self.nodes[next]) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        if x == HEAD { NIL } else { x }
+    }
+
+    // Collects nodes from `x` onwards (backwards when `rev`) while `in_range` holds, skipping
+    // the first `offset` and stopping after `count`.
+    fn walk(
+        &self,
+        mut x: usize,
+        rev: bool,
+        mut offset: usize,
+        count: Option<usize>,
+        in_range: impl Fn(&Node) -> bool,
+    ) -> ScoredMembers {
+        let mut out = Vec::new();
+        while x != NIL && count.is_none_or(|c| out.len() < c) {
+            let node = &self.nodes[x];
+            if !in_range(node) {
+                break;
+            }
+            if offset > 0 {
+                offset -= 1;
+            } else {
+                out.push((node.member.clone(), node.score));
+            }
+            x = if rev { node.back } else { node.links[0].next };
+        }
+        out
+    }
+}
+
+// One end of a ZRANGEBYSCORE range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ScoreBound {
+    pub(crate) value: f64,
+    pub(crate) exclusive: bool,
+}
+
+impl ScoreBound {
+    fn above_min(&self, score: f64) -> bool {
+        if self.exclusive { score > self.value } else { score >= self.value }
+    }
+
+    fn below_max(&self, score: f64) -> bool {
+        if self.exclusive { score < self.value } else { score <= self.value }
+    }
+}
+
+// One end of a ZRANGEBYLEX range: `-`, `+`, `[member` or `(member`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum LexBound {
+    Min,
+    Max,
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+}
+
+impl LexBound {
+    pub(crate) fn parse(s: &[u8]) -> Option<LexBound> {
+        match s {
+            b"-" => Some(LexBound::Min),
+            b"+" => Some(LexBound::Max),
+            [b'[', m @ ..] => Some(LexBound::Inclusive(m.to_vec())),
+            [b'(', m @ ..] => Some(LexBound::Exclusive(m.to_vec())),
+            _ => None,
+        }
+    }
+
+    fn above_min(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(v) => member >= v.as_slice(),
+            LexBound::Exclusive(v) => member > v.as_slice(),
+        }
+    }
+
+    fn below_max(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(v) => member <= v.as_slice(),
+            LexBound::Exclusive(v) => member < v.as_slice(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    list: SkipList,
+}
+
+impl SortedSet {
+    pub(crate) fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub(crate) fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    // Sets the member's score. Returns the previous score, None if the member is new.
+    pub(crate) fn insert(&mut self, member: Vec<u8>, score: f64) -> Option<f64> {
+        match self.scores.get_mut(&member) {
+            Some(old) => {
+                let prev = *old;
+                if prev != score {
+                    *old = score;
+                    self.list.remove(prev, &member);
+                    self.list.insert(score, member);
+                }
+                Some(prev)
+            }
+            None => {
+                self.scores.insert(member.clone(), score);
+                self.list.insert(score, member);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        let score = self.scores.remove(member)?;
+        self.list.remove(score, member);
+        Some(score)
+    }
+
+    // 0-based rank by ascending score, or descending when `rev`.
+    pub(crate) fn rank(&self, member: &[u8], rev: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let rank = self.list.rank(score, member)?;
+        Some(if rev { self.len() - 1 - rank } else { rank })
+    }
+
+    // Members at ranks `start..=end` (already clamped to the set), in rank order.
+    pub(crate) fn range(&self, start: usize, end: usize, rev: bool) -> ScoredMembers {
+        let first = if rev { self.len() - 1 - start } else { start };
+        self.list.walk(self.list.by_rank(first), rev, 0, Some(end + 1 - start), |_| true)
+    }
+
+    pub(crate) fn range_by_score(
+        &self,
+        min: ScoreBound,
+        max: ScoreBound,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+    ) -> ScoredMembers {
+        if rev {
+            let first = self.list.last_within(|n| max.below_max(n.score));
+            self.list.walk(first, true, offset, count, |n| min.above_min(n.score))
+        } else {
+            let first = self.list.first_not(|n| !min.above_min(n.score));
+            self.list.walk(first, false, offset, count, |n| max.below_max(n.score))
+        }
+    }
+
+    // Meaningful when all members share a score, as in Redis.
+    pub(crate) fn range_by_lex(
+        &self,
+        min: &LexBound,
+        max: &LexBound,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+    ) -> ScoredMembers {
+        if rev {
+            let first = self.list.last_within(|n| max.below_max(&n.member));
+            self.list.walk(first, true, offset, count, |n| min.above_min(&n.member))
+        } else {
+            let first = self.list.first_not(|n| !min.above_min(&n.member));
+            self.list.walk(first, false, offset, count, |n| max.below_max(&n.member))
+        }
+    }
+
+    // Members in ascending order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> + '_ {
+        let mut x = self.list.next(HEAD, 0);
+        std::iter::from_fn(move || {
+            let node = self.list.nodes.get(x)?;
+            x = node.links[0].next;
+            Some((node.member.as_slice(), node.score))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(items: Vec<(Vec<u8>, f64)>) -> Vec<String> {
+        items.into_iter().map(|(m, _)| String::from_utf8(m).unwrap()).collect()
+    }
+
+    #[test]
+    fn ranks_and_ranges_follow_score_then_member_order() {
+        let mut ss = SortedSet::default();
+        // Insert out of order with ties and updates, enough to build several levels.
+        for i in (0..500).rev() {
+            ss.insert(format!("m{:03}", i).into_bytes(), (i / 2) as f64);
+        }
+        ss.insert(b"m000".to_vec(), 1000.0);
+        assert_eq!(ss.remove(b"m001"), Some(0.0));
+        assert_eq!(ss.remove(b"m001"), None);
+        assert_eq!(ss.len(), 499);
+
+        let order: Vec<&[u8]> = ss.iter().map(|(m, _)| m).collect();
+        assert_eq!(order.first(), Some(&&b"m002"[..]));
+        assert_eq!(order.last(), Some(&&b"m000"[..]));
+        for (i, m) in order.iter().enumerate() {
+            assert_eq!(ss.rank(m, false), Some(i));
+            assert_eq!(ss.rank(m, true), Some(498 - i));
+        }
+        assert_eq!(ss.rank(b"m001", false), None);
+
+        assert_eq!(members(ss.range(0, 2, false)), ["m002", "m003", "m004"]);
+        assert_eq!(members(ss.range(0, 1, true)), ["m000", "m499"]);
+        assert_eq!(ss.range(498, 498, false), vec![(b"m000".to_vec(), 1000.0)]);
+    }
+
+    #[test]
+    fn score_and_lex_ranges_honour_bounds_and_limits() {
+        let mut ss = SortedSet::default();
+        for (m, s) in [("a", 1.0), ("b", 2.0), ("c", 2.0), ("d", 3.0), ("e", f64::INFINITY)] {
+            ss.insert(m.as_bytes().to_vec(), s);
+        }
+        let inc = |value| ScoreBound { value, exclusive: false };
+        let exc = |value| ScoreBound { value, exclusive: true };
+        assert_eq!(members(ss.range_by_score(inc(2.0), inc(3.0), false, 0, None)), ["b", "c", "d"]);
+        assert_eq!(members(ss.range_by_score(exc(1.0), exc(3.0), false, 0, None)), ["b", "c"]);
+        assert_eq!(members(ss.range_by_score(inc(f64::NEG_INFINITY), inc(f64::INFINITY), true, 1, Some(2))), ["d", "c"]);
+        assert_eq!(members(ss.range_by_score(inc(2.0), inc(2.0), true, 0, None)), ["c", "b"]);
+        assert!(ss.range_by_score(inc(4.0), exc(f64::INFINITY), false, 0, None).is_empty());
+
+        let mut lex = SortedSet::default();
+        for m in ["apple", "banana", "cherry", "date"] {
+            lex.insert(m.as_bytes().to_vec(), 0.0);
+        }
+        let b = |s: &str| LexBound::parse(s.as_bytes()).unwrap();
+        assert_eq!(members(lex.range_by_lex(&b("[banana"), &b("(date"), false, 0, None)), ["banana", "cherry"]);
+        assert_eq!(members(lex.range_by_lex(&b("-"), &b("+"), true, 0, Some(2))), ["date", "cherry"]);
+        assert_eq!(members(lex.range_by_lex(&b("(banana"), &b("+"), false, 1, None)), ["date"]);
+        assert_eq!(LexBound::parse(b"banana"), None);
+        assert_eq!(LexBound::parse(b""), None);
+    }
+}